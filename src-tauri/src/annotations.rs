@@ -0,0 +1,105 @@
+//! Private, timestamped remarks about a note ("waiting on Bob") kept in its metadata
+//! rather than its body, so they never show up in the content, its hash or its history.
+//! They follow the metadata through rekeys, the trash and archive, and note packets.
+
+use tauri::Runtime;
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, NoteMeta};
+
+/// Per note; adding past this drops the oldest.
+const MAX_ANNOTATIONS: usize = 50;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub id: String,
+    pub text: String,
+    /// Unix milliseconds
+    pub created_at: u64,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct NoteAnnotated {
+    id: String,
+    count: usize,
+    latest: Option<String>,
+}
+
+/// The newest annotation's text, for listings.
+pub fn latest_text(meta: &NoteMeta) -> Option<String> {
+    meta.annotations.last().map(|a| a.text.clone())
+}
+
+fn prune(annotations: &mut Vec<Annotation>) {
+    let excess = annotations.len().saturating_sub(MAX_ANNOTATIONS);
+    annotations.drain(..excess);
+}
+
+/// Adds the incoming annotations `local` doesn't have yet (matched by id), keeping
+/// the result oldest first and within the cap.
+pub fn merge(local: &mut Vec<Annotation>, incoming: &[Annotation]) {
+    for annotation in incoming {
+        if !local.iter().any(|a| a.id == annotation.id) {
+            local.push(annotation.clone());
+        }
+    }
+    local.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    prune(local);
+}
+
+/// Whether any annotation matches `needle` (already lowercased).
+pub fn matches(meta: &NoteMeta, needle: &str) -> bool {
+    meta.annotations.iter().any(|a| a.text.to_lowercase().contains(needle))
+}
+
+fn notify<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, meta: &NoteMeta) {
+    app.emit_note_event(
+        id,
+        "note-annotated",
+        NoteAnnotated {
+            id: id.to_string(),
+            count: meta.annotations.len(),
+            latest: latest_text(meta),
+        },
+    );
+}
+
+#[tauri::command]
+pub async fn add_note_annotation(id: String, text: String, app: tauri::AppHandle) -> Result<Annotation, String> {
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("Annotation text is empty".to_string());
+    }
+    let annotation = Annotation {
+        id: Uuid::new_v4().to_string(),
+        text,
+        created_at: crate::now_millis(),
+    };
+    let updated = meta::update_meta(&app, &id, |meta| {
+        meta.annotations.push(annotation.clone());
+        prune(&mut meta.annotations);
+    })?;
+    notify(&app, &id, &updated);
+    Ok(annotation)
+}
+
+/// Oldest first.
+#[tauri::command]
+pub async fn list_note_annotations(id: String, app: tauri::AppHandle) -> Result<Vec<Annotation>, String> {
+    Ok(meta::get_meta(&app, &id).annotations)
+}
+
+#[tauri::command]
+pub async fn delete_note_annotation(id: String, annotation_id: String, app: tauri::AppHandle) -> Result<(), String> {
+    if !meta::get_meta(&app, &id)
+        .annotations
+        .iter()
+        .any(|a| a.id == annotation_id)
+    {
+        return Err(format!("Note {} has no annotation {}", id, annotation_id));
+    }
+    let updated = meta::update_meta(&app, &id, |meta| meta.annotations.retain(|a| a.id != annotation_id))?;
+    notify(&app, &id, &updated);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Per-note font and zoom, kept in the note's `NoteMeta` as overrides of the `font_family`,
+//! `font_size` and `zoom` settings. A note window gets the values in effect from
+//! `get_note_meta` (`effective_appearance`) when it loads, and as
+//! `note-appearance-changed` whenever its own or the default values change.
+//!
+//! Zoom is the webview's own, applied here to the whole window; the frontend only applies
+//! the font.
+
+use tauri::{Emitter, EventTarget, Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, NoteMeta};
+use crate::settings::{self, MAX_FONT_SIZE, MAX_ZOOM, MIN_FONT_SIZE, MIN_ZOOM};
+use crate::WindowRegistry;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct NoteAppearance {
+    /// `None` for the setting of the same name, here and below
+    pub font_family: Option<String>,
+    pub font_size: Option<f64>,
+    /// 1.0 is 100%
+    pub zoom: Option<f64>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct EffectiveAppearance {
+    /// `None` for the frontend's default
+    font_family: Option<String>,
+    font_size: f64,
+    zoom: f64,
+}
+
+pub fn effective(backend: &impl NotesBackend, appearance: &NoteAppearance) -> EffectiveAppearance {
+    let settings = settings::current(backend);
+    EffectiveAppearance {
+        font_family: appearance.font_family.clone().or(settings.font_family),
+        font_size: appearance.font_size.unwrap_or(settings.font_size),
+        zoom: appearance.zoom.unwrap_or(settings.zoom),
+    }
+}
+
+fn validate(appearance: &NoteAppearance) -> Result<(), String> {
+    if let Some(size) = appearance.font_size {
+        if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&size) {
+            return Err(format!("Font size must be {} to {}", MIN_FONT_SIZE, MAX_FONT_SIZE));
+        }
+    }
+    if let Some(zoom) = appearance.zoom {
+        if !(MIN_ZOOM..=MAX_ZOOM).contains(&zoom) {
+            return Err(format!("Zoom must be {} to {}", MIN_ZOOM, MAX_ZOOM));
+        }
+    }
+    Ok(())
+}
+
+/// Zooms a note window being built, before its frontend asks for the rest.
+pub fn apply_zoom<R: Runtime>(window: &tauri::WebviewWindow<R>, note_meta: &NoteMeta) {
+    let zoom = effective(window.app_handle(), &note_meta.appearance).zoom;
+    if let Err(e) = window.set_zoom(zoom) {
+        println!("Failed to zoom {}: {}", window.label(), e);
+    }
+}
+
+fn send<R: Runtime>(window: &tauri::WebviewWindow<R>, appearance: &EffectiveAppearance) {
+    if let Err(e) = window.set_zoom(appearance.zoom) {
+        println!("Failed to zoom {}: {}", window.label(), e);
+    }
+    let _ = window.emit_to(
+        EventTarget::webview_window(window.label()),
+        "note-appearance-changed",
+        appearance.clone(),
+    );
+}
+
+/// Sends every open note what is now in effect, after the defaults changed.
+pub fn refresh_all<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let metas = meta::load_all(app);
+    for label in app.state::<WindowRegistry>().note_labels() {
+        let (Some(window), Some(id)) = (app.get_webview_window(&label), label.strip_prefix("note-")) else {
+            continue;
+        };
+        let appearance = metas.get(id).map(|m| m.appearance.clone()).unwrap_or_default();
+        send(&window, &effective(app, &appearance));
+    }
+}
+
+/// Sets note `id`'s font and zoom; fields left out (or `null`) go back to the defaults.
+#[tauri::command]
+pub async fn set_note_appearance(
+    id: String,
+    appearance: NoteAppearance,
+    app: tauri::AppHandle,
+) -> Result<EffectiveAppearance, String> {
+    let appearance = NoteAppearance {
+        font_family: appearance
+            .font_family
+            .map(|family| family.trim().to_string())
+            .filter(|family| !family.is_empty()),
+        ..appearance
+    };
+    validate(&appearance)?;
+    let effective = effective(&app, &appearance);
+    meta::update_meta(&app, &id, |meta| meta.appearance = appearance)?;
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        send(&window, &effective);
+    }
+    Ok(effective)
+}
@@ -0,0 +1,430 @@
+//! A PIN lock on the whole app. With it on, the app starts locked and locks again after
+//! `idle_minutes` without activity (0 for only on request): every window but the
+//! dashboard is hidden, the dashboard gets `app-locked` to show its PIN screen, and
+//! `load_note` and everything that shows a note window fail with `AppLocked` until
+//! `unlock_app` gets the PIN. Unlocking brings back the windows the lock hid, or runs
+//! the session restore a locked start put off.
+//!
+//! The PIN is kept as an Argon2id hash with its salt in settings.bin `app_lock`. This
+//! keeps the notes out of sight of someone at an unattended machine; the files
+//! themselves are only protected by the vault (`vault.rs`).
+//!
+//! Guessing is slowed down: after `FREE_ATTEMPTS` wrong PINs in a row every further one
+//! makes the next attempt wait, 30 seconds at first and twice as long each time, up to an
+//! hour. The count and the wait are saved with the PIN, so restarting doesn't reset them.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::encryption::{derive_key, random_salt};
+use crate::error::NoteError;
+use crate::flush;
+use crate::focusmode;
+use crate::restore;
+use crate::{get_session_order, now_millis, show_dashboard};
+
+const SETTINGS_KEY: &str = "app_lock";
+const DEFAULT_IDLE_MINUTES: u32 = 10;
+const MIN_PIN_LEN: usize = 4;
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Wrong PINs in a row before they start costing a wait.
+const FREE_ATTEMPTS: u32 = 5;
+const FIRST_LOCKOUT_MS: u64 = 30 * 1000;
+const MAX_LOCKOUT_MS: u64 = 60 * 60 * 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+struct AppLockSettings {
+    enabled: bool,
+    idle_minutes: u32,
+    /// Base64
+    salt: Option<String>,
+    /// Base64 of the key Argon2id derives from the PIN and salt
+    hash: Option<String>,
+    /// Wrong PINs since the last right one
+    failed_attempts: u32,
+    /// Unix milliseconds; no PIN is checked before then
+    retry_at: Option<u64>,
+}
+
+impl Default for AppLockSettings {
+    fn default() -> Self {
+        AppLockSettings {
+            enabled: false,
+            idle_minutes: DEFAULT_IDLE_MINUTES,
+            salt: None,
+            hash: None,
+            failed_attempts: 0,
+            retry_at: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct AppLockStatus {
+    enabled: bool,
+    idle_minutes: u32,
+    locked: bool,
+    /// Unix milliseconds, while wrong PINs hold off the next attempt
+    retry_at: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct AppLock {
+    locked: AtomicBool,
+    /// Windows the lock hid, shown again on unlock
+    hidden: Mutex<Vec<String>>,
+    /// The startup restore was skipped because the app started locked
+    restore_pending: AtomicBool,
+}
+
+impl AppLock {
+    pub fn new(locked: bool) -> Self {
+        AppLock {
+            locked: AtomicBool::new(locked),
+            ..Default::default()
+        }
+    }
+
+    /// `AppLocked` while locked.
+    pub fn ensure_unlocked(&self) -> Result<(), String> {
+        if self.locked.load(Ordering::SeqCst) {
+            return Err(NoteError::AppLocked.into());
+        }
+        Ok(())
+    }
+}
+
+fn settings(backend: &impl NotesBackend) -> AppLockSettings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn save_settings(backend: &impl NotesBackend, settings: &AppLockSettings) -> Result<(), String> {
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    backend.write_store("settings.bin", SETTINGS_KEY, value)
+}
+
+fn is_active(settings: &AppLockSettings) -> bool {
+    settings.enabled && settings.hash.is_some()
+}
+
+fn status<R: Runtime>(app: &tauri::AppHandle<R>) -> AppLockStatus {
+    let settings = settings(app);
+    AppLockStatus {
+        enabled: is_active(&settings),
+        idle_minutes: settings.idle_minutes,
+        locked: is_locked(app),
+        retry_at: settings.retry_at.filter(|at| *at > now_millis()),
+    }
+}
+
+/// Compares in constant time, so timing doesn't tell how much of a guess was right.
+fn hash_matches(given: &[u8], stored: &[u8]) -> bool {
+    given.len() == stored.len() && given.iter().zip(stored).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether `pin` is the PIN.
+fn check_pin(settings: &AppLockSettings, pin: &str) -> Result<bool, String> {
+    let (Some(salt), Some(hash)) = (&settings.salt, &settings.hash) else {
+        return Err("No PIN is set".to_string());
+    };
+    let damaged = |e: base64::DecodeError| format!("App lock settings are damaged: {}", e);
+    let salt = STANDARD.decode(salt).map_err(damaged)?;
+    let hash = STANDARD.decode(hash).map_err(damaged)?;
+    Ok(hash_matches(&derive_key(pin, &salt)?, &hash))
+}
+
+/// How long the `failures`th wrong PIN in a row makes the next attempt wait.
+fn lockout_ms(failures: u32) -> u64 {
+    if failures <= FREE_ATTEMPTS {
+        return 0;
+    }
+    let doublings = (failures - FREE_ATTEMPTS - 1).min(16);
+    (FIRST_LOCKOUT_MS << doublings).min(MAX_LOCKOUT_MS)
+}
+
+/// `WrongPassword` unless `pin` is the PIN, counting wrong ones (see the module docs).
+/// While a wait is on, fails with `TooManyAttempts` without checking.
+fn verify_pin(backend: &impl NotesBackend, pin: &str, now: u64) -> Result<(), String> {
+    let mut settings = settings(backend);
+    if let Some(retry_at) = settings.retry_at.filter(|at| *at > now) {
+        let retry_in_secs = (retry_at - now).div_ceil(1000);
+        return Err(NoteError::TooManyAttempts { retry_in_secs }.into());
+    }
+    let right = check_pin(&settings, pin)?;
+    if right && settings.failed_attempts == 0 && settings.retry_at.is_none() {
+        return Ok(());
+    }
+    if right {
+        settings.failed_attempts = 0;
+        settings.retry_at = None;
+    } else {
+        settings.failed_attempts = settings.failed_attempts.saturating_add(1);
+        let wait = lockout_ms(settings.failed_attempts);
+        settings.retry_at = (wait > 0).then_some(now + wait);
+        println!("App lock: wrong PIN, {} in a row", settings.failed_attempts);
+    }
+    save_settings(backend, &settings)?;
+    if right {
+        Ok(())
+    } else {
+        Err(NoteError::WrongPassword.into())
+    }
+}
+
+pub fn is_locked<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    app.try_state::<AppLock>()
+        .is_some_and(|lock| lock.locked.load(Ordering::SeqCst))
+}
+
+/// `AppLocked` while the app is locked.
+pub fn ensure_unlocked<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
+    app.try_state::<AppLock>().map_or(Ok(()), |lock| lock.ensure_unlocked())
+}
+
+/// Called by the startup restore: whether the app is locked, in which case the restore
+/// is left for `unlock_app`.
+pub fn defer_restore<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    let Some(lock) = app.try_state::<AppLock>() else {
+        return false;
+    };
+    if !lock.locked.load(Ordering::SeqCst) {
+        return false;
+    }
+    lock.restore_pending.store(true, Ordering::SeqCst);
+    println!("App lock: restoring the session once unlocked");
+    true
+}
+
+/// Hides every window but the dashboard and locks. Does nothing if already locked.
+pub fn lock<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let state = app.state::<AppLock>();
+    if state.locked.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    // Windows focus mode hid come back first, so unlocking shows them too
+    focusmode::end_focus(app);
+    let mut hidden = Vec::new();
+    for (label, window) in app.webview_windows() {
+        if label != "main" && window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+            hidden.push(label);
+        }
+    }
+    println!("App lock: locked, {} windows hidden", hidden.len());
+    if let Ok(mut previous) = state.hidden.lock() {
+        previous.extend(hidden);
+    }
+    show_dashboard(app);
+    app.emit_event("app-locked", ());
+}
+
+/// Locks on startup if the lock is on, and after the idle timeout from then on.
+pub fn init<R: Runtime>(app: &tauri::AppHandle<R>) {
+    app.manage(AppLock::new(is_active(&settings(app))));
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            let settings = settings(&app);
+            if !is_active(&settings) || settings.idle_minutes == 0 || is_locked(&app) {
+                continue;
+            }
+            let idle_for = now_millis().saturating_sub(flush::last_activity());
+            if idle_for >= u64::from(settings.idle_minutes) * 60 * 1000 {
+                lock(&app);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_app_lock(app: tauri::AppHandle) -> Result<AppLockStatus, String> {
+    Ok(status(&app))
+}
+
+/// Turns the lock on with `pin`, or changes the PIN or timeout; once a PIN is set,
+/// `current_pin` must match it.
+#[tauri::command]
+pub async fn set_app_lock(
+    pin: String,
+    current_pin: Option<String>,
+    idle_minutes: Option<u32>,
+    app: tauri::AppHandle,
+) -> Result<AppLockStatus, String> {
+    if settings(&app).hash.is_some() {
+        verify_pin(&app, current_pin.as_deref().unwrap_or_default(), now_millis())?;
+    }
+    if pin.chars().count() < MIN_PIN_LEN {
+        return Err(format!("The PIN must be at least {} characters", MIN_PIN_LEN));
+    }
+    let mut settings = settings(&app);
+    let salt = random_salt();
+    settings.salt = Some(STANDARD.encode(salt));
+    settings.hash = Some(STANDARD.encode(derive_key(&pin, &salt)?));
+    settings.enabled = true;
+    if let Some(minutes) = idle_minutes {
+        settings.idle_minutes = minutes;
+    }
+    save_settings(&app, &settings)?;
+    Ok(status(&app))
+}
+
+/// Turns the lock off and forgets the PIN.
+#[tauri::command]
+pub async fn disable_app_lock(pin: String, app: tauri::AppHandle) -> Result<AppLockStatus, String> {
+    verify_pin(&app, &pin, now_millis())?;
+    let settings = settings(&app);
+    save_settings(
+        &app,
+        &AppLockSettings {
+            idle_minutes: settings.idle_minutes,
+            ..Default::default()
+        },
+    )?;
+    if is_locked(&app) {
+        unlock(&app);
+    }
+    Ok(status(&app))
+}
+
+#[tauri::command]
+pub async fn lock_app(app: tauri::AppHandle) -> Result<AppLockStatus, String> {
+    if !is_active(&settings(&app)) {
+        return Err("Set a PIN before locking the app".to_string());
+    }
+    lock(&app);
+    Ok(status(&app))
+}
+
+fn unlock<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let state = app.state::<AppLock>();
+    state.locked.store(false, Ordering::SeqCst);
+    flush::touch();
+    let hidden = state
+        .hidden
+        .lock()
+        .map(|mut hidden| std::mem::take(&mut *hidden))
+        .unwrap_or_default();
+    for label in hidden {
+        if let Some(window) = app.get_webview_window(&label) {
+            let _ = window.show();
+        }
+    }
+    if state.restore_pending.swap(false, Ordering::SeqCst) {
+        tauri::async_runtime::spawn(restore::restore_session(app.clone(), get_session_order(app)));
+    }
+    println!("App lock: unlocked");
+    app.emit_event("app-unlocked", ());
+}
+
+#[tauri::command]
+pub async fn unlock_app(pin: String, app: tauri::AppHandle) -> Result<AppLockStatus, String> {
+    verify_pin(&app, &pin, now_millis())?;
+    unlock(&app);
+    Ok(status(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn with_pin(pin: &str) -> TempBackend {
+        let backend = TempBackend::new();
+        let salt = random_salt();
+        let settings = AppLockSettings {
+            enabled: true,
+            salt: Some(STANDARD.encode(salt)),
+            hash: Some(STANDARD.encode(derive_key(pin, &salt).unwrap())),
+            ..Default::default()
+        };
+        save_settings(&backend, &settings).unwrap();
+        backend
+    }
+
+    #[test]
+    fn waits_start_after_the_free_attempts_and_double() {
+        let waits: Vec<u64> = (0..=8).map(lockout_ms).collect();
+        assert_eq!(waits, [0, 0, 0, 0, 0, 0, 30_000, 60_000, 120_000]);
+        assert_eq!(lockout_ms(20), MAX_LOCKOUT_MS);
+        assert_eq!(lockout_ms(u32::MAX), MAX_LOCKOUT_MS);
+    }
+
+    #[test]
+    fn right_pin_passes() {
+        let backend = with_pin("2468");
+        assert!(verify_pin(&backend, "2468", NOW).is_ok());
+        assert_eq!(settings(&backend).failed_attempts, 0);
+    }
+
+    #[test]
+    fn wrong_pins_are_counted() {
+        let backend = with_pin("2468");
+        for _ in 0..FREE_ATTEMPTS {
+            let error = verify_pin(&backend, "0000", NOW).unwrap_err();
+            assert!(error.starts_with("WrongPassword"), "{}", error);
+        }
+        let settings = settings(&backend);
+        assert_eq!(settings.failed_attempts, FREE_ATTEMPTS);
+        assert_eq!(settings.retry_at, None);
+    }
+
+    #[test]
+    fn too_many_wrong_pins_hold_off_even_the_right_one() {
+        let backend = with_pin("2468");
+        for _ in 0..=FREE_ATTEMPTS {
+            let _ = verify_pin(&backend, "0000", NOW);
+        }
+        assert_eq!(settings(&backend).retry_at, Some(NOW + FIRST_LOCKOUT_MS));
+
+        let error = verify_pin(&backend, "2468", NOW + 1000).unwrap_err();
+        assert_eq!(error, "TooManyAttempts: try again in 29 seconds");
+        // Refused attempts aren't checked, so they don't count either
+        assert_eq!(settings(&backend).failed_attempts, FREE_ATTEMPTS + 1);
+    }
+
+    #[test]
+    fn each_wrong_pin_after_a_wait_waits_longer() {
+        let backend = with_pin("2468");
+        let mut now = NOW;
+        for _ in 0..=FREE_ATTEMPTS {
+            let _ = verify_pin(&backend, "0000", now);
+        }
+        now = settings(&backend).retry_at.unwrap();
+        let error = verify_pin(&backend, "0000", now).unwrap_err();
+        assert!(error.starts_with("WrongPassword"), "{}", error);
+        assert_eq!(settings(&backend).retry_at, Some(now + 2 * FIRST_LOCKOUT_MS));
+    }
+
+    #[test]
+    fn right_pin_after_the_wait_resets_the_count() {
+        let backend = with_pin("2468");
+        for _ in 0..=FREE_ATTEMPTS {
+            let _ = verify_pin(&backend, "0000", NOW);
+        }
+        let retry_at = settings(&backend).retry_at.unwrap();
+
+        assert!(verify_pin(&backend, "2468", retry_at).is_ok());
+        let settings = settings(&backend);
+        assert_eq!((settings.failed_attempts, settings.retry_at), (0, None));
+    }
+
+    #[test]
+    fn no_pin_set_is_not_counted() {
+        let backend = TempBackend::new();
+        assert_eq!(verify_pin(&backend, "1234", NOW).unwrap_err(), "No PIN is set");
+        assert_eq!(settings(&backend).failed_attempts, 0);
+    }
+}
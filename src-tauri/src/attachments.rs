@@ -0,0 +1,324 @@
+//! Files attached to notes, stored in the note's asset folder as `<id>/attachments/<name>`:
+//! images pasted into a note and files dropped onto its window. A note refers to an
+//! attachment by that relative path, which `duplicate` and packets already carry along;
+//! the webview shows it through the `attachment:` protocol, which only serves files from
+//! attachment folders.
+//!
+//! Attachments move to the trash and archive with their note's asset folder. Those of a
+//! note deleted before it was ever saved are removed right away. Like note metadata,
+//! attachments are not encrypted by the vault.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::Runtime;
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::error::NoteError;
+use crate::git;
+use crate::limits::effective_limits;
+use crate::notes_dir;
+use crate::safepath::{sanitize_file_name, Root, SafePath};
+use crate::scan::is_valid_note_id;
+use crate::storage::{self, ensure_writes_allowed};
+
+pub const SCHEME: &str = "attachment";
+const ATTACHMENTS_DIR: &str = "attachments";
+/// Types pasted images may have, with the extension they are saved with.
+const IMAGE_TYPES: [(&str, &str); 5] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/bmp", "bmp"),
+];
+/// Types served for other extensions; anything else goes out as `application/octet-stream`.
+const OTHER_TYPES: [(&str, &str); 6] = [
+    ("image/jpeg", "jpeg"),
+    ("application/pdf", "pdf"),
+    ("text/plain", "txt"),
+    ("text/markdown", "md"),
+    ("text/csv", "csv"),
+    ("application/zip", "zip"),
+];
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Attachment {
+    name: String,
+    /// Relative to the notes folder, what the note's content links to
+    path: String,
+    /// Where the webview can load it from
+    url: String,
+    mime: String,
+    size: u64,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct AttachmentAdded {
+    attachment: Attachment,
+    /// An image or link reference to insert where the file was dropped
+    markdown: String,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct AttachmentRejected {
+    /// The dropped file's name
+    name: String,
+    reason: String,
+}
+
+fn mime_of(name: &str) -> &'static str {
+    name.rsplit_once('.')
+        .and_then(|(_, extension)| {
+            IMAGE_TYPES
+                .iter()
+                .chain(OTHER_TYPES.iter())
+                .find(|(_, ext)| ext.eq_ignore_ascii_case(extension))
+        })
+        .map_or("application/octet-stream", |(mime, _)| *mime)
+}
+
+fn relative_path(id: &str, name: &str) -> String {
+    format!("{}/{}/{}", id, ATTACHMENTS_DIR, name)
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// WebKitGTK and WKWebView take custom schemes as they are; WebView2 and Android only
+/// load them as `http://<scheme>.localhost`.
+fn url_of(relative: &str) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", SCHEME, encode_path(relative))
+    } else {
+        format!("{}://localhost/{}", SCHEME, encode_path(relative))
+    }
+}
+
+fn attachments_dir<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<PathBuf, String> {
+    if !is_valid_note_id(id) {
+        return Err(format!("Invalid note id: {}", id));
+    }
+    Ok(notes_dir(app)?.join(id).join(ATTACHMENTS_DIR))
+}
+
+/// Where attachment `name` of note `id` lives, if both are names we could have given out.
+fn attachment_path<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, name: &str) -> Result<PathBuf, String> {
+    if name.trim().is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("Invalid attachment name: {:?}", name));
+    }
+    Ok(attachments_dir(app, id)?.join(name))
+}
+
+fn describe(id: &str, name: &str, size: u64) -> Attachment {
+    let path = relative_path(id, name);
+    Attachment {
+        name: name.to_string(),
+        url: url_of(&path),
+        path,
+        mime: mime_of(name).to_string(),
+        size,
+    }
+}
+
+/// `![name](path)` for images, `[name](path)` for everything else.
+pub fn markdown_for(attachment: &Attachment) -> String {
+    let bang = if attachment.mime.starts_with("image/") { "!" } else { "" };
+    // Spaces and parentheses would end a bare link destination early
+    if attachment.path.contains([' ', '(', ')']) {
+        format!("{}[{}](<{}>)", bang, attachment.name, attachment.path)
+    } else {
+        format!("{}[{}]({})", bang, attachment.name, attachment.path)
+    }
+}
+
+/// `file_name` made safe, or `name (2).ext` and so on if that is taken in `dir`.
+fn unique_name(dir: &Path, file_name: &Path) -> String {
+    let stem = file_name
+        .file_stem()
+        .and_then(|stem| sanitize_file_name(&stem.to_string_lossy()).ok())
+        .unwrap_or_else(|| "attachment".to_string());
+    let extension: String = file_name
+        .extension()
+        .map(|ext| {
+            ext.to_string_lossy()
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect()
+        })
+        .unwrap_or_default();
+    let with_suffix = |suffix: String| match extension.as_str() {
+        "" => format!("{}{}", stem, suffix),
+        ext => format!("{}{}.{}", stem, suffix, ext),
+    };
+    let mut name = with_suffix(String::new());
+    let mut n = 2;
+    while dir.join(&name).exists() {
+        name = with_suffix(format!(" ({})", n));
+        n += 1;
+    }
+    name
+}
+
+fn check_size(size: usize, limit: usize) -> Result<(), String> {
+    if size > limit {
+        return Err(NoteError::TooLarge { size, limit }.into());
+    }
+    Ok(())
+}
+
+/// Copies `source` into note `id`'s attachments under its own name.
+fn attach_file<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, source: &Path) -> Result<Attachment, String> {
+    ensure_writes_allowed(app)?;
+    let metadata = fs::metadata(source).map_err(|e| e.to_string())?;
+    if !metadata.is_file() {
+        return Err("Only files can be attached, not folders".to_string());
+    }
+    check_size(metadata.len() as usize, effective_limits(app).max_attachment_bytes)?;
+    let file_name = source.file_name().ok_or("The file has no name")?;
+    let dir = attachments_dir(app, id)?;
+    fs::create_dir_all(&dir).map_err(|e| storage::write_error(app, e))?;
+    let name = unique_name(&dir, Path::new(file_name));
+    let size = fs::copy(source, dir.join(&name)).map_err(|e| storage::write_error(app, e))?;
+    git::changed(app);
+    Ok(describe(id, &name, size))
+}
+
+/// Attaches files dropped onto note `id`'s window, off the main thread. Each one is
+/// reported to the window as `attachment-added`, or `attachment-rejected` with the reason.
+pub fn files_dropped<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, paths: Vec<PathBuf>) {
+    let app = app.clone();
+    let id = id.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        for path in paths {
+            match attach_file(&app, &id, &path) {
+                Ok(attachment) => {
+                    let markdown = markdown_for(&attachment);
+                    app.emit_note_event(&id, "attachment-added", AttachmentAdded { attachment, markdown });
+                }
+                Err(reason) => {
+                    println!("Could not attach {:?} to {}: {}", path, id, reason);
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    app.emit_note_event(&id, "attachment-rejected", AttachmentRejected { name, reason });
+                }
+            }
+        }
+    });
+}
+
+/// Removes the attachments of note `id`, which was deleted without ever being saved and
+/// so had no asset folder to recycle them with.
+pub fn remove_unsaved<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    let Ok(attachments) = attachments_dir(app, id) else {
+        return;
+    };
+    if !attachments.is_dir() {
+        return;
+    }
+    let asset_dir = attachments.parent().map(Path::to_path_buf).unwrap_or_default();
+    match SafePath::new(app, Root::Notes, attachments).and_then(|path| path.remove()) {
+        // Only goes if nothing else was in it
+        Ok(()) => {
+            let _ = fs::remove_dir(&asset_dir);
+            git::changed(app);
+        }
+        Err(e) => println!("Failed to remove the attachments of {}: {}", id, e),
+    }
+}
+
+/// Answers `attachment:` requests: `/<id>/attachments/<name>` below the notes folder.
+pub fn serve<R: Runtime>(app: &tauri::AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let path = decode_path(request.uri().path()).unwrap_or_default();
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let found = match parts[..] {
+        [id, ATTACHMENTS_DIR, name] => attachment_path(app, id, name)
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .map(|bytes| (bytes, mime_of(name))),
+        _ => None,
+    };
+    let response = match found {
+        Some((bytes, mime)) => Response::builder()
+            .header(header::CONTENT_TYPE, mime)
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(bytes),
+        None => Response::builder().status(StatusCode::NOT_FOUND).body(Vec::new()),
+    };
+    response.unwrap_or_else(|_| Response::new(Vec::new()))
+}
+
+/// Stores image `bytes` of type `mime` in note `id`'s attachments under a fresh name.
+/// The note doesn't have to be saved yet.
+pub fn save_image<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    bytes: &[u8],
+    mime: &str,
+) -> Result<Attachment, String> {
+    ensure_writes_allowed(app)?;
+    if bytes.is_empty() {
+        return Err("The attachment is empty".to_string());
+    }
+    check_size(bytes.len(), effective_limits(app).max_attachment_bytes)?;
+    // "image/png; charset=..." and "IMAGE/PNG" both happen on some platforms
+    let mime = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let (_, extension) = IMAGE_TYPES
+        .iter()
+        .find(|(known, _)| *known == mime)
+        .ok_or_else(|| format!("Unsupported attachment type: {}", mime))?;
+    let name = format!("{}.{}", Uuid::new_v4().simple(), extension);
+    let path = attachment_path(app, id, &name)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| storage::write_error(app, e))?;
+    }
+    fs::write(&path, bytes).map_err(|e| storage::write_error(app, e))?;
+    git::changed(app);
+    Ok(describe(id, &name, bytes.len() as u64))
+}
+
+/// Stores an image pasted into note `note_id`, see `save_image`.
+#[tauri::command]
+pub async fn save_attachment(
+    note_id: String,
+    bytes: Vec<u8>,
+    mime: String,
+    app: tauri::AppHandle,
+) -> Result<Attachment, String> {
+    save_image(&app, &note_id, &bytes, &mime)
+}
+
+#[tauri::command]
+pub async fn get_attachment(note_id: String, name: String, app: tauri::AppHandle) -> Result<Attachment, String> {
+    let path = attachment_path(&app, &note_id, &name)?;
+    let metadata = fs::metadata(&path)
+        .ok()
+        .filter(|m| m.is_file())
+        .ok_or_else(|| format!("Note {} has no attachment {:?}", note_id, name))?;
+    Ok(describe(&note_id, &name, metadata.len()))
+}
@@ -0,0 +1,61 @@
+//! Starting with the user's login, through the autostart plugin. The login item launches
+//! the app with `--hidden`: the session is restored into the tray without showing any
+//! note, unless there is no tray to find them in.
+
+use tauri::menu::CheckMenuItem;
+use tauri::{Manager, Runtime};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::tray;
+use crate::usage::record_usage;
+
+/// Passed by the login item.
+pub const HIDDEN_FLAG: &str = "--hidden";
+pub const MENU_ID: &str = "autostart";
+
+/// The tray's "Start at Login" toggle.
+pub struct AutostartMenu<R: Runtime>(pub CheckMenuItem<R>);
+
+/// Whether this launch should leave the restored notes hidden in the tray.
+pub fn start_hidden<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    std::env::args().any(|arg| arg == HIDDEN_FLAG) && tray::tray_available(app)
+}
+
+pub fn is_enabled<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    app.autolaunch().is_enabled().unwrap_or(false)
+}
+
+/// Adds or removes the login item and brings the tray toggle in line. Returns whether
+/// the app now starts at login.
+pub fn set_enabled<R: Runtime>(app: &tauri::AppHandle<R>, enabled: bool) -> Result<bool, String> {
+    let result = if enabled {
+        app.autolaunch().enable()
+    } else {
+        app.autolaunch().disable()
+    };
+    // Refresh the toggle even on failure: clicking it has already flipped its check mark
+    let now = is_enabled(app);
+    if let Some(menu) = app.try_state::<AutostartMenu<R>>() {
+        let _ = menu.0.set_checked(now);
+    }
+    result.map_err(|e| e.to_string())?;
+    Ok(now)
+}
+
+/// From the tray toggle.
+pub fn toggle<R: Runtime>(app: &tauri::AppHandle<R>) {
+    record_usage(app, "autostart_tray");
+    if let Err(e) = set_enabled(app, !is_enabled(app)) {
+        println!("Failed to change start at login: {}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn get_autostart(app: tauri::AppHandle) -> Result<bool, String> {
+    app.autolaunch().is_enabled().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_autostart(enabled: bool, app: tauri::AppHandle) -> Result<bool, String> {
+    set_enabled(&app, enabled)
+}
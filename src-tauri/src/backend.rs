@@ -0,0 +1,97 @@
+//! The filesystem, store and event access the core note logic needs, behind a trait so
+//! that logic doesn't have to hold a `tauri::AppHandle`. The app implements it for
+//! `AppHandle`; anything else (a temp-dir double, a CLI) can provide its own.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::vault::VaultKey;
+
+/// Stores with values set by `stage_store` that the auto-save may not have written yet.
+static STAGED_STORES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+pub fn take_staged_stores() -> Vec<String> {
+    STAGED_STORES
+        .lock()
+        .map(|mut staged| std::mem::take(&mut *staged).into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Puts stores back after a failed save so the next flush retries them.
+pub fn restage(stores: &[String]) {
+    if let Ok(mut staged) = STAGED_STORES.lock() {
+        staged.extend(stores.iter().cloned());
+    }
+}
+
+pub trait NotesBackend {
+    /// Directory holding the live `<id>.md` files: settings.bin `notes_directory`, or
+    /// `notes/` in the app data dir.
+    fn notes_dir(&self) -> Result<PathBuf, String>;
+    /// The app data dir, which holds the trash, archive, journal and templates.
+    fn data_dir(&self) -> Result<PathBuf, String>;
+    /// A value from one of the key/value stores (`settings.bin`, `session.bin`, ...).
+    fn read_store(&self, store: &str, key: &str) -> Option<serde_json::Value>;
+    /// Sets and persists a store value.
+    fn write_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String>;
+    /// Sets a store value and leaves the disk write to the store's debounced auto-save,
+    /// for values that change in quick bursts.
+    fn stage_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String>;
+    /// Sends an event to every window subscribed to it.
+    fn emit_event<S: serde::Serialize + Clone>(&self, event: &str, payload: S);
+    /// Sends an event about note `id`, skipping windows subscribed to other notes.
+    fn emit_note_event<S: serde::Serialize + Clone>(&self, id: &str, event: &str, payload: S);
+    /// The vault key while the vault is unlocked.
+    fn vault_key(&self) -> Option<VaultKey>;
+    /// Runs `op`, which writes, moves or deletes the files of notes `ids`, so that the
+    /// change isn't taken for an edit from outside the app.
+    fn own_change<T>(&self, ids: &[&str], op: impl FnOnce() -> T) -> T;
+}
+
+impl<R: Runtime> NotesBackend for tauri::AppHandle<R> {
+    fn notes_dir(&self) -> Result<PathBuf, String> {
+        if let Some(dir) = crate::relocate::configured(self) {
+            return Ok(dir);
+        }
+        Ok(self.data_dir()?.join("notes"))
+    }
+
+    fn data_dir(&self) -> Result<PathBuf, String> {
+        self.path().app_data_dir().map_err(|e| e.to_string())
+    }
+
+    fn read_store(&self, store: &str, key: &str) -> Option<serde_json::Value> {
+        self.store(store).ok().and_then(|store| store.get(key))
+    }
+
+    fn write_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String> {
+        let store = self.store(store).map_err(|e| e.to_string())?;
+        store.set(key, value);
+        store.save().map_err(|e| e.to_string())
+    }
+
+    fn stage_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String> {
+        self.store(store).map_err(|e| e.to_string())?.set(key, value);
+        restage(&[store.to_string()]);
+        Ok(())
+    }
+
+    fn emit_event<S: serde::Serialize + Clone>(&self, event: &str, payload: S) {
+        crate::events::emit(self, event, None, payload);
+    }
+
+    fn emit_note_event<S: serde::Serialize + Clone>(&self, id: &str, event: &str, payload: S) {
+        crate::events::emit(self, event, Some(id), payload);
+    }
+
+    fn vault_key(&self) -> Option<VaultKey> {
+        crate::vault::key(self)
+    }
+
+    fn own_change<T>(&self, ids: &[&str], op: impl FnOnce() -> T) -> T {
+        crate::changes::own_change(self, ids, op)
+    }
+}
@@ -0,0 +1,363 @@
+//! Rolling backups: every `interval_hours` (daily by default) the notes are zipped into
+//! the backups folder as `notes-YYYYMMDD-HHMMSS.zip`, and all but the newest `keep` are
+//! deleted. Settings are in settings.bin `backups`; `backup_now` makes one on demand.
+//!
+//! An archive holds each note's file as stored, so vault-encrypted notes stay encrypted,
+//! next to its metadata as `<id>.meta.json`, the layout `import_notes` reads. Restoring
+//! is an import: missing notes come back under their ids, and notes changed since come
+//! back beside the current ones rather than over them.
+//!
+//! A failed scheduled backup is shown as a notification (once until one succeeds) and
+//! on the tray badge.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::backend::NotesBackend;
+use crate::flush::flush_now;
+use crate::import::{import_notes, ImportSummary};
+use crate::meta;
+use crate::noteindex;
+use crate::storage::ensure_writes_allowed;
+use crate::{notes_dir, to_millis, tray};
+
+const SETTINGS_KEY: &str = "backups";
+const FILE_PREFIX: &str = "notes-";
+const FILE_EXTENSION: &str = ".zip";
+/// How often the scheduler checks whether a backup is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const ATTENTION_SOURCE: &str = "backups";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BackupSettings {
+    enabled: bool,
+    /// `None` for `backups` in the app data folder
+    folder: Option<String>,
+    interval_hours: u32,
+    /// Archives kept; older ones are deleted after each backup
+    keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            enabled: true,
+            folder: None,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BackupState {
+    /// One backup at a time
+    running: Mutex<()>,
+    /// The last scheduled backup failed and the user was told
+    failing: AtomicBool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct BackupInfo {
+    path: String,
+    /// Unix milliseconds
+    created_at: Option<u64>,
+    bytes: u64,
+}
+
+fn settings(backend: &impl NotesBackend) -> BackupSettings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn backups_dir<R: Runtime>(app: &tauri::AppHandle<R>, settings: &BackupSettings) -> Result<PathBuf, String> {
+    match settings.folder.as_deref() {
+        Some(folder) => Ok(PathBuf::from(folder)),
+        None => Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("backups")),
+    }
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(FILE_PREFIX) && name.ends_with(FILE_EXTENSION)
+}
+
+/// The archives in `dir`, newest first. The names sort by time.
+fn archives(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut archives: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_backup_name))
+        .map(|entry| entry.path())
+        .collect();
+    archives.sort_unstable_by(|a, b| b.cmp(a));
+    archives
+}
+
+fn info(path: &Path) -> BackupInfo {
+    let metadata = fs::metadata(path).ok();
+    BackupInfo {
+        path: path.to_string_lossy().into_owned(),
+        created_at: metadata.as_ref().and_then(|m| to_millis(m.modified())),
+        bytes: metadata.map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+/// Writes the archive through a `.partial` file renamed into place, so a crash never
+/// leaves one that looks finished.
+fn write_archive<R: Runtime>(app: &tauri::AppHandle<R>, destination: &Path) -> Result<usize, String> {
+    let notes = notes_dir(app)?;
+    let mut ids: Vec<String> = noteindex::listing(app)?.into_keys().collect();
+    ids.sort();
+    let metas = meta::load_all(app);
+
+    let partial = destination.with_extension("zip.partial");
+    let file = fs::File::create(&partial).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let result = ids.iter().try_for_each(|id| {
+        let stored = match fs::read(notes.join(format!("{}.md", id))) {
+            Ok(stored) => stored,
+            // Deleted since the index was read
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("{}: {}", id, e)),
+        };
+        zip.start_file(format!("{}.md", id), options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(&stored).map_err(|e| e.to_string())?;
+        if let Some(note_meta) = metas.get(id) {
+            let json = serde_json::to_vec(note_meta).map_err(|e| e.to_string())?;
+            zip.start_file(format!("{}.meta.json", id), options)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(&json).map_err(|e| e.to_string())?;
+        }
+        Ok::<(), String>(())
+    });
+    let result = result
+        .and_then(|_| zip.finish().map(|_| ()).map_err(|e| e.to_string()))
+        .and_then(|_| fs::rename(&partial, destination).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(ids.len())
+}
+
+/// Deletes all but the newest `keep` archives in `dir`.
+fn prune(dir: &Path, keep: usize) {
+    for old in archives(dir).into_iter().skip(keep.max(1)) {
+        match fs::remove_file(&old) {
+            Ok(()) => println!("Deleted old backup {}", old.display()),
+            Err(e) => println!("Failed to delete old backup {}: {}", old.display(), e),
+        }
+    }
+}
+
+fn back_up<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<BackupInfo, String> {
+    let state = app.state::<BackupState>();
+    let _running = state.running.lock().map_err(|e| e.to_string())?;
+    ensure_writes_allowed(app)?;
+    flush_now(app, "backup");
+    let settings = settings(app);
+    let dir = backups_dir(app, &settings)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let name = format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        FILE_EXTENSION
+    );
+    let destination = dir.join(name);
+    let count = write_archive(app, &destination)?;
+    println!("Backed up {} notes to {}", count, destination.display());
+    prune(&dir, settings.keep);
+    Ok(info(&destination))
+}
+
+/// Whether the newest archive is older than the interval, or there is none.
+fn is_due<R: Runtime>(app: &tauri::AppHandle<R>, settings: &BackupSettings) -> bool {
+    let Ok(dir) = backups_dir(app, settings) else {
+        return false;
+    };
+    let interval = Duration::from_secs(u64::from(settings.interval_hours.max(1)) * 60 * 60);
+    let newest = archives(&dir)
+        .first()
+        .and_then(|newest| fs::metadata(newest).and_then(|m| m.modified()).ok());
+    let Some(newest) = newest else {
+        return true;
+    };
+    // One from the future (a clock set back) waits for the clock to catch up
+    SystemTime::now()
+        .duration_since(newest)
+        .is_ok_and(|age| age >= interval)
+}
+
+fn report<R: Runtime>(app: &tauri::AppHandle<R>, result: &Result<BackupInfo, String>) {
+    let state = app.state::<BackupState>();
+    match result {
+        Ok(_) => {
+            state.failing.store(false, Ordering::SeqCst);
+            tray::set_attention(app, ATTENTION_SOURCE, 0);
+        }
+        Err(e) => {
+            println!("Scheduled backup failed: {}", e);
+            tray::set_attention(app, ATTENTION_SOURCE, 1);
+            if !state.failing.swap(true, Ordering::SeqCst) {
+                let body = format!("Your notes could not be backed up: {}", e);
+                if let Err(e) = app.notification().builder().title("Backup failed").body(&body).show() {
+                    println!("Failed to show backup notification: {}", e);
+                }
+            }
+        }
+    }
+}
+
+pub fn spawn_backup_scheduler<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let settings = settings(&app);
+            if !settings.enabled || !is_due(&app, &settings) {
+                continue;
+            }
+            let handle = app.clone();
+            match tauri::async_runtime::spawn_blocking(move || back_up(&handle)).await {
+                Ok(result) => report(&app, &result),
+                Err(e) => println!("Scheduled backup panicked: {}", e),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_backup_settings(app: tauri::AppHandle) -> Result<BackupSettings, String> {
+    Ok(settings(&app))
+}
+
+#[tauri::command]
+pub async fn set_backup_settings(settings: BackupSettings, app: tauri::AppHandle) -> Result<(), String> {
+    if settings.interval_hours == 0 {
+        return Err("Backups must be at least an hour apart".to_string());
+    }
+    if settings.keep == 0 {
+        return Err("At least one backup must be kept".to_string());
+    }
+    if settings
+        .folder
+        .as_deref()
+        .is_some_and(|folder| !Path::new(folder).is_absolute())
+    {
+        return Err("The backups folder must be an absolute path".to_string());
+    }
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    app.write_store("settings.bin", SETTINGS_KEY, value)
+}
+
+/// The archives in the backups folder, newest first.
+#[tauri::command]
+pub async fn list_backups(app: tauri::AppHandle) -> Result<Vec<BackupInfo>, String> {
+    let dir = backups_dir(&app, &settings(&app))?;
+    Ok(archives(&dir).iter().map(|path| info(path)).collect())
+}
+
+/// Makes a backup now, whatever the schedule, and deletes archives past `keep`.
+#[tauri::command]
+pub async fn backup_now(app: tauri::AppHandle) -> Result<BackupInfo, String> {
+    let result = back_up(&app);
+    // A failure here is shown where it was asked for; a success clears a scheduled one's
+    if result.is_ok() {
+        report(&app, &result);
+    }
+    result
+}
+
+/// Brings back the notes in the archive at `path`, see the module docs.
+#[tauri::command]
+pub async fn restore_backup(path: String, app: tauri::AppHandle) -> Result<ImportSummary, String> {
+    if !Path::new(&path).is_file() {
+        return Err(format!("No backup at {}", path));
+    }
+    import_notes(path, app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make(dir: &Path, names: &[&str]) {
+        for name in names {
+            fs::write(dir.join(name), "").unwrap();
+        }
+    }
+
+    fn names(paths: Vec<PathBuf>) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn archives_are_listed_newest_first() {
+        let dir = TempDir::new().unwrap();
+        make(
+            dir.path(),
+            &[
+                "notes-20260301-090000.zip",
+                "notes-20260302-090000.zip",
+                "notes-20260302-090000.zip.partial",
+                "notes.txt",
+                "other-20260303-090000.zip",
+            ],
+        );
+        assert_eq!(
+            names(archives(dir.path())),
+            ["notes-20260302-090000.zip", "notes-20260301-090000.zip"]
+        );
+        assert!(archives(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_and_at_least_one() {
+        let dir = TempDir::new().unwrap();
+        make(
+            dir.path(),
+            &[
+                "notes-20260301-090000.zip",
+                "notes-20260302-090000.zip",
+                "notes-20260303-090000.zip",
+                "notes.txt",
+            ],
+        );
+        prune(dir.path(), 2);
+        assert_eq!(
+            names(archives(dir.path())),
+            ["notes-20260303-090000.zip", "notes-20260302-090000.zip"]
+        );
+        prune(dir.path(), 0);
+        assert_eq!(names(archives(dir.path())), ["notes-20260303-090000.zip"]);
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn missing_settings_take_the_defaults() {
+        let settings: BackupSettings = serde_json::from_value(serde_json::json!({ "keep": 3 })).unwrap();
+        assert!(settings.enabled);
+        assert_eq!(settings.interval_hours, 24);
+        assert_eq!(settings.keep, 3);
+        assert_eq!(settings.folder, None);
+    }
+}
@@ -0,0 +1,221 @@
+//! The "batch focusing" flag tells the `Focused` handlers to ignore focus changes we
+//! cause ourselves (show-all and friends) so they don't scramble the session order.
+//!
+//! The flag is only ever set through a `BatchFocusGuard`, and readers treat it as
+//! expired after `max_duration`, so a lost release can't freeze session ordering.
+//!
+//! Note windows created while a pass runs have their `Focused` event swallowed too, so
+//! they are recorded here and reconciled once the batch ends.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, Runtime};
+
+use crate::{dimming, settings, update_session_order};
+
+pub const DEFAULT_MAX_BATCH_MS: u64 = 2000;
+/// How long after a pass ends we keep ignoring focus events, because `set_focus` is
+/// asynchronous and its `Focused` event can arrive after the pass has returned.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+struct ActiveBatch {
+    generation: u64,
+    owner: &'static str,
+    since: Instant,
+}
+
+pub struct IsBatchFocusing {
+    active: Mutex<Option<ActiveBatch>>,
+    next_generation: Mutex<u64>,
+    max_duration: Duration,
+    /// Labels of note windows created during the active batch, oldest first
+    created_during: Mutex<Vec<String>>,
+}
+
+impl IsBatchFocusing {
+    pub fn load<R: Runtime>(app: &tauri::AppHandle<R>) -> Self {
+        Self::new(Duration::from_millis(settings::current(app).batch_focus_max_ms))
+    }
+
+    fn new(max_duration: Duration) -> Self {
+        IsBatchFocusing {
+            active: Mutex::new(None),
+            next_generation: Mutex::new(0),
+            max_duration,
+            created_during: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Whether a batch pass is running. Clears (and warns about) a flag held too long.
+    pub fn is_active(&self) -> bool {
+        let Ok(mut active) = self.active.lock() else {
+            return false;
+        };
+        match active.as_ref() {
+            Some(batch) if batch.since.elapsed() > self.max_duration => {
+                println!(
+                    "Warning: batch focus flag set by '{}' expired after {:?}, clearing it",
+                    batch.owner,
+                    batch.since.elapsed()
+                );
+                *active = None;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Called when a note window is registered; remembered only while a batch runs.
+    pub fn note_created(&self, label: &str) {
+        if self.is_active() {
+            if let Ok(mut created) = self.created_during.lock() {
+                created.push(label.to_string());
+            }
+        }
+    }
+
+    fn begin(&self, owner: &'static str) -> u64 {
+        let generation = self
+            .next_generation
+            .lock()
+            .map(|mut next| {
+                *next += 1;
+                *next
+            })
+            .unwrap_or(0);
+        if let Ok(mut created) = self.created_during.lock() {
+            created.clear();
+        }
+        if let Ok(mut active) = self.active.lock() {
+            *active = Some(ActiveBatch {
+                generation,
+                owner,
+                since: Instant::now(),
+            });
+        }
+        generation
+    }
+
+    /// Ends the batch only if it is still the one `generation` started, so a late
+    /// release from an earlier pass can't cut a newer one short. Returns the windows
+    /// created during the batch when it did end.
+    fn end(&self, generation: u64) -> Vec<String> {
+        let Ok(mut active) = self.active.lock() else {
+            return Vec::new();
+        };
+        if active.as_ref().map(|b| b.generation) != Some(generation) {
+            return Vec::new();
+        }
+        *active = None;
+        self.created_during
+            .lock()
+            .map(|mut created| std::mem::take(&mut *created))
+            .unwrap_or_default()
+    }
+}
+
+/// A window created mid-pass lost its `Focused` event to the batch and may have been
+/// raised over by the pass; give it the focus and session slot it would have had.
+fn reconcile_created<R: Runtime>(app: &tauri::AppHandle<R>, created: Vec<String>) {
+    let Some(window) = created.iter().rev().find_map(|label| app.get_webview_window(label)) else {
+        return;
+    };
+    if let Some(id) = window.label().strip_prefix("note-") {
+        update_session_order(app, id.to_string(), false);
+    }
+    let _ = window.set_focus();
+}
+
+/// Holds the batch flag for the lifetime of a pass. Dropping it (including on early
+/// return or panic) schedules the release after `SETTLE_DELAY`.
+pub struct BatchFocusGuard<R: Runtime> {
+    app: tauri::AppHandle<R>,
+    generation: u64,
+}
+
+impl<R: Runtime> BatchFocusGuard<R> {
+    pub fn begin(app: &tauri::AppHandle<R>, owner: &'static str) -> Self {
+        let generation = app.state::<IsBatchFocusing>().begin(owner);
+        dimming::force_full_opacity(app);
+        BatchFocusGuard {
+            app: app.clone(),
+            generation,
+        }
+    }
+}
+
+impl<R: Runtime> Drop for BatchFocusGuard<R> {
+    fn drop(&mut self) {
+        let app = self.app.clone();
+        let generation = self.generation;
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SETTLE_DELAY).await;
+            let created = app.state::<IsBatchFocusing>().end(generation);
+            reconcile_created(&app, created);
+            dimming::refresh_all(&app);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_session_order;
+    use crate::testing::TempBackend;
+
+    const MAX: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn leaked_flag_expires_and_session_order_resumes() {
+        let backend = TempBackend::new();
+        let batch = IsBatchFocusing::new(MAX);
+        // As the `Focused` handler does: focus changes during a batch don't count
+        let focused = |id: &str| !batch.is_active() && update_session_order(&backend, id.to_string(), false);
+
+        // A pass whose guard never released the flag
+        batch.begin("leaky pass");
+        assert!(!focused("a"));
+        assert!(get_session_order(&backend).is_empty());
+
+        std::thread::sleep(MAX * 2);
+        assert!(focused("a"));
+        assert!(focused("b"));
+        assert_eq!(get_session_order(&backend), ["a", "b"]);
+    }
+
+    #[test]
+    fn flag_holds_until_expiry() {
+        let batch = IsBatchFocusing::new(Duration::from_secs(60));
+        assert!(!batch.is_active());
+        batch.begin("pass");
+        assert!(batch.is_active());
+        assert!(batch.is_active());
+    }
+
+    #[test]
+    fn late_release_of_an_earlier_pass_keeps_the_newer_one() {
+        let batch = IsBatchFocusing::new(Duration::from_secs(60));
+        let first = batch.begin("first");
+        let second = batch.begin("second");
+
+        batch.end(first);
+        assert!(batch.is_active());
+        batch.end(second);
+        assert!(!batch.is_active());
+    }
+
+    #[test]
+    fn windows_created_during_a_pass_are_handed_back_once() {
+        let batch = IsBatchFocusing::new(Duration::from_secs(60));
+        batch.note_created("note-before");
+        let generation = batch.begin("pass");
+        batch.note_created("note-a");
+        batch.note_created("note-b");
+
+        assert_eq!(batch.end(generation), ["note-a", "note-b"]);
+        assert!(batch.end(generation).is_empty());
+        batch.note_created("note-after");
+        assert!(batch.end(generation).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! `bulk_note_action`: one action on many notes in one call, for the dashboard's
+//! multi-select. Listings are refreshed once at the end instead of once per note, and
+//! each note gets its own outcome, so one failure doesn't stop the rest.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::export::{self, ExportFormat};
+use crate::flush::flush_now;
+use crate::meta;
+use crate::safepath::sanitize_file_name;
+use crate::tags::{self, TagOutcome};
+use crate::{move_to_archive, trash_note};
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// To the trash
+    Delete,
+    Archive,
+    /// Adds `tag`
+    Tag {
+        tag: String,
+    },
+    /// `None` goes back to the default
+    Color {
+        color: Option<String>,
+    },
+    /// A document per note in `folder`, named after its title
+    Export {
+        format: ExportFormat,
+        folder: String,
+    },
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BulkOutcome {
+    Done {
+        id: String,
+    },
+    /// Only for `tag`, on notes that already had it
+    Unchanged {
+        id: String,
+    },
+    Failed {
+        id: String,
+        error: String,
+    },
+}
+
+impl From<TagOutcome> for BulkOutcome {
+    fn from(outcome: TagOutcome) -> Self {
+        match outcome {
+            TagOutcome::Updated { id } => BulkOutcome::Done { id },
+            TagOutcome::Unchanged { id } => BulkOutcome::Unchanged { id },
+            TagOutcome::Failed { id, error } => BulkOutcome::Failed { id, error },
+        }
+    }
+}
+
+fn each(ids: &[String], mut run: impl FnMut(&str) -> Result<(), String>) -> Vec<BulkOutcome> {
+    ids.iter()
+        .map(|id| match run(id) {
+            Ok(()) => BulkOutcome::Done { id: id.clone() },
+            Err(error) => BulkOutcome::Failed { id: id.clone(), error },
+        })
+        .collect()
+}
+
+/// `<title>.<ext>` in `folder`, or `<title> (2).<ext>` and so on if that is taken.
+fn export_path(folder: &Path, title: &str, id: &str, format: ExportFormat) -> PathBuf {
+    let stem = sanitize_file_name(title).unwrap_or_else(|_| id.to_string());
+    let mut path = folder.join(format!("{}.{}", stem, format.extension()));
+    let mut n = 2;
+    while path.exists() {
+        path = folder.join(format!("{} ({}).{}", stem, n, format.extension()));
+        n += 1;
+    }
+    path
+}
+
+fn export_one<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    format: ExportFormat,
+    folder: &Path,
+) -> Result<(), String> {
+    let (title, bytes) = export::render_note(app, id, format)?;
+    fs::write(export_path(folder, &title, id, format), bytes).map_err(|e| e.to_string())
+}
+
+/// Runs `action` on every note in `ids` and returns how it went for each, in order.
+#[tauri::command]
+pub async fn bulk_note_action(
+    ids: Vec<String>,
+    action: BulkAction,
+    app: tauri::AppHandle,
+) -> Result<Vec<BulkOutcome>, String> {
+    println!("Bulk {:?} on {} notes", action, ids.len());
+    let outcomes = match action {
+        BulkAction::Delete => each(&ids, |id| trash_note(&app, id)),
+        BulkAction::Archive => each(&ids, |id| move_to_archive(&app, id)),
+        BulkAction::Color { color } => each(&ids, |id| meta::set_color(&app, id, color.clone())),
+        // Refreshes listings itself, once
+        BulkAction::Tag { tag } => {
+            let outcomes = tags::tag_notes(&app, &ids, &tag)?;
+            return Ok(outcomes.into_iter().map(BulkOutcome::from).collect());
+        }
+        // Changes nothing listed
+        BulkAction::Export { format, folder } => {
+            let folder = Path::new(&folder);
+            if !folder.is_absolute() {
+                return Err("The export folder must be an absolute path".to_string());
+            }
+            fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+            flush_now(&app, "export");
+            return Ok(each(&ids, |id| export_one(&app, id, format, folder)));
+        }
+    };
+    app.emit_event("refresh-notes", ());
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn each_note_gets_its_own_outcome() {
+        let ids = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut seen = Vec::new();
+        let outcomes = each(&ids, |id| {
+            seen.push(id.to_string());
+            if id == "b" {
+                Err("no such note".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(seen, ids);
+        let statuses: Vec<String> = outcomes
+            .iter()
+            .map(|outcome| match outcome {
+                BulkOutcome::Done { id } => format!("{} done", id),
+                BulkOutcome::Unchanged { id } => format!("{} unchanged", id),
+                BulkOutcome::Failed { id, error } => format!("{} failed: {}", id, error),
+            })
+            .collect();
+        assert_eq!(statuses, ["a done", "b failed: no such note", "c done"]);
+    }
+
+    #[test]
+    fn exports_are_named_after_titles_without_clobbering() {
+        let dir = TempDir::new().unwrap();
+        let first = export_path(dir.path(), "Plan: week 2", "a", ExportFormat::Pdf);
+        assert_eq!(first, dir.path().join("Plan_ week 2.pdf"));
+        fs::write(&first, "").unwrap();
+        let second = export_path(dir.path(), "Plan: week 2", "b", ExportFormat::Pdf);
+        assert_eq!(second, dir.path().join("Plan_ week 2 (2).pdf"));
+        fs::write(&second, "").unwrap();
+        assert_eq!(
+            export_path(dir.path(), "Plan: week 2", "c", ExportFormat::Pdf),
+            dir.path().join("Plan_ week 2 (3).pdf")
+        );
+        assert_eq!(
+            export_path(dir.path(), "Plan: week 2", "d", ExportFormat::Html),
+            dir.path().join("Plan_ week 2.html")
+        );
+        assert_eq!(
+            export_path(dir.path(), "...", "e", ExportFormat::Html),
+            dir.path().join("e.html")
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Bounded LRU of listing previews, shared by every view that lists notes (dashboard,
+//! pinboard, trash, archive). Entries are keyed by file path and checked against the
+//! file's size and modification time, so a stale or evicted entry only costs a re-read.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::backend::NotesBackend;
+use crate::settings;
+use crate::tasks::TaskCounts;
+
+pub const DEFAULT_MAX_ENTRIES: usize = 2000;
+pub const DEFAULT_MAX_BYTES: usize = 8 * 1024 * 1024;
+/// Rough per-entry bookkeeping (path, map slots, timestamps) on top of the strings.
+const ENTRY_OVERHEAD: usize = 96;
+
+#[derive(Clone)]
+pub struct CachedPreview {
+    pub preview: String,
+    pub title: String,
+    /// Encrypted; preview and title are placeholders
+    pub locked: bool,
+    /// Task list items in what was read, see `tasks.rs`
+    pub tasks: TaskCounts,
+}
+
+struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    value: CachedPreview,
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<PathBuf, Entry>,
+    /// last_used tick -> path; the first key is the least recently listed entry
+    order: BTreeMap<u64, PathBuf>,
+    tick: u64,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Hits over lookups since start, 0 before the first lookup
+    pub hit_rate: f64,
+}
+
+pub struct PreviewCache {
+    inner: Mutex<Lru>,
+    max_entries: usize,
+    max_bytes: usize,
+}
+
+impl PreviewCache {
+    pub fn load(backend: &impl NotesBackend) -> Self {
+        let settings = settings::current(backend);
+        PreviewCache {
+            inner: Mutex::new(Lru::default()),
+            max_entries: settings.preview_cache_max_entries,
+            max_bytes: settings.preview_cache_max_bytes,
+        }
+    }
+
+    /// The cached preview for `path` if the file hasn't changed since it was cached.
+    pub fn get(&self, path: &Path, modified: Option<SystemTime>, len: u64) -> Option<CachedPreview> {
+        let mut lru = self.inner.lock().ok()?;
+        let lru = &mut *lru;
+        lru.tick += 1;
+        let tick = lru.tick;
+        match lru.entries.get_mut(path) {
+            Some(entry) if entry.modified == modified && entry.len == len => {
+                lru.order.remove(&entry.last_used);
+                lru.order.insert(tick, path.to_path_buf());
+                entry.last_used = tick;
+                lru.hits += 1;
+                Some(entry.value.clone())
+            }
+            _ => {
+                lru.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, path: &Path, modified: Option<SystemTime>, len: u64, value: CachedPreview) {
+        let bytes = ENTRY_OVERHEAD + path.as_os_str().len() + value.preview.len() + value.title.len();
+        // An entry that can never fit would just evict everything else
+        if bytes > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        let Ok(mut lru) = self.inner.lock() else {
+            return;
+        };
+        let lru = &mut *lru;
+        lru.tick += 1;
+        let tick = lru.tick;
+
+        if let Some(old) = lru.entries.remove(path) {
+            lru.order.remove(&old.last_used);
+            lru.bytes -= old.bytes;
+        }
+        while lru.entries.len() >= self.max_entries || lru.bytes + bytes > self.max_bytes {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = lru.entries.remove(&oldest) {
+                lru.bytes -= evicted.bytes;
+                lru.evictions += 1;
+            }
+        }
+
+        lru.order.insert(tick, path.to_path_buf());
+        lru.bytes += bytes;
+        lru.entries.insert(
+            path.to_path_buf(),
+            Entry {
+                modified,
+                len,
+                value,
+                bytes,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Drops the entry for `path`. Saves and deletes call this, since a rewrite within the
+    /// filesystem's timestamp granularity can keep both the size and the mtime.
+    pub fn remove(&self, path: &Path) {
+        if let Ok(mut lru) = self.inner.lock() {
+            if let Some(old) = lru.entries.remove(path) {
+                lru.order.remove(&old.last_used);
+                lru.bytes -= old.bytes;
+            }
+        }
+    }
+
+    /// Drops every entry, for when what a preview would show changed without the file changing.
+    pub fn clear(&self) {
+        if let Ok(mut lru) = self.inner.lock() {
+            lru.entries.clear();
+            lru.order.clear();
+            lru.bytes = 0;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let Ok(lru) = self.inner.lock() else {
+            return CacheStats {
+                entries: 0,
+                bytes: 0,
+                max_entries: self.max_entries,
+                max_bytes: self.max_bytes,
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+                hit_rate: 0.0,
+            };
+        };
+        let lookups = lru.hits + lru.misses;
+        CacheStats {
+            entries: lru.entries.len(),
+            bytes: lru.bytes,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            hits: lru.hits,
+            misses: lru.misses,
+            evictions: lru.evictions,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                lru.hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+    use crate::{read_note_info, NoteInfo};
+
+    const NOTES: usize = 5000;
+    /// Every `n`th note is in a view: a tag, a color, the pinboard, say.
+    const VIEWS: [usize; 3] = [3, 5, 7];
+
+    fn content(i: usize) -> String {
+        let body = "Lorem ipsum dolor sit amet. ".repeat(1 + i % 8);
+        format!("# Note {}\n\n- [ ] item {}\n{}\n", i, i, body)
+    }
+
+    fn generate(backend: &TempBackend) -> Vec<PathBuf> {
+        (0..NOTES)
+            .map(|i| backend.put_note(&format!("n{}", i), &content(i)))
+            .collect()
+    }
+
+    fn list_view(cache: &PreviewCache, paths: &[PathBuf], every: usize) -> Vec<NoteInfo> {
+        let metas = HashMap::new();
+        (0..NOTES)
+            .step_by(every)
+            .map(|i| read_note_info(cache, None, &metas, format!("n{}", i), &paths[i]))
+            .collect()
+    }
+
+    /// Switches between the views a few times, listing each twice as a refresh would,
+    /// and checks every listing against the notes and the cache against its budget.
+    fn browse(cache: &PreviewCache, paths: &[PathBuf]) -> CacheStats {
+        for _ in 0..3 {
+            for every in VIEWS {
+                for _ in 0..2 {
+                    for note in list_view(cache, paths, every) {
+                        let i: usize = note.id[1..].parse().unwrap();
+                        assert_eq!(note.title, format!("Note {}", i));
+                        assert_eq!(note.tasks.open, 1);
+                    }
+                    let stats = cache.stats();
+                    assert!(stats.entries <= stats.max_entries, "{:?}", stats);
+                    assert!(stats.bytes <= stats.max_bytes, "{:?}", stats);
+                }
+            }
+        }
+        cache.stats()
+    }
+
+    #[test]
+    fn stays_within_the_entry_cap_across_views() {
+        let backend = TempBackend::new();
+        let paths = generate(&backend);
+        let cache = PreviewCache::load(&backend);
+
+        let stats = browse(&cache, &paths);
+        assert_eq!(stats.max_entries, DEFAULT_MAX_ENTRIES);
+        assert!(stats.evictions > 0, "{:?}", stats);
+        assert!(stats.hit_rate > 0.4, "{:?}", stats);
+    }
+
+    #[test]
+    fn stays_within_the_byte_budget_across_views() {
+        let backend = TempBackend::new();
+        let paths = generate(&backend);
+        // Room for the largest view, but not for all three
+        let largest = PreviewCache::load(&backend);
+        list_view(&largest, &paths, VIEWS[0]);
+        let budget = largest.stats().bytes * 11 / 10;
+        backend.set_setting("preview_cache_max_bytes", serde_json::json!(budget));
+        let cache = PreviewCache::load(&backend);
+
+        let stats = browse(&cache, &paths);
+        assert_eq!(stats.max_bytes, budget);
+        assert!(stats.entries < DEFAULT_MAX_ENTRIES, "{:?}", stats);
+        assert!(stats.evictions > 0, "{:?}", stats);
+        assert!(stats.hit_rate > 0.4, "{:?}", stats);
+    }
+
+    #[test]
+    fn changed_files_miss_and_evicted_ones_are_read_again() {
+        let backend = TempBackend::new();
+        let path = backend.put_note("a", "# Before");
+        let cache = PreviewCache::load(&backend);
+        let metas = HashMap::new();
+
+        let title = |cache: &PreviewCache| read_note_info(cache, None, &metas, "a".to_string(), &path).title;
+        assert_eq!(title(&cache), "Before");
+        assert_eq!(title(&cache), "Before");
+        backend.put_note("a", "# After, longer");
+        assert_eq!(title(&cache), "After, longer");
+        cache.clear();
+        assert_eq!(title(&cache), "After, longer");
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+    }
+}
@@ -0,0 +1,190 @@
+//! The pending reminders as an iCalendar file, `calendar/sticky-notes.ics` in the app
+//! data folder, for calendar apps to subscribe to. It is rewritten whenever the reminders
+//! change, so fired and cancelled ones drop out on the next write. Each event's UID is
+//! its reminder's id, so clients update events rather than adding them again.
+//!
+//! Times are written in UTC, which clients show in their own zone, so the file needs no
+//! VTIMEZONE. Turning `calendar_file` off in `Settings` removes the file.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::backend::NotesBackend;
+use crate::deeplink::SCHEME;
+use crate::settings;
+use crate::storage;
+
+/// Tray item that shows the file in the file manager.
+pub const MENU_ID: &str = "reveal_calendar";
+
+/// One reminder as the calendar shows it.
+pub struct CalendarEvent<'a> {
+    /// The reminder's id
+    pub uid: &'a str,
+    pub note_id: &'a str,
+    /// The note's title
+    pub summary: String,
+    pub message: Option<&'a str>,
+    /// Unix millis
+    pub due_at: u64,
+    /// Unix millis
+    pub created_at: u64,
+}
+
+pub fn calendar_path(backend: &impl NotesBackend) -> Result<PathBuf, String> {
+    Ok(backend.data_dir()?.join("calendar").join("sticky-notes.ics"))
+}
+
+/// Escapes the characters TEXT values give a meaning to (RFC 5545 3.3.11).
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Appends `line` with its CRLF, folded so no line is over 75 bytes (RFC 5545 3.1).
+fn push_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            // The leading space counts towards the continuation's 75
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn utc(millis: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis as i64)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// A VCALENDAR with one VEVENT per reminder.
+pub fn render(events: &[CalendarEvent]) -> String {
+    let mut ics = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//Sticky Notes//Reminders//EN",
+        "CALSCALE:GREGORIAN",
+        "X-WR-CALNAME:Sticky Notes",
+    ] {
+        push_line(&mut ics, line);
+    }
+    for event in events {
+        let link = format!("{}://note/{}", SCHEME, event.note_id);
+        let description = match event.message {
+            Some(message) => format!("{}\n{}", message, link),
+            None => link.clone(),
+        };
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}@sticky-notes", event.uid));
+        push_line(&mut ics, &format!("DTSTAMP:{}", utc(event.created_at)));
+        push_line(&mut ics, &format!("DTSTART:{}", utc(event.due_at)));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape_text(&event.summary)));
+        push_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&description)));
+        push_line(&mut ics, &format!("URL:{}", link));
+        push_line(&mut ics, "END:VEVENT");
+    }
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Rewrites the calendar file with `events`, or removes it while it's turned off.
+pub fn write(backend: &impl NotesBackend, events: &[CalendarEvent]) -> Result<(), String> {
+    let path = calendar_path(backend)?;
+    if !settings::current(backend).calendar_file {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    storage::write_atomically(&path, &render(events), false).map_err(|e| e.to_string())
+}
+
+/// Where the calendar file is, to subscribe to; `None` while it's turned off.
+#[tauri::command]
+pub async fn get_calendar_file_path(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    if !settings::current(&app).calendar_file {
+        return Ok(None);
+    }
+    Ok(Some(calendar_path(&app)?.to_string_lossy().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    fn event<'a>(uid: &'a str, message: Option<&'a str>) -> CalendarEvent<'a> {
+        CalendarEvent {
+            uid,
+            note_id: "note-1",
+            summary: "Groceries, eggs; milk".to_string(),
+            message,
+            due_at: 1_772_355_600_000,
+            created_at: 1_772_300_000_000,
+        }
+    }
+
+    #[test]
+    fn reminders_become_vevents() {
+        let ics = render(&[event("r-1", Some("Buy\nmilk"))]);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        let vevent = &ics[ics.find("BEGIN:VEVENT").unwrap()..ics.find("END:VCALENDAR").unwrap()];
+        assert_eq!(
+            vevent,
+            "BEGIN:VEVENT\r\n\
+             UID:r-1@sticky-notes\r\n\
+             DTSTAMP:20260228T173320Z\r\n\
+             DTSTART:20260301T090000Z\r\n\
+             SUMMARY:Groceries\\, eggs\\; milk\r\n\
+             DESCRIPTION:Buy\\nmilk\\nstickynotes://note/note-1\r\n\
+             URL:stickynotes://note/note-1\r\n\
+             END:VEVENT\r\n"
+        );
+        assert_eq!(render(&[]).matches("VEVENT").count(), 0);
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut ics = String::new();
+        push_line(&mut ics, &format!("SUMMARY:{}", "é".repeat(60)));
+        let lines: Vec<&str> = ics.split("\r\n").collect();
+        assert!(lines.len() > 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1..lines.len() - 1].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(ics.replace("\r\n ", ""), format!("SUMMARY:{}\r\n", "é".repeat(60)));
+    }
+
+    #[test]
+    fn the_file_goes_away_when_turned_off() {
+        let backend = TempBackend::new();
+        write(&backend, &[event("r-1", None)]).unwrap();
+        let path = calendar_path(&backend).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("UID:r-1@sticky-notes"));
+
+        backend.set_setting("calendar_file", serde_json::json!(false));
+        write(&backend, &[event("r-1", None)]).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,70 @@
+//! Notes captured from the clipboard by a global shortcut: text becomes the note's
+//! content, an image is saved as an attachment the note shows. The note opens at the
+//! mouse cursor, so it appears where the user is looking.
+
+use tauri::Runtime;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::meta::Rect;
+use crate::notewindow::{NoteWindowOptions, DEFAULT_NOTE_SIZE};
+use crate::{create_note_window, write_note};
+
+/// Encodes the clipboard's RGBA pixels as a PNG.
+fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// The content for note `id`: the clipboard's text, or else its image saved as one of the
+/// note's attachments.
+fn clipboard_content<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<String, String> {
+    if let Some(text) = app.clipboard().read_text().ok().filter(|text| !text.trim().is_empty()) {
+        return Ok(text);
+    }
+    let image = app
+        .clipboard()
+        .read_image()
+        .map_err(|_| "The clipboard holds no text or image")?;
+    let png = encode_png(image.rgba(), image.width(), image.height())?;
+    let attachment = attachments::save_image(app, id, &png, "image/png")?;
+    Ok(format!("{}\n", attachments::markdown_for(&attachment)))
+}
+
+/// Where a window of `size` should open: its top left corner at the cursor, moved in as
+/// far as needed to fit the display the cursor is on.
+pub fn at_cursor<R: Runtime>(app: &tauri::AppHandle<R>, size: (f64, f64)) -> Option<Rect> {
+    let cursor = app.cursor_position().ok()?;
+    let monitor = app.monitor_from_point(cursor.x, cursor.y).ok().flatten()?;
+    let scale = monitor.scale_factor();
+    let cursor = cursor.to_logical::<f64>(scale);
+    let position = monitor.position().to_logical::<f64>(scale);
+    let display = monitor.size().to_logical::<f64>(scale);
+    let (width, height) = size;
+    Some(Rect {
+        x: cursor.x.min(position.x + display.width - width).max(position.x),
+        y: cursor.y.min(position.y + display.height - height).max(position.y),
+        width,
+        height,
+    })
+}
+
+/// Creates a note from what's on the clipboard and opens it at the cursor.
+pub fn note_from_clipboard<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<String, String> {
+    let id = Uuid::new_v4().to_string();
+    let content = clipboard_content(app, &id)?;
+    write_note(app, &id, &content)?;
+    let mut options = NoteWindowOptions::open(&id);
+    if let Some(rect) = at_cursor(app, DEFAULT_NOTE_SIZE) {
+        options = options.geometry(rect);
+    }
+    create_note_window(app, options)?;
+    Ok(id)
+}
@@ -0,0 +1,329 @@
+//! Feed of note files changed by something other than us (sync clients, editors).
+//!
+//! A poller compares the notes directory against what it last saw. A filesystem watcher
+//! wakes it as soon as a note file is touched; the interval is the fallback for volumes
+//! where watching doesn't work (network shares, some sync mounts). Our own writes,
+//! deletes and moves run through `own_change`, which holds the same lock and updates the
+//! baseline, so they never show up in the feed.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{Emitter, EventTarget, Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::conflicts;
+use crate::git;
+use crate::meta;
+use crate::mute::{self, Notification};
+use crate::noteindex;
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::suspect;
+use crate::{notes_dir, now_millis, restart};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// After the watcher fires, wait this long so an editor's save (often several events)
+/// is picked up in one poll.
+const WATCH_SETTLE: Duration = Duration::from_millis(300);
+/// Oldest records are dropped beyond this, acknowledged or not.
+const MAX_RECORDS: usize = 500;
+const ACKNOWLEDGED_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Modified,
+    Created,
+    Deleted,
+}
+
+/// What happened to the note's open window, if it had one.
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowReaction {
+    /// The window was told to reload from disk.
+    AutoReloaded,
+    /// The window holds unsaved edits, so it was left alone.
+    Conflict,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ExternalChange {
+    id: String,
+    kind: ChangeKind,
+    /// Unix milliseconds of the latest change folded into this record
+    detected_at: u64,
+    byte_delta: i64,
+    window: Option<WindowReaction>,
+    acknowledged_at: Option<u64>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ChangesPending {
+    count: usize,
+}
+
+type FileStamp = (Option<SystemTime>, u64);
+
+#[derive(Default)]
+pub struct ExternalChanges {
+    /// Last seen stamp per note id; `None` until the first scan sets the baseline
+    known: Mutex<Option<HashMap<String, FileStamp>>>,
+    records: Mutex<Vec<ExternalChange>>,
+    /// Signalled by the watcher to poll ahead of the interval
+    wake: tokio::sync::Notify,
+    /// Kept alive for as long as the app runs; `None` if watching failed
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+fn stamp(metadata: &fs::Metadata) -> FileStamp {
+    (metadata.modified().ok(), metadata.len())
+}
+
+fn scan_stamps(dir: &std::path::Path) -> HashMap<String, FileStamp> {
+    scan_notes(dir, ScanOptions::default())
+        .filter_map(|entry| match entry {
+            ScanEntry::Note { id, path, .. } => fs::metadata(path).ok().map(|m| (id, stamp(&m))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Runs one of our own file operations on the notes of `ids` and re-baselines them, so
+/// the poller can't mistake it for an external change.
+pub fn own_change<R: Runtime, T>(app: &tauri::AppHandle<R>, ids: &[&str], op: impl FnOnce() -> T) -> T {
+    let state = app.state::<ExternalChanges>();
+    let Ok(mut known) = state.known.lock() else {
+        return op();
+    };
+    let result = op();
+    if let (Some(known), Ok(dir)) = (known.as_mut(), notes_dir(app)) {
+        for id in ids {
+            match fs::metadata(dir.join(format!("{}.md", id))) {
+                Ok(metadata) => known.insert(id.to_string(), stamp(&metadata)),
+                Err(_) => known.remove(*id),
+            };
+        }
+    }
+    noteindex::refresh_ids(app, ids);
+    git::changed(app);
+    result
+}
+
+fn react_in_window<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, kind: ChangeKind) -> Option<WindowReaction> {
+    let label = format!("note-{}", id);
+    app.get_webview_window(&label)?;
+    if restart::is_editor_dirty(app, id) {
+        return Some(WindowReaction::Conflict);
+    }
+    if kind != ChangeKind::Deleted {
+        let _ = app.emit_to(EventTarget::webview_window(&label), "note-changed-externally", id);
+    }
+    Some(WindowReaction::AutoReloaded)
+}
+
+/// Folds a detected change into the feed; an unacknowledged record for the same note
+/// is updated rather than duplicated.
+fn record(records: &mut Vec<ExternalChange>, change: ExternalChange) {
+    if let Some(existing) = records
+        .iter_mut()
+        .find(|r| r.id == change.id && r.acknowledged_at.is_none())
+    {
+        existing.kind = match (existing.kind, change.kind) {
+            // Still new as far as the user is concerned
+            (ChangeKind::Created, ChangeKind::Modified) => ChangeKind::Created,
+            (_, kind) => kind,
+        };
+        existing.detected_at = change.detected_at;
+        existing.byte_delta += change.byte_delta;
+        existing.window = change.window.or(existing.window);
+        return;
+    }
+    records.push(change);
+    if records.len() > MAX_RECORDS {
+        let excess = records.len() - MAX_RECORDS;
+        records.drain(..excess);
+    }
+}
+
+/// Unacknowledged records, leaving out muted notes so they don't raise notifications.
+fn pending_count(records: &[ExternalChange], muted: &HashSet<String>) -> usize {
+    records
+        .iter()
+        .filter(|r| r.acknowledged_at.is_none() && !muted.contains(&r.id))
+        .count()
+}
+
+fn poll<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Ok(dir) = notes_dir(app) else {
+        return;
+    };
+    let state = app.state::<ExternalChanges>();
+    let detected = {
+        let Ok(mut known) = state.known.lock() else {
+            return;
+        };
+        let current = scan_stamps(&dir);
+        let Some(previous) = known.replace(current.clone()) else {
+            return;
+        };
+
+        let now = now_millis();
+        let mut detected = Vec::new();
+        for (id, (modified, len)) in &current {
+            let (kind, delta) = match previous.get(id) {
+                None => (ChangeKind::Created, *len as i64),
+                Some((old_modified, old_len)) if old_modified != modified || old_len != len => {
+                    (ChangeKind::Modified, *len as i64 - *old_len as i64)
+                }
+                Some(_) => continue,
+            };
+            detected.push((id.clone(), kind, delta, now));
+        }
+        for (id, (_, old_len)) in &previous {
+            if !current.contains_key(id) {
+                detected.push((id.clone(), ChangeKind::Deleted, -(*old_len as i64), now));
+            }
+        }
+        detected
+    };
+
+    let Ok(mut records) = state.records.lock() else {
+        return;
+    };
+    let now = now_millis();
+    records.retain(|r| {
+        r.acknowledged_at
+            .is_none_or(|at| now.saturating_sub(at) < ACKNOWLEDGED_TTL_MS)
+    });
+    if detected.is_empty() {
+        return;
+    }
+    let ids: Vec<&str> = detected.iter().map(|(id, ..)| id.as_str()).collect();
+    noteindex::refresh_ids(app, &ids);
+    git::changed(app);
+    let mut notify = false;
+    for (id, kind, byte_delta, detected_at) in detected {
+        if kind == ChangeKind::Modified {
+            suspect::check(app, &id);
+        }
+        notify |= !mute::suppress(app, &id, Notification::ExternalChange);
+        let window = react_in_window(app, &id, kind);
+        record(
+            &mut records,
+            ExternalChange {
+                id,
+                kind,
+                detected_at,
+                byte_delta,
+                window,
+                acknowledged_at: None,
+            },
+        );
+    }
+
+    if notify {
+        let count = pending_count(&records, &mute::muted_ids(&meta::load_all(app)));
+        app.emit_event("external-changes-pending", ChangesPending { count });
+    }
+    app.emit_event("refresh-notes", ());
+}
+
+/// Note files themselves; temp files, swap files and asset folders don't wake the poller.
+fn is_note_file(path: &std::path::Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(".md") && !name.starts_with('.'))
+}
+
+fn start_watcher<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<RecommendedWatcher, String> {
+    let dir = notes_dir(app)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let handle = app.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|event| event.paths.iter().any(|path| is_note_file(path))) {
+            handle.state::<ExternalChanges>().wake.notify_one();
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+    Ok(watcher)
+}
+
+/// (Re)starts watching the notes folder, dropping the watcher of any previous one.
+fn watch_notes_dir<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let watcher = match start_watcher(app) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            println!("Watching the notes folder failed, polling only: {}", e);
+            None
+        }
+    };
+    if let Ok(mut slot) = app.state::<ExternalChanges>().watcher.lock() {
+        *slot = watcher;
+    }
+}
+
+/// Runs `op`, which moves the notes folder, with our own writes held off. Afterwards the
+/// new folder is watched and the next poll takes a fresh baseline instead of reporting
+/// every note as created.
+pub fn repoint<R: Runtime, T>(app: &tauri::AppHandle<R>, op: impl FnOnce() -> T) -> T {
+    let result = {
+        let state = app.state::<ExternalChanges>();
+        let Ok(mut known) = state.known.lock() else {
+            return op();
+        };
+        let result = op();
+        *known = None;
+        result
+    };
+    watch_notes_dir(app);
+    result
+}
+
+pub fn spawn_change_poller<R: Runtime>(app: tauri::AppHandle<R>) {
+    watch_notes_dir(&app);
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            poll(&app);
+            conflicts::sweep(&app);
+            let woken = tokio::time::timeout(POLL_INTERVAL, app.state::<ExternalChanges>().wake.notified()).await;
+            if woken.is_ok() {
+                tokio::time::sleep(WATCH_SETTLE).await;
+            }
+        }
+    });
+}
+
+/// Records detected after `since` (Unix milliseconds), or all of them.
+#[tauri::command]
+pub async fn get_external_changes(since: Option<u64>, app: tauri::AppHandle) -> Result<Vec<ExternalChange>, String> {
+    let state = app.state::<ExternalChanges>();
+    let records = state.records.lock().map_err(|e| e.to_string())?;
+    Ok(records
+        .iter()
+        .filter(|r| since.is_none_or(|since| r.detected_at > since))
+        .cloned()
+        .collect())
+}
+
+/// Marks the pending records of these note ids as seen; they age out after a day.
+#[tauri::command]
+pub async fn acknowledge_external_changes(ids: Vec<String>, app: tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<ExternalChanges>();
+    let mut records = state.records.lock().map_err(|e| e.to_string())?;
+    let now = now_millis();
+    for record in records.iter_mut() {
+        if record.acknowledged_at.is_none() && ids.contains(&record.id) {
+            record.acknowledged_at = Some(now);
+        }
+    }
+    let count = pending_count(&records, &mute::muted_ids(&meta::load_all(&app)));
+    app.emit_event("external-changes-pending", ChangesPending { count });
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Click-through notes: the window ignores the mouse, so a pinned, see-through reference
+//! note can sit over other apps without getting in the way. The flag is kept in the
+//! note's `NoteMeta` and reapplied when the window is rebuilt.
+//!
+//! A click-through window can't be clicked to turn the mode off again, so the
+//! `ReleaseClickThrough` shortcut makes every note interactive again.
+
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::meta;
+use crate::WindowRegistry;
+
+#[derive(serde::Serialize, Clone)]
+struct ClickThroughChanged {
+    id: String,
+    enabled: bool,
+}
+
+fn apply<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, enabled: bool) -> Result<(), String> {
+    meta::update_meta(app, id, |meta| meta.click_through = enabled)?;
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        window.set_ignore_cursor_events(enabled).map_err(|e| e.to_string())?;
+    }
+    app.emit_note_event(
+        id,
+        "click-through-changed",
+        ClickThroughChanged {
+            id: id.to_string(),
+            enabled,
+        },
+    );
+    Ok(())
+}
+
+/// Turns click-through off for every open note that has it. Returns how many changed.
+pub fn release_all<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<usize, String> {
+    let mut released = 0;
+    for label in app.state::<WindowRegistry>().note_labels() {
+        let Some(id) = label.strip_prefix("note-") else {
+            continue;
+        };
+        if meta::get_meta(app, id).click_through {
+            apply(app, id, false)?;
+            released += 1;
+        }
+    }
+    Ok(released)
+}
+
+#[tauri::command]
+pub async fn set_click_through(id: String, enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    apply(&app, &id, enabled)
+}
@@ -0,0 +1,101 @@
+//! Rolling a note window up to a thin title strip and back. The collapsed flag lives in
+//! the note's `NoteMeta`, whose width and height keep the expanded size while the window
+//! is rolled up (`meta` leaves them alone), so expanding and restarting both bring the
+//! note back at its full size.
+
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::meta;
+use crate::notewindow::{DEFAULT_NOTE_SIZE, MIN_NOTE_SIZE};
+
+/// Logical height of a collapsed note: the frontend's drag region and buttons, no content.
+pub const COLLAPSED_HEIGHT: f64 = 32.0;
+
+#[derive(serde::Serialize, Clone)]
+struct NoteCollapsedChanged {
+    id: String,
+    collapsed: bool,
+}
+
+fn note_window<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Option<tauri::WebviewWindow<R>> {
+    app.get_webview_window(&format!("note-{}", id))
+}
+
+/// Shrinks `window` to the title strip at its current width. Resizing is turned off
+/// until it is expanded, so the strip can't be dragged open by accident.
+pub fn shrink<R: Runtime>(window: &tauri::WebviewWindow<R>) -> Result<(), String> {
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let width = window
+        .inner_size()
+        .map_err(|e| e.to_string())?
+        .to_logical::<f64>(scale)
+        .width;
+    window
+        .set_min_size(Some(tauri::LogicalSize::new(MIN_NOTE_SIZE.0, COLLAPSED_HEIGHT)))
+        .and_then(|_| window.set_size(tauri::LogicalSize::new(width, COLLAPSED_HEIGHT)))
+        .and_then(|_| window.set_resizable(false))
+        .map_err(|e| e.to_string())
+}
+
+fn grow<R: Runtime>(window: &tauri::WebviewWindow<R>, size: (f64, f64)) -> Result<(), String> {
+    window
+        .set_resizable(true)
+        .and_then(|_| window.set_min_size(Some(tauri::LogicalSize::new(MIN_NOTE_SIZE.0, MIN_NOTE_SIZE.1))))
+        .and_then(|_| window.set_size(tauri::LogicalSize::new(size.0, size.1)))
+        .map_err(|e| e.to_string())
+}
+
+/// Rolls note `id` up to its title strip, remembering the size it had.
+#[tauri::command]
+pub async fn collapse_note(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let window = note_window(&app, &id);
+    // The saved size may lag behind a resize still being debounced
+    let size = window.as_ref().and_then(|window| {
+        let scale = window.scale_factor().ok()?;
+        Some(window.inner_size().ok()?.to_logical::<f64>(scale))
+    });
+    meta::update_meta(&app, &id, |meta| {
+        if !meta.collapsed {
+            if let Some(size) = size {
+                meta.width = Some(size.width);
+                meta.height = Some(size.height);
+            }
+        }
+        meta.collapsed = true;
+    })?;
+    if let Some(window) = &window {
+        shrink(window)?;
+    }
+    app.emit_note_event(
+        &id,
+        "note-collapsed-changed",
+        NoteCollapsedChanged {
+            id: id.clone(),
+            collapsed: true,
+        },
+    );
+    Ok(())
+}
+
+/// Rolls note `id` back down to the size it had before it was collapsed.
+#[tauri::command]
+pub async fn expand_note(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let meta = meta::update_meta(&app, &id, |meta| meta.collapsed = false)?;
+    if let Some(window) = note_window(&app, &id) {
+        let size = (
+            meta.width.unwrap_or(DEFAULT_NOTE_SIZE.0).max(MIN_NOTE_SIZE.0),
+            meta.height.unwrap_or(DEFAULT_NOTE_SIZE.1).max(MIN_NOTE_SIZE.1),
+        );
+        grow(&window, size)?;
+    }
+    app.emit_note_event(
+        &id,
+        "note-collapsed-changed",
+        NoteCollapsedChanged {
+            id: id.clone(),
+            collapsed: false,
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,342 @@
+//! Sync conflicts and their resolution. Sync clients that can't merge leave a copy next
+//! to the note (`<id> (conflicted copy).md`, `<id> (1).md`, `<id>.sync-conflict-….md`),
+//! which the scanner quarantines. The change poller sweeps for those copies and keeps
+//! one pending conflict per note in `session.bin` until it is resolved, so conflicts
+//! survive restarts and count towards the tray's attention badge.
+//!
+//! Resolving never loses a version: whatever isn't kept goes to the trash as a note of
+//! its own, and the resolution is recorded in the journal.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use tauri::{Emitter, EventTarget, Runtime};
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::duplicate::append_to_title;
+use crate::journal;
+use crate::recycle::{recycle_content, RecycledKind};
+use crate::safepath::{Root, SafePath};
+use crate::scan::is_valid_note_id;
+use crate::vault;
+use crate::{derive_title, notes_dir, now_millis, to_millis, tray, write_note};
+
+const CONFLICTS_KEY: &str = "conflicts";
+/// Above this many line pairs the diff gives up on alignment and shows both sides whole.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PendingConflict {
+    /// File name of the sync client's copy, in the notes directory
+    copy: String,
+    detected_at: u64,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ConflictVersion {
+    content: String,
+    size: u64,
+    modified_at: Option<u64>,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffSide {
+    Both,
+    /// Only in the note
+    Mine,
+    /// Only in the sync client's copy
+    Theirs,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct DiffLine {
+    side: DiffSide,
+    text: String,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Conflict {
+    id: String,
+    #[serde(flatten)]
+    pending: PendingConflict,
+    mine: ConflictVersion,
+    theirs: ConflictVersion,
+    diff: Vec<DiffLine>,
+    /// The versions were too large to align, so `diff` lists each side whole
+    diff_truncated: bool,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Resolution {
+    KeepMine,
+    KeepTheirs,
+    /// The copy becomes a new note titled "… (conflict copy)"
+    KeepBoth,
+    Merged {
+        content: String,
+    },
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ConflictResolved {
+    id: String,
+    /// The note made from the copy with `keep_both`
+    copy_id: Option<String>,
+    /// Trash ids of the versions that weren't kept
+    trashed: Vec<String>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct ResolvedContent<'a> {
+    id: &'a str,
+    content: &'a str,
+}
+
+fn load_pending(backend: &impl NotesBackend) -> BTreeMap<String, PendingConflict> {
+    backend
+        .read_store("session.bin", CONFLICTS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn save_pending<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pending: &BTreeMap<String, PendingConflict>,
+) -> Result<(), String> {
+    let value = serde_json::to_value(pending).map_err(|e| e.to_string())?;
+    app.write_store("session.bin", CONFLICTS_KEY, value)?;
+    tray::set_attention(app, "conflicts", pending.len());
+    let ids: Vec<&String> = pending.keys().collect();
+    app.emit_event("conflicts-changed", ids);
+    Ok(())
+}
+
+/// The note id a sync client's conflict copy belongs to, judging by its file stem.
+fn conflicted_id(stem: &str) -> Option<&str> {
+    let id = match stem.find(" (") {
+        Some(i) => &stem[..i],
+        None => &stem[..stem.find(".sync-conflict-")?],
+    };
+    is_valid_note_id(id).then_some(id)
+}
+
+/// Registers conflict copies that aren't pending yet and drops pending conflicts whose
+/// copy has disappeared (resolved by hand or by the sync client).
+pub fn sweep<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Ok(dir) = notes_dir(app) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+    let mut copies: Vec<(String, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let id = conflicted_id(name.strip_suffix(".md")?)?.to_string();
+            dir.join(format!("{}.md", id)).is_file().then_some((id, name))
+        })
+        .collect();
+    copies.sort();
+
+    let mut pending = load_pending(app);
+    let before = pending.len();
+    pending.retain(|_, conflict| dir.join(&conflict.copy).is_file());
+    let mut changed = pending.len() != before;
+    // One conflict per note at a time; further copies come up once it is resolved
+    for (id, copy) in copies {
+        if let Entry::Vacant(slot) = pending.entry(id) {
+            println!("Sync conflict for {}: {}", slot.key(), copy);
+            slot.insert(PendingConflict {
+                copy,
+                detected_at: now_millis(),
+            });
+            changed = true;
+        }
+    }
+    if changed {
+        if let Err(e) = save_pending(app, &pending) {
+            println!("Failed to save pending conflicts: {}", e);
+        }
+    }
+}
+
+/// Whether note `id` has a conflict waiting to be resolved.
+pub fn is_pending(backend: &impl NotesBackend, id: &str) -> bool {
+    load_pending(backend).contains_key(id)
+}
+
+/// Shows persisted conflicts on the tray badge at startup, then sweeps.
+pub fn init<R: Runtime>(app: &tauri::AppHandle<R>) {
+    tray::set_attention(app, "conflicts", load_pending(app).len());
+    sweep(app);
+}
+
+/// Line diff of `mine` against `theirs` by longest common subsequence, after trimming
+/// the common head and tail. Returns whether alignment was skipped for size.
+pub fn line_diff(mine: &str, theirs: &str) -> (Vec<DiffLine>, bool) {
+    let a: Vec<&str> = mine.lines().collect();
+    let b: Vec<&str> = theirs.lines().collect();
+    let head = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let tail = a[head..]
+        .iter()
+        .rev()
+        .zip(b[head..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[head..a.len() - tail], &b[head..b.len() - tail]);
+
+    let line = |side, text: &str| DiffLine {
+        side,
+        text: text.to_string(),
+    };
+    let mut diff: Vec<DiffLine> = a[..head].iter().map(|t| line(DiffSide::Both, t)).collect();
+    let truncated = a_mid.len().saturating_mul(b_mid.len()) > MAX_DIFF_CELLS;
+    if truncated {
+        diff.extend(a_mid.iter().map(|t| line(DiffSide::Mine, t)));
+        diff.extend(b_mid.iter().map(|t| line(DiffSide::Theirs, t)));
+    } else {
+        // lcs[i][j]: common subsequence length of a_mid[i..] and b_mid[j..]
+        let (n, m) = (a_mid.len(), b_mid.len());
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a_mid[i] == b_mid[j] {
+                diff.push(line(DiffSide::Both, a_mid[i]));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                diff.push(line(DiffSide::Mine, a_mid[i]));
+                i += 1;
+            } else {
+                diff.push(line(DiffSide::Theirs, b_mid[j]));
+                j += 1;
+            }
+        }
+    }
+    diff.extend(a[a.len() - tail..].iter().map(|t| line(DiffSide::Both, t)));
+    (diff, truncated)
+}
+
+fn read_version<R: Runtime>(app: &tauri::AppHandle<R>, path: &std::path::Path) -> Result<ConflictVersion, String> {
+    let content = vault::read_file(app, path)?;
+    let modified_at = fs::metadata(path).ok().and_then(|m| to_millis(m.modified()));
+    Ok(ConflictVersion {
+        size: content.len() as u64,
+        content,
+        modified_at,
+    })
+}
+
+/// Ids of notes with a pending conflict.
+#[tauri::command]
+pub async fn list_conflicts(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(load_pending(&app).into_keys().collect())
+}
+
+/// Both versions of a pending conflict and a line diff between them.
+#[tauri::command]
+pub async fn get_conflict(id: String, app: tauri::AppHandle) -> Result<Conflict, String> {
+    let pending = load_pending(&app)
+        .remove(&id)
+        .ok_or_else(|| format!("No conflict pending for {}", id))?;
+    let dir = notes_dir(&app)?;
+    let mine = read_version(&app, &dir.join(format!("{}.md", id)))?;
+    let theirs = read_version(&app, &dir.join(&pending.copy))?;
+    let (diff, diff_truncated) = line_diff(&mine.content, &theirs.content);
+    Ok(Conflict {
+        id,
+        pending,
+        mine,
+        theirs,
+        diff,
+        diff_truncated,
+    })
+}
+
+#[tauri::command]
+pub async fn resolve_conflict(
+    id: String,
+    resolution: Resolution,
+    app: tauri::AppHandle,
+) -> Result<ConflictResolved, String> {
+    let mut pending = load_pending(&app);
+    let conflict = pending
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("No conflict pending for {}", id))?;
+    let dir = notes_dir(&app)?;
+    let copy_path = SafePath::new(&app, Root::Notes, dir.join(&conflict.copy))?;
+    let mine = vault::read_file(&app, &dir.join(format!("{}.md", id)))?;
+    let theirs = vault::read_file(&app, copy_path.path())?;
+
+    // The kept content is written before anything goes to the trash
+    let (content, copy_id, losers, summary) = match &resolution {
+        Resolution::KeepMine => (
+            mine.clone(),
+            None,
+            vec![&theirs],
+            "Kept this device's version".to_string(),
+        ),
+        Resolution::KeepTheirs => {
+            write_note(&app, &id, &theirs)?;
+            (theirs.clone(), None, vec![&mine], "Kept the synced version".to_string())
+        }
+        Resolution::KeepBoth => {
+            let copy_id = Uuid::new_v4().to_string();
+            write_note(&app, &copy_id, &append_to_title(&theirs, " (conflict copy)"))?;
+            let summary = format!("Kept both; the synced version is now note {}", copy_id);
+            (mine.clone(), Some(copy_id), vec![], summary)
+        }
+        Resolution::Merged { content } => {
+            write_note(&app, &id, content)?;
+            (
+                content.clone(),
+                None,
+                vec![&mine, &theirs],
+                "Merged both versions".to_string(),
+            )
+        }
+    };
+    let trashed = losers
+        .into_iter()
+        .map(|loser| recycle_content(&app, RecycledKind::Trash, loser))
+        .collect::<Result<Vec<_>, String>>()?;
+    copy_path.remove()?;
+
+    pending.remove(&id);
+    save_pending(&app, &pending)?;
+
+    let body = format!("{} ({}). Trashed: {}.", summary, conflict.copy, trashed.len());
+    if let Err(e) = journal::record_event(
+        &app,
+        &format!("conflict:{}:{}", id, now_millis()),
+        &format!("Resolved sync conflict in {}", derive_title(&content)),
+        &body,
+    ) {
+        println!("Failed to journal conflict resolution for {}: {}", id, e);
+    }
+
+    let _ = app.emit_to(
+        EventTarget::webview_window(format!("note-{}", id)),
+        "conflict-resolved",
+        ResolvedContent {
+            id: &id,
+            content: &content,
+        },
+    );
+    app.emit_event("refresh-notes", ());
+    Ok(ConflictResolved { id, copy_id, trashed })
+}
@@ -0,0 +1,159 @@
+//! The daily note: one note per day, found or made by `open_daily_note` (also in the
+//! tray and on a shortcut), for keeping the app as a light journal. Its id is today's
+//! date through a strftime pattern from settings.bin `daily_note`, `daily-%Y-%m-%d` by
+//! default, so finding it needs no index and every synced device makes the same file.
+//! A new one starts from the configured template, or with the date as a heading.
+//!
+//! Unrelated to `journal.rs`, the monthly files archived notes are appended to.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::meta;
+use crate::notewindow::NoteWindowOptions;
+use crate::scan::is_valid_note_id;
+use crate::storage::ensure_writes_allowed;
+use crate::templates;
+use crate::usage::record_usage;
+use crate::{create_note_window, notes_dir, write_note};
+
+const SETTINGS_KEY: &str = "daily_note";
+const DEFAULT_ID_PATTERN: &str = "daily-%Y-%m-%d";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DailyNoteSettings {
+    /// strftime pattern giving the note id (the file name without `.md`) for a date
+    id_pattern: String,
+    /// Template new daily notes are made from
+    template: Option<String>,
+}
+
+impl Default for DailyNoteSettings {
+    fn default() -> Self {
+        DailyNoteSettings {
+            id_pattern: DEFAULT_ID_PATTERN.to_string(),
+            template: None,
+        }
+    }
+}
+
+fn settings(backend: &impl NotesBackend) -> DailyNoteSettings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// The note id `pattern` gives for `date`.
+fn note_id(pattern: &str, date: NaiveDate) -> Result<String, String> {
+    // Formatting panics on an unknown specifier, so those are caught first
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid daily note pattern {:?}", pattern));
+    }
+    let id = date.format(pattern).to_string();
+    if !is_valid_note_id(&id) {
+        return Err(format!(
+            "Daily note pattern {:?} gives {:?}; ids may only use letters, digits, - and _",
+            pattern, id
+        ));
+    }
+    Ok(id)
+}
+
+/// Checks that `pattern` gives a valid id, and a different one every day.
+fn check_pattern(pattern: &str) -> Result<(), String> {
+    let day = NaiveDate::from_ymd_opt(2001, 2, 3).ok_or("Invalid date")?;
+    let next = day.succ_opt().ok_or("Invalid date")?;
+    if note_id(pattern, day)? == note_id(pattern, next)? {
+        return Err(format!("Daily note pattern {:?} must include the day", pattern));
+    }
+    Ok(())
+}
+
+/// Opens today's daily note, making it first if there isn't one, and returns its id.
+pub fn open_daily<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<String, String> {
+    let settings = settings(app);
+    let today = chrono::Local::now().date_naive();
+    let id = note_id(&settings.id_pattern, today)?;
+    if !notes_dir(app)?.join(format!("{}.md", id)).is_file() {
+        ensure_writes_allowed(app)?;
+        let (content, color) = match settings.template.as_deref() {
+            Some(name) => templates::render_template(app, name)?,
+            None => (format!("# {}\n\n", today.format("%A, %-d %B %Y")), None),
+        };
+        write_note(app, &id, &content)?;
+        if color.is_some() {
+            meta::update_meta(app, &id, |meta| meta.color = color.clone())?;
+        }
+        println!("Created daily note {}", id);
+        app.emit_event("refresh-notes", ());
+    }
+    create_note_window(app, NoteWindowOptions::open(id.clone()))?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn open_daily_note(app: tauri::AppHandle) -> Result<String, String> {
+    record_usage(&app, "daily_note");
+    open_daily(&app)
+}
+
+#[tauri::command]
+pub async fn get_daily_note_settings(app: tauri::AppHandle) -> Result<DailyNoteSettings, String> {
+    Ok(settings(&app))
+}
+
+/// Replaces the daily note settings. Notes already made under an old pattern are left
+/// as they are; only today's lookup changes.
+#[tauri::command]
+pub async fn set_daily_note_settings(
+    id_pattern: String,
+    template: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<DailyNoteSettings, String> {
+    check_pattern(&id_pattern)?;
+    let settings = DailyNoteSettings {
+        id_pattern,
+        template: template.filter(|name| !name.trim().is_empty()),
+    };
+    let value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+    app.write_store("settings.bin", SETTINGS_KEY, value)?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn ids_follow_the_pattern() {
+        assert_eq!(
+            note_id(DEFAULT_ID_PATTERN, date(2026, 3, 9)).unwrap(),
+            "daily-2026-03-09"
+        );
+        assert_eq!(note_id("%d_%m_%y", date(2026, 3, 9)).unwrap(), "09_03_26");
+    }
+
+    #[test]
+    fn bad_patterns_are_errors_not_panics() {
+        assert!(note_id("daily-%Q", date(2026, 3, 9)).is_err());
+        assert!(note_id("%Y/%m/%d", date(2026, 3, 9)).is_err());
+        assert!(note_id("%Y %m %d", date(2026, 3, 9)).is_err());
+    }
+
+    #[test]
+    fn patterns_must_change_daily() {
+        assert!(check_pattern(DEFAULT_ID_PATTERN).is_ok());
+        assert!(check_pattern("day-%j-%Y").is_ok());
+        assert!(check_pattern("month-%Y-%m").is_err());
+        assert!(check_pattern("journal").is_err());
+        assert!(check_pattern("%Y:%m").is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! `stickynotes://` links, so notes can be referenced from calendars, task managers and
+//! bookmarks:
+//!
+//! - `stickynotes://note/<id>` opens (or focuses) the note
+//! - `stickynotes://new?text=...` opens a new note, holding `text` if given
+//!
+//! macOS hands links to the running app through the deep-link plugin. Windows and Linux
+//! start the app with the link as its argument instead, which `instance` passes on here
+//! (forwarded by the single-instance plugin when the app is already running).
+
+use tauri::{Runtime, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::instance;
+use crate::usage::record_usage;
+
+pub const SCHEME: &str = "stickynotes";
+
+/// Whether a command-line argument is one of our links.
+pub fn is_link(arg: &str) -> bool {
+    arg.strip_prefix(SCHEME).is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Follows one `stickynotes://` link.
+pub fn open_url<R: Runtime>(app: &tauri::AppHandle<R>, url: &Url) -> Result<(), String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("Not a {} link: {}", SCHEME, url));
+    }
+    record_usage(app, "deep_link");
+    match url.host_str() {
+        Some("note") => {
+            let id = url
+                .path_segments()
+                .and_then(|mut segments| segments.find(|segment| !segment.is_empty()))
+                .ok_or_else(|| format!("No note id in {}", url))?;
+            instance::open_note(app, id)
+        }
+        Some("new") => {
+            let text = url
+                .query_pairs()
+                .find(|(key, _)| key == "text")
+                .map(|(_, text)| text.into_owned());
+            instance::new_note(app, text.as_deref())
+        }
+        _ => Err(format!("Unknown link: {}", url)),
+    }
+}
+
+/// Listens for links the OS delivers to the running app, and makes sure the scheme is
+/// registered where that can be done at runtime (Linux, and Windows outside installers).
+pub fn init<R: Runtime>(app: &tauri::AppHandle<R>) {
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    if let Err(e) = app.deep_link().register_all() {
+        println!("Failed to register the {} link scheme: {}", SCHEME, e);
+    }
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            if let Err(e) = open_url(&handle, &url) {
+                println!("Failed to open link {}: {}", url, e);
+            }
+        }
+    });
+}
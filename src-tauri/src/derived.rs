@@ -0,0 +1,111 @@
+//! What a note made from other notes starts out with. A duplicate takes its source's
+//! color, sidecar tags and pin; a note made from a template takes the template's
+//! declared `color:`, its tags staying in the frontmatter they came in.
+//!
+//! Favorites and reminders never carry over: a copy that rang alongside its original
+//! would look like a duplicate notification, so the new note starts without either.
+
+use crate::meta::NoteMeta;
+
+/// Where a new note's content came from.
+#[derive(Clone, Copy, Debug)]
+pub enum DerivedKind<'a> {
+    Duplicate,
+    /// The `color:` from the template's frontmatter, if it declared one
+    Template {
+        color: Option<&'a str>,
+    },
+}
+
+/// Returned by the commands that make derived notes, so the frontend can show the note
+/// right away rather than after the next listing.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct DerivedMetadata {
+    pub color: Option<String>,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+}
+
+impl DerivedMetadata {
+    pub fn apply(&self, meta: &mut NoteMeta) {
+        meta.color = self.color.clone();
+        meta.tags = self.tags.clone();
+        meta.pinned = self.pinned;
+    }
+}
+
+/// Metadata for a note made from `sources` (in order, `None` for a source without any).
+pub fn derive_metadata(sources: &[Option<&NoteMeta>], kind: DerivedKind) -> DerivedMetadata {
+    match kind {
+        DerivedKind::Duplicate => sources
+            .first()
+            .copied()
+            .flatten()
+            .map(|source| DerivedMetadata {
+                color: source.color.clone(),
+                tags: source.tags.clone(),
+                pinned: source.pinned,
+            })
+            .unwrap_or_default(),
+        DerivedKind::Template { color } => DerivedMetadata {
+            color: color.map(str::to_string),
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> NoteMeta {
+        NoteMeta {
+            color: Some("yellow".to_string()),
+            tags: vec!["home".to_string()],
+            pinned: true,
+            title: Some("Groceries".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duplicates_take_color_tags_and_pin() {
+        let source = source();
+        let derived = derive_metadata(&[Some(&source)], DerivedKind::Duplicate);
+        assert_eq!(
+            derived,
+            DerivedMetadata {
+                color: Some("yellow".to_string()),
+                tags: vec!["home".to_string()],
+                pinned: true,
+            }
+        );
+
+        let mut meta = NoteMeta::default();
+        derived.apply(&mut meta);
+        assert_eq!(
+            (meta.color.as_deref(), meta.pinned, meta.title),
+            (Some("yellow"), true, None)
+        );
+    }
+
+    #[test]
+    fn templates_take_only_their_declared_color() {
+        let source = source();
+        let derived = derive_metadata(&[Some(&source)], DerivedKind::Template { color: Some("blue") });
+        assert_eq!(derived.color.as_deref(), Some("blue"));
+        assert!(derived.tags.is_empty() && !derived.pinned);
+
+        let derived = derive_metadata(&[], DerivedKind::Template { color: None });
+        assert_eq!(derived, DerivedMetadata::default());
+    }
+
+    #[test]
+    fn sources_without_metadata_give_none() {
+        assert_eq!(
+            derive_metadata(&[None], DerivedKind::Duplicate),
+            DerivedMetadata::default()
+        );
+        assert_eq!(derive_metadata(&[], DerivedKind::Duplicate), DerivedMetadata::default());
+    }
+}
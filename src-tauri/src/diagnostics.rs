@@ -0,0 +1,60 @@
+use tauri::Manager;
+
+use crate::cache::{CacheStats, PreviewCache};
+use crate::events::{event_stats, EventStats};
+use crate::flush::{flush_status, FlushStatus};
+use crate::focustrack::{self, FocusStats};
+use crate::limits::{effective_limits, NoteLimits};
+use crate::safepath::{destructive_calls, Root};
+use crate::storage::{storage_info, StorageInfo};
+use crate::WindowRegistry;
+
+#[derive(serde::Serialize)]
+pub struct Diagnostics {
+    version: String,
+    data_dir: Option<String>,
+    open_note_windows: usize,
+    limits: NoteLimits,
+    storage: StorageInfo,
+    /// Last flush of batched writes and how each part went
+    flush: FlushStatus,
+}
+
+#[tauri::command]
+pub async fn get_diagnostics(app: tauri::AppHandle) -> Result<Diagnostics, String> {
+    let open_note_windows = app.state::<WindowRegistry>().note_labels().len();
+
+    Ok(Diagnostics {
+        version: app.package_info().version.to_string(),
+        data_dir: app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned()),
+        open_note_windows,
+        limits: effective_limits(&app),
+        storage: storage_info(&app),
+        flush: flush_status(&app),
+    })
+}
+
+#[derive(serde::Serialize)]
+pub struct Metrics {
+    preview_cache: CacheStats,
+    /// Deletes and renames per data root since start
+    destructive_calls: Vec<(Root, u64)>,
+    /// What happened to note focus events with respect to the session order
+    focus_events: FocusStats,
+    /// Event deliveries to windows, and windows skipped for not subscribing
+    events: EventStats,
+}
+
+#[tauri::command]
+pub async fn get_metrics(app: tauri::AppHandle) -> Result<Metrics, String> {
+    Ok(Metrics {
+        preview_cache: app.state::<PreviewCache>().stats(),
+        destructive_calls: destructive_calls(),
+        focus_events: focustrack::stats(&app),
+        events: event_stats(&app),
+    })
+}
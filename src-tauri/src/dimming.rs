@@ -0,0 +1,142 @@
+//! Note opacity: focus dimming, where unfocused notes fade so the focused one stands
+//! out, and each note's own opacity, kept in its `NoteMeta`.
+//!
+//! Tauri has no cross-platform window opacity call, so the backend decides the
+//! effective opacity and sends it to the note's frontend as `note-opacity-changed`,
+//! which applies it to its (transparent) window contents. When per-note opacity is
+//! set explicitly, the two compose multiplicatively: a 0.8 note dimmed at 0.7 shows
+//! at 0.56, and regains exactly 0.8 on focus. A note window that opens reads its own
+//! opacity from `get_note_meta`.
+
+use serde_json::Map;
+use tauri::{Emitter, EventTarget, Manager, Runtime};
+
+use crate::meta;
+use crate::settings;
+use crate::WindowRegistry;
+
+/// The most transparent a note can be made, so it never disappears altogether.
+const MIN_OPACITY: f64 = 0.1;
+
+/// Kept in `Settings`; `set_focus_dimming` is a shorthand for updating it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct FocusDimming {
+    pub enabled: bool,
+    /// Opacity multiplier applied to unfocused notes, 0.1..=1.0
+    pub level: f64,
+    /// Leave always-on-top notes at full opacity
+    pub exempt_pinned: bool,
+}
+
+impl Default for FocusDimming {
+    fn default() -> Self {
+        FocusDimming {
+            enabled: false,
+            level: 0.7,
+            exempt_pinned: true,
+        }
+    }
+}
+
+fn get_settings<R: Runtime>(app: &tauri::AppHandle<R>) -> FocusDimming {
+    settings::current(app).focus_dimming
+}
+
+fn send_opacity<R: Runtime>(window: &tauri::WebviewWindow<R>, opacity: f64) {
+    let _ = window.emit_to(
+        EventTarget::webview_window(window.label()),
+        "note-opacity-changed",
+        opacity,
+    );
+}
+
+/// The note's own opacity, before any dimming.
+fn note_opacity<R: Runtime>(window: &tauri::WebviewWindow<R>) -> f64 {
+    window
+        .label()
+        .strip_prefix("note-")
+        .and_then(|id| meta::get_meta(window.app_handle(), id).opacity)
+        .unwrap_or(1.0)
+}
+
+/// Applies the dimmed or full opacity to one note window for the given focus state.
+pub fn apply_focus_opacity<R: Runtime>(window: &tauri::WebviewWindow<R>, focused: bool) {
+    let settings = get_settings(window.app_handle());
+    let base = note_opacity(window);
+    if !settings.enabled {
+        // Nothing changes with focus; re-sending the note's own opacity covers a window
+        // whose frontend missed it
+        if base < 1.0 {
+            send_opacity(window, base);
+        }
+        return;
+    }
+    let exempt = settings.exempt_pinned && window.is_always_on_top().unwrap_or(false);
+    let dim = if focused || exempt { 1.0 } else { settings.level };
+    send_opacity(window, base * dim);
+}
+
+/// Used by batch passes (show-all, arrange) so no note is dimmed while they run. Notes
+/// keep their own opacity.
+pub fn force_full_opacity<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if !get_settings(app).enabled {
+        return;
+    }
+    for label in app.state::<WindowRegistry>().note_labels() {
+        if let Some(window) = app.get_webview_window(&label) {
+            send_opacity(&window, note_opacity(&window));
+        }
+    }
+}
+
+/// Re-applies dimming to every note from its current focus state, e.g. after a batch pass.
+pub fn refresh_all<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let enabled = get_settings(app).enabled;
+    for label in app.state::<WindowRegistry>().note_labels() {
+        if let Some(window) = app.get_webview_window(&label) {
+            if enabled {
+                apply_focus_opacity(&window, window.is_focused().unwrap_or(false));
+            } else {
+                send_opacity(&window, note_opacity(&window));
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_focus_dimming(app: tauri::AppHandle) -> Result<FocusDimming, String> {
+    Ok(get_settings(&app))
+}
+
+/// Updating the settings re-applies dimming to every note.
+#[tauri::command]
+pub async fn set_focus_dimming(settings: FocusDimming, app: tauri::AppHandle) -> Result<(), String> {
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    settings::update(&app, Map::from_iter([("focus_dimming".to_string(), value)]))?;
+    Ok(())
+}
+
+/// Sets note `id`'s own opacity, from `MIN_OPACITY` to 1.0; `None` makes it opaque again.
+/// Focus dimming still applies on top of it.
+#[tauri::command]
+pub async fn set_note_opacity(id: String, opacity: Option<f64>, app: tauri::AppHandle) -> Result<(), String> {
+    if let Some(opacity) = opacity {
+        if !(MIN_OPACITY..=1.0).contains(&opacity) {
+            return Err(format!(
+                "Opacity must be between {} and 1.0, got {}",
+                MIN_OPACITY, opacity
+            ));
+        }
+    }
+    // 1.0 is the default; storing it would only clutter the metadata
+    let opacity = opacity.filter(|&o| o < 1.0);
+    meta::update_meta(&app, &id, |meta| meta.opacity = opacity)?;
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        apply_focus_opacity(&window, window.is_focused().unwrap_or(false));
+        if opacity.is_none() && !get_settings(&app).enabled {
+            send_opacity(&window, 1.0);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+//! Copying a note under a fresh id, optionally straight into a saved workspace instead of
+//! the live session.
+//!
+//! The copy takes the content, the asset folder (without history), and the metadata
+//! `derive_metadata` gives a duplicate. Links into the original's asset folder are
+//! pointed at the copy's, and " (copy)" is appended to the title so the two can be told
+//! apart in listings.
+
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::changes::own_change;
+use crate::derived::{derive_metadata, DerivedKind, DerivedMetadata};
+use crate::encryption;
+use crate::error::NoteError;
+use crate::limits::effective_limits;
+use crate::meta::{self, Rect};
+use crate::notewindow::NoteWindowOptions;
+use crate::packet::HISTORY_DIR;
+use crate::scan::is_valid_note_id;
+use crate::tags::frontmatter_close;
+use crate::workspaces;
+use crate::{create_note_window, notes_dir, read_note, write_note};
+
+/// How far down and right of the original a copy's window opens, in logical pixels.
+const COPY_WINDOW_OFFSET: f64 = 24.0;
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DuplicateTarget {
+    /// An existing saved workspace; the copy is added to it and not opened
+    Workspace { name: String },
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct DuplicateResult {
+    id: String,
+    /// Where the copy's file ended up
+    path: String,
+    workspace: Option<String>,
+    metadata: DerivedMetadata,
+}
+
+/// Points `](<old>/…`, `](./<old>/…` and `src="<old>/…` style links at `new`'s folder.
+fn relink_assets(content: &str, old: &str, new: &str) -> String {
+    ["(", "(./", "\"", "\"./", "'", "'./"]
+        .iter()
+        .fold(content.to_string(), |content, prefix| {
+            content.replace(&format!("{}{}/", prefix, old), &format!("{}{}/", prefix, new))
+        })
+}
+
+/// Appends `suffix` to the line the title is derived from, below any frontmatter. A
+/// locked note's title can't be read, so its content is returned unchanged.
+pub fn append_to_title(content: &str, suffix: &str) -> String {
+    if encryption::is_locked(content) {
+        return content.to_string();
+    }
+    let body_start = frontmatter_close(content)
+        .map(|close| content[close..].find('\n').map_or(content.len(), |end| close + end + 1))
+        .unwrap_or(0);
+    let (head, body) = content.split_at(body_start);
+    let mut marked = false;
+    let lines: Vec<String> = body
+        .split_inclusive('\n')
+        .map(|line| {
+            let text = line.trim_end_matches(['\r', '\n']);
+            if marked || text.trim().trim_start_matches('#').trim().is_empty() {
+                return line.to_string();
+            }
+            marked = true;
+            format!("{}{}{}", text, suffix, &line[text.len()..])
+        })
+        .collect();
+    if marked {
+        format!("{}{}", head, lines.concat())
+    } else {
+        format!("{}{}\n{}", head, suffix.trim_start(), body)
+    }
+}
+
+fn copy_assets(from: &Path, to: &Path, top_level: bool) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if top_level && name == HISTORY_DIR {
+            continue;
+        }
+        // symlink_metadata so links are skipped rather than followed out of the folder
+        let file_type = fs::symlink_metadata(entry.path())?.file_type();
+        if file_type.is_dir() {
+            copy_assets(&entry.path(), &to.join(&name), false)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies note `id`. Without a target the copy lands next to the original and opens
+/// like a new note. Targets are checked before anything is written.
+#[tauri::command]
+pub async fn duplicate_note(
+    id: String,
+    target: Option<DuplicateTarget>,
+    app: tauri::AppHandle,
+) -> Result<DuplicateResult, String> {
+    if !is_valid_note_id(&id) || !notes_dir(&app)?.join(format!("{}.md", id)).is_file() {
+        return Err(format!("Note {} does not exist", id));
+    }
+    let workspace = match target {
+        None => None,
+        Some(DuplicateTarget::Workspace { name }) => {
+            if !workspaces::exists(&app, &name) {
+                return Err(format!("No workspace named {:?}", name));
+            }
+            Some(name)
+        }
+    };
+
+    let new_id = Uuid::new_v4().to_string();
+    let content = append_to_title(&relink_assets(&read_note(&app, &id)?, &id, &new_id), " (copy)");
+    // Relinking can grow the content; still nothing has been written
+    let limit = effective_limits(&app).max_note_bytes;
+    if content.len() > limit {
+        return Err(NoteError::TooLarge {
+            size: content.len(),
+            limit,
+        }
+        .into());
+    }
+
+    write_note(&app, &new_id, &content)?;
+    let dir = notes_dir(&app)?;
+    let assets = dir.join(&id);
+    if assets.is_dir() {
+        own_change(&app, &[&new_id], || copy_assets(&assets, &dir.join(&new_id), true)).map_err(|e| e.to_string())?;
+    }
+
+    let original = meta::get_meta(&app, &id);
+    let metadata = derive_metadata(&[Some(&original)], DerivedKind::Duplicate);
+    meta::update_meta(&app, &new_id, |meta| metadata.apply(meta))?;
+
+    match &workspace {
+        Some(name) => workspaces::add_note(&app, name, &new_id)?,
+        None => {
+            // Offset so the copy doesn't open exactly on top of the original
+            let mut options = NoteWindowOptions::open(new_id.clone());
+            if let Some(rect) = meta::restored_geometry(&app, &original) {
+                options = options.geometry(Rect {
+                    x: rect.x + COPY_WINDOW_OFFSET,
+                    y: rect.y + COPY_WINDOW_OFFSET,
+                    ..rect
+                });
+            }
+            create_note_window(&app, options)?;
+        }
+    }
+
+    app.emit_event("refresh-notes", ());
+    Ok(DuplicateResult {
+        path: dir.join(format!("{}.md", new_id)).to_string_lossy().to_string(),
+        id: new_id,
+        workspace,
+        metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_title_line_gets_the_suffix() {
+        assert_eq!(
+            append_to_title("# Groceries\n- milk\n", " (copy)"),
+            "# Groceries (copy)\n- milk\n"
+        );
+        assert_eq!(append_to_title("Groceries", " (copy)"), "Groceries (copy)");
+        assert_eq!(
+            append_to_title("\n\nTitle\r\nbody", " (copy)"),
+            "\n\nTitle (copy)\r\nbody"
+        );
+    }
+
+    #[test]
+    fn frontmatter_is_skipped() {
+        let note = "---\ntags: [a]\ntitle: x\n---\n\n# Title\r\nbody";
+        assert_eq!(
+            append_to_title(note, " (copy)"),
+            "---\ntags: [a]\ntitle: x\n---\n\n# Title (copy)\r\nbody"
+        );
+    }
+
+    #[test]
+    fn notes_without_a_title_line_get_one() {
+        assert_eq!(append_to_title("", " (copy)"), "(copy)\n");
+        assert_eq!(append_to_title("#\n\n", " (copy)"), "(copy)\n#\n\n");
+        assert_eq!(
+            append_to_title("---\ntags: []\n---\n", " (copy)"),
+            "---\ntags: []\n---\n(copy)\n"
+        );
+    }
+
+    #[test]
+    fn locked_notes_are_left_alone() {
+        let locked = format!("{}c2VhbGVk\n", encryption::LOCKED_HEADER);
+        assert_eq!(append_to_title(&locked, " (copy)"), locked);
+    }
+
+    #[test]
+    fn asset_links_follow_the_copy() {
+        let content = "![a](old/a.png) [b](./old/b.pdf) <img src=\"old/c.png\"> <img src='./old/d.png'>\n\
+                       (old-2/e.png) old/plain [f](other/old/f.png)";
+        assert_eq!(
+            relink_assets(content, "old", "new"),
+            "![a](new/a.png) [b](./new/b.pdf) <img src=\"new/c.png\"> <img src='./new/d.png'>\n\
+             (old-2/e.png) old/plain [f](other/old/f.png)"
+        );
+    }
+}
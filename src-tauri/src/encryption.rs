@@ -0,0 +1,332 @@
+//! Password-protected notes. A locked note's file is `LOCKED_HEADER` followed by the
+//! base64 of a random salt, a nonce and the AES-256-GCM ciphertext of the content; the
+//! key comes from the password and salt through Argon2id. Metadata (color, tags,
+//! geometry) is not encrypted.
+//!
+//! `unlock_note` keeps the derived key in memory until the note's window closes or
+//! `relock_note` is called. While it is held, loads decrypt and saves encrypt with it;
+//! without it `load_note` fails with `Locked` and the frontend asks for the password.
+//! Decrypted content is never written to disk, and locking a note drops its history
+//! snapshots, which hold the plain text.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::error::NoteError;
+use crate::packet::HISTORY_DIR;
+use crate::safepath::{Root, SafePath};
+use crate::storage::ensure_writes_allowed;
+use crate::{notes_dir, read_note, write_note, writequeue};
+
+/// First line of a locked note's file; also how listings recognize one.
+pub const LOCKED_HEADER: &str = "<!-- sticky-notes-locked v1 -->\n";
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+#[derive(Clone)]
+struct NoteKey {
+    salt: [u8; SALT_LEN],
+    key: [u8; 32],
+}
+
+/// Keys of the notes unlocked this session, by note id.
+#[derive(Default)]
+pub struct NoteKeys(Mutex<HashMap<String, NoteKey>>);
+
+struct Sealed {
+    salt: [u8; SALT_LEN],
+    /// Nonce and ciphertext, as `encrypt` returns them
+    body: Vec<u8>,
+}
+
+pub fn is_locked(content: &str) -> bool {
+    content.starts_with(LOCKED_HEADER)
+}
+
+pub fn random_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// A 256-bit key from `password` and `salt`, through Argon2id.
+pub fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// A fresh nonce followed by the AES-256-GCM ciphertext of `plain`.
+pub fn encrypt(key: &[u8; 32], plain: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| "Encrypting the note failed".to_string())?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// The reverse of `encrypt`; `None` for the wrong key or damaged input.
+pub fn decrypt(key: &[u8; 32], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+}
+
+fn note_key(password: &str, salt: [u8; SALT_LEN]) -> Result<NoteKey, String> {
+    Ok(NoteKey {
+        salt,
+        key: derive_key(password, &salt)?,
+    })
+}
+
+fn parse(content: &str) -> Result<Sealed, String> {
+    let body = content.strip_prefix(LOCKED_HEADER).unwrap_or(content);
+    let bytes = STANDARD
+        .decode(body.trim())
+        .map_err(|e| format!("Locked note is damaged: {}", e))?;
+    if bytes.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+        return Err("Locked note is damaged: too short".to_string());
+    }
+    let (salt, body) = bytes.split_at(SALT_LEN);
+    Ok(Sealed {
+        salt: salt.try_into().map_err(|_| "Locked note is damaged".to_string())?,
+        body: body.to_vec(),
+    })
+}
+
+fn seal(key: &NoteKey, content: &str) -> Result<String, String> {
+    let mut bytes = key.salt.to_vec();
+    bytes.extend(encrypt(&key.key, content.as_bytes())?);
+    Ok(format!("{}{}\n", LOCKED_HEADER, STANDARD.encode(bytes)))
+}
+
+/// Decrypts with `key`; `None` when it's the wrong key.
+fn open_sealed(key: &NoteKey, sealed: &Sealed) -> Option<String> {
+    if key.salt != sealed.salt {
+        return None;
+    }
+    String::from_utf8(decrypt(&key.key, &sealed.body)?).ok()
+}
+
+fn held_key<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Option<NoteKey> {
+    app.state::<NoteKeys>().0.lock().ok()?.get(id).cloned()
+}
+
+/// Drops note `id`'s key; its content can't be read again without the password.
+pub fn forget<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    if let Ok(mut keys) = app.state::<NoteKeys>().0.lock() {
+        keys.remove(id);
+    }
+}
+
+/// What is stored for note `id` right now: a queued save if there is one, else the file.
+fn stored_content<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<String, String> {
+    match writequeue::queued_content(app, id) {
+        Some(content) => Ok(content),
+        None => read_note(app, id),
+    }
+}
+
+/// `stored` as the editor should see it: decrypted when it's a locked note that was
+/// unlocked this session, `Locked` when it wasn't, and unchanged when it isn't locked.
+pub fn open<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, stored: String) -> Result<String, String> {
+    if !is_locked(&stored) {
+        return Ok(stored);
+    }
+    let sealed = parse(&stored)?;
+    let plain = held_key(app, id).and_then(|key| open_sealed(&key, &sealed));
+    if plain.is_none() {
+        // The file may have been replaced by one locked with another password
+        forget(app, id);
+    }
+    plain.ok_or_else(|| NoteError::Locked { id: id.to_string() }.into())
+}
+
+/// What a save of `content` to note `id` should store: the content itself, or for a
+/// locked note its encryption with the session's key. Unchanged content keeps the
+/// stored ciphertext, so a save that changes nothing writes nothing new.
+pub fn seal_for_save<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, content: String) -> Result<String, String> {
+    let stored = stored_content(app, id)?;
+    if !is_locked(&stored) {
+        return Ok(content);
+    }
+    if open(app, id, stored.clone())? == content {
+        return Ok(stored);
+    }
+    let key = held_key(app, id).ok_or_else(|| NoteError::Locked { id: id.to_string() })?;
+    crate::check_note_size(app, &content)?;
+    seal(&key, &content)
+}
+
+/// Refuses to work on a locked note's content, for features that would write it out
+/// decrypted (templates) or can't handle ciphertext (tidying).
+pub fn ensure_not_locked(id: &str, content: &str) -> Result<(), String> {
+    if is_locked(content) {
+        return Err(NoteError::Locked { id: id.to_string() }.into());
+    }
+    Ok(())
+}
+
+/// Checks `password` against locked note `id` and returns its key and plain text.
+fn unlock<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, password: &str) -> Result<(NoteKey, String), String> {
+    let stored = stored_content(app, id)?;
+    if !is_locked(&stored) {
+        return Err(format!("Note {} is not locked", id));
+    }
+    let sealed = parse(&stored)?;
+    let key = note_key(password, sealed.salt)?;
+    let plain = open_sealed(&key, &sealed).ok_or(NoteError::WrongPassword)?;
+    Ok((key, plain))
+}
+
+/// Encrypts note `id` with `password`. The note is locked straight away; its window
+/// gets `note-locked` and should drop what it shows. Its `history/` snapshots are
+/// removed, but versions already committed to the Git repository (`git.rs`) stay there
+/// in plain text.
+#[tauri::command]
+pub async fn lock_note(id: String, password: String, app: tauri::AppHandle) -> Result<(), String> {
+    ensure_writes_allowed(&app)?;
+    if password.is_empty() {
+        return Err("The password is empty".to_string());
+    }
+    if !notes_dir(&app)?.join(format!("{}.md", id)).is_file() {
+        return Err(format!("Note {} does not exist", id));
+    }
+    if writequeue::queued_content(&app, &id).is_some() {
+        return Err(format!("Note {} has queued saves; lock it once they are written", id));
+    }
+    let content = read_note(&app, &id)?;
+    if is_locked(&content) {
+        return Err(format!("Note {} is already locked", id));
+    }
+
+    let key = note_key(&password, random_salt())?;
+    write_note(&app, &id, &seal(&key, &content)?)?;
+
+    // Snapshots, including the one this write just took, are plain text
+    let history = notes_dir(&app)?.join(&id).join(HISTORY_DIR);
+    if history.is_dir() {
+        SafePath::new(&app, Root::Notes, history)?.remove()?;
+    }
+    forget(&app, &id);
+    println!("Locked note {}", id);
+
+    app.emit_note_event(&id, "note-locked", &id);
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Checks `password` and returns the note's content, keeping the key for this session
+/// so the note can be edited and saved.
+#[tauri::command]
+pub async fn unlock_note(id: String, password: String, app: tauri::AppHandle) -> Result<String, String> {
+    let (key, plain) = unlock(&app, &id, &password)?;
+    app.state::<NoteKeys>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(id.clone(), key);
+    Ok(plain)
+}
+
+/// Forgets an unlocked note's key before its window closes.
+#[tauri::command]
+pub async fn relock_note(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    forget(&app, &id);
+    app.emit_note_event(&id, "note-locked", &id);
+    Ok(())
+}
+
+/// Decrypts note `id` for good, writing its plain text back, and returns that text.
+#[tauri::command]
+pub async fn remove_note_lock(id: String, password: String, app: tauri::AppHandle) -> Result<String, String> {
+    ensure_writes_allowed(&app)?;
+    let (_, plain) = unlock(&app, &id, &password)?;
+    write_note(&app, &id, &plain)?;
+    forget(&app, &id);
+    println!("Removed the lock from note {}", id);
+    app.emit_event("refresh-notes", ());
+    Ok(plain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    fn key(password: &str) -> NoteKey {
+        note_key(password, [9; SALT_LEN]).unwrap()
+    }
+
+    #[test]
+    fn sealed_notes_open_with_the_right_password_only() {
+        let sealed = seal(&key("hunter2"), "Bank PIN: 1234").unwrap();
+        assert!(is_locked(&sealed));
+        assert!(!sealed.contains("1234"));
+
+        let parsed = parse(&sealed).unwrap();
+        assert_eq!(open_sealed(&key("hunter2"), &parsed).as_deref(), Some("Bank PIN: 1234"));
+        assert_eq!(open_sealed(&key("hunter3"), &parsed), None);
+        // Same password, other note: the salt differs, so the key does too
+        let other = note_key("hunter2", [1; SALT_LEN]).unwrap();
+        assert_eq!(open_sealed(&other, &parsed), None);
+    }
+
+    #[test]
+    fn sealing_twice_gives_different_files() {
+        let key = key("pw");
+        let first = seal(&key, "same").unwrap();
+        let second = seal(&key, "same").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(open_sealed(&key, &parse(&second).unwrap()).as_deref(), Some("same"));
+    }
+
+    #[test]
+    fn tampered_or_damaged_notes_are_refused() {
+        let key = key("pw");
+        let sealed = seal(&key, "content").unwrap();
+        let mut bytes = STANDARD.decode(sealed[LOCKED_HEADER.len()..].trim()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!("{}{}\n", LOCKED_HEADER, STANDARD.encode(bytes));
+        assert_eq!(open_sealed(&key, &parse(&tampered).unwrap()), None);
+
+        assert!(parse(&format!("{}not base64!", LOCKED_HEADER)).is_err());
+        assert!(parse(&format!("{}{}", LOCKED_HEADER, STANDARD.encode([0u8; 20]))).is_err());
+    }
+
+    #[test]
+    fn only_locked_notes_are_refused_by_content_features() {
+        assert!(ensure_not_locked("a", "# Plain").is_ok());
+        let error = ensure_not_locked("a", &seal(&key("pw"), "x").unwrap()).unwrap_err();
+        assert!(error.starts_with("Locked"), "{}", error);
+    }
+
+    #[test]
+    fn sealed_notes_are_held_to_the_limit_by_their_text() {
+        let backend = TempBackend::new();
+        backend.set_setting("max_note_bytes", serde_json::json!(1024));
+        let sealed = seal(&key("pw"), &"x".repeat(1000)).unwrap();
+        assert!(sealed.len() > 1024);
+        assert!(crate::encode_note(&backend, &sealed).is_ok());
+        assert!(crate::check_note_size(&backend, &"x".repeat(1025)).is_err());
+    }
+}
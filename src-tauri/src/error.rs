@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// Failures the frontend needs to tell apart. Commands still return `String`
+/// errors; the leading code (`TooLarge: ...`) is what the frontend matches on.
+#[derive(Debug)]
+pub enum NoteError {
+    TooLarge { size: usize, limit: usize },
+    /// A note packet that is not ours, is from a newer version, or is malformed.
+    InvalidPacket { reason: String },
+    /// A destructive filesystem call aimed outside the directory it belongs to.
+    UnsafePath { path: String, reason: String },
+    /// Optional writes are paused because the data volume is nearly full.
+    LowDiskSpace,
+    /// Saving would replace an existing template and overwrite wasn't asked for.
+    TemplateExists { name: String },
+    /// The notes folder can't be written (permissions, read-only mount); saves are queued.
+    ReadOnlyStorage { reason: String },
+    /// The accelerator is already bound to another shortcut action.
+    ShortcutConflict { accelerator: String, action: String },
+    /// The note's file shrank drastically since the last save; saving needs `acknowledge_suspect`.
+    SuspectContent { old_size: u64, new_size: u64 },
+    /// The file changed on disk since the version the save was based on.
+    ModifiedExternally { modified_at: Option<u64> },
+    /// The note is encrypted and wasn't unlocked this session, see `encryption.rs`.
+    Locked { id: String },
+    /// The password doesn't decrypt the note.
+    WrongPassword,
+    /// The notes are encrypted and the vault wasn't unlocked this session, see `vault.rs`.
+    VaultLocked,
+    /// The app is locked until its PIN is entered, see `applock.rs`.
+    AppLocked,
+    /// Too many wrong PINs in a row; none is checked until the wait is over.
+    TooManyAttempts { retry_in_secs: u64 },
+}
+
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteError::TooLarge { size, limit } => write!(
+                f,
+                "TooLarge: note is {} bytes, the limit is {} bytes",
+                size, limit
+            ),
+            NoteError::InvalidPacket { reason } => write!(f, "InvalidPacket: {}", reason),
+            NoteError::LowDiskSpace => write!(f, "LowDiskSpace: paused until disk space recovers"),
+            NoteError::UnsafePath { path, reason } => write!(f, "UnsafePath: refusing to touch {}: {}", path, reason),
+            NoteError::ReadOnlyStorage { reason } => write!(f, "ReadOnlyStorage: cannot write the notes folder: {}", reason),
+            NoteError::TemplateExists { name } => write!(f, "TemplateExists: a template named {:?} already exists", name),
+            NoteError::ModifiedExternally { modified_at } => match modified_at {
+                Some(at) => write!(f, "ModifiedExternally: the note changed on disk at {}", at),
+                None => write!(f, "ModifiedExternally: the note was removed from disk"),
+            },
+            NoteError::SuspectContent { old_size, new_size } => write!(
+                f,
+                "SuspectContent: the note shrank from {} to {} bytes outside the app",
+                old_size, new_size
+            ),
+            NoteError::Locked { id } => write!(f, "Locked: note {} is locked", id),
+            NoteError::WrongPassword => write!(f, "WrongPassword: the password is wrong"),
+            NoteError::VaultLocked => write!(f, "VaultLocked: the vault is locked"),
+            NoteError::AppLocked => write!(f, "AppLocked: the app is locked"),
+            NoteError::TooManyAttempts { retry_in_secs } => {
+                write!(f, "TooManyAttempts: try again in {} seconds", retry_in_secs)
+            }
+            NoteError::ShortcutConflict { accelerator, action } => {
+                write!(f, "ShortcutConflict: {} is already the shortcut for {}", accelerator, action)
+            }
+        }
+    }
+}
+
+impl From<NoteError> for String {
+    fn from(e: NoteError) -> Self {
+        e.to_string()
+    }
+}
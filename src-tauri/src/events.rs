@@ -0,0 +1,145 @@
+//! Which windows get which events. Each window calls `subscribe_events` when it starts:
+//! the dashboard subscribes to everything (`"*"`), a note window to the kinds it handles
+//! and only for its own note. Events about one note (`NotesBackend::emit_note_event`)
+//! then skip windows subscribed to other notes, and every event skips windows that didn't
+//! ask for its kind.
+//!
+//! Windows that never subscribe (frontends from before this) still get everything while
+//! the `broadcast_unsubscribed_events` setting is on, which is the default.
+
+use serde_json::Map;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{Emitter, EventTarget, Manager, Runtime};
+
+use crate::settings;
+
+/// Subscribing to this kind subscribes to all of them.
+const ALL_KINDS: &str = "*";
+
+struct Subscription {
+    kinds: HashSet<String>,
+    /// `None` takes note events for every note
+    note_ids: Option<HashSet<String>>,
+}
+
+impl Subscription {
+    fn wants(&self, event: &str, note_id: Option<&str>) -> bool {
+        let kind = self.kinds.contains(ALL_KINDS) || self.kinds.contains(event);
+        match (note_id, &self.note_ids) {
+            (Some(id), Some(ids)) => kind && ids.contains(id),
+            _ => kind,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EventSubscriptions {
+    windows: Mutex<HashMap<String, Subscription>>,
+    /// Deliveries to a window
+    emitted: AtomicU64,
+    /// Windows skipped because they didn't subscribe to the event
+    suppressed: AtomicU64,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct EventStats {
+    emitted: u64,
+    suppressed: u64,
+}
+
+fn broadcast_unsubscribed<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    settings::current(app).broadcast_unsubscribed_events
+}
+
+/// Sends `event` to the windows that want it; `note_id` marks it as being about one note.
+pub fn emit<R: Runtime, S: serde::Serialize + Clone>(
+    app: &tauri::AppHandle<R>,
+    event: &str,
+    note_id: Option<&str>,
+    payload: S,
+) {
+    // Events sent during startup, before there is anyone to subscribe
+    let Some(state) = app.try_state::<EventSubscriptions>() else {
+        let _ = app.emit_to(EventTarget::any(), event, payload);
+        return;
+    };
+    let labels: Vec<String> = app.webview_windows().into_keys().collect();
+    let targets: Vec<&String> = {
+        let Ok(windows) = state.windows.lock() else {
+            let _ = app.emit_to(EventTarget::any(), event, payload);
+            return;
+        };
+        let legacy = broadcast_unsubscribed(app);
+        labels
+            .iter()
+            .filter(|label| match windows.get(*label) {
+                Some(subscription) => subscription.wants(event, note_id),
+                None => legacy,
+            })
+            .collect()
+    };
+
+    state.emitted.fetch_add(targets.len() as u64, Ordering::Relaxed);
+    state
+        .suppressed
+        .fetch_add((labels.len() - targets.len()) as u64, Ordering::Relaxed);
+    if targets.len() == labels.len() {
+        let _ = app.emit_to(EventTarget::any(), event, payload);
+        return;
+    }
+    for label in targets {
+        let _ = app.emit_to(EventTarget::webview_window(label), event, payload.clone());
+    }
+}
+
+/// Forgets a window's subscription; called when the window is destroyed.
+pub fn unsubscribe<R: Runtime>(app: &tauri::AppHandle<R>, label: &str) {
+    if let Some(state) = app.try_state::<EventSubscriptions>() {
+        if let Ok(mut windows) = state.windows.lock() {
+            windows.remove(label);
+        }
+    }
+}
+
+pub fn event_stats<R: Runtime>(app: &tauri::AppHandle<R>) -> EventStats {
+    let state = app.state::<EventSubscriptions>();
+    EventStats {
+        emitted: state.emitted.load(Ordering::Relaxed),
+        suppressed: state.suppressed.load(Ordering::Relaxed),
+    }
+}
+
+/// Replaces the calling window's subscription. `kinds` may contain `"*"`; `note_ids`
+/// limits note events to those notes, without it the window gets them for every note.
+#[tauri::command]
+pub async fn subscribe_events(
+    kinds: Vec<String>,
+    note_ids: Option<Vec<String>>,
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let state = app.state::<EventSubscriptions>();
+    let mut windows = state.windows.lock().map_err(|e| e.to_string())?;
+    windows.insert(
+        window.label().to_string(),
+        Subscription {
+            kinds: kinds.into_iter().collect(),
+            note_ids: note_ids.map(|ids| ids.into_iter().collect()),
+        },
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_broadcast_unsubscribed_events(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(broadcast_unsubscribed(&app))
+}
+
+/// Compatibility switch for frontends that don't subscribe; turn off once they all do.
+#[tauri::command]
+pub async fn set_broadcast_unsubscribed_events(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    let patch = Map::from_iter([("broadcast_unsubscribed_events".to_string(), enabled.into())]);
+    settings::update(&app, patch).map(|_| ())
+}
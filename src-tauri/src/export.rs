@@ -0,0 +1,349 @@
+//! Exporting a note as a readable document: a standalone HTML page, or a PDF set by
+//! `pdf.rs`. Frontmatter is left out of both.
+
+use pulldown_cmark::{html, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::fs;
+use tauri::Runtime;
+
+use crate::encryption;
+use crate::flush::flush_now;
+use crate::pdf::{BlockStyle, Document, Font, Span};
+use crate::tags::body_start;
+use crate::{derive_title, read_note};
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,'Segoe UI',Helvetica,Arial,sans-serif;\
+max-width:42em;margin:2em auto;padding:0 1em;line-height:1.5;color:#222}\
+pre,code{font-family:Menlo,Consolas,monospace;background:#f4f4f4;border-radius:3px}\
+pre{padding:.75em;overflow-x:auto}code{padding:.1em .3em}pre code{padding:0}\
+blockquote{margin-left:0;padding-left:1em;border-left:3px solid #ddd;color:#555}\
+table{border-collapse:collapse}th,td{border:1px solid #ddd;padding:.3em .6em}img{max-width:100%}";
+
+const BODY_SIZE: f64 = 11.0;
+const CODE_SIZE: f64 = 9.5;
+const BLOCK_GAP: f64 = 6.0;
+const LIST_INDENT: f64 = 18.0;
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Html,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_html(title: &str, content: &str) -> String {
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(content, markdown_options()));
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        HTML_STYLE,
+        body
+    )
+}
+
+fn heading_size(level: HeadingLevel) -> f64 {
+    match level {
+        HeadingLevel::H1 => 20.0,
+        HeadingLevel::H2 => 16.0,
+        HeadingLevel::H3 => 13.5,
+        _ => 12.0,
+    }
+}
+
+/// A list being set: the next item number, or `None` for bullets.
+struct ListState {
+    next: Option<u64>,
+}
+
+/// Walks the markdown events, gathering inline text into spans and setting each block
+/// as it ends.
+struct PdfWriter {
+    doc: Document,
+    spans: Vec<Span>,
+    bold: usize,
+    italic: usize,
+    quote_depth: usize,
+    lists: Vec<ListState>,
+    /// Bullet or number waiting for the first block of a list item
+    prefix: Option<Span>,
+    heading: Option<HeadingLevel>,
+    code: Option<String>,
+    in_metadata: bool,
+    /// Cells of the table row being read
+    row: Vec<String>,
+    in_cell: bool,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Self {
+        PdfWriter {
+            doc: Document::new(title),
+            spans: Vec::new(),
+            bold: 0,
+            italic: 0,
+            quote_depth: 0,
+            lists: Vec::new(),
+            prefix: None,
+            heading: None,
+            code: None,
+            in_metadata: false,
+            row: Vec::new(),
+            in_cell: false,
+        }
+    }
+
+    fn font(&self) -> Font {
+        if self.bold > 0 || self.heading.is_some() {
+            Font::Bold
+        } else if self.italic > 0 {
+            Font::Italic
+        } else {
+            Font::Regular
+        }
+    }
+
+    fn indent(&self) -> f64 {
+        (self.quote_depth + self.lists.len().saturating_sub(1)) as f64 * LIST_INDENT
+    }
+
+    fn text(&mut self, text: &str, font: Font) {
+        if self.in_cell {
+            if let Some(cell) = self.row.last_mut() {
+                cell.push_str(text);
+            }
+            return;
+        }
+        self.spans.push(Span {
+            font,
+            text: text.to_string(),
+        });
+    }
+
+    /// Sets the gathered spans as one block, if there are any.
+    fn finish_block(&mut self) {
+        if self.spans.is_empty() && self.prefix.is_none() {
+            return;
+        }
+        let size = self.heading.map(heading_size).unwrap_or(BODY_SIZE);
+        let style = BlockStyle {
+            size,
+            indent: self.indent(),
+            space_before: if self.heading.is_some() { size * 0.6 } else { BLOCK_GAP },
+        };
+        let spans = std::mem::take(&mut self.spans);
+        self.doc.paragraph(&spans, style, self.prefix.take());
+    }
+
+    /// Tables are set one line per row, cells separated by bars.
+    fn finish_row(&mut self, font: Font) {
+        let row = std::mem::take(&mut self.row);
+        self.spans.push(Span {
+            font,
+            text: row.iter().map(|c| c.trim()).collect::<Vec<_>>().join("  |  "),
+        });
+        self.finish_block();
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => self.in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => self.in_metadata = false,
+            _ if self.in_metadata => {}
+
+            Event::Start(Tag::Heading { level, .. }) => {
+                self.finish_block();
+                self.heading = Some(level);
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                self.finish_block();
+                self.heading = None;
+            }
+            // A loose list item's first paragraph continues on its bullet's line
+            Event::Start(Tag::Paragraph) if self.prefix.is_none() => self.finish_block(),
+            Event::End(TagEnd::Paragraph) => self.finish_block(),
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.finish_block();
+                self.quote_depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.finish_block();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            Event::Start(Tag::List(start)) => {
+                self.finish_block();
+                self.lists.push(ListState { next: start });
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.finish_block();
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.finish_block();
+                let marker = match self.lists.last_mut().and_then(|l| l.next.as_mut()) {
+                    Some(number) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    None => "• ".to_string(),
+                };
+                self.prefix = Some(Span {
+                    font: Font::Regular,
+                    text: marker,
+                });
+            }
+            Event::End(TagEnd::Item) => self.finish_block(),
+            Event::TaskListMarker(checked) => {
+                let mark = if checked { "[x] " } else { "[ ] " };
+                if let Some(prefix) = self.prefix.as_mut() {
+                    prefix.text.push_str(mark);
+                } else {
+                    self.text(mark, Font::Regular);
+                }
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.finish_block();
+                self.code = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                let code = self.code.take().unwrap_or_default();
+                let style = BlockStyle {
+                    size: CODE_SIZE,
+                    indent: self.indent() + LIST_INDENT / 2.0,
+                    space_before: BLOCK_GAP,
+                };
+                self.doc.preformatted(&code, style);
+            }
+            Event::Start(Tag::Table(_)) => self.finish_block(),
+            Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => self.row.clear(),
+            Event::Start(Tag::TableCell) => {
+                self.in_cell = true;
+                self.row.push(String::new());
+            }
+            Event::End(TagEnd::TableCell) => self.in_cell = false,
+            Event::End(TagEnd::TableHead) => self.finish_row(Font::Bold),
+            Event::End(TagEnd::TableRow) => self.finish_row(Font::Regular),
+            Event::Start(Tag::Strong) => self.bold += 1,
+            Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+            Event::Start(Tag::Emphasis) => self.italic += 1,
+            Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+            Event::Rule => {
+                self.finish_block();
+                self.doc.rule(BLOCK_GAP);
+            }
+            Event::Text(text) => match self.code.as_mut() {
+                Some(code) => code.push_str(&text),
+                None => self.text(&text, self.font()),
+            },
+            Event::Code(code) => self.text(&code, Font::Mono),
+            Event::SoftBreak => self.text(" ", self.font()),
+            Event::HardBreak => self.text("\n", self.font()),
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.finish_block();
+        self.doc.render()
+    }
+}
+
+fn render_pdf(title: &str, content: &str) -> Vec<u8> {
+    let mut writer = PdfWriter::new(title);
+    for event in Parser::new_ext(content, markdown_options()) {
+        writer.event(event);
+    }
+    writer.finish()
+}
+
+/// The title from the first line after any frontmatter.
+pub fn document_title(content: &str) -> String {
+    derive_title(&content[body_start(content)..])
+}
+
+/// Note `id` as a document in `format`, with its title.
+pub fn render_note<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    format: ExportFormat,
+) -> Result<(String, Vec<u8>), String> {
+    let content = encryption::open(app, id, read_note(app, id)?)?;
+    let title = document_title(&content);
+    let bytes = match format {
+        ExportFormat::Html => render_html(&title, &content).into_bytes(),
+        ExportFormat::Pdf => render_pdf(&title, &content),
+    };
+    Ok((title, bytes))
+}
+
+/// Writes note `id` to `destination` as a standalone HTML page or a PDF.
+#[tauri::command]
+pub async fn export_note(
+    id: String,
+    format: ExportFormat,
+    destination: String,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    flush_now(&app, "export");
+    let (_, bytes) = render_note(&app, &id, format)?;
+    fs::write(&destination, bytes).map_err(|e| e.to_string())?;
+    println!("Exported {} as {:?} to {}", id, format, destination);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str =
+        "---\ntags: [work]\n---\n# Plan & review\n\n- [x] draft\n- [ ] send\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+
+    #[test]
+    fn titles_come_from_the_first_line_after_the_frontmatter() {
+        assert_eq!(document_title(NOTE), "Plan & review");
+        assert_eq!(document_title("Just text\nmore"), "Just text");
+        assert_eq!(document_title("---\ntags: [a]\n---\n"), "Untitled");
+    }
+
+    #[test]
+    fn html_pages_escape_the_title_and_leave_out_frontmatter() {
+        let page = render_html("1 < 2 & \"3\"", NOTE);
+        assert!(page.contains("<title>1 &lt; 2 &amp; &quot;3&quot;</title>"), "{}", page);
+        assert!(page.contains("<h1>Plan &amp; review</h1>"), "{}", page);
+        assert!(page.contains("<table>"));
+        assert!(page.contains("type=\"checkbox\""));
+        assert!(!page.contains("tags:"));
+    }
+
+    #[test]
+    fn pdfs_carry_the_title_but_not_the_frontmatter() {
+        let pdf = render_pdf("Plan", NOTE);
+        assert!(pdf.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Title <FEFF0050006C0061006E>"));
+        // "tags:" in WinAnsi hex, as a content stream would hold it
+        assert!(!text.contains("746167733A"));
+    }
+}
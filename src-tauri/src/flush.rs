@@ -0,0 +1,163 @@
+//! Coordinated flushing of everything that batches writes in memory (usage counters,
+//! device-local note state, staged store values). Writers register a `Flushable`;
+//! `flush_now` runs them when the app goes idle, before exports and on quit.
+//!
+//! Idle means no command and no window event for `idle_flush_secs` (default 30). Both
+//! only bump one timestamp, and a flusher with nothing pending never touches the disk.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::{now_millis, settings, storage};
+
+pub const DEFAULT_IDLE_SECS: u64 = 30;
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Unix millis of the last command or window event.
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FlushOutcome {
+    /// Nothing was pending
+    Clean,
+    Wrote,
+    Failed { error: String },
+}
+
+pub trait Flushable<R: Runtime>: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Writes whatever is pending; must return `Clean` without I/O when nothing is.
+    fn flush(&self, app: &tauri::AppHandle<R>) -> FlushOutcome;
+}
+
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct FlushStatus {
+    /// Unix millis of the last flush
+    last_flush_at: Option<u64>,
+    /// What triggered it (`idle`, `quit`, `export`, ...)
+    trigger: Option<String>,
+    results: Vec<(String, FlushOutcome)>,
+}
+
+pub struct FlushRegistry<R: Runtime> {
+    flushers: Mutex<Vec<Box<dyn Flushable<R>>>>,
+    status: Mutex<FlushStatus>,
+}
+
+impl<R: Runtime> Default for FlushRegistry<R> {
+    fn default() -> Self {
+        FlushRegistry {
+            flushers: Mutex::new(Vec::new()),
+            status: Mutex::new(FlushStatus::default()),
+        }
+    }
+}
+
+/// Records activity; called for every command and window event, so it stays a single store.
+pub fn touch() {
+    LAST_ACTIVITY.store(now_millis(), Ordering::Relaxed);
+}
+
+/// Unix millis of the last command or window event.
+pub fn last_activity() -> u64 {
+    LAST_ACTIVITY.load(Ordering::Relaxed)
+}
+
+pub fn register<R: Runtime>(app: &tauri::AppHandle<R>, flusher: impl Flushable<R> + 'static) {
+    if let Ok(mut flushers) = app.state::<FlushRegistry<R>>().flushers.lock() {
+        flushers.push(Box::new(flusher));
+    }
+}
+
+/// Runs every registered flusher and records the results in the flush status.
+pub fn flush_now<R: Runtime>(app: &tauri::AppHandle<R>, trigger: &str) -> FlushStatus {
+    let registry = app.state::<FlushRegistry<R>>();
+    let results: Vec<(String, FlushOutcome)> = registry
+        .flushers
+        .lock()
+        .map(|flushers| flushers.iter().map(|f| (f.name().to_string(), f.flush(app))).collect())
+        .unwrap_or_default();
+    for (name, outcome) in &results {
+        if let FlushOutcome::Failed { error } = outcome {
+            println!("Flush of {} ({}) failed: {}", name, trigger, error);
+        }
+    }
+
+    let status = FlushStatus {
+        last_flush_at: Some(now_millis()),
+        trigger: Some(trigger.to_string()),
+        results,
+    };
+    if let Ok(mut current) = registry.status.lock() {
+        *current = status.clone();
+    }
+    status
+}
+
+pub fn flush_status<R: Runtime>(app: &tauri::AppHandle<R>) -> FlushStatus {
+    app.state::<FlushRegistry<R>>()
+        .status
+        .lock()
+        .map(|s| s.clone())
+        .unwrap_or_default()
+}
+
+fn idle_after<R: Runtime>(app: &tauri::AppHandle<R>) -> u64 {
+    settings::current(app).idle_flush_secs
+}
+
+/// Flushes once per idle period: after `idle_flush_secs` without activity, and not again
+/// until something has happened since.
+pub fn spawn_idle_flusher<R: Runtime>(app: tauri::AppHandle<R>) {
+    touch();
+    tauri::async_runtime::spawn(async move {
+        let mut flushed_after = 0;
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            let last_activity = LAST_ACTIVITY.load(Ordering::Relaxed);
+            let idle = now_millis().saturating_sub(last_activity) >= idle_after(&app) * 1000;
+            // Reduced-writes mode holds these back; leaving it flushes anyway
+            if idle && last_activity != flushed_after && !storage::reduced_writes(&app) {
+                flush_now(&app, "idle");
+                flushed_after = last_activity;
+            }
+        }
+    });
+}
+
+/// Writes everything batched in memory now.
+#[tauri::command]
+pub async fn flush_all(app: tauri::AppHandle) -> Result<FlushStatus, String> {
+    Ok(flush_now(&app, "command"))
+}
+
+/// Saves the key/value stores that had values staged with `NotesBackend::stage_store`.
+pub struct StagedStores;
+
+impl<R: Runtime> Flushable<R> for StagedStores {
+    fn name(&self) -> &'static str {
+        "staged_stores"
+    }
+
+    fn flush(&self, app: &tauri::AppHandle<R>) -> FlushOutcome {
+        let staged = crate::backend::take_staged_stores();
+        if staged.is_empty() {
+            return FlushOutcome::Clean;
+        }
+        for name in &staged {
+            let result = app
+                .store(name)
+                .map_err(|e| e.to_string())
+                .and_then(|store| store.save().map_err(|e| e.to_string()));
+            if let Err(error) = result {
+                crate::backend::restage(&staged);
+                return FlushOutcome::Failed { error };
+            }
+        }
+        FlushOutcome::Wrote
+    }
+}
@@ -0,0 +1,188 @@
+//! Focus mode: one note stays visible, pinned and centered while every other note window
+//! is hidden, until a timer runs out or it is ended explicitly (including by the tray
+//! show-all and by quitting). Only window state changes; nothing is persisted.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, PhysicalPosition, Runtime};
+
+use crate::applock;
+use crate::backend::NotesBackend;
+use crate::focustrack::focus_programmatically;
+use crate::notewindow::NoteWindowOptions;
+use crate::{create_note_window, WindowRegistry};
+
+#[derive(Clone, Copy)]
+struct SavedWindow {
+    visible: bool,
+    pinned: bool,
+    position: Option<PhysicalPosition<i32>>,
+}
+
+struct ActiveFocus {
+    id: String,
+    generation: u64,
+    saved: HashMap<String, SavedWindow>,
+}
+
+#[derive(Default)]
+pub struct FocusModeState {
+    active: Mutex<Option<ActiveFocus>>,
+    next_generation: Mutex<u64>,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct FocusModeChanged {
+    active: bool,
+    id: Option<String>,
+}
+
+fn save_window<R: Runtime>(window: &tauri::WebviewWindow<R>) -> SavedWindow {
+    SavedWindow {
+        visible: window.is_visible().unwrap_or(true),
+        pinned: window.is_always_on_top().unwrap_or(false),
+        position: window.outer_position().ok(),
+    }
+}
+
+/// A note window created during focus mode stays visible but is put back as it is now
+/// when focus mode ends.
+pub fn note_created<R: Runtime>(window: &tauri::WebviewWindow<R>) {
+    let state = window.app_handle().state::<FocusModeState>();
+    let Ok(mut active) = state.active.lock() else {
+        return;
+    };
+    if let Some(focus) = active.as_mut() {
+        let mut saved = save_window(window);
+        saved.visible = true;
+        focus.saved.entry(window.label().to_string()).or_insert(saved);
+    }
+}
+
+/// Puts every recorded window back the way it was. Does nothing outside focus mode.
+pub fn end_focus<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Some(focus) = app
+        .state::<FocusModeState>()
+        .active
+        .lock()
+        .ok()
+        .and_then(|mut active| active.take())
+    else {
+        return;
+    };
+
+    let focused_label = format!("note-{}", focus.id);
+    for (label, saved) in &focus.saved {
+        let Some(window) = app.get_webview_window(label) else {
+            continue;
+        };
+        let _ = window.set_always_on_top(saved.pinned);
+        if *label == focused_label {
+            if let Some(position) = saved.position {
+                let _ = window.set_position(position);
+            }
+        }
+        if saved.visible {
+            let _ = window.show();
+        } else {
+            let _ = window.hide();
+        }
+    }
+
+    app.emit_event(
+        "focus-mode-changed",
+        FocusModeChanged {
+            active: false,
+            id: None,
+        },
+    );
+}
+
+fn schedule_end<R: Runtime>(app: &tauri::AppHandle<R>, generation: u64, after: Duration) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(after).await;
+        // A later focus mode (or an explicit end) supersedes this timer
+        let still_current = app
+            .state::<FocusModeState>()
+            .active
+            .lock()
+            .map(|active| active.as_ref().map(|f| f.generation) == Some(generation))
+            .unwrap_or(false);
+        if still_current {
+            end_focus(&app);
+        }
+    });
+}
+
+/// Hides every other note, pins and centers `id`, and restores everything after
+/// `minutes` (if given) or on `end_focus_mode`.
+#[tauri::command]
+pub async fn focus_note_mode(id: String, minutes: Option<u32>, app: tauri::AppHandle) -> Result<(), String> {
+    applock::ensure_unlocked(&app)?;
+    // Starting over from a clean slate keeps the recorded states the user's own
+    end_focus(&app);
+
+    let label = format!("note-{}", id);
+    let target = match app.get_webview_window(&label) {
+        Some(window) => window,
+        None => create_note_window(&app, NoteWindowOptions::open(id.clone()))?,
+    };
+
+    let saved: HashMap<String, SavedWindow> = app
+        .state::<WindowRegistry>()
+        .note_labels()
+        .into_iter()
+        .filter_map(|l| app.get_webview_window(&l).map(|w| (l, save_window(&w))))
+        .collect();
+
+    let generation = {
+        let state = app.state::<FocusModeState>();
+        let generation = state
+            .next_generation
+            .lock()
+            .map(|mut next| {
+                *next += 1;
+                *next
+            })
+            .unwrap_or(0);
+        let mut active = state.active.lock().map_err(|e| e.to_string())?;
+        *active = Some(ActiveFocus {
+            id: id.clone(),
+            generation,
+            saved: saved.clone(),
+        });
+        generation
+    };
+
+    for other in saved.keys().filter(|l| **l != label) {
+        if let Some(window) = app.get_webview_window(other) {
+            let _ = window.hide();
+        }
+    }
+    let _ = target.show();
+    let _ = target.unminimize();
+    let _ = target.set_always_on_top(true);
+    let _ = target.center();
+    focus_programmatically(&target);
+
+    if let Some(minutes) = minutes.filter(|m| *m > 0) {
+        schedule_end(&app, generation, Duration::from_secs(minutes as u64 * 60));
+    }
+
+    app.emit_event(
+        "focus-mode-changed",
+        FocusModeChanged {
+            active: true,
+            id: Some(id),
+        },
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn end_focus_mode(app: tauri::AppHandle) -> Result<(), String> {
+    end_focus(&app);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Decides which note `Focused(true)` events reach the session order, and counts the
+//! outcome so the saved churn can be checked in `get_metrics`.
+//!
+//! Focus we cause ourselves (show-all's final focus, focus mode) is tagged just before
+//! `set_focus`; the matching event arriving shortly after is not a user choice.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, Runtime};
+
+/// How long after a tagged `set_focus` its `Focused` event is attributed to us.
+const PROGRAMMATIC_WINDOW: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub struct FocusTracker {
+    tagged: Mutex<HashMap<String, Instant>>,
+    persisted: AtomicU64,
+    skipped_unchanged: AtomicU64,
+    skipped_programmatic: AtomicU64,
+    skipped_batch: AtomicU64,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct FocusStats {
+    /// Focus events that changed and saved the session order
+    persisted: u64,
+    /// The note was already last in the order
+    skipped_unchanged: u64,
+    /// Caused by our own `set_focus`
+    skipped_programmatic: u64,
+    /// Arrived during a batch focus pass
+    skipped_batch: u64,
+}
+
+#[derive(Clone, Copy)]
+pub enum FocusOutcome {
+    Persisted,
+    Unchanged,
+    Batch,
+}
+
+/// Focuses `window`, marking the resulting focus event as ours.
+pub fn focus_programmatically<R: Runtime>(window: &tauri::WebviewWindow<R>) {
+    if let Ok(mut tagged) = window.app_handle().state::<FocusTracker>().tagged.lock() {
+        tagged.retain(|_, at| at.elapsed() < PROGRAMMATIC_WINDOW);
+        tagged.insert(window.label().to_string(), Instant::now());
+    }
+    let _ = window.set_focus();
+}
+
+/// Whether a focus event for `label` comes from a tagged `set_focus`. Counts it if so.
+pub fn is_programmatic<R: Runtime>(app: &tauri::AppHandle<R>, label: &str) -> bool {
+    let tracker = app.state::<FocusTracker>();
+    let programmatic = tracker
+        .tagged
+        .lock()
+        .ok()
+        .and_then(|mut tagged| tagged.remove(label))
+        .is_some_and(|at| at.elapsed() < PROGRAMMATIC_WINDOW);
+    if programmatic {
+        tracker.skipped_programmatic.fetch_add(1, Ordering::Relaxed);
+    }
+    programmatic
+}
+
+pub fn count<R: Runtime>(app: &tauri::AppHandle<R>, outcome: FocusOutcome) {
+    let tracker = app.state::<FocusTracker>();
+    let counter = match outcome {
+        FocusOutcome::Persisted => &tracker.persisted,
+        FocusOutcome::Unchanged => &tracker.skipped_unchanged,
+        FocusOutcome::Batch => &tracker.skipped_batch,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn stats<R: Runtime>(app: &tauri::AppHandle<R>) -> FocusStats {
+    let tracker = app.state::<FocusTracker>();
+    FocusStats {
+        persisted: tracker.persisted.load(Ordering::Relaxed),
+        skipped_unchanged: tracker.skipped_unchanged.load(Ordering::Relaxed),
+        skipped_programmatic: tracker.skipped_programmatic.load(Ordering::Relaxed),
+        skipped_batch: tracker.skipped_batch.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,241 @@
+//! "Follow me" mode: a single note that stays with the user across virtual desktops.
+//! Which note follows is kept in its metadata (`NoteMeta::follow`), so it survives renames
+//! and goes away with the note.
+//!
+//! On Windows the note is moved to each virtual desktop the user switches to, through
+//! `IVirtualDesktopManager`; switches aren't announced there, so a watcher thread polls.
+//! On macOS it is carried to each space the user switches to when
+//! `NSWorkspaceActiveSpaceDidChangeNotification` arrives. Where neither is available the
+//! fallback is marking the window visible on all workspaces at once. The command result
+//! says which mode was applied so the frontend can explain it.
+
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::meta;
+
+/// Where follow mode used to be kept, before it moved into the note's metadata.
+const LEGACY_KEY: &str = "follow_note";
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FollowMode {
+    /// Moved to each desktop or space the user switches to
+    Follow,
+    /// Fallback: shown on every workspace at once
+    AllWorkspaces,
+}
+
+/// The mode this run supports, decided by whether the platform watcher started.
+pub struct FollowState(FollowMode);
+
+#[derive(serde::Serialize)]
+pub struct FollowResult {
+    enabled: bool,
+    mode: Option<FollowMode>,
+    /// The note that lost follow mode because this one took it
+    previous: Option<String>,
+}
+
+fn mode<R: Runtime>(app: &tauri::AppHandle<R>) -> FollowMode {
+    app.try_state::<FollowState>()
+        .map_or(FollowMode::AllWorkspaces, |state| state.0)
+}
+
+fn get_follow_note<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<String> {
+    meta::load_all(app)
+        .into_iter()
+        .find(|(_, meta)| meta.follow)
+        .map(|(id, _)| id)
+}
+
+/// The following note's window, if it is open and showing.
+#[cfg(any(windows, target_os = "macos"))]
+fn following_window<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<tauri::WebviewWindow<R>> {
+    let window = app.get_webview_window(&format!("note-{}", get_follow_note(app)?))?;
+    window.is_visible().unwrap_or(false).then_some(window)
+}
+
+fn set_window_following<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, enabled: bool) {
+    if mode(app) != FollowMode::AllWorkspaces {
+        // The watcher picks the note up on the next switch
+        return;
+    }
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        let _ = window.set_visible_on_all_workspaces(enabled);
+    }
+}
+
+/// Moves follow mode from session.bin `follow_note` into the note's metadata.
+fn migrate<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Ok(store) = app.store("session.bin") else {
+        return;
+    };
+    let Some(id) = store.get(LEGACY_KEY).and_then(|v| v.as_str().map(str::to_string)) else {
+        return;
+    };
+    if let Err(e) = meta::update_meta(app, &id, |meta| meta.follow = true) {
+        println!("Failed to migrate follow mode of {}: {}", id, e);
+        return;
+    }
+    store.delete(LEGACY_KEY);
+    let _ = store.save();
+}
+
+/// Starts the platform watcher, falling back to all-workspaces mode where there is none.
+pub fn init<R: Runtime>(app: &tauri::AppHandle<R>) {
+    migrate(app);
+    let mode = if platform::watch(app.clone()) {
+        FollowMode::Follow
+    } else {
+        FollowMode::AllWorkspaces
+    };
+    println!("Follow mode: {:?}", mode);
+    app.manage(FollowState(mode));
+}
+
+/// Re-applies follow mode to a freshly built note window.
+pub fn apply_follow_state<R: Runtime>(window: &tauri::WebviewWindow<R>, id: &str) {
+    let app = window.app_handle();
+    if mode(app) == FollowMode::AllWorkspaces && meta::get_meta(app, id).follow {
+        let _ = window.set_visible_on_all_workspaces(true);
+    }
+}
+
+#[tauri::command]
+pub async fn set_note_follow(id: String, enabled: bool, app: tauri::AppHandle) -> Result<FollowResult, String> {
+    if !enabled {
+        meta::update_meta(&app, &id, |meta| meta.follow = false)?;
+        set_window_following(&app, &id, false);
+        return Ok(FollowResult {
+            enabled: false,
+            mode: None,
+            previous: None,
+        });
+    }
+
+    // Only one note may follow at a time
+    let previous = get_follow_note(&app).filter(|previous| previous != &id);
+    if let Some(previous) = &previous {
+        meta::update_meta(&app, previous, |meta| meta.follow = false)?;
+        set_window_following(&app, previous, false);
+    }
+
+    meta::update_meta(&app, &id, |meta| meta.follow = true)?;
+    set_window_following(&app, &id, true);
+
+    Ok(FollowResult {
+        enabled: true,
+        mode: Some(mode(&app)),
+        previous,
+    })
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tauri::Runtime;
+    use windows::core::GUID;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::{IVirtualDesktopManager, VirtualDesktopManager};
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    /// How often the watcher checks whether the user switched desktops.
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Moves `hwnd` to the desktop the user is on, if it isn't there already. That desktop
+    /// is the foreground window's; there is no public call asking for it directly.
+    fn bring_to_current(desktops: &IVirtualDesktopManager, hwnd: HWND) -> bool {
+        unsafe {
+            let elsewhere = desktops
+                .IsWindowOnCurrentVirtualDesktop(hwnd)
+                .is_ok_and(|on| !on.as_bool());
+            if !elsewhere {
+                return false;
+            }
+            let foreground = GetForegroundWindow();
+            if foreground.is_invalid() {
+                return false;
+            }
+            // The desktop and task bar belong to no desktop and report the zero GUID
+            match desktops.GetWindowDesktopId(foreground) {
+                Ok(desktop) if desktop != GUID::zeroed() => desktops.MoveWindowToDesktop(hwnd, &desktop).is_ok(),
+                _ => false,
+            }
+        }
+    }
+
+    /// Starts the polling thread. The desktop manager is a COM object bound to the thread
+    /// that created it, so it is created there; returns whether that worked.
+    pub fn watch<R: Runtime>(app: tauri::AppHandle<R>) -> bool {
+        let (started, result) = mpsc::channel();
+        std::thread::spawn(move || {
+            let desktops: Option<IVirtualDesktopManager> = unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                CoCreateInstance(&VirtualDesktopManager, None, CLSCTX_ALL).ok()
+            };
+            let _ = started.send(desktops.is_some());
+            let Some(desktops) = desktops else {
+                return;
+            };
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let Some(window) = super::following_window(&app) else {
+                    continue;
+                };
+                if let Ok(hwnd) = window.hwnd() {
+                    if bring_to_current(&desktops, hwnd) {
+                        println!("Moved the following note to the current desktop");
+                    }
+                }
+            }
+        });
+        result.recv().unwrap_or(false)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use block2::RcBlock;
+    use objc2_app_kit::{NSWorkspace, NSWorkspaceActiveSpaceDidChangeNotification};
+    use objc2_foundation::{NSNotification, NSOperationQueue};
+    use std::ptr::NonNull;
+    use tauri::Runtime;
+
+    /// Joining all spaces and leaving them again leaves the window on the active one.
+    fn carry<R: Runtime>(app: &tauri::AppHandle<R>) {
+        if let Some(window) = super::following_window(app) {
+            let _ = window.set_visible_on_all_workspaces(true);
+            let _ = window.set_visible_on_all_workspaces(false);
+        }
+    }
+
+    /// Observes space switches for the rest of the run, on the main queue.
+    pub fn watch<R: Runtime>(app: tauri::AppHandle<R>) -> bool {
+        let block = RcBlock::new(move |_: NonNull<NSNotification>| carry(&app));
+        unsafe {
+            let center = NSWorkspace::sharedWorkspace().notificationCenter();
+            let observer = center.addObserverForName_object_queue_usingBlock(
+                Some(NSWorkspaceActiveSpaceDidChangeNotification),
+                None,
+                Some(&NSOperationQueue::mainQueue()),
+                &block,
+            );
+            // Never removed; dropping it would only release our reference
+            std::mem::forget(observer);
+        }
+        true
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use tauri::Runtime;
+
+    /// No workspace switches to watch; follow mode falls back to all workspaces.
+    pub fn watch<R: Runtime>(_app: tauri::AppHandle<R>) -> bool {
+        false
+    }
+}
@@ -0,0 +1,632 @@
+//! Optional Git repository in the notes folder (settings.bin `git`), for versioning and
+//! for syncing through any Git remote.
+//!
+//! Our own writes, deletes and moves (`own_change`) and changes the poller detects wake a
+//! committer that, once things have settled for `COMMIT_SETTLE`, commits the whole
+//! folder with a message naming the note. Pushing and pulling are explicit. A pull that
+//! can't fast-forward is merged; where both sides changed a note, ours stays and theirs
+//! is written as `<id>.sync-conflict-<millis>.md` for `conflicts.rs` to offer, as with
+//! any other sync.
+//!
+//! Per-note `history/` snapshots are left out of the repository, and vault-encrypted
+//! notes are committed as ciphertext.
+//!
+//! Locking a note (`encryption.rs`) doesn't reach into the repository: the versions
+//! committed before the lock stay there in plain text, for anyone with the folder or the
+//! remote. `git_history` and `git_diff_version` refuse locked notes so the app at least
+//! doesn't show them; rewriting the history is left to the user.
+
+use git2::{
+    build::CheckoutBuilder, Cred, CredentialType, Delta, DiffOptions, FetchOptions, IndexAddOption, Oid, PushOptions,
+    RemoteCallbacks, Repository, RepositoryInitOptions, Signature, Sort, Tree,
+};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::conflicts::{self, line_diff, DiffLine};
+use crate::encryption::ensure_not_locked;
+use crate::flush::flush_now;
+use crate::packet::HISTORY_DIR;
+use crate::scan::is_valid_note_id;
+use crate::storage::ensure_writes_allowed;
+use crate::vault;
+use crate::{derive_title, notes_dir, now_millis, read_note};
+
+const CONFIG_KEY: &str = "git";
+const REMOTE_NAME: &str = "origin";
+const DEFAULT_BRANCH: &str = "main";
+/// Quiet time after the last change before it is committed.
+const COMMIT_SETTLE: Duration = Duration::from_secs(5);
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+/// Credential callbacks are retried by libgit2 until they give up; this is when we do.
+const MAX_AUTH_ATTEMPTS: usize = 3;
+/// Index stage bits of an entry's flags; 0 for a resolved entry.
+const STAGE_MASK: u16 = 0x3000;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GitConfig {
+    /// URL of the remote to push to and pull from, if any
+    #[serde(default)]
+    remote_url: Option<String>,
+    /// For HTTPS remotes; SSH remotes use the SSH agent
+    #[serde(default)]
+    username: Option<String>,
+    /// Password or access token for HTTPS remotes
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    author_name: Option<String>,
+    #[serde(default)]
+    author_email: Option<String>,
+}
+
+#[derive(Default)]
+pub struct GitState {
+    /// Held for every repository operation
+    repo: Mutex<()>,
+    pending: AtomicBool,
+    wake: tokio::sync::Notify,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct GitVersion {
+    commit: String,
+    summary: String,
+    author: String,
+    /// Unix millis
+    committed_at: u64,
+    /// The commit removed the note
+    deleted: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct GitVersionDiff {
+    commit: String,
+    /// The note as of `commit`, empty if it didn't exist there
+    content: String,
+    /// `mine` is the note as it is now, `theirs` the version
+    diff: Vec<DiffLine>,
+    diff_truncated: bool,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PullOutcome {
+    UpToDate,
+    FastForward,
+    Merged,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct PullReport {
+    outcome: PullOutcome,
+    /// Notes changed on both sides, now pending as sync conflicts
+    conflicts: usize,
+}
+
+fn config(backend: &impl NotesBackend) -> Option<GitConfig> {
+    backend
+        .read_store("settings.bin", CONFIG_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+fn git_error(e: git2::Error) -> String {
+    e.message().to_string()
+}
+
+fn open(backend: &impl NotesBackend) -> Result<Repository, String> {
+    if config(backend).is_none() {
+        return Err("Git is not enabled".to_string());
+    }
+    Repository::open(notes_dir(backend)?).map_err(git_error)
+}
+
+fn signature<'a>(repo: &Repository, config: &GitConfig) -> Result<Signature<'a>, String> {
+    match (&config.author_name, &config.author_email) {
+        (Some(name), Some(email)) => Signature::now(name, email),
+        _ => repo
+            .signature()
+            .or_else(|_| Signature::now("Sticky Notes", "sticky-notes@localhost")),
+    }
+    .map_err(git_error)
+}
+
+/// The note id of a top-level `<id>.md` path in the repository.
+fn note_id_of(path: &Path) -> Option<&str> {
+    if path.parent().is_some_and(|parent| !parent.as_os_str().is_empty()) {
+        return None;
+    }
+    let id = path.to_str()?.strip_suffix(".md")?;
+    is_valid_note_id(id).then_some(id)
+}
+
+/// "Update “Shopping”", or a count when several notes changed at once.
+fn describe<R: Runtime>(app: &tauri::AppHandle<R>, repo: &Repository, old: Option<&Tree>, new: &Tree) -> String {
+    let notes: Vec<(Delta, String)> = repo
+        .diff_tree_to_tree(old, Some(new), Some(&mut DiffOptions::new()))
+        .map(|diff| {
+            diff.deltas()
+                .filter_map(|delta| {
+                    let path = delta.new_file().path().or(delta.old_file().path())?;
+                    Some((delta.status(), note_id_of(path)?.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    match notes.as_slice() {
+        [] => "Update attachments".to_string(),
+        [(Delta::Deleted, id)] => format!("Delete note {}", id),
+        [(status, id)] => {
+            let title = notes_dir(app)
+                .and_then(|dir| vault::read_file(app, &dir.join(format!("{}.md", id))))
+                .map(|content| derive_title(&content))
+                .unwrap_or_else(|_| id.clone());
+            let verb = if *status == Delta::Added { "Add" } else { "Update" };
+            format!("{} “{}”", verb, title)
+        }
+        notes => format!("Update {} notes", notes.len()),
+    }
+}
+
+/// Commits everything in the notes folder that changed since HEAD. Returns the new
+/// commit, or `None` if there was nothing to commit.
+fn commit_all<R: Runtime>(app: &tauri::AppHandle<R>, repo: &Repository) -> Result<Option<Oid>, String> {
+    let config = config(app).ok_or("Git is not enabled")?;
+    let mut index = repo.index().map_err(git_error)?;
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).map_err(git_error)?;
+    index.update_all(["*"], None).map_err(git_error)?;
+    index.write().map_err(git_error)?;
+    let tree = repo
+        .find_tree(index.write_tree().map_err(git_error)?)
+        .map_err(git_error)?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parent_tree = parent.as_ref().and_then(|p| p.tree().ok());
+    if parent_tree.as_ref().is_some_and(|t| t.id() == tree.id()) {
+        return Ok(None);
+    }
+    let message = describe(app, repo, parent_tree.as_ref(), &tree);
+    let signature = signature(repo, &config)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents)
+        .map(Some)
+        .map_err(git_error)
+}
+
+fn commit_pending<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<Option<Oid>, String> {
+    let state = app.state::<GitState>();
+    let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+    state.pending.store(false, Ordering::Relaxed);
+    commit_all(app, &open(app)?)
+}
+
+/// Notes changed on disk; they are committed once things settle.
+pub fn changed<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if config(app).is_none() {
+        return;
+    }
+    let state = app.state::<GitState>();
+    state.pending.store(true, Ordering::Relaxed);
+    state.wake.notify_one();
+}
+
+pub fn spawn_git_committer<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            app.state::<GitState>().wake.notified().await;
+            // Restart the wait for as long as changes keep coming
+            while tokio::time::timeout(COMMIT_SETTLE, app.state::<GitState>().wake.notified())
+                .await
+                .is_ok()
+            {}
+            if app.state::<GitState>().pending.load(Ordering::Relaxed) {
+                if let Err(e) = commit_pending(&app) {
+                    println!("Git auto-commit failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+fn callbacks(config: &GitConfig) -> RemoteCallbacks<'static> {
+    let (username, token) = (config.username.clone(), config.token.clone());
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |_url, url_username, allowed| {
+        attempts += 1;
+        if attempts > MAX_AUTH_ATTEMPTS {
+            return Err(git2::Error::from_str("The remote refused the credentials"));
+        }
+        let user = username.as_deref().or(url_username).unwrap_or("git");
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(token) = &token {
+                return Cred::userpass_plaintext(user, token);
+            }
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(user);
+        }
+        Cred::default()
+    });
+    callbacks
+}
+
+fn branch_name(repo: &Repository) -> String {
+    repo.head()
+        .ok()
+        .and_then(|head| head.shorthand().map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_BRANCH.to_string())
+}
+
+fn origin<'r>(repo: &'r Repository, config: &GitConfig) -> Result<git2::Remote<'r>, String> {
+    let url = config.remote_url.as_deref().ok_or("No Git remote is configured")?;
+    match repo.find_remote(REMOTE_NAME) {
+        Ok(remote) if remote.url() == Some(url) => Ok(remote),
+        Ok(_) => {
+            repo.remote_set_url(REMOTE_NAME, url).map_err(git_error)?;
+            repo.find_remote(REMOTE_NAME).map_err(git_error)
+        }
+        Err(_) => repo.remote(REMOTE_NAME, url).map_err(git_error),
+    }
+}
+
+/// Writes the remote's side of a conflicted note next to it and returns whether it did.
+fn write_conflict_copy(repo: &Repository, workdir: &Path, entry: &git2::IndexEntry) -> Result<bool, String> {
+    let path = String::from_utf8_lossy(&entry.path).into_owned();
+    let Some(id) = note_id_of(Path::new(&path)) else {
+        return Ok(false);
+    };
+    let blob = repo.find_blob(entry.id).map_err(git_error)?;
+    let copy = workdir.join(format!("{}.sync-conflict-{}.md", id, now_millis()));
+    fs::write(copy, blob.content()).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Merges `theirs` into HEAD, keeping our side of conflicting files. Unrelated histories
+/// (the same remote set up from two machines) are merged against an empty tree.
+fn merge(backend: &impl NotesBackend, repo: &Repository, theirs: &git2::Commit) -> Result<usize, String> {
+    let config = config(backend).ok_or("Git is not enabled")?;
+    let ours = repo.head().and_then(|h| h.peel_to_commit()).map_err(git_error)?;
+    let ancestor = match repo.merge_base(ours.id(), theirs.id()) {
+        Ok(base) => repo.find_commit(base).and_then(|c| c.tree()).map_err(git_error)?,
+        Err(_) => {
+            let empty = repo.treebuilder(None).and_then(|b| b.write()).map_err(git_error)?;
+            repo.find_tree(empty).map_err(git_error)?
+        }
+    };
+    let our_tree = ours.tree().map_err(git_error)?;
+    let their_tree = theirs.tree().map_err(git_error)?;
+    let mut index = repo
+        .merge_trees(&ancestor, &our_tree, &their_tree, None)
+        .map_err(git_error)?;
+
+    let workdir = repo.workdir().ok_or("The notes repository has no working folder")?;
+    let found: Vec<git2::IndexConflict> = index
+        .conflicts()
+        .map_err(git_error)?
+        .collect::<Result<_, _>>()
+        .map_err(git_error)?;
+    let mut copies = 0;
+    for conflict in found {
+        let Some(path) = conflict
+            .our
+            .as_ref()
+            .or(conflict.their.as_ref())
+            .map(|e| e.path.clone())
+        else {
+            continue;
+        };
+        // Drops every stage of the path, leaving room for the entry we keep
+        index
+            .remove_path(Path::new(&String::from_utf8_lossy(&path).into_owned()))
+            .map_err(git_error)?;
+        let mut kept = match (conflict.our, conflict.their) {
+            (Some(our), Some(their)) => {
+                if write_conflict_copy(repo, workdir, &their)? {
+                    copies += 1;
+                }
+                our
+            }
+            // Deleted on one side, changed on the other: the change wins
+            (Some(entry), None) | (None, Some(entry)) => entry,
+            (None, None) => continue,
+        };
+        kept.flags &= !STAGE_MASK;
+        index.add(&kept).map_err(git_error)?;
+    }
+
+    let tree = repo
+        .find_tree(index.write_tree_to(repo).map_err(git_error)?)
+        .map_err(git_error)?;
+    let signature = signature(repo, &config)?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &format!("Merge notes from {}", REMOTE_NAME),
+        &tree,
+        &[&ours, theirs],
+    )
+    .map_err(git_error)?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))
+        .map_err(git_error)?;
+    Ok(copies)
+}
+
+/// Sets up the repository (or turns the integration off with `None`, leaving the
+/// repository in place). The first set-up commits the notes as they are.
+#[tauri::command]
+pub async fn set_git_config(config: Option<GitConfig>, app: tauri::AppHandle) -> Result<(), String> {
+    let Some(config) = config else {
+        return app.write_store("settings.bin", CONFIG_KEY, serde_json::Value::Null);
+    };
+    ensure_writes_allowed(&app)?;
+    let dir = notes_dir(&app)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let state = app.state::<GitState>();
+    let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+    let repo = match Repository::open(&dir) {
+        Ok(repo) => repo,
+        Err(_) => {
+            let repo = Repository::init_opts(&dir, RepositoryInitOptions::new().initial_head(DEFAULT_BRANCH))
+                .map_err(git_error)?;
+            // Snapshots are what Git replaces; sync clients' conflict copies stay local
+            let ignore = format!("{}/\n*.sync-conflict-*.md\n* (conflicted copy)*.md\n", HISTORY_DIR);
+            fs::write(dir.join(".gitignore"), ignore).map_err(|e| e.to_string())?;
+            repo
+        }
+    };
+    app.write_store(
+        "settings.bin",
+        CONFIG_KEY,
+        serde_json::to_value(&config).map_err(|e| e.to_string())?,
+    )?;
+    if config.remote_url.is_some() {
+        origin(&repo, &config)?;
+    }
+    flush_now(&app, "git");
+    commit_all(&app, &repo)?;
+    Ok(())
+}
+
+/// Commits that touched note `id`, newest first. `Locked` for a locked note.
+#[tauri::command]
+pub async fn git_history(id: String, limit: Option<usize>, app: tauri::AppHandle) -> Result<Vec<GitVersion>, String> {
+    if !is_valid_note_id(&id) {
+        return Err(format!("Invalid note id {:?}", id));
+    }
+    ensure_not_locked(&id, &read_note(&app, &id)?)?;
+    let state = app.state::<GitState>();
+    let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+    let repo = open(&app)?;
+    let path = format!("{}.md", id);
+    let blob_at =
+        |commit: &git2::Commit| -> Option<Oid> { commit.tree().ok()?.get_path(Path::new(&path)).ok().map(|e| e.id()) };
+
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    if walk.push_head().is_err() {
+        // No commits yet
+        return Ok(Vec::new());
+    }
+    walk.set_sorting(Sort::TIME).map_err(git_error)?;
+    let mut versions = Vec::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid.map_err(git_error)?).map_err(git_error)?;
+        let blob = blob_at(&commit);
+        let before = commit.parent(0).ok().and_then(|parent| blob_at(&parent));
+        if blob == before {
+            continue;
+        }
+        versions.push(GitVersion {
+            commit: commit.id().to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            committed_at: commit.time().seconds().max(0) as u64 * 1000,
+            deleted: blob.is_none(),
+        });
+        if versions.len() >= limit.unwrap_or(DEFAULT_HISTORY_LIMIT) {
+            break;
+        }
+    }
+    Ok(versions)
+}
+
+/// Note `id` as of `commit`, with a line diff against the note as it is now. `Locked`
+/// for a locked note, whose earlier versions would otherwise be shown in plain text.
+#[tauri::command]
+pub async fn git_diff_version(id: String, commit: String, app: tauri::AppHandle) -> Result<GitVersionDiff, String> {
+    if !is_valid_note_id(&id) {
+        return Err(format!("Invalid note id {:?}", id));
+    }
+    let current = read_note(&app, &id)?;
+    ensure_not_locked(&id, &current)?;
+    let content = {
+        let state = app.state::<GitState>();
+        let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+        let repo = open(&app)?;
+        let oid = Oid::from_str(&commit).map_err(git_error)?;
+        let tree = repo.find_commit(oid).and_then(|c| c.tree()).map_err(git_error)?;
+        match tree.get_path(Path::new(&format!("{}.md", id))) {
+            Ok(entry) => {
+                let blob = repo.find_blob(entry.id()).map_err(git_error)?;
+                let stored = String::from_utf8(blob.content().to_vec()).map_err(|e| e.to_string())?;
+                vault::decode(&app, stored)?
+            }
+            Err(_) => String::new(),
+        }
+    };
+    let (diff, diff_truncated) = line_diff(&current, &content);
+    Ok(GitVersionDiff {
+        commit,
+        content,
+        diff,
+        diff_truncated,
+    })
+}
+
+/// Commits anything pending and pushes the current branch to the remote.
+#[tauri::command]
+pub async fn git_push(app: tauri::AppHandle) -> Result<(), String> {
+    let config = config(&app).ok_or("Git is not enabled")?;
+    flush_now(&app, "git push");
+    let state = app.state::<GitState>();
+    let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+    let repo = open(&app)?;
+    commit_all(&app, &repo)?;
+    let branch = branch_name(&repo);
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks(&config));
+    origin(&repo, &config)?
+        .push(&[format!("refs/heads/{0}:refs/heads/{0}", branch)], Some(&mut options))
+        .map_err(git_error)?;
+    println!("Pushed notes to {}", REMOTE_NAME);
+    Ok(())
+}
+
+/// Commits anything pending, then fetches the remote branch and fast-forwards or merges
+/// it into the notes folder.
+#[tauri::command]
+pub async fn git_pull(app: tauri::AppHandle) -> Result<PullReport, String> {
+    let config = config(&app).ok_or("Git is not enabled")?;
+    ensure_writes_allowed(&app)?;
+    flush_now(&app, "git pull");
+    let report = {
+        let state = app.state::<GitState>();
+        let _repo = state.repo.lock().map_err(|e| e.to_string())?;
+        let repo = open(&app)?;
+        commit_all(&app, &repo)?;
+        let branch = branch_name(&repo);
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(callbacks(&config));
+        origin(&repo, &config)?
+            .fetch(&[branch.as_str()], Some(&mut options), None)
+            .map_err(git_error)?;
+
+        let fetched = repo
+            .find_reference("FETCH_HEAD")
+            .and_then(|r| r.peel_to_commit())
+            .map_err(git_error)?;
+        let annotated = repo.find_annotated_commit(fetched.id()).map_err(git_error)?;
+        let (analysis, _) = repo.merge_analysis(&[&annotated]).map_err(git_error)?;
+        if analysis.is_up_to_date() {
+            PullReport {
+                outcome: PullOutcome::UpToDate,
+                conflicts: 0,
+            }
+        } else if analysis.is_fast_forward() || analysis.is_unborn() {
+            repo.reference(
+                &format!("refs/heads/{}", branch),
+                fetched.id(),
+                true,
+                "Fast-forward notes",
+            )
+            .map_err(git_error)?;
+            repo.set_head(&format!("refs/heads/{}", branch)).map_err(git_error)?;
+            repo.checkout_head(Some(CheckoutBuilder::new().force()))
+                .map_err(git_error)?;
+            PullReport {
+                outcome: PullOutcome::FastForward,
+                conflicts: 0,
+            }
+        } else {
+            PullReport {
+                outcome: PullOutcome::Merged,
+                conflicts: merge(&app, &repo, &fetched)?,
+            }
+        }
+    };
+    println!("Pulled notes: {:?}", report);
+    if report.conflicts > 0 {
+        conflicts::sweep(&app);
+    }
+    app.emit_event("refresh-notes", ());
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+    use tempfile::TempDir;
+
+    /// Commits `files` as the whole tree on top of `parents`, moving HEAD if `head`.
+    fn commit<'r>(
+        repo: &'r Repository,
+        head: bool,
+        parents: &[&git2::Commit],
+        files: &[(&str, &str)],
+    ) -> git2::Commit<'r> {
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@localhost").unwrap();
+        let id = repo
+            .commit(head.then_some("HEAD"), &signature, &signature, "Test", &tree, parents)
+            .unwrap();
+        repo.find_commit(id).unwrap()
+    }
+
+    fn read(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn two_sided_conflicts_keep_ours_and_copy_theirs() {
+        let backend = TempBackend::new();
+        backend
+            .write_store("settings.bin", CONFIG_KEY, serde_json::json!({}))
+            .unwrap();
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        let base = commit(
+            &repo,
+            true,
+            &[],
+            &[("a.md", "Plan\n"), ("b.md", "Old\n"), ("c.md", "Same\n")],
+        );
+        let theirs = commit(
+            &repo,
+            false,
+            &[&base],
+            &[("a.md", "Plan\n- theirs\n"), ("c.md", "Same\n")],
+        );
+        let ours = commit(
+            &repo,
+            true,
+            &[&base],
+            &[
+                ("a.md", "Plan\n- ours\n"),
+                ("b.md", "Old, edited\n"),
+                ("c.md", "Same\n"),
+            ],
+        );
+
+        assert_eq!(merge(&backend, &repo, &theirs).unwrap(), 1);
+
+        let merged = repo.head().unwrap().peel_to_commit().unwrap();
+        let parents: Vec<Oid> = merged.parent_ids().collect();
+        assert_eq!(parents, [ours.id(), theirs.id()]);
+        assert!(!repo.index().unwrap().has_conflicts());
+
+        assert_eq!(read(dir.path(), "a.md"), "Plan\n- ours\n");
+        // Deleted there but changed here: the change wins, without a copy
+        assert_eq!(read(dir.path(), "b.md"), "Old, edited\n");
+        assert_eq!(read(dir.path(), "c.md"), "Same\n");
+        let copies: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.contains(".sync-conflict-"))
+            .collect();
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].starts_with("a.sync-conflict-"), "{:?}", copies);
+        assert_eq!(read(dir.path(), &copies[0]), "Plan\n- theirs\n");
+    }
+}
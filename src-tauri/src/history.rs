@@ -0,0 +1,145 @@
+//! Automatic snapshots in each note's `history/` folder. When a save replaces content,
+//! the replaced version is kept as `history/<unix millis>.md`, at most once per
+//! `SNAPSHOT_INTERVAL_MS` so typing doesn't produce a snapshot per keystroke. The oldest
+//! are pruned beyond the `history_max_snapshots` setting.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::encryption;
+use crate::packet::HISTORY_DIR;
+use crate::safepath::{Root, SafePath};
+use crate::settings;
+use crate::timestamps::parse_snapshot_name;
+use crate::vault;
+use crate::{notes_dir, now_millis, read_note, storage, write_note};
+
+const SNAPSHOT_INTERVAL_MS: u64 = 5 * 60 * 1000;
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 50;
+/// Characters of each version returned by `list_note_versions`.
+const VERSION_PREVIEW_CHARS: usize = 100;
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct NoteVersion {
+    /// File stem, what `restore_note_version` takes
+    version: String,
+    /// Unix millis
+    taken_at: u64,
+    size: u64,
+    preview: String,
+}
+
+/// Snapshots in a note's asset directory with when they were taken, oldest first. Files
+/// whose names don't carry a date are skipped.
+pub fn snapshots(asset_dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = fs::read_dir(asset_dir.join(HISTORY_DIR)) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<(PathBuf, u64)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let taken_at = parse_snapshot_name(path.file_stem()?.to_str()?)?;
+            Some((path, taken_at))
+        })
+        .collect();
+    snapshots.sort_by_key(|(_, taken_at)| *taken_at);
+    snapshots
+}
+
+/// The newest snapshot and when it was taken.
+pub fn latest_snapshot(asset_dir: &Path) -> Option<(PathBuf, u64)> {
+    snapshots(asset_dir).pop()
+}
+
+fn max_snapshots(backend: &impl NotesBackend) -> usize {
+    settings::current(backend).history_max_snapshots
+}
+
+fn write_snapshot<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, content: &str) -> Result<(), String> {
+    let asset_dir = notes_dir(app)?.join(id);
+    let dir = asset_dir.join(HISTORY_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let stored = vault::encode(app, content)?;
+    fs::write(dir.join(format!("{}.md", now_millis())), stored).map_err(|e| e.to_string())?;
+
+    let existing = snapshots(&asset_dir);
+    let excess = existing.len().saturating_sub(max_snapshots(app).max(1));
+    for (path, _) in existing.into_iter().take(excess) {
+        SafePath::new(app, Root::Notes, path)?.remove()?;
+    }
+    Ok(())
+}
+
+/// Keeps `previous`, the content a save of note `id` is replacing, unless a snapshot was
+/// taken recently. Snapshots are optional writes and are skipped when disk space is low.
+pub fn note_written<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, previous: Option<&str>, new: &str) {
+    let Some(previous) = previous.filter(|p| !p.trim().is_empty() && *p != new) else {
+        return;
+    };
+    if storage::reduced_writes(app) {
+        return;
+    }
+    let Ok(dir) = notes_dir(app) else {
+        return;
+    };
+    let recent = latest_snapshot(&dir.join(id))
+        .is_some_and(|(_, taken_at)| now_millis().saturating_sub(taken_at) < SNAPSHOT_INTERVAL_MS);
+    if recent {
+        return;
+    }
+    if let Err(e) = write_snapshot(app, id, previous) {
+        println!("Failed to snapshot {}: {}", id, e);
+    }
+}
+
+/// A note's snapshots, newest first.
+#[tauri::command]
+pub async fn list_note_versions(id: String, app: tauri::AppHandle) -> Result<Vec<NoteVersion>, String> {
+    let asset_dir = notes_dir(&app)?.join(&id);
+    let mut versions: Vec<NoteVersion> = snapshots(&asset_dir)
+        .into_iter()
+        .filter_map(|(path, taken_at)| {
+            let size = fs::metadata(&path).ok()?.len();
+            let content = vault::read_file(&app, &path).unwrap_or_default();
+            // Versions of a locked note are encrypted too
+            let preview = if encryption::is_locked(&content) {
+                String::new()
+            } else {
+                content.chars().take(VERSION_PREVIEW_CHARS).collect()
+            };
+            Some(NoteVersion {
+                version: path.file_stem()?.to_str()?.to_string(),
+                taken_at,
+                size,
+                preview,
+            })
+        })
+        .collect();
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Rolls note `id` back to one of its snapshots and returns the restored content. The
+/// current content is snapshotted first, so the rollback can itself be undone.
+#[tauri::command]
+pub async fn restore_note_version(id: String, version: String, app: tauri::AppHandle) -> Result<String, String> {
+    let asset_dir = notes_dir(&app)?.join(&id);
+    let (path, _) = snapshots(&asset_dir)
+        .into_iter()
+        .find(|(path, _)| path.file_stem().is_some_and(|stem| stem == version.as_str()))
+        .ok_or_else(|| format!("Note {} has no version {}", id, version))?;
+    let content = vault::read_file(&app, &path)?;
+
+    let current = read_note(&app, &id)?;
+    if !current.trim().is_empty() && current != content {
+        write_snapshot(&app, &id, &current)?;
+    }
+    write_note(&app, &id, &content)?;
+    println!("Restored {} to version {}", id, version);
+
+    app.emit_note_event(&id, "note-restored-from-history", &id);
+    app.emit_event("refresh-notes", ());
+    encryption::open(&app, &id, content)
+}
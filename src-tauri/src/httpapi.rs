@@ -0,0 +1,650 @@
+//! An opt-in HTTP API on 127.0.0.1 for automation (scripts, Home Assistant, Stream Deck),
+//! configured in settings.bin `http_api`. Every request needs the header
+//! `Authorization: Bearer <token>`; the token is generated when the API is first turned
+//! on and shown by `get_http_api`.
+//!
+//! - `GET /notes` lists the notes (`?q=` searches their titles and content)
+//! - `POST /notes` with `{"content": ..., "open": true}` creates a note, opening it unless
+//!   `open` is false
+//! - `GET /notes/<id>` returns `{"id", "content"}`
+//! - `PUT /notes/<id>` with `{"content": ...}` replaces the content
+//! - `DELETE /notes/<id>` moves the note to the trash
+//!
+//! Requests go through the same commands the frontend calls, one at a time on the
+//! server's own thread.
+//!
+//! Each token gets `requests_per_minute` requests a minute; past that the API answers
+//! 429 with `Retry-After`. `rotate_api_token` replaces the token but keeps the old one
+//! working for `ROTATION_GRACE_MS`, so scripts can be moved over. Every request is noted
+//! in an in-memory audit log (method, path and status, never bodies) that
+//! `get_api_audit` returns.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, Url};
+use tiny_http::{Header, Method, Request, Response, Server};
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::instance;
+use crate::limits::effective_limits;
+use crate::notewindow::NoteWindowOptions;
+use crate::scan::is_valid_note_id;
+use crate::usage::record_usage;
+use crate::{create_note_window, delete_note, load_note, notes_dir, now_millis, save_note};
+
+const SETTINGS_KEY: &str = "http_api";
+pub const DEFAULT_PORT: u16 = 47_380;
+pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 120;
+const RATE_WINDOW_MS: u64 = 60 * 1000;
+/// How long the token `rotate_api_token` replaced keeps working.
+const ROTATION_GRACE_MS: u64 = 24 * 60 * 60 * 1000;
+/// Audit entries kept; older ones are dropped.
+const AUDIT_CAPACITY: usize = 500;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HttpApiSettings {
+    enabled: bool,
+    port: u16,
+    /// `None` until the API is first turned on
+    token: Option<String>,
+    /// Per token
+    requests_per_minute: u32,
+    /// The token `rotate_api_token` replaced, while it still works
+    previous_token: Option<String>,
+    /// Unix milliseconds
+    previous_token_expires_at: Option<u64>,
+}
+
+impl Default for HttpApiSettings {
+    fn default() -> Self {
+        HttpApiSettings {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: None,
+            requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+            previous_token: None,
+            previous_token_expires_at: None,
+        }
+    }
+}
+
+/// The running server, if any, and the audit log, which outlives restarts of it.
+#[derive(Default)]
+pub struct HttpApi {
+    server: Mutex<Option<Arc<Server>>>,
+    audit: Arc<AuditLog>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// Unix milliseconds
+    at: u64,
+    method: String,
+    /// Without the query, which may hold search terms
+    path: String,
+    status: u16,
+    /// The request came with the token `rotate_api_token` replaced
+    previous_token: bool,
+}
+
+#[derive(Default)]
+pub struct AuditLog(Mutex<VecDeque<AuditEntry>>);
+
+impl AuditLog {
+    fn record(&self, entry: AuditEntry) {
+        if let Ok(mut entries) = self.0.lock() {
+            if entries.len() == AUDIT_CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    /// Up to `limit` entries, newest first.
+    fn latest(&self, limit: usize) -> Vec<AuditEntry> {
+        self.0
+            .lock()
+            .map(|entries| entries.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Which token a request carried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Caller {
+    Current,
+    Previous,
+}
+
+/// The tokens the server accepts and how often each may be used, in fixed one-minute
+/// windows.
+struct Access {
+    token: String,
+    /// The replaced token and when it stops working (unix milliseconds)
+    previous: Option<(String, u64)>,
+    requests_per_minute: u32,
+    /// Per caller: when its window started and the requests in it
+    windows: HashMap<Caller, (u64, u32)>,
+}
+
+impl Access {
+    fn new(settings: &HttpApiSettings, token: String) -> Self {
+        Access {
+            token,
+            previous: settings.previous_token.clone().zip(settings.previous_token_expires_at),
+            requests_per_minute: settings.requests_per_minute.max(1),
+            windows: HashMap::new(),
+        }
+    }
+
+    fn caller(&self, request: &Request, now: u64) -> Option<Caller> {
+        let given = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .and_then(|header| header.value.as_str().strip_prefix("Bearer "))?
+            .trim();
+        if token_matches(given, &self.token) {
+            return Some(Caller::Current);
+        }
+        self.previous
+            .as_ref()
+            .filter(|(token, expires_at)| now < *expires_at && token_matches(given, token))
+            .map(|_| Caller::Previous)
+    }
+
+    /// Counts a request by `caller`; over the limit, the seconds until its next window.
+    fn count(&mut self, caller: Caller, now: u64) -> Result<(), u64> {
+        let (start, used) = self.windows.entry(caller).or_insert((now, 0));
+        if now >= *start + RATE_WINDOW_MS {
+            (*start, *used) = (now, 0);
+        }
+        if *used >= self.requests_per_minute {
+            return Err((*start + RATE_WINDOW_MS - now).div_ceil(1000));
+        }
+        *used += 1;
+        Ok(())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct NewNote {
+    content: String,
+    #[serde(default = "open_by_default")]
+    open: bool,
+}
+
+fn open_by_default() -> bool {
+    true
+}
+
+#[derive(serde::Deserialize)]
+struct NoteUpdate {
+    content: String,
+}
+
+#[derive(serde::Serialize)]
+struct NoteBody {
+    id: String,
+    content: String,
+}
+
+#[derive(serde::Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// What a request is answered with: a status and a JSON body.
+type Reply = (u16, serde_json::Value);
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct RotatedToken {
+    token: String,
+    previous_token: String,
+    /// Unix milliseconds
+    previous_token_expires_at: u64,
+}
+
+fn settings(backend: &impl NotesBackend) -> HttpApiSettings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn error(status: u16, message: impl Into<String>) -> Reply {
+    let body = ErrorBody { error: message.into() };
+    (status, serde_json::to_value(body).unwrap_or_default())
+}
+
+fn json<T: serde::Serialize>(status: u16, body: &T) -> Reply {
+    match serde_json::to_value(body) {
+        Ok(value) => (status, value),
+        Err(e) => error(500, e.to_string()),
+    }
+}
+
+/// Compares in constant time, so response timing doesn't give the token away.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn read_body<T: serde::de::DeserializeOwned>(request: &mut Request, limit: usize) -> Result<T, Reply> {
+    let mut body = Vec::new();
+    // One byte over the limit is enough to tell the body is too large
+    request
+        .as_reader()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| error(400, e.to_string()))?;
+    if body.len() > limit {
+        return Err(error(413, format!("Request body is over {} bytes", limit)));
+    }
+    serde_json::from_slice(&body).map_err(|e| error(400, e.to_string()))
+}
+
+fn note_exists(app: &tauri::AppHandle, id: &str) -> bool {
+    is_valid_note_id(id) && notes_dir(app).is_ok_and(|dir| dir.join(format!("{}.md", id)).is_file())
+}
+
+fn list(app: &tauri::AppHandle, query: Option<&str>) -> Reply {
+    let notes = match instance::list_notes(app) {
+        Ok(notes) => notes,
+        Err(e) => return error(500, e),
+    };
+    let Some(query) = query.map(str::to_lowercase).filter(|q| !q.trim().is_empty()) else {
+        return json(200, &notes);
+    };
+    // Locked notes can't be read, so only their titles are searched
+    let found: Vec<_> = notes
+        .into_iter()
+        .filter(|note| {
+            note.title.to_lowercase().contains(&query)
+                || tauri::async_runtime::block_on(load_note(note.id.clone(), app.clone()))
+                    .is_ok_and(|content| content.to_lowercase().contains(&query))
+        })
+        .collect();
+    json(200, &found)
+}
+
+fn create(app: &tauri::AppHandle, new: NewNote) -> Reply {
+    let id = Uuid::new_v4().to_string();
+    let saved = match tauri::async_runtime::block_on(save_note(id.clone(), new.content, None, None, app.clone())) {
+        Ok(saved) => saved,
+        Err(e) => return error(500, e),
+    };
+    record_usage(app, "http_api_create");
+    if new.open {
+        if let Err(e) = create_note_window(app, NoteWindowOptions::open(&id)) {
+            println!("HTTP API: created note {} but could not open it: {}", id, e);
+        }
+    }
+    app.emit_event("refresh-notes", ());
+    json(
+        201,
+        &NoteBody {
+            id,
+            content: saved.content,
+        },
+    )
+}
+
+fn update(app: &tauri::AppHandle, id: &str, update: NoteUpdate) -> Reply {
+    match tauri::async_runtime::block_on(save_note(id.to_string(), update.content, None, None, app.clone())) {
+        Ok(saved) => {
+            // An open window reloads as it would for an edit made outside the app
+            app.emit_note_event(id, "note-changed-externally", id);
+            app.emit_event("refresh-notes", ());
+            json(
+                200,
+                &NoteBody {
+                    id: id.to_string(),
+                    content: saved.content,
+                },
+            )
+        }
+        Err(e) => error(409, e),
+    }
+}
+
+fn route(app: &tauri::AppHandle, request: &mut Request) -> Reply {
+    let Ok(url) = Url::parse(&format!("http://localhost{}", request.url())) else {
+        return error(400, "Malformed URL");
+    };
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    // Notes plus JSON quoting; a note can't be larger than this anyway
+    let limit = effective_limits(app).max_note_bytes * 2 + 1024;
+
+    let method = request.method().clone();
+    match (&method, segments.as_slice()) {
+        (Method::Get, ["notes"]) => {
+            let query = url
+                .query_pairs()
+                .find(|(key, _)| key == "q")
+                .map(|(_, q)| q.into_owned());
+            list(app, query.as_deref())
+        }
+        (Method::Post, ["notes"]) => match read_body(request, limit) {
+            Ok(new) => create(app, new),
+            Err(reply) => reply,
+        },
+        (_, ["notes"]) => error(405, "Use GET or POST"),
+        (method, ["notes", id]) => {
+            if !note_exists(app, id) {
+                return error(404, format!("No note with id {:?}", id));
+            }
+            match method {
+                Method::Get => match tauri::async_runtime::block_on(load_note(id.to_string(), app.clone())) {
+                    Ok(content) => json(
+                        200,
+                        &NoteBody {
+                            id: id.to_string(),
+                            content,
+                        },
+                    ),
+                    Err(e) => error(403, e),
+                },
+                Method::Put => match read_body(request, limit) {
+                    Ok(body) => update(app, id, body),
+                    Err(reply) => reply,
+                },
+                Method::Delete => match tauri::async_runtime::block_on(delete_note(id.to_string(), app.clone())) {
+                    Ok(()) => (204, serde_json::Value::Null),
+                    Err(e) => error(500, e),
+                },
+                _ => error(405, "Use GET, PUT or DELETE"),
+            }
+        }
+        _ => error(404, "Not found"),
+    }
+}
+
+fn respond(request: Request, (status, body): Reply, retry_after: Option<u64>) {
+    let headers = [
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).ok(),
+        retry_after.and_then(|secs| Header::from_bytes(&b"Retry-After"[..], secs.to_string()).ok()),
+    ];
+    let mut response = Response::from_string(if body.is_null() {
+        String::new()
+    } else {
+        body.to_string()
+    })
+    .with_status_code(status);
+    for header in headers.into_iter().flatten() {
+        response = response.with_header(header);
+    }
+    if let Err(e) = request.respond(response) {
+        println!("HTTP API: failed to respond: {}", e);
+    }
+}
+
+/// Answers requests until the server is unblocked, passing those with a token and
+/// within the rate limit to `handle`.
+fn serve(server: Arc<Server>, mut access: Access, audit: Arc<AuditLog>, mut handle: impl FnMut(&mut Request) -> Reply) {
+    for mut request in server.incoming_requests() {
+        let now = now_millis();
+        let caller = access.caller(&request, now);
+        let (reply, retry_after) = match caller.map(|caller| access.count(caller, now)) {
+            None => (error(401, "Missing or wrong token"), None),
+            Some(Err(secs)) => (error(429, "Too many requests"), Some(secs)),
+            Some(Ok(())) => (handle(&mut request), None),
+        };
+        audit.record(AuditEntry {
+            at: now,
+            method: request.method().to_string(),
+            path: request.url().split('?').next().unwrap_or_default().to_string(),
+            status: reply.0,
+            previous_token: caller == Some(Caller::Previous),
+        });
+        respond(request, reply, retry_after);
+    }
+    println!("HTTP API: stopped");
+}
+
+fn stop(app: &tauri::AppHandle) {
+    let state = app.state::<HttpApi>();
+    let Ok(mut running) = state.server.lock() else {
+        return;
+    };
+    if let Some(server) = running.take() {
+        server.unblock();
+    }
+}
+
+/// (Re)starts the server from the settings, or stops it if the API is off.
+pub fn apply(app: &tauri::AppHandle) -> Result<(), String> {
+    stop(app);
+    let settings = settings(app);
+    let (true, Some(token)) = (settings.enabled, settings.token.clone()) else {
+        return Ok(());
+    };
+    let server = Server::http(("127.0.0.1", settings.port))
+        .map(Arc::new)
+        .map_err(|e| format!("Could not listen on port {}: {}", settings.port, e))?;
+    let state = app.state::<HttpApi>();
+    if let Ok(mut running) = state.server.lock() {
+        *running = Some(server.clone());
+    }
+    let access = Access::new(&settings, token);
+    let audit = state.audit.clone();
+    let handle = app.clone();
+    std::thread::spawn(move || serve(server, access, audit, |request| route(&handle, request)));
+    println!("HTTP API: listening on 127.0.0.1:{}", settings.port);
+    Ok(())
+}
+
+fn save_settings(app: &tauri::AppHandle, settings: &HttpApiSettings) -> Result<(), String> {
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    app.write_store("settings.bin", SETTINGS_KEY, value)?;
+    apply(app)
+}
+
+#[tauri::command]
+pub async fn get_http_api(app: tauri::AppHandle) -> Result<HttpApiSettings, String> {
+    Ok(settings(&app))
+}
+
+/// Turns the API on or off, on `port` and with `requests_per_minute` if given. The first
+/// time it is turned on a token is generated.
+#[tauri::command]
+pub async fn set_http_api(
+    enabled: bool,
+    port: Option<u16>,
+    requests_per_minute: Option<u32>,
+    app: tauri::AppHandle,
+) -> Result<HttpApiSettings, String> {
+    let mut settings = settings(&app);
+    settings.enabled = enabled;
+    if let Some(port) = port {
+        if port == 0 {
+            return Err("Port must not be 0".to_string());
+        }
+        settings.port = port;
+    }
+    if let Some(requests_per_minute) = requests_per_minute {
+        if requests_per_minute == 0 {
+            return Err("requests_per_minute must be at least 1".to_string());
+        }
+        settings.requests_per_minute = requests_per_minute;
+    }
+    if enabled && settings.token.is_none() {
+        settings.token = Some(Uuid::new_v4().simple().to_string());
+    }
+    save_settings(&app, &settings)?;
+    Ok(settings)
+}
+
+/// Replaces the token; whatever used the old one (or one rotated out) stops working.
+#[tauri::command]
+pub async fn regenerate_http_api_token(app: tauri::AppHandle) -> Result<HttpApiSettings, String> {
+    let mut settings = settings(&app);
+    settings.token = Some(Uuid::new_v4().simple().to_string());
+    settings.previous_token = None;
+    settings.previous_token_expires_at = None;
+    save_settings(&app, &settings)?;
+    Ok(settings)
+}
+
+/// Replaces the token, keeping the old one working for `ROTATION_GRACE_MS`, and returns
+/// both.
+#[tauri::command]
+pub async fn rotate_api_token(app: tauri::AppHandle) -> Result<RotatedToken, String> {
+    let mut settings = settings(&app);
+    let Some(previous_token) = settings.token.take() else {
+        return Err("The HTTP API has no token yet; turn it on first".to_string());
+    };
+    let rotated = RotatedToken {
+        token: Uuid::new_v4().simple().to_string(),
+        previous_token,
+        previous_token_expires_at: now_millis() + ROTATION_GRACE_MS,
+    };
+    settings.token = Some(rotated.token.clone());
+    settings.previous_token = Some(rotated.previous_token.clone());
+    settings.previous_token_expires_at = Some(rotated.previous_token_expires_at);
+    save_settings(&app, &settings)?;
+    println!("HTTP API: token rotated");
+    Ok(rotated)
+}
+
+/// The latest `limit` API requests (default 100), newest first.
+#[tauri::command]
+pub async fn get_api_audit(limit: Option<usize>, app: tauri::AppHandle) -> Result<Vec<AuditEntry>, String> {
+    Ok(app.state::<HttpApi>().audit.latest(limit.unwrap_or(100)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpStream};
+    use std::thread::JoinHandle;
+
+    struct TestServer {
+        server: Arc<Server>,
+        addr: SocketAddr,
+        audit: Arc<AuditLog>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            self.server.unblock();
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Serves with a handler that answers GETs and echoes JSON bodies of up to 64 bytes.
+    fn start(settings: HttpApiSettings) -> TestServer {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let audit = Arc::new(AuditLog::default());
+        let access = Access::new(&settings, "secret".to_string());
+        let (serving, log) = (server.clone(), audit.clone());
+        let thread = std::thread::spawn(move || {
+            serve(serving, access, log, |request| match request.method() {
+                Method::Get => (200, serde_json::json!({ "ok": true })),
+                _ => read_body::<serde_json::Value>(request, 64).map_or_else(|reply| reply, |body| (200, body)),
+            })
+        });
+        TestServer {
+            server,
+            addr,
+            audit,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(server: &TestServer, method: &str, token: Option<&str>, body: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        let auth = token.map_or(String::new(), |token| format!("Authorization: Bearer {}\r\n", token));
+        let request = format!(
+            "{} /notes?q=private HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            auth,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn status(response: &str) -> &str {
+        response.split(' ').nth(1).unwrap_or_default()
+    }
+
+    #[test]
+    fn requests_need_the_token_and_are_audited() {
+        let server = start(HttpApiSettings::default());
+        assert_eq!(status(&send(&server, "GET", None, "")), "401");
+        assert_eq!(status(&send(&server, "GET", Some("guess"), "")), "401");
+        assert_eq!(status(&send(&server, "GET", Some("secret"), "")), "200");
+
+        let audit = server.audit.latest(10);
+        let statuses: Vec<u16> = audit.iter().map(|entry| entry.status).collect();
+        assert_eq!(statuses, [200, 401, 401]);
+        assert!(audit
+            .iter()
+            .all(|entry| entry.method == "GET" && entry.path == "/notes"));
+        assert_eq!(server.audit.latest(1).len(), 1);
+    }
+
+    #[test]
+    fn requests_over_the_limit_get_429_with_retry_after() {
+        let server = start(HttpApiSettings {
+            requests_per_minute: 2,
+            ..Default::default()
+        });
+        assert_eq!(status(&send(&server, "GET", Some("secret"), "")), "200");
+        assert_eq!(status(&send(&server, "GET", Some("secret"), "")), "200");
+
+        let limited = send(&server, "GET", Some("secret"), "");
+        assert_eq!(status(&limited), "429");
+        let retry_after: u64 = limited
+            .lines()
+            .find_map(|line| line.strip_prefix("Retry-After: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after), "{}", retry_after);
+        // A wrong token is refused before it's counted against anyone
+        assert_eq!(status(&send(&server, "GET", None, "")), "401");
+    }
+
+    #[test]
+    fn oversized_bodies_get_413() {
+        let server = start(HttpApiSettings::default());
+        let small = send(&server, "POST", Some("secret"), r#"{"content":"hi"}"#);
+        assert_eq!(status(&small), "200");
+        assert!(small.ends_with(r#"{"content":"hi"}"#), "{}", small);
+
+        let large = format!(r#"{{"content":"{}"}}"#, "x".repeat(100));
+        assert_eq!(status(&send(&server, "POST", Some("secret"), &large)), "413");
+    }
+
+    #[test]
+    fn rotated_out_tokens_work_until_the_grace_period_ends() {
+        let rotated = |expires_at| HttpApiSettings {
+            previous_token: Some("old".to_string()),
+            previous_token_expires_at: Some(expires_at),
+            ..Default::default()
+        };
+        let server = start(rotated(now_millis() + 60_000));
+        assert_eq!(status(&send(&server, "GET", Some("old"), "")), "200");
+        assert_eq!(status(&send(&server, "GET", Some("secret"), "")), "200");
+        let audit = server.audit.latest(2);
+        assert_eq!((audit[0].previous_token, audit[1].previous_token), (false, true));
+
+        let server = start(rotated(now_millis() - 1));
+        assert_eq!(status(&send(&server, "GET", Some("old"), "")), "401");
+    }
+}
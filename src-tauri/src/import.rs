@@ -0,0 +1,398 @@
+//! Bulk import of `.md` files from a folder (walked recursively) or a zip archive, for
+//! moving over from another notes app or bringing back a backup.
+//!
+//! A file named like a note id keeps that id unless a live note already has it; other
+//! names and collisions get a fresh UUID. A `<name>.meta.json` next to `<name>.md`
+//! (a serialized `NoteMeta`) contributes color, tags, annotations and creation time.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+use zip::ZipArchive;
+
+use crate::annotations;
+use crate::backend::NotesBackend;
+use crate::limits::effective_limits;
+use crate::meta::{self, NoteMeta};
+use crate::scan::is_valid_note_id;
+use crate::storage::ensure_writes_allowed;
+use crate::vault;
+use crate::{notes_dir, read_note, write_note};
+
+const NOTE_EXTENSION: &str = ".md";
+const META_EXTENSION: &str = ".meta.json";
+const MAX_META_BYTES: u64 = 1024 * 1024;
+
+#[derive(serde::Serialize, Debug)]
+pub struct ImportedNote {
+    /// Path of the file within the folder or archive
+    source: String,
+    id: String,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct SkippedImport {
+    source: String,
+    reason: String,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct ImportSummary {
+    imported: Vec<ImportedNote>,
+    /// Files identical to the live note with the same id
+    unchanged: Vec<String>,
+    skipped: Vec<SkippedImport>,
+}
+
+/// Files worth reading, keyed by their `/`-separated path in the source.
+#[derive(Default)]
+struct Collected {
+    files: HashMap<String, Vec<u8>>,
+    skipped: Vec<SkippedImport>,
+}
+
+impl Collected {
+    fn size_cap(name: &str, max_note_bytes: usize) -> Option<u64> {
+        if name.ends_with(META_EXTENSION) {
+            Some(MAX_META_BYTES)
+        } else if name.ends_with(NOTE_EXTENSION) {
+            Some(max_note_bytes as u64)
+        } else {
+            None
+        }
+    }
+
+    fn add(&mut self, name: String, size: u64, cap: u64, reader: impl Read) {
+        if size > cap {
+            self.skipped.push(SkippedImport {
+                source: name,
+                reason: format!("{} bytes is over the {} byte limit", size, cap),
+            });
+            return;
+        }
+        let mut bytes = Vec::new();
+        match reader.take(cap + 1).read_to_end(&mut bytes) {
+            Ok(_) => {
+                self.files.insert(name, bytes);
+            }
+            Err(e) => self.skipped.push(SkippedImport {
+                source: name,
+                reason: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// Hidden files and folders, and the resource forks macOS adds to archives.
+fn is_ignored(part: &str) -> bool {
+    part.starts_with('.') || part == "__MACOSX"
+}
+
+fn source_name(relative: &Path) -> Option<String> {
+    let parts: Option<Vec<&str>> = relative
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str().filter(|p| !is_ignored(p)),
+            _ => None,
+        })
+        .collect();
+    Some(parts?.join("/"))
+}
+
+fn collect_folder(dir: &Path, relative: &Path, max_note_bytes: usize, out: &mut Collected) -> Result<(), String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let relative = relative.join(entry.file_name());
+        let Some(name) = source_name(&relative) else {
+            continue;
+        };
+        // symlink_metadata so links aren't followed out of the folder being imported
+        let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_folder(&entry.path(), &relative, max_note_bytes, out)?;
+        } else if let (true, Some(cap)) = (metadata.is_file(), Collected::size_cap(&name, max_note_bytes)) {
+            match fs::File::open(entry.path()) {
+                Ok(file) => out.add(name, metadata.len(), cap, file),
+                Err(e) => out.skipped.push(SkippedImport {
+                    source: name,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_zip(path: &Path, max_note_bytes: usize) -> Result<Collected, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut out = Collected::default();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|e| e.to_string())?;
+        if entry.is_dir() {
+            continue;
+        }
+        // enclosed_name rejects absolute paths and `..`
+        let Some(name) = entry.enclosed_name().and_then(|relative| source_name(&relative)) else {
+            continue;
+        };
+        if let Some(cap) = Collected::size_cap(&name, max_note_bytes) {
+            let size = entry.size();
+            out.add(name, size, cap, entry);
+        }
+    }
+    Ok(out)
+}
+
+fn merge_meta(meta: &mut NoteMeta, incoming: &NoteMeta) {
+    // Geometry and pinning describe the other desk, as with note packets
+    meta.color = incoming.color.clone().or(meta.color.take());
+    for tag in &incoming.tags {
+        if !meta.tags.contains(tag) {
+            meta.tags.push(tag.clone());
+        }
+    }
+    annotations::merge(&mut meta.annotations, &incoming.annotations);
+    if incoming.created_at.is_some() {
+        meta.created_at = incoming.created_at;
+    }
+}
+
+/// Imports every `.md` file in the folder or `.zip` archive at `path` as a note.
+#[tauri::command]
+pub async fn import_notes(path: String, app: tauri::AppHandle) -> Result<ImportSummary, String> {
+    ensure_writes_allowed(&app)?;
+    let source = PathBuf::from(&path);
+    let max_note_bytes = effective_limits(&app).max_note_bytes;
+    let collected = if source.is_dir() {
+        let mut collected = Collected::default();
+        collect_folder(&source, Path::new(""), max_note_bytes, &mut collected)?;
+        collected
+    } else if source.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
+        collect_zip(&source, max_note_bytes)?
+    } else {
+        return Err(format!("{} is neither a folder nor a .zip archive", path));
+    };
+
+    let mut summary = ImportSummary {
+        skipped: collected.skipped,
+        ..Default::default()
+    };
+    let dir = notes_dir(&app)?;
+    let mut notes: Vec<(&String, &Vec<u8>)> = collected
+        .files
+        .iter()
+        .filter(|(name, _)| name.ends_with(NOTE_EXTENSION))
+        .collect();
+    notes.sort_by_key(|(name, _)| *name);
+
+    for (name, bytes) in notes {
+        let Ok(content) = String::from_utf8(bytes.clone()) else {
+            summary.skipped.push(SkippedImport {
+                source: name.clone(),
+                reason: "not UTF-8 text".to_string(),
+            });
+            continue;
+        };
+        // Backups keep vault notes as stored; `write_note` encrypts them again
+        let content = match vault::decode(&app, content) {
+            Ok(content) => content,
+            Err(e) => {
+                summary.skipped.push(SkippedImport {
+                    source: name.clone(),
+                    reason: e,
+                });
+                continue;
+            }
+        };
+        let base = &name[..name.len() - NOTE_EXTENSION.len()];
+        let stem = base.rsplit('/').next().unwrap_or(base);
+
+        let id = if !is_valid_note_id(stem) {
+            Uuid::new_v4().to_string()
+        } else if dir.join(format!("{}.md", stem)).exists() {
+            if read_note(&app, stem)? == content {
+                summary.unchanged.push(name.clone());
+                continue;
+            }
+            Uuid::new_v4().to_string()
+        } else {
+            stem.to_string()
+        };
+
+        if let Err(e) = write_note(&app, &id, &content) {
+            summary.skipped.push(SkippedImport {
+                source: name.clone(),
+                reason: e,
+            });
+            continue;
+        }
+        let incoming = collected
+            .files
+            .get(&format!("{}{}", base, META_EXTENSION))
+            .and_then(|bytes| serde_json::from_slice::<NoteMeta>(bytes).ok());
+        if let Some(incoming) = incoming {
+            meta::update_meta(&app, &id, |meta| merge_meta(meta, &incoming))?;
+        }
+        summary.imported.push(ImportedNote {
+            source: name.clone(),
+            id,
+        });
+    }
+
+    println!(
+        "Imported {} notes from {} ({} unchanged, {} skipped)",
+        summary.imported.len(),
+        path,
+        summary.unchanged.len(),
+        summary.skipped.len()
+    );
+    if !summary.imported.is_empty() {
+        app.emit_event("refresh-notes", ());
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::Annotation;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const MAX_NOTE_BYTES: usize = 64;
+
+    fn write(root: &Path, name: &str, content: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn names(collected: &Collected) -> Vec<&str> {
+        let mut names: Vec<&str> = collected.files.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    fn skipped(collected: &Collected) -> Vec<&str> {
+        collected.skipped.iter().map(|s| s.source.as_str()).collect()
+    }
+
+    #[test]
+    fn folders_are_walked_for_notes_and_their_metadata() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "a.md", "# A");
+        write(dir.path(), "sub/b.md", "# B");
+        write(dir.path(), "sub/b.meta.json", "{}");
+        write(dir.path(), "sub/deeper/c.md", "# C");
+        write(dir.path(), "notes.txt", "not a note");
+        write(dir.path(), ".hidden.md", "# Hidden");
+        write(dir.path(), ".obsidian/d.md", "# Config");
+        write(dir.path(), "__MACOSX/a.md", "fork");
+        write(dir.path(), "big.md", &"x".repeat(MAX_NOTE_BYTES + 1));
+
+        let mut collected = Collected::default();
+        collect_folder(dir.path(), Path::new(""), MAX_NOTE_BYTES, &mut collected).unwrap();
+        assert_eq!(
+            names(&collected),
+            ["a.md", "sub/b.md", "sub/b.meta.json", "sub/deeper/c.md"]
+        );
+        assert_eq!(skipped(&collected), ["big.md"]);
+        assert!(collected.skipped[0].reason.contains("over the 64 byte limit"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed_out_of_the_folder() {
+        let outside = TempDir::new().unwrap();
+        write(outside.path(), "secret.md", "# Secret");
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "a.md", "# A");
+        std::os::unix::fs::symlink(outside.path().join("secret.md"), dir.path().join("link.md")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("linked")).unwrap();
+
+        let mut collected = Collected::default();
+        collect_folder(dir.path(), Path::new(""), MAX_NOTE_BYTES, &mut collected).unwrap();
+        assert_eq!(names(&collected), ["a.md"]);
+    }
+
+    #[test]
+    fn archives_skip_escaping_and_hidden_entries() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.zip");
+        let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+        for (name, content) in [
+            ("a.md", "# A"),
+            ("folder/b.md", "# B"),
+            ("folder/b.meta.json", "{}"),
+            ("../escape.md", "# Out"),
+            ("/absolute.md", "# Abs"),
+            ("__MACOSX/folder/._b.md", "fork"),
+            ("folder/.hidden.md", "# Hidden"),
+            ("readme.txt", "text"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.start_file("big.md", options).unwrap();
+        zip.write_all("x".repeat(MAX_NOTE_BYTES + 1).as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let collected = collect_zip(&path, MAX_NOTE_BYTES).unwrap();
+        assert_eq!(names(&collected), ["a.md", "folder/b.md", "folder/b.meta.json"]);
+        assert_eq!(skipped(&collected), ["big.md"]);
+        assert_eq!(collected.files["folder/b.md"], b"# B");
+    }
+
+    #[test]
+    fn metadata_merges_without_the_other_desks_geometry() {
+        let annotation = |id: &str, created_at| Annotation {
+            id: id.to_string(),
+            text: id.to_string(),
+            created_at,
+        };
+        let mut meta = NoteMeta {
+            x: Some(10.0),
+            color: Some("yellow".to_string()),
+            tags: vec!["work".to_string()],
+            annotations: vec![annotation("a", 1)],
+            created_at: Some(5),
+            ..Default::default()
+        };
+        let incoming = NoteMeta {
+            x: Some(900.0),
+            pinned: true,
+            color: Some("blue".to_string()),
+            tags: vec!["home".to_string(), "work".to_string()],
+            annotations: vec![annotation("b", 2), annotation("a", 1)],
+            created_at: Some(3),
+            ..Default::default()
+        };
+
+        merge_meta(&mut meta, &incoming);
+        assert_eq!(meta.x, Some(10.0));
+        assert!(!meta.pinned);
+        assert_eq!(meta.color.as_deref(), Some("blue"));
+        assert_eq!(meta.tags, ["work", "home"]);
+        let ids: Vec<&str> = meta.annotations.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(meta.created_at, Some(3));
+
+        // Missing values on the incoming side keep what the note had
+        merge_meta(&mut meta, &NoteMeta::default());
+        assert_eq!(meta.color.as_deref(), Some("blue"));
+        assert_eq!(meta.created_at, Some(3));
+    }
+}
@@ -0,0 +1,296 @@
+//! The command line, from this launch or forwarded by the single-instance plugin when the
+//! app is started again while running. The second process exits straight away; the
+//! running one acts on its arguments:
+//!
+//! - `new [TEXT]` (or `--new-note [TEXT]`, `--new-note=TEXT`) opens a new note, holding
+//!   `TEXT` if given
+//! - `open ID` opens (or focuses) a note
+//! - `list [PATH]` writes the notes' ids and titles as JSON to `PATH`, or to the log
+//! - `export PATH` writes every note's markdown to a zip that `import_notes` reads back,
+//!   encrypted while the vault is enabled
+//! - `--quick-capture` shows the quick-capture popup
+//! - a `stickynotes://` link is followed, see `deeplink`
+//! - `--hidden` (the login item, see `autostart`) does nothing
+//!
+//! A second launch with none of these brings up the dashboard. The forwarding channel
+//! only goes one way, so commands with output write it to a file: scripts wait for it
+//! to appear, which it does whole, never half-written. Relative paths are taken from
+//! the directory the command was run in.
+
+use std::fs;
+use std::io::Write;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+use tauri::{Runtime, Url};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::autostart;
+use crate::deeplink;
+use crate::flush::flush_now;
+use crate::meta;
+use crate::noteindex;
+use crate::notewindow::NoteWindowOptions;
+use crate::quickcapture;
+use crate::scan::is_valid_note_id;
+use crate::storage;
+use crate::usage::record_usage;
+use crate::vault;
+use crate::{build_note_info, create_note_window, notes_dir, read_note, show_dashboard, sort_for_display, write_note};
+
+const NEW_NOTE_FLAG: &str = "--new-note";
+const QUICK_CAPTURE_FLAG: &str = "--quick-capture";
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    NewNote(Option<String>),
+    Open(String),
+    List(Option<PathBuf>),
+    Export(PathBuf),
+    QuickCapture,
+    Hidden,
+    Link(String),
+}
+
+#[derive(serde::Serialize)]
+pub struct ListedNote {
+    pub id: String,
+    pub title: String,
+    pub modified_at: Option<u64>,
+}
+
+/// The argument after a subcommand or flag, unless it is another flag.
+fn operand(args: &mut Peekable<Iter<String>>) -> Option<String> {
+    args.next_if(|next| !next.starts_with("--")).cloned()
+}
+
+/// The actions in `args`, without the program name. Subcommands only count as the first
+/// argument, so a note text of "list" is just text.
+fn parse(args: &[String]) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    let mut args = args.iter().peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("new") => {
+            args.next();
+            actions.push(Action::NewNote(operand(&mut args)));
+        }
+        Some("open") => {
+            args.next();
+            let id = operand(&mut args).ok_or("open needs a note id")?;
+            actions.push(Action::Open(id));
+        }
+        Some("list") => {
+            args.next();
+            actions.push(Action::List(operand(&mut args).map(PathBuf::from)));
+        }
+        Some("export") => {
+            args.next();
+            let path = operand(&mut args).ok_or("export needs a destination path")?;
+            actions.push(Action::Export(PathBuf::from(path)));
+        }
+        _ => {}
+    }
+    while let Some(arg) = args.next() {
+        if let Some(text) = arg.strip_prefix(NEW_NOTE_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+            actions.push(Action::NewNote(Some(text.to_string())));
+        } else if arg == NEW_NOTE_FLAG {
+            actions.push(Action::NewNote(operand(&mut args)));
+        } else if arg == QUICK_CAPTURE_FLAG {
+            actions.push(Action::QuickCapture);
+        } else if arg == autostart::HIDDEN_FLAG {
+            actions.push(Action::Hidden);
+        } else if deeplink::is_link(arg) {
+            actions.push(Action::Link(arg.clone()));
+        }
+    }
+    Ok(actions)
+}
+
+pub fn new_note<R: Runtime>(app: &tauri::AppHandle<R>, text: Option<&str>) -> Result<(), String> {
+    let mut options = NoteWindowOptions::new_note();
+    if let Some(text) = text.filter(|text| !text.trim().is_empty()) {
+        let id = Uuid::new_v4().to_string();
+        write_note(app, &id, text)?;
+        options = NoteWindowOptions::open(id);
+    }
+    create_note_window(app, options).map(|_| ())
+}
+
+pub fn open_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    if !is_valid_note_id(id) || !notes_dir(app)?.join(format!("{}.md", id)).is_file() {
+        return Err(format!("No note with id {:?}", id));
+    }
+    create_note_window(app, NoteWindowOptions::open(id)).map(|_| ())
+}
+
+/// The notes in dashboard order.
+pub fn list_notes<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<Vec<ListedNote>, String> {
+    let metas = meta::load_all(app);
+    let mut notes: Vec<_> = noteindex::listing(app)?
+        .into_iter()
+        .map(|(id, entry)| {
+            let (modified_at, created_at) = (entry.file_modified_at(), entry.file_created_at());
+            build_note_info(id, entry.listing(), modified_at, created_at, &metas)
+        })
+        .collect();
+    sort_for_display(app, &mut notes, None);
+    Ok(notes
+        .into_iter()
+        .map(|note| ListedNote {
+            id: note.id,
+            title: note.title,
+            modified_at: note.modified_at,
+        })
+        .collect())
+}
+
+fn write_list<R: Runtime>(app: &tauri::AppHandle<R>, destination: Option<&Path>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&list_notes(app)?).map_err(|e| e.to_string())?;
+    match destination {
+        Some(path) => storage::write_atomically(path, &json, false).map_err(|e| e.to_string()),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+/// Every note's markdown as `<id>.md` in a zip at `destination`. Notes locked with a
+/// passphrase go in as stored, still encrypted, and while the vault is enabled every note
+/// goes in encrypted with the vault key, so an export never holds what the vault protects
+/// in plain text. Such a zip only imports into a vault with the same passphrase.
+fn export_all<R: Runtime>(app: &tauri::AppHandle<R>, destination: &Path) -> Result<usize, String> {
+    flush_now(app, "export");
+    let mut ids: Vec<String> = noteindex::listing(app)?.into_keys().collect();
+    ids.sort();
+
+    // Built next to the destination and renamed into place, so it only appears finished
+    let partial = destination.with_extension("zip.partial");
+    let file = fs::File::create(&partial).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let result = ids.iter().try_for_each(|id| {
+        let content = vault::encode(app, &read_note(app, id)?)?;
+        zip.start_file(format!("{}.md", id), options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(content.as_bytes()).map_err(|e| e.to_string())
+    });
+    let result = result
+        .and_then(|_| zip.finish().map(|_| ()).map_err(|e| e.to_string()))
+        .and_then(|_| fs::rename(&partial, destination).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(ids.len())
+}
+
+fn run_action<R: Runtime>(app: &tauri::AppHandle<R>, action: &Action, cwd: &Path) -> Result<(), String> {
+    match action {
+        Action::NewNote(text) => {
+            record_usage(app, "new_note_cli");
+            new_note(app, text.as_deref())
+        }
+        Action::Open(id) => open_note(app, id),
+        Action::List(path) => write_list(app, path.as_ref().map(|path| cwd.join(path)).as_deref()),
+        Action::Export(path) => {
+            let destination = cwd.join(path);
+            let count = export_all(app, &destination)?;
+            println!("Exported {} notes to {}", count, destination.display());
+            Ok(())
+        }
+        Action::QuickCapture => quickcapture::show_popup(app),
+        Action::Hidden => Ok(()),
+        Action::Link(link) => {
+            let url = Url::parse(link).map_err(|e| e.to_string())?;
+            deeplink::open_url(app, &url)
+        }
+    }
+}
+
+/// Carries out the actions in `args`, with relative paths taken from `cwd`. Returns
+/// whether there were any.
+pub fn apply<R: Runtime>(app: &tauri::AppHandle<R>, args: &[String], cwd: &Path) -> bool {
+    let actions = match parse(args) {
+        Ok(actions) => actions,
+        Err(e) => {
+            println!("Ignoring command line {:?}: {}", args, e);
+            return false;
+        }
+    };
+    for action in &actions {
+        if let Err(e) = run_action(app, action, cwd) {
+            println!("Failed to run {:?} from the command line: {}", action, e);
+        }
+    }
+    !actions.is_empty()
+}
+
+/// The single-instance plugin's callback: `argv` is the second launch's command line and
+/// `cwd` the directory it ran in.
+pub fn second_launch<R: Runtime>(app: &tauri::AppHandle<R>, argv: Vec<String>, cwd: String) {
+    println!("Second launch with {:?}, handled by the running instance", argv);
+    if !apply(app, argv.get(1..).unwrap_or_default(), Path::new(&cwd)) {
+        show_dashboard(app);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(args: &[&str]) -> Result<Vec<Action>, String> {
+        parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn subcommands_take_their_operand() {
+        assert_eq!(parsed(&["new"]), Ok(vec![Action::NewNote(None)]));
+        assert_eq!(parsed(&["new", "milk"]), Ok(vec![Action::NewNote(Some("milk".into()))]));
+        assert_eq!(parsed(&["open", "a-1"]), Ok(vec![Action::Open("a-1".into())]));
+        assert_eq!(parsed(&["list"]), Ok(vec![Action::List(None)]));
+        assert_eq!(
+            parsed(&["list", "out.json"]),
+            Ok(vec![Action::List(Some(PathBuf::from("out.json")))])
+        );
+        assert_eq!(
+            parsed(&["export", "notes.zip"]),
+            Ok(vec![Action::Export(PathBuf::from("notes.zip"))])
+        );
+    }
+
+    #[test]
+    fn missing_operands_are_errors() {
+        assert!(parsed(&["open"]).is_err());
+        assert!(parsed(&["open", "--hidden"]).is_err());
+        assert!(parsed(&["export"]).is_err());
+    }
+
+    #[test]
+    fn subcommands_only_count_first() {
+        assert_eq!(
+            parsed(&["--new-note", "list"]),
+            Ok(vec![Action::NewNote(Some("list".into()))])
+        );
+        assert_eq!(parsed(&["--hidden", "open", "a-1"]), Ok(vec![Action::Hidden]));
+    }
+
+    #[test]
+    fn flags_and_links() {
+        assert_eq!(
+            parsed(&["--new-note=buy milk", "--quick-capture"]),
+            Ok(vec![Action::NewNote(Some("buy milk".into())), Action::QuickCapture])
+        );
+        assert_eq!(
+            parsed(&["--new-note", "--hidden"]),
+            Ok(vec![Action::NewNote(None), Action::Hidden])
+        );
+        assert_eq!(
+            parsed(&["stickynotes://open/a-1", "stickynotes-other:x", "--unknown"]),
+            Ok(vec![Action::Link("stickynotes://open/a-1".into())])
+        );
+        assert_eq!(parsed(&[]), Ok(vec![]));
+    }
+}
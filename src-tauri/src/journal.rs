@@ -0,0 +1,253 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::encryption;
+use crate::packet::content_hash;
+use crate::recycle::{move_to_recycled, RecycledKind};
+use crate::safepath::{Root, SafePath};
+use crate::storage::ensure_writes_allowed;
+use crate::vault;
+use crate::{close_note, derive_title, notes_dir};
+
+/// Serializes appends so two archivals into the same month can't clobber each other.
+pub struct JournalLock(pub Mutex<()>);
+
+fn journal_dir(backend: &impl NotesBackend) -> Result<PathBuf, String> {
+    Root::Journal.dir(backend)
+}
+
+/// Months are used as file names, so only accept `YYYY-MM`.
+fn is_valid_month(month: &str) -> bool {
+    let bytes = month.as_bytes();
+    bytes.len() == 7
+        && bytes[4] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || b.is_ascii_digit())
+}
+
+/// Keyed on the content too: a note restored, edited and archived again in the same
+/// month gets a second entry, while a retry of the same archival finds its first one.
+fn entry_marker(id: &str, content: &str) -> String {
+    format!("<!-- sticky-note:{}:{} -->", id, content_hash(content))
+}
+
+/// The note id in an entry marker line. Markers written before they were keyed on the
+/// content have no hash.
+pub fn marker_note_id(line: &str) -> Option<&str> {
+    let marker = line.strip_prefix("<!-- sticky-note:")?.strip_suffix(" -->")?;
+    Some(marker.split_once(':').map_or(marker, |(id, _)| id))
+}
+
+/// Months that have a journal file in `dir`, newest first.
+fn months_in(dir: &Path) -> Result<Vec<String>, String> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut months: Vec<String> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let month = name.strip_suffix(".md")?;
+            is_valid_month(month).then(|| month.to_string())
+        })
+        .collect();
+    months.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(months)
+}
+
+/// Whether any month's journal already has an entry with `marker`, so an archival retried
+/// after the month rolled over isn't written twice.
+fn has_entry(backend: &impl NotesBackend, marker: &str) -> Result<bool, String> {
+    let dir = journal_dir(backend)?;
+    for month in months_in(&dir)? {
+        if vault::read_file(backend, &dir.join(format!("{}.md", month)))?.contains(marker) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn this_month<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(journal_dir(app)?.join(format!("{}.md", chrono::Local::now().format("%Y-%m"))))
+}
+
+/// Appends an entry to its month file unless one with the same marker is already in any
+/// month's.
+/// The file is rewritten through a temp copy so a crash never leaves half an entry, and
+/// encrypted as a whole while the vault is enabled.
+fn append_entry(
+    backend: &impl NotesBackend,
+    journal: &Path,
+    marker: &str,
+    title: &str,
+    content: &str,
+) -> Result<(), String> {
+    if has_entry(backend, marker)? {
+        return Ok(());
+    }
+
+    let mut updated = if journal.exists() {
+        vault::read_file(backend, journal)?
+    } else {
+        String::new()
+    };
+    if !updated.is_empty() && !updated.ends_with("\n\n") {
+        updated.push_str(if updated.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    updated.push_str(&format!(
+        "## {} — {}\n{}\n\n{}\n",
+        chrono::Local::now().format("%Y-%m-%d"),
+        title,
+        marker,
+        content.trim_end()
+    ));
+
+    let tmp = journal.with_extension("md.tmp");
+    fs::write(&tmp, vault::encode(backend, &updated)?).map_err(|e| e.to_string())?;
+    SafePath::new(backend, Root::Journal, tmp)?.rename_to(&SafePath::new(backend, Root::Journal, journal)?)
+}
+
+/// Appends a note to this month's journal file and moves the original to the trash. A
+/// locked note is archived as its text, so it must be unlocked this session.
+#[tauri::command]
+pub async fn archive_note_to_journal(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    ensure_writes_allowed(&app)?;
+    let stored = vault::read_file(&app, &notes_dir(&app)?.join(format!("{}.md", id)))?;
+    let content = encryption::open(&app, &id, stored)?;
+    let journal = this_month(&app)?;
+
+    {
+        let lock = app.state::<JournalLock>();
+        let _guard = lock.0.lock().map_err(|e| e.to_string())?;
+        fs::create_dir_all(journal_dir(&app)?).map_err(|e| e.to_string())?;
+        append_entry(
+            &app,
+            &journal,
+            &entry_marker(&id, &content),
+            &derive_title(&content),
+            &content,
+        )?;
+    }
+
+    // If this fails the retry finds the marker and only repeats the trash step
+    move_to_recycled(&app, RecycledKind::Trash, &id)?;
+    close_note(&app, &id);
+
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Appends something the app did on the user's behalf (e.g. resolving a sync conflict)
+/// to this month's journal. `key` identifies the event; a repeat is written only once.
+pub fn record_event<R: Runtime>(app: &tauri::AppHandle<R>, key: &str, title: &str, body: &str) -> Result<(), String> {
+    let journal = this_month(app)?;
+    let lock = app.state::<JournalLock>();
+    let _guard = lock.0.lock().map_err(|e| e.to_string())?;
+    fs::create_dir_all(journal_dir(app)?).map_err(|e| e.to_string())?;
+    append_entry(app, &journal, &format!("<!-- sticky-event:{} -->", key), title, body)
+}
+
+/// Months that have a journal file, newest first.
+#[tauri::command]
+pub async fn get_journal_months(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    months_in(&journal_dir(&app)?)
+}
+
+#[tauri::command]
+pub async fn load_journal(month: String, app: tauri::AppHandle) -> Result<String, String> {
+    if !is_valid_month(&month) {
+        return Err(format!("Invalid journal month: {}", month));
+    }
+    let path = journal_dir(&app)?.join(format!("{}.md", month));
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    vault::read_file(&app, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    fn archive(backend: &TempBackend, journal: &Path, id: &str, content: &str) {
+        fs::create_dir_all(journal_dir(backend).unwrap()).unwrap();
+        append_entry(
+            backend,
+            journal,
+            &entry_marker(id, content),
+            &derive_title(content),
+            content,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn retrying_an_archival_appends_once() {
+        let backend = TempBackend::new();
+        let journal = journal_dir(&backend).unwrap().join("2026-10.md");
+        archive(&backend, &journal, "a", "Groceries\n- milk");
+        archive(&backend, &journal, "a", "Groceries\n- milk");
+
+        let written = fs::read_to_string(&journal).unwrap();
+        assert_eq!(written.matches("<!-- sticky-note:a:").count(), 1);
+    }
+
+    #[test]
+    fn retrying_in_a_later_month_finds_the_first_entry() {
+        let backend = TempBackend::new();
+        let dir = journal_dir(&backend).unwrap();
+        archive(&backend, &dir.join("2026-09.md"), "a", "Groceries\n- milk");
+        archive(&backend, &dir.join("2026-10.md"), "a", "Groceries\n- milk");
+
+        assert!(!dir.join("2026-10.md").exists());
+        archive(&backend, &dir.join("2026-10.md"), "a", "Groceries\n- eggs");
+        let written = fs::read_to_string(dir.join("2026-10.md")).unwrap();
+        assert!(written.contains("- eggs"));
+    }
+
+    #[test]
+    fn archiving_an_edited_note_again_keeps_both_versions() {
+        let backend = TempBackend::new();
+        let journal = journal_dir(&backend).unwrap().join("2026-10.md");
+        archive(&backend, &journal, "a", "Groceries\n- milk");
+        archive(&backend, &journal, "a", "Groceries\n- milk\n- eggs");
+
+        let written = fs::read_to_string(&journal).unwrap();
+        assert_eq!(written.matches("<!-- sticky-note:a:").count(), 2);
+        assert!(written.contains("- eggs"));
+    }
+
+    #[test]
+    fn markers_give_back_their_note_id() {
+        let marker = entry_marker("a-1", "Groceries");
+        assert_eq!(marker_note_id(&marker), Some("a-1"));
+        assert_eq!(marker_note_id("<!-- sticky-note:a-1 -->"), Some("a-1"));
+        assert_eq!(marker_note_id("<!-- sticky-event:sync -->"), None);
+    }
+
+    #[test]
+    fn vault_journals_are_encrypted_on_disk() {
+        let backend = TempBackend::with_vault([7; 32]);
+        let journal = journal_dir(&backend).unwrap().join("2026-10.md");
+        archive(&backend, &journal, "a", "secret plans");
+        archive(&backend, &journal, "b", "more plans");
+
+        let on_disk = fs::read_to_string(&journal).unwrap();
+        assert!(on_disk.starts_with(vault::VAULT_HEADER));
+        assert!(!on_disk.contains("plans"));
+        let read = vault::read_file(&backend, &journal).unwrap();
+        assert!(read.contains("secret plans") && read.contains("more plans"));
+
+        backend.set_vault_key(None);
+        let error = append_entry(&backend, &journal, "<!-- x -->", "Locked", "nope").unwrap_err();
+        assert!(error.starts_with("VaultLocked"), "{}", error);
+    }
+}
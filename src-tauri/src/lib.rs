@@ -3,318 +3,1185 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use tauri::{
-    menu::{Menu, MenuItem, PredefinedMenuItem},
-    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    Emitter, EventTarget, Manager, RunEvent, Runtime, WebviewWindowBuilder,
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
+    Manager, RunEvent, Runtime, WebviewWindowBuilder,
 };
-use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
 use tauri_plugin_store::StoreExt;
-use uuid::Uuid;
+
+mod annotations;
+mod appearance;
+mod applock;
+mod attachments;
+mod autostart;
+mod backend;
+mod backup;
+mod batch;
+mod bulk;
+mod cache;
+mod calendar;
+mod capture;
+mod changes;
+mod clickthrough;
+mod collapse;
+mod conflicts;
+mod daily;
+mod deeplink;
+mod derived;
+mod diagnostics;
+mod dimming;
+mod duplicate;
+mod encryption;
+mod error;
+mod events;
+mod export;
+mod flush;
+mod focusmode;
+mod focustrack;
+mod follow;
+mod git;
+mod history;
+mod httpapi;
+mod import;
+mod instance;
+mod journal;
+mod limits;
+mod links;
+mod localstate;
+mod mcp;
+mod meta;
+mod mute;
+mod noteindex;
+mod notemenu;
+mod notewindow;
+mod packet;
+mod palette;
+mod pdf;
+mod pinboard;
+mod quickcapture;
+mod recycle;
+mod reminders;
+mod rekey;
+mod relocate;
+mod rescue;
+mod restart;
+mod restore;
+mod safepath;
+mod scan;
+mod settings;
+mod share;
+mod shortcuts;
+mod showall;
+mod snap;
+mod sort;
+mod stats;
+mod storage;
+mod suspect;
+mod sync;
+mod tags;
+mod tasks;
+mod templates;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod timestamps;
+#[cfg(debug_assertions)]
+mod testdata;
+mod tidy;
+mod titles;
+mod tray;
+mod usage;
+mod vault;
+#[cfg(windows)]
+mod webview2;
+mod workspaces;
+mod writequeue;
+
+use backend::NotesBackend;
+use batch::IsBatchFocusing;
+use cache::{CachedPreview, PreviewCache};
+use changes::{own_change, ExternalChanges};
+use error::NoteError;
+use focustrack::FocusOutcome;
+use limits::{effective_limits, PREVIEW_CHARS};
+use meta::NoteMeta;
+use notewindow::{NoteWindowOptions, DEFAULT_NOTE_SIZE, MIN_NOTE_SIZE};
+use shortcuts::ShortcutAction;
+use tasks::TaskCounts;
+use usage::{record_usage, UsageTracker};
 
 struct AllowExit(AtomicBool);
-struct IsBatchFocusing(AtomicBool);
-struct NoteRegistry(RwLock<HashSet<String>>);
 
-fn get_session_order<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<String> {
-    if let Ok(store) = app.store("session.bin") {
-        store
-            .get("open_notes")
-            .and_then(|v| serde_json::from_value::<Vec<String>>(v).ok())
-            .unwrap_or_else(|| vec![])
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WindowKind {
+    Note,
+    /// Auxiliary windows (e.g. the pinboard) that must stay out of note session logic
+    Utility,
+}
+
+struct WindowRegistry(RwLock<HashMap<String, WindowKind>>);
+
+impl WindowRegistry {
+    fn note_labels(&self) -> Vec<String> {
+        self.0
+            .read()
+            .map(|registry| {
+                registry
+                    .iter()
+                    .filter(|(_, kind)| **kind == WindowKind::Note)
+                    .map(|(label, _)| label.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn notes_dir(backend: &impl NotesBackend) -> Result<PathBuf, String> {
+    backend.notes_dir()
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn get_session_order(backend: &impl NotesBackend) -> Vec<String> {
+    backend
+        .read_store("session.bin", "open_notes")
+        .and_then(|v| serde_json::from_value::<Vec<String>>(v).ok())
+        .unwrap_or_default()
+}
+
+/// Moves a note to the end of the session order (or drops it). Returns whether the
+/// order changed; an unchanged order isn't written at all.
+fn update_session_order(backend: &impl NotesBackend, note_id: String, remove: bool) -> bool {
+    let mut order = get_session_order(backend);
+    let unchanged = if remove {
+        !order.contains(&note_id)
     } else {
-        vec![]
+        order.last() == Some(&note_id)
+    };
+    if unchanged {
+        return false;
     }
+
+    order.retain(|id| id != &note_id);
+    if !remove {
+        order.push(note_id);
+    }
+
+    // Focus switches come in bursts; prepare_exit saves session.bin explicitly
+    let _ = backend.stage_store("session.bin", "open_notes", serde_json::to_value(order).unwrap());
+    true
 }
 
-fn update_session_order<R: Runtime>(app: &tauri::AppHandle<R>, note_id: String, remove: bool) {
-    if let Ok(store) = app.store("session.bin") {
-        let mut order = get_session_order(app);
+fn read_note(backend: &impl NotesBackend, id: &str) -> Result<String, String> {
+    let path = notes_dir(backend)?.join(format!("{}.md", id));
 
-        order.retain(|id| id != &note_id);
-        if !remove {
-            order.push(note_id);
-        }
+    if !path.exists() {
+        return Ok("".to_string());
+    }
 
-        let _ = store.set("open_notes", serde_json::to_value(order).unwrap());
-        let _ = store.save();
+    vault::read_file(backend, &path)
+}
+
+/// What goes in a note's file for `content`: refused over the size limit, encrypted while
+/// the vault is enabled.
+fn encode_note(backend: &impl NotesBackend, content: &str) -> Result<String, String> {
+    // A locked note's ciphertext is larger than its text, which was checked before sealing
+    if !encryption::is_locked(content) {
+        check_note_size(backend, content)?;
     }
+    vault::encode(backend, content)
 }
 
-#[tauri::command]
-async fn save_note(id: String, content: String, app: tauri::AppHandle) -> Result<(), String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("notes");
-
-    fs::create_dir_all(&path).map_err(|e| e.to_string())?;
-    fs::write(path.join(format!("{}.md", id)), content).map_err(|e| e.to_string())?;
+/// Refuses note text over `max_note_bytes`, the limit `get_limits` reports.
+fn check_note_size(backend: &impl NotesBackend, content: &str) -> Result<(), String> {
+    let limit = effective_limits(backend).max_note_bytes;
+    if content.len() > limit {
+        return Err(NoteError::TooLarge { size: content.len(), limit }.into());
+    }
+    Ok(())
+}
+
+/// Replaces note `id`'s file with `stored`, creating the notes directory if needed.
+fn write_note_file(backend: &impl NotesBackend, id: &str, stored: &str) -> std::io::Result<PathBuf> {
+    let path = notes_dir(backend).map_err(std::io::Error::other)?;
+    fs::create_dir_all(&path)?;
+    let file = path.join(format!("{}.md", id));
+    let sync = storage::sync_writes(backend);
+    backend.own_change(&[id], || storage::write_atomically(&file, stored, sync))?;
+    Ok(file)
+}
+
+fn write_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, content: &str) -> Result<(), String> {
+    let stored = encode_note(app, content)?;
+    let previous = vault::read_file(app, &notes_dir(app)?.join(format!("{}.md", id))).ok();
+    let file = write_note_file(app, id, &stored).map_err(|e| storage::write_error(app, e))?;
+    app.state::<PreviewCache>().remove(&file);
+    timestamps::note_written(app, id, previous.as_deref(), content);
+    history::note_written(app, id, previous.as_deref(), content);
+    suspect::note_written(app, id, stored.len());
+    storage::note_saved(app);
     Ok(())
 }
 
+fn file_modified_at<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Option<u64> {
+    let path = notes_dir(app).ok()?.join(format!("{}.md", id));
+    to_millis(fs::metadata(path).ok()?.modified())
+}
+
+/// Refuses a save based on a version older than the file: the note was edited outside
+/// the app (or by another window) after `expected` (the file's mtime the editor last saw).
+fn check_not_modified<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, expected: Option<u64>) -> Result<(), String> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let modified_at = file_modified_at(app, id);
+    if modified_at == Some(expected) {
+        return Ok(());
+    }
+    Err(NoteError::ModifiedExternally { modified_at }.into())
+}
+
+/// Saves an edit, queueing it instead while the notes directory can't be written.
+/// Returns the file's new mtime, `None` when queued.
+fn save_or_queue<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, content: &str) -> Result<Option<u64>, String> {
+    if storage::is_writable(app) {
+        match write_note(app, id, content) {
+            Ok(()) => return Ok(file_modified_at(app, id)),
+            // Only a failure that made storage unwritable is queued
+            Err(e) if storage::is_writable(app) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    writequeue::enqueue(app, id, content)?;
+    Ok(None)
+}
+
+#[derive(serde::Serialize)]
+struct SavedNote {
+    /// Differs from what was sent when `tidy_on_save` is on
+    content: String,
+    /// Unix milliseconds, the file's mtime after the save; pass it back as
+    /// `expected_modified_at` with the next save. `None` while saves are queued.
+    modified_at: Option<u64>,
+}
+
+/// Returns the content as saved and the file's new mtime. Saving over a note marked
+/// suspect (see `suspect.rs`) needs `acknowledge_suspect`; with `expected_modified_at`
+/// the save fails with `ModifiedExternally` if the file changed since.
+#[tauri::command]
+async fn save_note(
+    id: String,
+    content: String,
+    acknowledge_suspect: Option<bool>,
+    expected_modified_at: Option<u64>,
+    app: tauri::AppHandle,
+) -> Result<SavedNote, String> {
+    suspect::guard_save(&app, &id, acknowledge_suspect.unwrap_or(false))?;
+    check_not_modified(&app, &id, expected_modified_at)?;
+    let content = tidy::prepare_for_save(&app, content);
+    let stored = encryption::seal_for_save(&app, &id, content.clone())?;
+    let modified_at = save_or_queue(&app, &id, &stored)?;
+    Ok(SavedNote { content, modified_at })
+}
+
+/// Partially received chunked saves, keyed by note id: (next expected index, content so far).
+struct ChunkedSaves(Mutex<HashMap<String, (usize, String)>>);
+
+/// The supported way to save notes larger than `get_limits().chunk_threshold_bytes`.
+/// Send chunks in order starting at index 0; the note is written once `last` is true,
+/// and that call returns what `save_note` does.
+#[tauri::command]
+async fn save_note_chunk(
+    id: String,
+    index: usize,
+    chunk: String,
+    last: bool,
+    acknowledge_suspect: Option<bool>,
+    expected_modified_at: Option<u64>,
+    app: tauri::AppHandle,
+) -> Result<Option<SavedNote>, String> {
+    let limit = effective_limits(&app).max_note_bytes;
+    let content = {
+        let state = app.state::<ChunkedSaves>();
+        let mut pending = state.0.lock().map_err(|e| e.to_string())?;
+
+        // Index 0 always starts over, which also recovers from an abandoned upload
+        if index == 0 {
+            pending.insert(id.clone(), (0, String::new()));
+        }
+        let (next_index, buffer) = pending
+            .get_mut(&id)
+            .ok_or_else(|| format!("No chunked save in progress for {}", id))?;
+        if index != *next_index {
+            let expected = *next_index;
+            pending.remove(&id);
+            return Err(format!("Expected chunk {} but received {}", expected, index));
+        }
+
+        let size = buffer.len() + chunk.len();
+        if size > limit {
+            pending.remove(&id);
+            return Err(NoteError::TooLarge { size, limit }.into());
+        }
+        buffer.push_str(&chunk);
+        *next_index += 1;
+
+        if !last {
+            return Ok(None);
+        }
+        pending.remove(&id).map(|(_, content)| content).unwrap_or_default()
+    };
+
+    suspect::guard_save(&app, &id, acknowledge_suspect.unwrap_or(false))?;
+    check_not_modified(&app, &id, expected_modified_at)?;
+    let content = tidy::prepare_for_save(&app, content);
+    let stored = encryption::seal_for_save(&app, &id, content.clone())?;
+    let modified_at = save_or_queue(&app, &id, &stored)?;
+    Ok(Some(SavedNote { content, modified_at }))
+}
+
 #[tauri::command]
 async fn load_note(id: String, app: tauri::AppHandle) -> Result<String, String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("notes")
-        .join(format!("{}.md", id));
+    applock::ensure_unlocked(&app)?;
+    // A queued save is newer than the file
+    let stored = match writequeue::queued_content(&app, &id) {
+        Some(content) => content,
+        None => read_note(&app, &id)?,
+    };
+    encryption::open(&app, &id, stored)
+}
 
-    if !path.exists() {
-        return Ok("".to_string());
+/// Moves the note to the trash; its metadata stays for a restore and goes with the purge.
+/// Leaves refreshing listings to the caller.
+fn trash_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    if notes_dir(app)?.join(format!("{}.md", id)).exists() {
+        recycle::move_to_recycled(app, recycle::RecycledKind::Trash, id)?;
+    } else {
+        attachments::remove_unsaved(app, id);
     }
 
-    fs::read_to_string(path).map_err(|e| e.to_string())
+    close_note(app, id);
+    localstate::remove_local_state(app, id);
+    Ok(())
 }
 
 #[tauri::command]
 async fn delete_note(id: String, app: tauri::AppHandle) -> Result<(), String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("notes")
-        .join(format!("{}.md", id));
-
-    if path.exists() {
-        fs::remove_file(path).map_err(|e| e.to_string())?;
+    trash_note(&app, &id)?;
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Moves a note out of the dashboard into the archive, where `list_archived_notes` finds
+/// it and `unarchive_note` brings it back. Leaves refreshing listings to the caller.
+fn move_to_archive<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    if !notes_dir(app)?.join(format!("{}.md", id)).is_file() {
+        return Err(format!("Note {} does not exist", id));
     }
+    recycle::move_to_recycled(app, recycle::RecycledKind::Archive, id)?;
+    close_note(app, id);
+    Ok(())
+}
+
+#[tauri::command]
+async fn archive_note(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    move_to_archive(&app, &id)?;
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Drops a note from the session and closes its window if it's open.
+fn close_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    update_session_order(app, id.to_string(), true);
 
-    update_session_order(&app, id.clone(), true);
-    
-    // Close window if it's open
     let label = format!("note-{}", id);
     if let Some(window) = app.get_webview_window(&label) {
         let _ = window.close();
     }
-
-    let _ = app.emit_to(EventTarget::any(), "refresh-notes", ());
-    Ok(())
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Clone, Default)]
 struct NoteInfo {
     id: String,
     preview: String,
+    title: String,
+    /// Unix milliseconds, what "modified" sorts by: `content_modified_at` when known,
+    /// otherwise `file_modified_at` (see `timestamps.rs`)
+    modified_at: Option<u64>,
+    /// Last content-changing save through the app, or an estimate if `content_modified_inferred`
+    content_modified_at: Option<u64>,
+    content_modified_inferred: bool,
+    /// The file's mtime, which restores and sync clients may reset; `None` where the
+    /// filesystem doesn't report it
+    file_modified_at: Option<u64>,
+    /// Unix milliseconds of the first save, or the file's creation time where the
+    /// filesystem reports one
+    created_at: Option<u64>,
+    color: Option<String>,
+    /// Always-on-top, as saved; applied when the note's window is built
+    pinned: bool,
+    /// Notifications about the note are being held back
+    muted: bool,
+    /// Set when the file shrank drastically outside the app; saves need acknowledging
+    suspect: Option<suspect::Suspect>,
+    /// Encrypted, see `encryption.rs`; `preview` and `title` are placeholders
+    locked: bool,
+    latest_annotation: Option<String>,
+    annotation_count: usize,
+    /// Open and done task list items. Listings from the index count the whole note;
+    /// those read through the preview cache only the first `PREVIEW_READ_BYTES`.
+    tasks: TaskCounts,
 }
 
-#[tauri::command]
-async fn get_all_notes(app: tauri::AppHandle) -> Result<Vec<NoteInfo>, String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("notes");
+/// First non-empty line with any markdown heading markers stripped.
+fn derive_title(content: &str) -> String {
+    if encryption::is_locked(content) {
+        return "Locked note".to_string();
+    }
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("Untitled")
+        .to_string()
+}
 
-    if !path.exists() {
-        return Ok(vec![]);
+fn to_millis(time: std::io::Result<std::time::SystemTime>) -> Option<u64> {
+    time.ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis() as u64)
+}
+
+/// Previews only need the start of a note; anything past this is never read for listings.
+const PREVIEW_READ_BYTES: u64 = 4096;
+
+/// The start of a note's file, as much as listings need. `None` while it is in the
+/// vault and the vault is locked.
+fn read_listing_head(path: &Path, vault_key: Option<&vault::VaultKey>) -> Option<String> {
+    let mut prefix = Vec::new();
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(PREVIEW_READ_BYTES).read_to_end(&mut prefix);
     }
+    // The cut may land inside a character; lossy decoding only affects the tail
+    let content = String::from_utf8_lossy(&prefix).into_owned();
+    if !vault::is_encoded(&content) {
+        return Some(content);
+    }
+    // Ciphertext can't be cut, so encrypted notes are read whole
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|stored| vault::decode_with(vault_key, stored))
+        .ok()
+}
 
-    let mut notes = Vec::new();
-    for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
-            let id = path.file_stem().unwrap().to_str().unwrap().to_string();
-            let content = fs::read_to_string(&path).unwrap_or_default();
-            // Take first 100 chars for preview
-            let preview = content.chars().take(100).collect();
-            notes.push(NoteInfo { id, preview });
+/// Title, preview and task counts of `content`, or of the start of it.
+fn preview_of(content: &str) -> CachedPreview {
+    if encryption::is_locked(content) {
+        return CachedPreview {
+            preview: String::new(),
+            title: derive_title(content),
+            locked: true,
+            tasks: TaskCounts::default(),
+        };
+    }
+    CachedPreview {
+        preview: content.chars().take(PREVIEW_CHARS).collect(),
+        title: derive_title(content),
+        locked: false,
+        tasks: tasks::count_tasks(content),
+    }
+}
+
+/// Stands in for the preview of a note in the locked vault.
+fn vault_locked_preview() -> CachedPreview {
+    CachedPreview {
+        preview: String::new(),
+        title: "Vault locked".to_string(),
+        locked: true,
+        tasks: TaskCounts::default(),
+    }
+}
+
+fn read_preview(path: &Path, vault_key: Option<&vault::VaultKey>) -> CachedPreview {
+    match read_listing_head(path, vault_key) {
+        Some(content) => preview_of(&content),
+        None => vault_locked_preview(),
+    }
+}
+
+/// A listing entry from the note's preview, file times and metadata.
+fn build_note_info(
+    id: String,
+    listing: CachedPreview,
+    file_modified_at: Option<u64>,
+    file_created_at: Option<u64>,
+    metas: &HashMap<String, NoteMeta>,
+) -> NoteInfo {
+    let CachedPreview {
+        preview,
+        title,
+        locked,
+        tasks,
+    } = listing;
+    let note_meta = metas.get(&id);
+    NoteInfo {
+        latest_annotation: note_meta.and_then(annotations::latest_text),
+        annotation_count: note_meta.map(|m| m.annotations.len()).unwrap_or(0),
+        id,
+        preview,
+        title: titles::effective_title(note_meta, &title),
+        modified_at: timestamps::effective_modified_at(note_meta, file_modified_at),
+        content_modified_at: note_meta.and_then(|m| m.content_modified_at),
+        content_modified_inferred: note_meta.is_some_and(|m| m.content_modified_inferred),
+        file_modified_at,
+        muted: mute::is_muted(note_meta),
+        suspect: note_meta.and_then(|m| m.suspect.clone()),
+        created_at: note_meta.and_then(|m| m.created_at).or(file_created_at),
+        color: note_meta.and_then(|m| m.color.clone()),
+        pinned: note_meta.is_some_and(|m| m.pinned),
+        locked,
+        tasks,
+    }
+}
+
+fn read_note_info(
+    cache: &PreviewCache,
+    vault_key: Option<&vault::VaultKey>,
+    metas: &HashMap<String, NoteMeta>,
+    id: String,
+    path: &Path,
+) -> NoteInfo {
+    let metadata = fs::metadata(path).ok();
+    let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+    let len = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+    let listing = cache.get(path, modified, len).unwrap_or_else(|| {
+        let fresh = read_preview(path, vault_key);
+        cache.insert(path, modified, len, fresh.clone());
+        fresh
+    });
+    build_note_info(
+        id,
+        listing,
+        metadata.as_ref().and_then(|m| to_millis(m.modified())),
+        metadata.as_ref().and_then(|m| to_millis(m.created())),
+        metas,
+    )
+}
+
+/// Listing entries for indexed notes, unsorted.
+fn indexed_note_infos(
+    entries: HashMap<String, noteindex::IndexEntry>,
+    metas: &HashMap<String, NoteMeta>,
+) -> Vec<NoteInfo> {
+    entries
+        .into_iter()
+        .map(|(id, entry)| {
+            let (modified_at, created_at) = (entry.file_modified_at(), entry.file_created_at());
+            build_note_info(id, entry.listing(), modified_at, created_at, metas)
+        })
+        .collect()
+}
+
+/// Favorites (pinboard) plus notes whose window is currently always-on-top.
+fn pinned_note_ids<R: Runtime>(app: &tauri::AppHandle<R>) -> HashSet<String> {
+    let mut pinned: HashSet<String> = pinboard::get_pinboard_ids(app).into_iter().collect();
+    for label in app.state::<WindowRegistry>().note_labels() {
+        let is_pinned = app
+            .get_webview_window(&label)
+            .and_then(|w| w.is_always_on_top().ok())
+            .unwrap_or(false);
+        if let (true, Some(id)) = (is_pinned, label.strip_prefix("note-")) {
+            pinned.insert(id.to_string());
         }
     }
-    Ok(notes)
+    pinned
+}
+
+/// Applies the user's default sort to a listing, or `sort_by` in its place.
+fn sort_for_display<R: Runtime>(app: &tauri::AppHandle<R>, notes: &mut [NoteInfo], sort_by: Option<sort::NoteSort>) {
+    let manual = sort::get_manual_order(app);
+    let opened = get_session_order(app);
+    let pinned = pinned_note_ids(app);
+    let locale = sort::system_locale();
+    let mut settings = sort::get_sort_settings(app);
+    if let Some(sort) = sort_by {
+        settings.sort = sort;
+    }
+    sort::sort_notes(
+        notes,
+        settings,
+        &sort::SortContext {
+            manual: &manual,
+            opened: &opened,
+            pinned: &pinned,
+            locale: &locale,
+        },
+    );
+}
+
+/// Every note in display order, or the `limit` notes from `offset` on. `sort_by` overrides
+/// the saved sort for this listing only. Notes come from `noteindex`, so listing doesn't
+/// touch the notes folder.
+#[tauri::command]
+async fn get_all_notes(
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort_by: Option<sort::NoteSort>,
+    app: tauri::AppHandle,
+) -> Result<Vec<NoteInfo>, String> {
+    // Previews show the notes as much as `load_note` would
+    applock::ensure_unlocked(&app)?;
+    let metas = meta::load_all(&app);
+    let mut notes = indexed_note_infos(noteindex::listing(&app)?, &metas);
+    sort_for_display(&app, &mut notes, sort_by);
+    Ok(notes
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect())
 }
 
 #[tauri::command]
 async fn open_note_window_cmd(id: String, app: tauri::AppHandle) -> Result<(), String> {
-    create_note_window(&app, Some(id), true, true);
-    Ok(())
+    create_note_window(&app, NoteWindowOptions::open(id)).map(|_| ())
 }
 
 #[tauri::command]
 async fn create_new_note_cmd(app: tauri::AppHandle) -> Result<(), String> {
     println!("Backend: create_new_note_cmd triggered");
-    match create_note_window(&app, None, true, true) {
-        Some(_) => {
+    record_usage(&app, "new_note");
+    match create_note_window(&app, NoteWindowOptions::new_note()) {
+        Ok(_) => {
             println!("Backend: Note window created successfully");
             let handle = app.clone();
             tauri::async_runtime::spawn(async move {
                 tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                let _ = handle.emit_to(EventTarget::any(), "refresh-notes", ());
+                handle.emit_event("refresh-notes", ());
             });
             Ok(())
         },
-        None => {
+        Err(e) => {
             println!("Backend: Failed to create note window");
-            Err("Failed to create note window".to_string())
+            Err(format!("Failed to create note window: {}", e))
         }
     }
 }
 
 #[tauri::command]
 async fn trigger_refresh_notes(app: tauri::AppHandle) -> Result<(), String> {
-    let _ = app.emit_to(EventTarget::any(), "refresh-notes", ());
+    app.emit_event("refresh-notes", ());
     Ok(())
 }
 
-fn create_note_window<R: Runtime>(app: &tauri::AppHandle<R>, id: Option<String>, save: bool, should_show: bool) -> Option<tauri::WebviewWindow<R>> {
-    let id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+/// Logical outer rectangles of the visible note windows other than `except`.
+fn open_note_rects<R: Runtime>(app: &tauri::AppHandle<R>, except: &str) -> Vec<meta::Rect> {
+    app.state::<WindowRegistry>()
+        .note_labels()
+        .iter()
+        .filter(|label| *label != except)
+        .filter_map(|label| app.get_webview_window(label))
+        .filter(|window| window.is_visible().unwrap_or(false))
+        .filter_map(|window| {
+            let scale = window.scale_factor().ok()?;
+            let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+            let size = window.outer_size().ok()?.to_logical::<f64>(scale);
+            Some(meta::Rect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            })
+        })
+        .collect()
+}
+
+fn create_note_window<R: Runtime>(app: &tauri::AppHandle<R>, options: NoteWindowOptions) -> Result<tauri::WebviewWindow<R>, String> {
+    applock::ensure_unlocked(app)?;
+    let id = options.resolve_id();
     let label = format!("note-{}", id);
 
     if let Some(window) = app.get_webview_window(&label) {
         let _ = window.show();
         let _ = window.unminimize();
         let _ = window.set_focus();
-        Some(window)
+        Ok(window)
     } else {
-        // Ensure notes directory exists so Dashboard can find it
-        if let Ok(path) = app.path().app_data_dir() {
-            let notes_path = path.join("notes");
+        // Ensure notes directory exists so Dashboard can find it. Without write access the
+        // note starts out only in the window; its first save goes to the write queue
+        if let (true, Ok(notes_path)) = (storage::is_writable(app), notes_dir(app)) {
             let _ = fs::create_dir_all(&notes_path);
             
             // If it's a new note, create an empty file so it appears in Dashboard immediately
             let note_file = notes_path.join(format!("{}.md", id));
             if !note_file.exists() {
-                let _ = fs::write(note_file, "");
+                let _ = own_change(app, &[&id], || fs::write(note_file, ""));
             }
         }
 
         println!("Building window with label: {}", label);
-        let window_res = WebviewWindowBuilder::new(app, label.clone(), tauri::WebviewUrl::App("index.html".into()))
-            .title("")
-            .inner_size(300.0, 300.0)
+        let note_meta = meta::get_meta(app, &id);
+        let mut builder = WebviewWindowBuilder::new(app, label.clone(), tauri::WebviewUrl::App("index.html".into()))
+            .title(note_meta.title.as_deref().unwrap_or(""))
             .resizable(true)
             .decorations(false)
             .transparent(true)
-            .always_on_top(false)
-            .skip_taskbar(true)
-            .visible(false)
-            .build();
+            .theme(theme::native(app))
+            .always_on_top(options.resolve_pinned(note_meta.pinned))
+            .min_inner_size(MIN_NOTE_SIZE.0, MIN_NOTE_SIZE.1)
+            .skip_taskbar(tray::tray_available(app))
+            .visible(false);
+        // Notes that were never moved open on the display under the cursor
+        let geometry = options
+            .resolve_geometry(meta::restored_geometry(app, &note_meta))
+            .or_else(|| meta::new_note_geometry(app, &open_note_rects(app, &label)));
+        builder = match geometry {
+            Some(rect) => builder.position(rect.x, rect.y).inner_size(rect.width, rect.height),
+            None => builder.inner_size(DEFAULT_NOTE_SIZE.0, DEFAULT_NOTE_SIZE.1),
+        };
+        let window_res = builder.build();
 
-        println!("Window build result for {}: {:?}", label, window_res.as_ref().map(|_| "Ok").map_err(|e| e));
+        println!("Window build result for {}: {:?}", label, window_res.as_ref().map(|_| "Ok"));
 
         match window_res {
             Ok(window) => {
                 // Register window as a sticky note
-                if let Ok(mut registry) = app.state::<NoteRegistry>().0.write() {
-                    registry.insert(label.clone());
+                if let Ok(mut registry) = app.state::<WindowRegistry>().0.write() {
+                    registry.insert(label.clone(), WindowKind::Note);
+                }
+                app.state::<IsBatchFocusing>().note_created(&label);
+                focusmode::note_created(&window);
+                if note_meta.collapsed {
+                    let _ = collapse::shrink(&window);
                 }
+                if note_meta.click_through {
+                    let _ = window.set_ignore_cursor_events(true);
+                }
+                appearance::apply_zoom(&window, &note_meta);
 
                 let id_for_events = id.clone();
                 let label_for_events = label.clone();
                 let handle_for_events = app.clone();
+                let window_for_events = window.clone();
                 window.on_window_event(move |event| match event {
                     tauri::WindowEvent::Focused(true) => {
-                        let is_batch = handle_for_events.state::<IsBatchFocusing>();
-                        if !is_batch.0.load(Ordering::SeqCst) {
-                            update_session_order(&handle_for_events, id_for_events.clone(), false);
+                        restart::note_focused(&handle_for_events);
+                        if handle_for_events.state::<IsBatchFocusing>().is_active() {
+                            focustrack::count(&handle_for_events, FocusOutcome::Batch);
+                        } else {
+                            if !focustrack::is_programmatic(&handle_for_events, &label_for_events) {
+                                let outcome = if update_session_order(&handle_for_events, id_for_events.clone(), false) {
+                                    FocusOutcome::Persisted
+                                } else {
+                                    FocusOutcome::Unchanged
+                                };
+                                focustrack::count(&handle_for_events, outcome);
+                            }
+                            dimming::apply_focus_opacity(&window_for_events, true);
                         }
                     }
+                    tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => {
+                        meta::schedule_geometry_save(&window_for_events, &id_for_events);
+                    }
+                    tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                        attachments::files_dropped(&handle_for_events, &id_for_events, paths.clone());
+                    }
+                    tauri::WindowEvent::Focused(false) if !handle_for_events.state::<IsBatchFocusing>().is_active() => {
+                        dimming::apply_focus_opacity(&window_for_events, false);
+                    }
                     tauri::WindowEvent::Destroyed => {
-                        if let Ok(mut registry) = handle_for_events.state::<NoteRegistry>().0.write() {
+                        if let Ok(mut registry) = handle_for_events.state::<WindowRegistry>().0.write() {
                             registry.remove(&label_for_events);
                         }
+                        events::unsubscribe(&handle_for_events, &label_for_events);
+                        encryption::forget(&handle_for_events, &id_for_events);
                         update_session_order(&handle_for_events, id_for_events.clone(), true);
+                        notemenu::note_closed(&handle_for_events, &id_for_events);
                     }
                     _ => {}
                 });
+                notemenu::note_opened(app, &id);
+
+                follow::apply_follow_state(&window, &id);
 
-                if save {
+                if options.saves_to_session() {
                     update_session_order(app, id, false);
                 }
 
-                if should_show {
+                if options.shows() {
                     let _ = window.show();
                 }
                 
-                Some(window)
+                Ok(window)
             },
             Err(e) => {
                 println!("Error building window: {:?}", e);
-                None
+                Err(e.to_string())
             }
         }
     }
 }
 
+/// Brings the main window forward with a fresh note list.
+fn show_dashboard<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if let Some(main_win) = app.get_webview_window("main") {
+        let _ = main_win.show();
+        let _ = main_win.unminimize();
+        let _ = main_win.set_focus();
+        app.emit_event("refresh-notes", ());
+    }
+}
+
+/// Flushes pending state and lifts the exit guard ahead of a deliberate quit or restart.
+fn prepare_exit<R: Runtime>(app: &tauri::AppHandle<R>) {
+    // Hidden-by-focus-mode windows must be back before the session is saved
+    focusmode::end_focus(app);
+    flush::flush_now(app, "quit");
+    if let Ok(store) = app.store("session.bin") {
+        let _ = store.save();
+    }
+    app.state::<AllowExit>().0.store(true, Ordering::SeqCst);
+}
+
+/// Pins the generated command handler to a concrete type, so it can be wrapped in
+/// another closure; `generate_handler!` alone leaves its argument type to inference.
+fn as_handler<F: Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static>(handler: F) -> F {
+    handler
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let new_note_shortcut = Shortcut::new(
-        Some(
-            tauri_plugin_global_shortcut::Modifiers::ALT
-                | tauri_plugin_global_shortcut::Modifiers::SHIFT,
-        ),
-        tauri_plugin_global_shortcut::Code::KeyN,
-    );
+    // Started by an MCP client to bridge to the running app; no window, no second instance
+    if std::env::args().nth(1).as_deref() == Some(mcp::SUBCOMMAND) {
+        std::process::exit(mcp::serve_stdio());
+    }
+
+    // Must happen before the builder touches any webview
+    #[cfg(windows)]
+    if !webview2::ensure_runtime() {
+        return;
+    }
+
+    let commands = as_handler(tauri::generate_handler![
+        save_note,
+        save_note_chunk,
+        load_note,
+        delete_note,
+        get_all_notes,
+        open_note_window_cmd,
+        create_new_note_cmd,
+        trigger_refresh_notes,
+        limits::get_limits,
+        diagnostics::get_diagnostics,
+        diagnostics::get_metrics,
+        pinboard::toggle_pinboard,
+        pinboard::get_pinboard_notes,
+        pinboard::add_to_pinboard,
+        pinboard::remove_from_pinboard,
+        rekey::rekey_note,
+        usage::set_collect_local_usage,
+        usage::get_usage_report,
+        usage::export_usage_report,
+        usage::reset_usage,
+        journal::archive_note_to_journal,
+        journal::get_journal_months,
+        journal::load_journal,
+        sort::get_default_sort,
+        sort::set_default_sort,
+        sort::set_manual_order,
+        recycle::query_recycled,
+        recycle::peek_recycled,
+        recycle::restore_recycled,
+        recycle::purge_recycled,
+        recycle::purge_trash,
+        archive_note,
+        recycle::list_archived_notes,
+        recycle::unarchive_note,
+        #[cfg(debug_assertions)]
+        testdata::generate_test_data,
+        #[cfg(debug_assertions)]
+        testdata::clear_test_data,
+        dimming::get_focus_dimming,
+        dimming::set_focus_dimming,
+        dimming::set_note_opacity,
+        follow::set_note_follow,
+        meta::get_note_meta,
+        meta::set_note_pinned,
+        meta::set_note_color,
+        collapse::collapse_note,
+        collapse::expand_note,
+        clickthrough::set_click_through,
+        restart::get_scheduled_restart,
+        restart::set_scheduled_restart,
+        restart::defer_scheduled_restart,
+        restart::set_editor_dirty,
+        packet::export_note_packet,
+        packet::import_note_packet,
+        changes::get_external_changes,
+        changes::acknowledge_external_changes,
+        storage::get_storage_info,
+        storage::set_disk_thresholds,
+        focusmode::focus_note_mode,
+        focusmode::end_focus_mode,
+        tidy::tidy_note,
+        tidy::get_tidy_on_save,
+        tidy::set_tidy_on_save,
+        annotations::add_note_annotation,
+        annotations::list_note_annotations,
+        annotations::delete_note_annotation,
+        tray::retry_tray_init,
+        tray::get_tray_status,
+        localstate::report_scroll_position,
+        tags::get_all_tags,
+        tags::rename_tag,
+        tags::merge_tags,
+        tags::delete_tag,
+        tags::add_tag_to_notes,
+        tags::remove_tag_from_notes,
+        tags::set_note_tags,
+        tags::get_notes_by_tag,
+        rescue::rescue_note_window,
+        duplicate::duplicate_note,
+        flush::flush_all,
+        templates::list_templates,
+        templates::save_note_as_template,
+        templates::delete_template,
+        templates::rename_template,
+        templates::save_template,
+        templates::create_note_from_template,
+        timestamps::rebuild_timestamps,
+        conflicts::list_conflicts,
+        conflicts::get_conflict,
+        conflicts::resolve_conflict,
+        mute::set_note_muted,
+        events::subscribe_events,
+        events::get_broadcast_unsubscribed_events,
+        events::set_broadcast_unsubscribed_events,
+        shortcuts::get_shortcut_bindings,
+        shortcuts::set_shortcut_binding,
+        suspect::clear_suspect,
+        suspect::restore_from_history,
+        history::list_note_versions,
+        history::restore_note_version,
+        import::import_notes,
+        tray::toggle_all_notes,
+        reminders::set_reminder,
+        reminders::list_reminders,
+        reminders::cancel_reminder,
+        calendar::get_calendar_file_path,
+        export::export_note,
+        encryption::lock_note,
+        encryption::unlock_note,
+        encryption::relock_note,
+        encryption::remove_note_lock,
+        vault::get_vault_status,
+        vault::enable_vault,
+        vault::unlock_vault,
+        vault::lock_vault,
+        vault::disable_vault,
+        relocate::get_notes_directory,
+        relocate::set_notes_directory,
+        sync::get_sync_status,
+        sync::set_sync_config,
+        sync::sync_now,
+        git::set_git_config,
+        git::git_history,
+        git::git_diff_version,
+        git::git_push,
+        git::git_pull,
+        links::resolve_link,
+        links::get_backlinks,
+        attachments::save_attachment,
+        attachments::get_attachment,
+        quickcapture::quick_capture,
+        quickcapture::submit_quick_capture,
+        quickcapture::cancel_quick_capture,
+        snap::get_snap_settings,
+        snap::set_snap_settings,
+        snap::arrange_notes,
+        workspaces::save_workspace,
+        workspaces::load_workspace,
+        workspaces::list_workspaces,
+        workspaces::delete_workspace,
+        notemenu::reopen_last_closed_note,
+        autostart::get_autostart,
+        autostart::set_autostart,
+        httpapi::get_http_api,
+        httpapi::set_http_api,
+        httpapi::regenerate_http_api_token,
+        httpapi::rotate_api_token,
+        httpapi::get_api_audit,
+        share::share_note,
+        share::stop_sharing_note,
+        tasks::toggle_task,
+        daily::open_daily_note,
+        daily::get_daily_note_settings,
+        daily::set_daily_note_settings,
+        stats::get_note_stats,
+        stats::get_vault_stats,
+        backup::get_backup_settings,
+        backup::set_backup_settings,
+        backup::list_backups,
+        backup::backup_now,
+        backup::restore_backup,
+        applock::get_app_lock,
+        applock::set_app_lock,
+        applock::disable_app_lock,
+        applock::lock_app,
+        applock::unlock_app,
+        settings::get_settings,
+        settings::update_settings,
+        theme::get_theme,
+        theme::set_theme,
+        appearance::set_note_appearance,
+        palette::palette_query,
+        palette::palette_run,
+        titles::rename_note,
+        bulk::bulk_note_action
+    ]);
 
     tauri::Builder::default()
+        // Registered first, so a second launch hands over before any other plugin starts
+        .plugin(tauri_plugin_single_instance::init(instance::second_launch))
+        .plugin(tauri_plugin_deep_link::init())
+        .register_uri_scheme_protocol(attachments::SCHEME, |ctx, request| {
+            attachments::serve(ctx.app_handle(), &request)
+        })
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_fs::init())
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(move |app, shortcut, event| {
-                    if shortcut == &new_note_shortcut
-                        && event.state() == tauri_plugin_global_shortcut::ShortcutState::Pressed
-                    {
-                        create_note_window(app, None, true, true);
+                .with_handler(|app, shortcut, event| {
+                    if event.state() == tauri_plugin_global_shortcut::ShortcutState::Pressed {
+                        shortcuts::dispatch(app, shortcut);
                     }
                 })
                 .build(),
         )
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![
-            save_note,
-            load_note,
-            delete_note,
-            get_all_notes,
-            open_note_window_cmd,
-            create_new_note_cmd,
-            trigger_refresh_notes
-        ])
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![autostart::HIDDEN_FLAG]),
+        ))
+        // Every command counts as activity for the idle flush
+        .invoke_handler(move |invoke| {
+            flush::touch();
+            commands(invoke)
+        })
+        .on_window_event(|window, event| {
+            flush::touch();
+            if let tauri::WindowEvent::ThemeChanged(reported) = event {
+                theme::window_theme_changed(window.app_handle(), *reported);
+            }
+        })
         .setup(move |app| {
+            // Before anything below reads a setting
+            settings::migrate(app.app_handle());
             app.manage(AllowExit(AtomicBool::new(false)));
-            app.manage(IsBatchFocusing(AtomicBool::new(false)));
-            app.manage(NoteRegistry(RwLock::new(HashSet::new())));
-            app.global_shortcut().register(new_note_shortcut)?;
-
-            // Restore session or create first note (Pro Logic)
-            let notes = get_session_order(app.app_handle());
-            let handle_for_startup = app.app_handle().clone();
-            
-            // Perform restoration in an async task to keep the startup process non-blocking
-            tauri::async_runtime::spawn(async move {
-                if notes.is_empty() {
-                    create_note_window(&handle_for_startup, None, true, true);
-                } else {
-                    let mut restored = Vec::new();
-                    for id in notes {
-                        if let Some(window) = create_note_window(&handle_for_startup, Some(id), false, false) {
-                            restored.push(window);
-                        }
-                    }
-                    // Batch show all restored windows at once
-                    for window in restored {
-                        let _ = window.show();
-                    }
-                }
-            });
+            app.manage(events::EventSubscriptions::default());
+            app.manage(IsBatchFocusing::load(app.app_handle()));
+            app.manage(WindowRegistry(RwLock::new(HashMap::new())));
+            app.manage(notemenu::RecentlyClosed::default());
+            app.manage(PreviewCache::load(app.app_handle()));
+            app.manage(ExternalChanges::default());
+            app.manage(storage::StorageState::default());
+            app.manage(focusmode::FocusModeState::default());
+            app.manage(focustrack::FocusTracker::default());
+            app.manage(tray::TrayState::default());
+            app.manage(ChunkedSaves(Mutex::new(HashMap::new())));
+            app.manage(meta::GeometrySaves(Mutex::new(HashMap::new())));
+            app.manage(journal::JournalLock(Mutex::new(())));
+            app.manage(UsageTracker::load(app.app_handle()));
+            app.manage(localstate::LocalState::load(app.app_handle()));
+            app.manage(writequeue::WriteQueue::load(app.app_handle()));
+            app.manage(encryption::NoteKeys::default());
+            app.manage(vault::VaultState::default());
+            app.manage(noteindex::NotesIndex::load(app.app_handle()));
+            app.manage(git::GitState::default());
+            noteindex::spawn_rebuild(app.app_handle().clone());
+            usage::spawn_usage_flusher(app.app_handle().clone());
+            changes::spawn_change_poller(app.app_handle().clone());
+            mute::spawn_mute_expiry(app.app_handle().clone());
+            recycle::spawn_trash_purge(app.app_handle().clone());
+            app.manage(reminders::ReminderEngine::default());
+            reminders::spawn_reminder_scheduler(app.app_handle().clone());
+            applock::init(app.app_handle());
+            follow::init(app.app_handle());
+            app.manage(backup::BackupState::default());
+            backup::spawn_backup_scheduler(app.app_handle().clone());
+            app.manage(sync::SyncState::default());
+            sync::spawn_sync_scheduler(app.app_handle().clone());
+            git::spawn_git_committer(app.app_handle().clone());
+            storage::spawn_disk_watchdog(app.app_handle().clone());
+            app.manage(flush::FlushRegistry::<tauri::Wry>::default());
+            flush::register(app.app_handle(), usage::UsageFlush);
+            flush::register(app.app_handle(), localstate::LocalStateFlush);
+            flush::register(app.app_handle(), noteindex::IndexFlush);
+            flush::register(app.app_handle(), flush::StagedStores);
+            flush::spawn_idle_flusher(app.app_handle().clone());
+            // Saves queued while storage was unavailable last session
+            writequeue::replay(app.app_handle());
+            app.manage(restart::RestartState::default());
+            restart::spawn_restart_scheduler(app.app_handle().clone());
+            app.manage(shortcuts::ShortcutManager::default());
+            shortcuts::declare(app.app_handle(), ShortcutAction::NewNote, Some("Alt+Shift+N"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::ShowAll, None);
+            shortcuts::declare(app.app_handle(), ShortcutAction::ToggleAll, Some("Alt+Shift+H"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::Dashboard, None);
+            shortcuts::declare(app.app_handle(), ShortcutAction::NewNoteFromClipboard, Some("Alt+Shift+V"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::QuickCapture, Some("Alt+Shift+C"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::ReopenLastClosed, Some("Alt+Shift+T"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::ReleaseClickThrough, Some("Alt+Shift+X"));
+            shortcuts::declare(app.app_handle(), ShortcutAction::OpenDailyNote, Some("Alt+Shift+D"));
+            shortcuts::apply(app.app_handle());
 
             let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let new_note_i = MenuItem::with_id(app, "new_note", "New Note", true, None::<&str>)?;
+            let template_i = Submenu::with_id(app, "templates", "New from template", false)?;
+            let daily_i = MenuItem::with_id(app, "daily", "Today's Note", true, None::<&str>)?;
             let dashboard_i = MenuItem::with_id(app, "dashboard", "Open Dashboard", true, None::<&str>)?;
+            let pinboard_i = MenuItem::with_id(app, "pinboard", "Toggle Pinboard", true, None::<&str>)?;
             let open_data_i = MenuItem::with_id(app, "open_data", "Open Data Folder", true, None::<&str>)?;
+            let calendar_i = MenuItem::with_id(app, calendar::MENU_ID, "Reveal Calendar File", true, None::<&str>)?;
+            let autostart_i = CheckMenuItem::with_id(
+                app,
+                autostart::MENU_ID,
+                "Start at Login",
+                true,
+                autostart::is_enabled(app.app_handle()),
+                None::<&str>,
+            )?;
+            let notes_i = Submenu::with_id(app, "notes", "Notes", false)?;
+            let recent_i = Submenu::with_id(app, "recent", "Recent", false)?;
+            let rescue_i = Submenu::with_id(app, "rescue", "Rescue note…", false)?;
+            let workspace_i = Submenu::with_id(app, "workspaces", "Workspaces", false)?;
 
             let menu = Menu::with_items(
                 app,
                 &[
                     &new_note_i,
+                    &template_i,
+                    &daily_i,
                     &dashboard_i,
+                    &notes_i,
+                    &recent_i,
+                    &pinboard_i,
+                    &workspace_i,
                     &open_data_i,
+                    &calendar_i,
+                    &autostart_i,
+                    &rescue_i,
                     &PredefinedMenuItem::separator(app)?,
                     &quit_i
                 ],
@@ -325,114 +1192,71 @@ pub fn run() {
                 let main_win_clone = main_win.clone();
                 main_win.on_window_event(move |event| {
                     if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                        main_win_clone.hide().unwrap();
+                        // Without a tray a hidden dashboard could never be brought back
+                        if tray::tray_available(main_win_clone.app_handle()) {
+                            main_win_clone.hide().unwrap();
+                        } else {
+                            let _ = main_win_clone.minimize();
+                        }
                         api.prevent_close();
                     }
                 });
             }
+            theme::init(app.app_handle());
 
-            let _tray = TrayIconBuilder::new()
-                .icon(app.default_window_icon().unwrap().clone())
-                .menu(&menu)
-                .show_menu_on_left_click(false)
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click {
-                        button: MouseButton::Left,
-                        button_state: MouseButtonState::Up,
-                        ..
-                    } = event
-                    {
-                        let handle = tray.app_handle();
-                        
-                        // Set batch flag to true to ignore 'Focused' events during this mass operation
-                        let is_batch = handle.state::<IsBatchFocusing>();
-                        is_batch.0.store(true, Ordering::SeqCst);
-
-                        // 1. Get ONLY windows that are explicitly registered in our NoteRegistry
-                        let registry_state = handle.state::<NoteRegistry>();
-                        let windows_to_process = {
-                            let registry = registry_state.0.read().unwrap();
-                            registry.iter()
-                                .filter_map(|label| handle.get_webview_window(label))
-                                .filter(|w| w.is_visible().unwrap_or(false))
-                                .collect::<Vec<_>>()
-                        };
-
-                        let mut windows = windows_to_process;
-
-
-                        let order = get_session_order(handle);
-
-                        let order_map: HashMap<String, usize> = order
-                            .iter()
-                            .enumerate()
-                            .map(|(rank, id)| (id.clone(), rank))
-                            .collect();
-
-                        // Sort by session order (bottom to top)
-                        windows.sort_by(|a, b| {
-                            let id_a = a.label().replace("note-", "");
-                            let id_b = b.label().replace("note-", "");
-                            let pos_a = order_map.get(&id_a).unwrap_or(&usize::MAX);
-                            let pos_b = order_map.get(&id_b).unwrap_or(&usize::MAX);
-                            pos_a.cmp(pos_b)
-                        });
-
-                        // Capture pin state BEFORE we start manipulation
-                        let pin_states: Vec<bool> = windows
-                            .iter()
-                            .map(|w| w.is_always_on_top().unwrap_or(false))
-                            .collect();
-
-                        // Pass 1: "The Hammer" - Bring all to front of OS stack
-                        for window in &windows {
-                            let _ = window.show();
-                            let _ = window.unminimize();
-                            let _ = window.set_always_on_top(true);
-                        }
-
-                        // Pass 2: "The Release" - Restore original pin states
-                        // This allows notes to drop back to normal Z-order but stay above other apps
-                        for (window, &was_pinned) in windows.iter().zip(pin_states.iter()) {
-                            let _ = window.set_always_on_top(was_pinned);
-                        }
 
-                        // Final Focus on the topmost (newest) window
-                        if let Some(top) = windows.last() {
-                            let _ = top.set_focus();
-                        }
+            app.manage(menu);
+            app.manage(notemenu::NotesMenu(notes_i));
+            app.manage(notemenu::RecentMenu(recent_i));
+            app.manage(rescue::RescueMenu(rescue_i));
+            notemenu::refresh_menus(app.app_handle());
+            app.manage(templates::TemplateMenu(template_i));
+            templates::refresh_menu(app.app_handle());
+            app.manage(workspaces::WorkspaceMenu(workspace_i));
+            workspaces::refresh_menu(app.app_handle());
+            app.manage(autostart::AutostartMenu(autostart_i));
+            tray::init_tray(app.app_handle());
+            conflicts::init(app.app_handle());
+            deeplink::init(app.app_handle());
+            app.manage(httpapi::HttpApi::default());
+            app.manage(share::NoteShares::default());
+            if let Err(e) = httpapi::apply(app.app_handle()) {
+                println!("HTTP API: {}", e);
+            }
 
-                        // Use a small delay before resetting batch flag because set_focus is asynchronous
-                        // and the 'Focused' event might arrive after this block finishes.
-                        let handle_clone = handle.clone();
-                        tauri::async_runtime::spawn(async move {
-                            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                            let is_batch = handle_clone.state::<IsBatchFocusing>();
-                            is_batch.0.store(false, Ordering::SeqCst);
-                        });
-                    }
-                })
-                .build(app)?;
+            // Restore session or create first note (Pro Logic). After the tray is up, so a
+            // launch at login knows whether there is a tray to stay hidden in
+            let notes = get_session_order(app.app_handle());
+            let handle_for_startup = app.app_handle().clone();
 
-            app.manage(menu);
+            // Perform restoration in an async task to keep the startup process non-blocking
+            tauri::async_runtime::spawn(restore::restore_session(handle_for_startup, notes));
+            let args: Vec<String> = std::env::args().skip(1).collect();
+            instance::apply(app.app_handle(), &args, &std::env::current_dir().unwrap_or_default());
 
             Ok(())
         })
         .on_menu_event(|app, event| match event.id.as_ref() {
             "quit" => {
-                let _ = app.store("session.bin").unwrap().save();
-                app.state::<AllowExit>().0.store(true, Ordering::SeqCst);
+                prepare_exit(app);
                 app.exit(0);
             }
             "new_note" => {
-                create_note_window(app, None, true, true);
+                record_usage(app, "new_note_tray");
+                let _ = create_note_window(app, NoteWindowOptions::new_note());
             }
-            "dashboard" => {
-                if let Some(main_win) = app.get_webview_window("main") {
-                    let _ = main_win.show();
-                    let _ = main_win.unminimize();
-                    let _ = main_win.set_focus();
-                    let _ = app.emit_to(EventTarget::any(), "refresh-notes", ());
+            "daily" => {
+                record_usage(app, "daily_note_tray");
+                if let Err(e) = daily::open_daily(app) {
+                    println!("Failed to open today's note: {}", e);
+                }
+            }
+            "dashboard" => show_dashboard(app),
+            autostart::MENU_ID => autostart::toggle(app),
+            "pinboard" => {
+                record_usage(app, "pinboard");
+                if let Err(e) = pinboard::toggle_pinboard_window(app) {
+                    println!("Failed to toggle pinboard: {}", e);
                 }
             }
             "open_data" => {
@@ -440,12 +1264,47 @@ pub fn run() {
                     let _ = tauri_plugin_opener::reveal_item_in_dir(path);
                 }
             }
+            calendar::MENU_ID => match calendar::calendar_path(app) {
+                Ok(path) if path.exists() => {
+                    let _ = tauri_plugin_opener::reveal_item_in_dir(path);
+                }
+                _ => println!("No calendar file to reveal"),
+            },
+            id if id.starts_with(templates::MENU_ID_PREFIX) => {
+                record_usage(app, "new_note_template_tray");
+                if let Err(e) = templates::new_note_from_template(app, &id[templates::MENU_ID_PREFIX.len()..]) {
+                    println!("Failed to create note from template: {}", e);
+                }
+            }
+            id if id.starts_with(notemenu::MENU_ID_PREFIX) => {
+                record_usage(app, "focus_note_tray");
+                if let Err(e) = notemenu::focus_note(app, &id[notemenu::MENU_ID_PREFIX.len()..]) {
+                    println!("Failed to focus note window: {}", e);
+                }
+            }
+            id if id.starts_with(notemenu::RECENT_MENU_ID_PREFIX) => {
+                record_usage(app, "reopen_note_tray");
+                if let Err(e) = notemenu::focus_note(app, &id[notemenu::RECENT_MENU_ID_PREFIX.len()..]) {
+                    println!("Failed to reopen note: {}", e);
+                }
+            }
+            id if id.starts_with(workspaces::MENU_ID_PREFIX) => {
+                record_usage(app, "load_workspace_tray");
+                if let Err(e) = workspaces::load(app, &id[workspaces::MENU_ID_PREFIX.len()..]) {
+                    println!("Failed to load workspace: {}", e);
+                }
+            }
+            id if id.starts_with(rescue::MENU_ID_PREFIX) => {
+                if let Err(e) = rescue::rescue_note(app, &id[rescue::MENU_ID_PREFIX.len()..]) {
+                    println!("Failed to rescue note window: {}", e);
+                }
+            }
             _ => {}
         })
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
-        .run(|app_handle, event| match event {
-            RunEvent::ExitRequested { api, .. } => {
+        .run(|app_handle, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
                 let allow_exit = app_handle.state::<AllowExit>();
                 if !allow_exit.0.load(Ordering::SeqCst) {
                     // We only prevent the application from exiting. 
@@ -453,6 +1312,8 @@ pub fn run() {
                     api.prevent_exit();
                 }
             }
-            _ => {}
         });
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,37 @@
+use crate::backend::NotesBackend;
+use crate::settings;
+
+/// Hard cap on a single note when no `max_note_bytes` is configured.
+pub const DEFAULT_MAX_NOTE_BYTES: usize = 10 * 1024 * 1024;
+/// Above this size the frontend should switch to `save_note_chunk`; a single IPC
+/// message this large is where WebView bridges start failing opaquely.
+pub const CHUNK_THRESHOLD_BYTES: usize = 1024 * 1024;
+/// Cap on a single attachment when no `max_attachment_bytes` is configured.
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: usize = 25 * 1024 * 1024;
+/// Number of characters returned as `NoteInfo::preview`.
+pub const PREVIEW_CHARS: usize = 100;
+
+#[derive(serde::Serialize, Clone, Copy)]
+pub struct NoteLimits {
+    pub max_note_bytes: usize,
+    pub chunk_threshold_bytes: usize,
+    pub preview_chars: usize,
+    pub max_attachment_bytes: usize,
+}
+
+pub fn effective_limits(backend: &impl NotesBackend) -> NoteLimits {
+    let settings = settings::current(backend);
+
+    NoteLimits {
+        max_note_bytes: settings.max_note_bytes,
+        // Chunking only makes sense below the hard cap
+        chunk_threshold_bytes: CHUNK_THRESHOLD_BYTES.min(settings.max_note_bytes),
+        preview_chars: PREVIEW_CHARS,
+        max_attachment_bytes: settings.max_attachment_bytes,
+    }
+}
+
+#[tauri::command]
+pub async fn get_limits(app: tauri::AppHandle) -> Result<NoteLimits, String> {
+    Ok(effective_limits(&app))
+}
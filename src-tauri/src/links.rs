@@ -0,0 +1,246 @@
+//! `[[Title]]` links between notes. A link names its target by title (case-insensitive)
+//! or by note id, optionally followed by `|label`. Links are collected per note by
+//! `noteindex`, so resolving them and finding backlinks never reads note files.
+//!
+//! When a note's title changes, the notes linking to it by the old title are sent
+//! `link-target-renamed`, so their open windows can offer to update the links.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, NoteMeta};
+use crate::noteindex::{self, IndexEntry};
+use crate::titles::effective_title;
+use crate::{build_note_info, sort_for_display, NoteInfo};
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ResolvedLink {
+    id: String,
+    title: String,
+    /// Other notes share the title; `id` is the most recently modified of them
+    ambiguous: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct LinkTargetRenamed<'a> {
+    /// The note that was retitled
+    id: &'a str,
+    old_title: &'a str,
+    new_title: &'a str,
+}
+
+fn link_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\[\[([^\[\]\n]+?)\]\]").expect("valid pattern"))
+}
+
+fn same_title(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// Targets of the `[[links]]` in `content`, without labels, each once.
+pub fn parse_links(content: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for link in link_pattern().captures_iter(content) {
+        let target = link[1].split('|').next().unwrap_or_default().trim();
+        if !target.is_empty() && !targets.iter().any(|t| same_title(t, target)) {
+            targets.push(target.to_string());
+        }
+    }
+    targets
+}
+
+/// `content` with every `[[old]]` link (labels kept) pointing at `new` instead, or `None`
+/// when nothing links to `old` by id.
+pub fn repoint(content: &str, old: &str, new: &str) -> Option<String> {
+    let mut changed = false;
+    let repointed = link_pattern().replace_all(content, |link: &regex::Captures| {
+        let inner = &link[1];
+        let (target, label) = inner.split_at(inner.find('|').unwrap_or(inner.len()));
+        if target.trim() != old {
+            return link[0].to_string();
+        }
+        changed = true;
+        format!("[[{}{}]]", target.replacen(old, new, 1), label)
+    });
+    changed.then(|| repointed.into_owned())
+}
+
+/// The note `target` links to: the note with that id, else the most recently modified
+/// note with that title. Notes given a title go by it, see `titles.rs`.
+fn resolve(
+    entries: &HashMap<String, IndexEntry>,
+    metas: &HashMap<String, NoteMeta>,
+    target: &str,
+) -> Option<ResolvedLink> {
+    let title_of = |id: &str, entry: &IndexEntry| effective_title(metas.get(id), entry.title());
+    if let Some(entry) = entries.get(target) {
+        return Some(ResolvedLink {
+            id: target.to_string(),
+            title: title_of(target, entry),
+            ambiguous: false,
+        });
+    }
+    let mut matches: Vec<(&String, &IndexEntry, String)> = entries
+        .iter()
+        .map(|(id, entry)| (id, entry, title_of(id, entry)))
+        .filter(|(_, _, title)| same_title(title, target))
+        .collect();
+    matches.sort_by_key(|(id, entry, _)| (std::cmp::Reverse(entry.file_modified_at()), *id));
+    let ambiguous = matches.len() > 1;
+    let (id, _, title) = matches.into_iter().next()?;
+    Some(ResolvedLink {
+        id: id.to_string(),
+        title,
+        ambiguous,
+    })
+}
+
+/// Tells the notes linking to `old_title` that note `id` is now called `new_title`.
+pub fn target_renamed<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, old_title: &str, new_title: &str) {
+    let Ok(entries) = noteindex::listing(app) else {
+        return;
+    };
+    let payload = LinkTargetRenamed {
+        id,
+        old_title,
+        new_title,
+    };
+    for (linking, entry) in &entries {
+        if linking != id && entry.links().iter().any(|t| same_title(t, old_title)) {
+            app.emit_note_event(linking, "link-target-renamed", payload.clone());
+        }
+    }
+}
+
+/// The note a link points to. `text` is the target, with or without the brackets and
+/// label.
+#[tauri::command]
+pub async fn resolve_link(text: String, app: tauri::AppHandle) -> Result<Option<ResolvedLink>, String> {
+    let target = parse_links(&text)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| text.trim().to_string());
+    if target.is_empty() {
+        return Ok(None);
+    }
+    Ok(resolve(&noteindex::listing(&app)?, &meta::load_all(&app), &target))
+}
+
+/// Notes with a link resolving to note `id`, in display order.
+#[tauri::command]
+pub async fn get_backlinks(id: String, app: tauri::AppHandle) -> Result<Vec<NoteInfo>, String> {
+    let entries = noteindex::listing(&app)?;
+    let metas = meta::load_all(&app);
+    // Lowercased target -> whether it resolves to `id`; most notes share a few targets
+    let mut resolved: HashMap<String, bool> = HashMap::new();
+    let mut links_here = |target: &str| {
+        *resolved
+            .entry(target.to_lowercase())
+            .or_insert_with(|| resolve(&entries, &metas, target).is_some_and(|link| link.id == id))
+    };
+    let mut notes: Vec<NoteInfo> = entries
+        .iter()
+        .filter(|(linking, entry)| **linking != id && entry.links().iter().any(|target| links_here(target)))
+        .map(|(linking, entry)| {
+            let (modified_at, created_at) = (entry.file_modified_at(), entry.file_created_at());
+            build_note_info(linking.clone(), entry.listing(), modified_at, created_at, &metas)
+        })
+        .collect();
+    sort_for_display(&app, &mut notes, None);
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use crate::testing::TempBackend;
+
+    /// Writes the notes with the given mtimes (seconds) and reads them into index entries.
+    fn index(backend: &TempBackend, notes: &[(&str, &str, u64)]) -> HashMap<String, IndexEntry> {
+        for (id, content, modified) in notes {
+            let path = backend.put_note(id, content);
+            let file = fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(*modified))
+                .unwrap();
+        }
+        let dir = backend.notes_dir().unwrap();
+        noteindex::read_entries(&dir, None, &HashMap::new()).unwrap().0
+    }
+
+    #[test]
+    fn links_are_parsed_without_labels_and_each_once() {
+        let content = "See [[Groceries]] and [[abc-123|the list]].\n\
+                       Again: [[groceries]], [[ Plans | later ]]\n\
+                       Not links: [[ ]] [[|label]] [[split\nline]] [single]";
+        assert_eq!(parse_links(content), ["Groceries", "abc-123", "Plans"]);
+        assert!(parse_links("no links here").is_empty());
+    }
+
+    #[test]
+    fn id_links_are_repointed_keeping_labels() {
+        let content = "[[old]], [[ old | the list ]] and [[Old]], [[older]], [[x|old]]";
+        assert_eq!(
+            repoint(content, "old", "new").unwrap(),
+            "[[new]], [[ new | the list ]] and [[Old]], [[older]], [[x|old]]"
+        );
+        assert!(repoint("[[other]]", "old", "new").is_none());
+    }
+
+    #[test]
+    fn titles_resolve_to_the_most_recently_modified_note() {
+        let backend = TempBackend::new();
+        let entries = index(
+            &backend,
+            &[
+                ("a", "# Groceries", 2000),
+                ("b", "# groceries", 1000),
+                ("c", "# Other", 1000),
+            ],
+        );
+        let metas = HashMap::new();
+
+        let link = resolve(&entries, &metas, "GROCERIES").unwrap();
+        assert_eq!(
+            (link.id.as_str(), link.title.as_str(), link.ambiguous),
+            ("a", "Groceries", true)
+        );
+        let link = resolve(&entries, &metas, "other").unwrap();
+        assert_eq!((link.id.as_str(), link.ambiguous), ("c", false));
+        assert!(resolve(&entries, &metas, "Missing").is_none());
+    }
+
+    #[test]
+    fn ids_win_over_titles() {
+        let backend = TempBackend::new();
+        let entries = index(
+            &backend,
+            &[("plans", "# Something else", 1000), ("p2", "# plans", 2000)],
+        );
+
+        let link = resolve(&entries, &HashMap::new(), "plans").unwrap();
+        assert_eq!((link.id.as_str(), link.title.as_str()), ("plans", "Something else"));
+    }
+
+    #[test]
+    fn given_titles_replace_the_first_line() {
+        let backend = TempBackend::new();
+        let entries = index(&backend, &[("a", "# First line", 1000)]);
+        let metas = HashMap::from([(
+            "a".to_string(),
+            NoteMeta {
+                title: Some("Renamed".to_string()),
+                ..Default::default()
+            },
+        )]);
+
+        assert_eq!(resolve(&entries, &metas, "renamed").unwrap().id, "a");
+        assert!(resolve(&entries, &metas, "First line").is_none());
+    }
+}
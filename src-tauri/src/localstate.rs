@@ -0,0 +1,134 @@
+//! Device-local per-note state (currently the editor scroll position), kept apart from
+//! `NoteMeta` in `local_state.json` so it never travels in exports or packets. Updates
+//! only touch memory; the usage flusher's loop and `flush::flush_now` write it out.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{Manager, Runtime};
+
+use crate::flush::{FlushOutcome, Flushable};
+use crate::notes_dir;
+
+const FILE_NAME: &str = "local_state.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NoteLocalState {
+    /// Editor position of the top of the viewport, as an offset into the content
+    pub scroll_offset: Option<u64>,
+}
+
+pub struct LocalState {
+    dirty: AtomicBool,
+    notes: Mutex<HashMap<String, NoteLocalState>>,
+}
+
+fn file_path<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join(FILE_NAME))
+}
+
+impl LocalState {
+    pub fn load<R: Runtime>(app: &tauri::AppHandle<R>) -> Self {
+        let notes = file_path(app)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        LocalState {
+            dirty: AtomicBool::new(false),
+            notes: Mutex::new(notes),
+        }
+    }
+
+    fn update(&self, update: impl FnOnce(&mut HashMap<String, NoteLocalState>) -> bool) {
+        if let Ok(mut notes) = self.notes.lock() {
+            if update(&mut notes) {
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Writes `local_state.json` if anything changed since the last flush.
+pub fn flush_local_state<R: Runtime>(app: &tauri::AppHandle<R>) -> FlushOutcome {
+    let state = app.state::<LocalState>();
+    if !state.dirty.swap(false, Ordering::Relaxed) {
+        return FlushOutcome::Clean;
+    }
+    let result = state
+        .notes
+        .lock()
+        .map_err(|e| e.to_string())
+        .and_then(|notes| serde_json::to_string(&*notes).map_err(|e| e.to_string()))
+        .and_then(|json| fs::write(file_path(app)?, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => FlushOutcome::Wrote,
+        Err(error) => {
+            // Keep it dirty so the next flush retries
+            state.dirty.store(true, Ordering::Relaxed);
+            println!("Failed to flush local note state: {}", error);
+            FlushOutcome::Failed { error }
+        }
+    }
+}
+
+pub struct LocalStateFlush;
+
+impl<R: Runtime> Flushable<R> for LocalStateFlush {
+    fn name(&self) -> &'static str {
+        "local_state"
+    }
+
+    fn flush(&self, app: &tauri::AppHandle<R>) -> FlushOutcome {
+        flush_local_state(app)
+    }
+}
+
+/// The saved scroll offset, dropped if the note has since shrunk below it.
+pub fn scroll_offset<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Option<u64> {
+    let state = app.state::<LocalState>();
+    let offset = state.notes.lock().ok()?.get(id)?.scroll_offset?;
+    let len = notes_dir(app)
+        .ok()
+        .and_then(|dir| fs::metadata(dir.join(format!("{}.md", id))).ok())
+        .map(|m| m.len())
+        .unwrap_or(0);
+    if offset <= len {
+        return Some(offset);
+    }
+    state.update(|notes| {
+        notes
+            .get_mut(id)
+            .is_some_and(|note| note.scroll_offset.take().is_some())
+    });
+    None
+}
+
+pub fn remove_local_state<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    app.state::<LocalState>().update(|notes| notes.remove(id).is_some());
+}
+
+pub fn rename_local_state<R: Runtime>(app: &tauri::AppHandle<R>, old: &str, new: &str) {
+    app.state::<LocalState>().update(|notes| match notes.remove(old) {
+        Some(note) => {
+            notes.insert(new.to_string(), note);
+            true
+        }
+        None => false,
+    });
+}
+
+/// Called by the editor (debounced) as the user scrolls.
+#[tauri::command]
+pub async fn report_scroll_position(id: String, offset: u64, app: tauri::AppHandle) -> Result<(), String> {
+    app.state::<LocalState>().update(|notes| {
+        let note = notes.entry(id).or_default();
+        let changed = note.scroll_offset != Some(offset);
+        note.scroll_offset = Some(offset);
+        changed
+    });
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! A Model Context Protocol server, so LLM clients (desktop assistants and the like) can
+//! read and write notes. The client starts `sticky-notes mcp` and talks JSON-RPC over its
+//! stdin and stdout; that process doesn't start the app, it passes each tool call on to
+//! the running app's HTTP API (see `httpapi`). Access therefore needs the API turned on
+//! and its token in the client's config, as `STICKY_NOTES_API_TOKEN` (and the port as
+//! `STICKY_NOTES_API_PORT` if it was changed); turning the API off cuts the client off.
+//!
+//! Tools: `list_notes`, `read_note`, `create_note` and `search`.
+//!
+//! stdout carries the protocol, so everything else is logged to stderr.
+
+use reqwest::{Client, Method};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::time::Duration;
+use tauri::Url;
+
+use crate::httpapi::DEFAULT_PORT;
+use crate::scan::is_valid_note_id;
+
+/// The first command-line argument that runs this instead of the app.
+pub const SUBCOMMAND: &str = "mcp";
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const TOKEN_VAR: &str = "STICKY_NOTES_API_TOKEN";
+const PORT_VAR: &str = "STICKY_NOTES_API_PORT";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The running app's HTTP API.
+struct Api {
+    client: Client,
+    base: String,
+    token: String,
+}
+
+impl Api {
+    /// The response body, or an error describing why the call failed.
+    async fn call(&self, method: Method, url: Url, body: Option<Value>) -> Result<String, String> {
+        let mut request = self.client.request(method, url).bearer_auth(&self.token);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Sticky Notes isn't running or its HTTP API is off: {}", e))?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        if status.is_success() {
+            Ok(text)
+        } else {
+            Err(format!("{}: {}", status, text))
+        }
+    }
+
+    fn url(&self, path: &str) -> Result<Url, String> {
+        Url::parse(&format!("{}{}", self.base, path)).map_err(|e| e.to_string())
+    }
+}
+
+fn tool_list() -> Value {
+    json!({
+        "tools": [
+            {
+                "name": "list_notes",
+                "description": "Lists the sticky notes: id, title and last modification (Unix \
+                    milliseconds), in the order the dashboard shows them.",
+                "inputSchema": { "type": "object", "properties": {} }
+            },
+            {
+                "name": "read_note",
+                "description": "Returns the markdown content of one note.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "The note's id, from list_notes or search" }
+                    },
+                    "required": ["id"]
+                }
+            },
+            {
+                "name": "create_note",
+                "description": "Creates a note with the given markdown content and opens it on the \
+                    user's desktop.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "content": { "type": "string" },
+                        "open": {
+                            "type": "boolean",
+                            "description": "Open the note's window, true by default"
+                        }
+                    },
+                    "required": ["content"]
+                }
+            },
+            {
+                "name": "search",
+                "description": "Finds the notes whose title or content contains the query, \
+                    ignoring case.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": { "query": { "type": "string" } },
+                    "required": ["query"]
+                }
+            }
+        ]
+    })
+}
+
+fn string_arg<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing string argument {:?}", name))
+}
+
+async fn call_tool(api: &Api, name: &str, arguments: &Value) -> Result<String, String> {
+    match name {
+        "list_notes" => api.call(Method::GET, api.url("/notes")?, None).await,
+        "read_note" => {
+            let id = string_arg(arguments, "id")?;
+            if !is_valid_note_id(id) {
+                return Err(format!("Invalid note id {:?}", id));
+            }
+            api.call(Method::GET, api.url(&format!("/notes/{}", id))?, None).await
+        }
+        "create_note" => {
+            let body = json!({
+                "content": string_arg(arguments, "content")?,
+                "open": arguments.get("open").and_then(Value::as_bool).unwrap_or(true),
+            });
+            api.call(Method::POST, api.url("/notes")?, Some(body)).await
+        }
+        "search" => {
+            let mut url = api.url("/notes")?;
+            url.query_pairs_mut().append_pair("q", string_arg(arguments, "query")?);
+            api.call(Method::GET, url, None).await
+        }
+        _ => Err(format!("Unknown tool {:?}", name)),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The response to one message, `None` for notifications.
+async fn handle(api: &Api, message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match message.get("method").and_then(Value::as_str).unwrap_or_default() {
+        "initialize" => json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "sticky-notes", "version": env!("CARGO_PKG_VERSION") }
+        }),
+        "ping" => json!({}),
+        "tools/list" => tool_list(),
+        "tools/call" => {
+            let Some(name) = params.get("name").and_then(Value::as_str) else {
+                return Some(error_response(id, INVALID_PARAMS, "Missing tool name"));
+            };
+            let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+            // Tool failures are results the model gets to see, not protocol errors
+            let (text, is_error) = match call_tool(api, name, &arguments).await {
+                Ok(text) => (text, false),
+                Err(e) => (e, true),
+            };
+            json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+        }
+        method => {
+            return Some(error_response(
+                id,
+                METHOD_NOT_FOUND,
+                &format!("Unknown method {}", method),
+            ))
+        }
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+/// Serves MCP on stdin and stdout until stdin closes. Returns the process exit code.
+pub fn serve_stdio() -> i32 {
+    let Ok(token) = std::env::var(TOKEN_VAR) else {
+        eprintln!("MCP: set {} to the token shown in the HTTP API settings", TOKEN_VAR);
+        return 1;
+    };
+    let port = std::env::var(PORT_VAR)
+        .ok()
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let client = match Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("MCP: {}", e);
+            return 1;
+        }
+    };
+    let api = Api {
+        client,
+        base: format!("http://127.0.0.1:{}", port),
+        token,
+    };
+
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => tauri::async_runtime::block_on(handle(&api, &message)),
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        if let Some(response) = response {
+            if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+                break;
+            }
+        }
+    }
+    0
+}
@@ -0,0 +1,372 @@
+//! Per-note window state kept in `session.bin` under `note_meta`, keyed by note id.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::annotations::Annotation;
+use crate::appearance::{self, EffectiveAppearance, NoteAppearance};
+use crate::backend::NotesBackend;
+use crate::localstate;
+use crate::mute::Mute;
+use crate::notewindow::MIN_NOTE_SIZE;
+use crate::rescue;
+use crate::snap;
+use crate::suspect::Suspect;
+
+/// Moves and resizes arrive per pixel while dragging; only persist once they settle.
+const GEOMETRY_DEBOUNCE: Duration = Duration::from_millis(400);
+/// How much of a note's top-left corner must be on a display for it to count as reachable.
+const MIN_VISIBLE: (f64, f64) = (100.0, 32.0);
+/// How far down and right a new note moves off an open note at the same spot.
+pub const CASCADE_STEP: f64 = 24.0;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NoteMeta {
+    /// Logical pixels; all `None` until the window is first moved or resized
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// Name of the display the window was last on
+    pub monitor: Option<String>,
+    pub pinned: bool,
+    pub color: Option<String>,
+    /// Set by `rename_note`, see `titles.rs`; `None` goes by the first line
+    pub title: Option<String>,
+    /// 0.1..=1.0, set by `set_note_opacity`; `None` is fully opaque
+    pub opacity: Option<f64>,
+    /// Rolled up to its title strip, see `collapse.rs`; `width` and `height` stay expanded
+    pub collapsed: bool,
+    /// The window ignores the mouse, see `clickthrough.rs`
+    pub click_through: bool,
+    /// Content hash of the last note packet exported or imported; the common base for merges
+    pub last_exchanged_hash: Option<String>,
+    /// Oldest first, see `annotations.rs`
+    pub annotations: Vec<Annotation>,
+    /// Only for notes without frontmatter, see `tags.rs`
+    pub tags: Vec<String>,
+    /// Unix millis of the first save through the app; many filesystems keep no creation time
+    pub created_at: Option<u64>,
+    /// Unix millis of the last save that changed the content, see `timestamps.rs`
+    pub content_modified_at: Option<u64>,
+    /// Set when `content_modified_at` was estimated by `rebuild_timestamps`
+    pub content_modified_inferred: bool,
+    /// Set while notifications about the note are held back, see `mute.rs`
+    pub mute: Option<Mute>,
+    /// Bytes of the last content saved through the app, see `suspect.rs`
+    pub known_size: Option<u64>,
+    /// Set when the file shrank drastically behind the app's back
+    pub suspect: Option<Suspect>,
+    /// Font and zoom overrides, see `appearance.rs`
+    pub appearance: NoteAppearance,
+    /// Stays with the user across virtual desktops, see `follow.rs`; one note at most
+    pub follow: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// Latest scheduled geometry save per note; older timers see a newer value and bail.
+pub struct GeometrySaves(pub Mutex<HashMap<String, u64>>);
+
+/// Every note's metadata; listings read this once instead of calling `get_meta` per note.
+pub fn load_all<R: Runtime>(app: &tauri::AppHandle<R>) -> HashMap<String, NoteMeta> {
+    app.store("session.bin")
+        .ok()
+        .and_then(|store| store.get("note_meta"))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn save_all<R: Runtime>(app: &tauri::AppHandle<R>, all: &HashMap<String, NoteMeta>) -> Result<(), String> {
+    let store = app.store("session.bin").map_err(|e| e.to_string())?;
+    store.set("note_meta", serde_json::to_value(all).map_err(|e| e.to_string())?);
+    store.save().map_err(|e| e.to_string())
+}
+
+pub fn get_meta<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> NoteMeta {
+    load_all(app).remove(id).unwrap_or_default()
+}
+
+pub fn update_meta<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    update: impl FnOnce(&mut NoteMeta),
+) -> Result<NoteMeta, String> {
+    let mut all = load_all(app);
+    let meta = all.entry(id.to_string()).or_default();
+    update(meta);
+    let updated = meta.clone();
+    save_all(app, &all)?;
+    Ok(updated)
+}
+
+/// Like `update_meta`, but leaves the disk write to the store's auto-save, for updates
+/// that come with every save of a note.
+pub fn stage_meta<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    update: impl FnOnce(&mut NoteMeta),
+) -> Result<(), String> {
+    let mut all = load_all(app);
+    update(all.entry(id.to_string()).or_default());
+    let value = serde_json::to_value(&all).map_err(|e| e.to_string())?;
+    app.stage_store("session.bin", "note_meta", value)
+}
+
+pub fn remove_meta<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    let mut all = load_all(app);
+    if all.remove(id).is_some() {
+        let _ = save_all(app, &all);
+    }
+}
+
+pub fn rename_meta<R: Runtime>(app: &tauri::AppHandle<R>, old: &str, new: &str) {
+    let mut all = load_all(app);
+    if let Some(meta) = all.remove(old) {
+        all.insert(new.to_string(), meta);
+        let _ = save_all(app, &all);
+    }
+}
+
+/// Keeps a saved rectangle reachable: if its top-left strip isn't on any display it is
+/// moved onto the first one, and it is shrunk to fit that display if needed.
+pub fn clamp_to_displays(rect: Rect, displays: &[Rect]) -> Rect {
+    let handle = Rect {
+        x: rect.x,
+        y: rect.y,
+        width: MIN_VISIBLE.0.min(rect.width),
+        height: MIN_VISIBLE.1.min(rect.height),
+    };
+    if displays.is_empty() || displays.iter().any(|d| d.intersects(&handle)) {
+        return rect;
+    }
+
+    let display = displays[0];
+    let width = rect.width.min(display.width);
+    let height = rect.height.min(display.height);
+    Rect {
+        x: display.x + (display.width - width) / 2.0,
+        y: display.y + (display.height - height) / 2.0,
+        width,
+        height,
+    }
+}
+
+pub fn monitor_rect(m: &tauri::Monitor) -> Rect {
+    let scale = m.scale_factor();
+    let position = m.position().to_logical::<f64>(scale);
+    let size = m.size().to_logical::<f64>(scale);
+    Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    }
+}
+
+/// Logical rectangles of all connected displays, primary first.
+pub fn display_rects<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<Rect> {
+    let mut rects: Vec<Rect> = app.primary_monitor().ok().flatten().iter().map(monitor_rect).collect();
+    for monitor in app.available_monitors().unwrap_or_default() {
+        let rect = monitor_rect(&monitor);
+        if !rects.contains(&rect) {
+            rects.push(rect);
+        }
+    }
+    rects
+}
+
+/// The rectangle to build a note window with, or `None` if it was never moved.
+pub fn restored_geometry<R: Runtime>(app: &tauri::AppHandle<R>, meta: &NoteMeta) -> Option<Rect> {
+    let (Some(x), Some(y), Some(width), Some(height)) = (meta.x, meta.y, meta.width, meta.height) else {
+        return None;
+    };
+    let saved = Rect { x, y, width, height };
+    if width < MIN_NOTE_SIZE.0 || height < MIN_NOTE_SIZE.1 {
+        let rescued = rescue::centered_default(display_rects(app).first().copied()?);
+        println!("Note window size {}x{} is below the minimum, rescued to {:?}", width, height, rescued);
+        return Some(rescued);
+    }
+    let mut displays = display_rects(app);
+    // An off-screen window goes back to the display it was on, if that is still connected
+    let previous = meta.monitor.as_deref().and_then(|name| {
+        app.available_monitors()
+            .unwrap_or_default()
+            .iter()
+            .find(|m| m.name().is_some_and(|n| n == name))
+            .map(monitor_rect)
+    });
+    if let Some(previous) = previous {
+        displays.retain(|d| *d != previous);
+        displays.insert(0, previous);
+    }
+    let clamped = clamp_to_displays(saved, &displays);
+    if clamped != saved {
+        println!("Note window at {:?} was off-screen, moved to {:?}", saved, clamped);
+    }
+    Some(clamped)
+}
+
+/// The display under the mouse cursor, or the primary one if the cursor can't be found.
+pub fn cursor_display<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<Rect> {
+    app.cursor_position()
+        .ok()
+        .and_then(|cursor| app.monitor_from_point(cursor.x, cursor.y).ok().flatten())
+        .map(|m| monitor_rect(&m))
+        .or_else(|| display_rects(app).first().copied())
+}
+
+/// Where a note without saved geometry opens: centered on the display under the cursor,
+/// cascaded past the `open` note windows already there so none hides another exactly.
+pub fn new_note_geometry<R: Runtime>(app: &tauri::AppHandle<R>, open: &[Rect]) -> Option<Rect> {
+    let display = cursor_display(app)?;
+    let mut rect = rescue::centered_default(display);
+    let taken = |rect: &Rect| {
+        open.iter()
+            .any(|o| (o.x - rect.x).abs() < CASCADE_STEP / 2.0 && (o.y - rect.y).abs() < CASCADE_STEP / 2.0)
+    };
+    // Each step clears at most one open window, so this always finds a free spot
+    for _ in 0..open.len() {
+        if !taken(&rect) {
+            break;
+        }
+        rect.x += CASCADE_STEP;
+        rect.y += CASCADE_STEP;
+        if rect.x + rect.width > display.x + display.width || rect.y + rect.height > display.y + display.height {
+            // Ran off the display; carry on from its top left corner
+            rect.x = display.x + CASCADE_STEP;
+            rect.y = display.y + CASCADE_STEP;
+        }
+    }
+    Some(rect)
+}
+
+fn save_geometry<R: Runtime>(window: &tauri::WebviewWindow<R>, id: &str) {
+    // Minimized windows report placeholder coordinates (e.g. -32000 on Windows)
+    if window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    let (Ok(scale), Ok(position), Ok(size)) = (
+        window.scale_factor(),
+        window.outer_position(),
+        window.inner_size(),
+    ) else {
+        return;
+    };
+    let position = position.to_logical::<f64>(scale);
+    let size = size.to_logical::<f64>(scale);
+    let monitor = window.current_monitor().ok().flatten().and_then(|m| m.name().cloned());
+    let _ = update_meta(window.app_handle(), id, |meta| {
+        meta.x = Some(position.x);
+        meta.y = Some(position.y);
+        // A collapsed note's size is the title strip; keep the one to expand back to
+        if !meta.collapsed {
+            meta.width = Some(size.width);
+            meta.height = Some(size.height);
+        }
+        meta.monitor = monitor;
+    });
+}
+
+/// Called from the note window's `Moved`/`Resized` handler; persists once the window
+/// has been still for `GEOMETRY_DEBOUNCE`.
+pub fn schedule_geometry_save<R: Runtime>(window: &tauri::WebviewWindow<R>, id: &str) {
+    let app = window.app_handle();
+    let generation = {
+        let state = app.state::<GeometrySaves>();
+        let Ok(mut pending) = state.0.lock() else {
+            return;
+        };
+        let generation = pending.get(id).copied().unwrap_or(0) + 1;
+        pending.insert(id.to_string(), generation);
+        generation
+    };
+
+    let window = window.clone();
+    let id = id.to_string();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(GEOMETRY_DEBOUNCE).await;
+        let is_latest = window
+            .app_handle()
+            .state::<GeometrySaves>()
+            .0
+            .lock()
+            .map(|pending| pending.get(&id) == Some(&generation))
+            .unwrap_or(false);
+        // A snap moves the window again, and that move's save records where it ended up
+        if is_latest && !snap::snap_window(&window) {
+            save_geometry(&window, &id);
+        }
+    });
+}
+
+/// `NoteMeta` plus the device-local state the note window restores on open.
+#[derive(serde::Serialize)]
+pub struct NoteMetaView {
+    #[serde(flatten)]
+    meta: NoteMeta,
+    scroll_offset: Option<u64>,
+    effective_appearance: EffectiveAppearance,
+}
+
+#[tauri::command]
+pub async fn get_note_meta(id: String, app: tauri::AppHandle) -> Result<NoteMetaView, String> {
+    let meta = get_meta(&app, &id);
+    Ok(NoteMetaView {
+        scroll_offset: localstate::scroll_offset(&app, &id),
+        effective_appearance: appearance::effective(&app, &meta.appearance),
+        meta,
+    })
+}
+
+#[tauri::command]
+pub async fn set_note_pinned(id: String, pinned: bool, app: tauri::AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        window.set_always_on_top(pinned).map_err(|e| e.to_string())?;
+    }
+    update_meta(&app, &id, |meta| meta.pinned = pinned)?;
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+#[derive(serde::Serialize, Clone)]
+struct NoteColorChanged {
+    id: String,
+    color: Option<String>,
+}
+
+/// Sets the note's tint; `None` (or an empty string) goes back to the default.
+pub fn set_color<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, color: Option<String>) -> Result<(), String> {
+    let color = color.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    update_meta(app, id, |meta| meta.color = color.clone())?;
+    let changed = NoteColorChanged {
+        id: id.to_string(),
+        color,
+    };
+    app.emit_note_event(id, "note-color-changed", changed);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_note_color(id: String, color: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    set_color(&app, &id, color)
+}
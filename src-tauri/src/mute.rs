@@ -0,0 +1,143 @@
+//! Per-note "do not disturb". A muted note's external-change notifications and reminders
+//! are held back and counted; the change feed, saving, listing and show-all are
+//! unaffected. Unmuting, by hand or when a timed mute runs out, emits one `note-unmuted`
+//! summary of what was held back.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, NoteMeta};
+use crate::now_millis;
+
+/// How often timed mutes are checked for expiry.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Mute {
+    /// Unix millis
+    pub since: u64,
+    /// Unix millis; `None` mutes until unmuted by hand
+    pub until: Option<u64>,
+    pub suppressed_changes: u32,
+    pub suppressed_reminders: u32,
+}
+
+impl Mute {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Notification {
+    ExternalChange,
+    Reminder,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct Unmuted {
+    id: String,
+    muted_for_ms: u64,
+    suppressed_changes: u32,
+    suppressed_reminders: u32,
+}
+
+pub fn is_muted(meta: Option<&NoteMeta>) -> bool {
+    meta.and_then(|m| m.mute.as_ref())
+        .is_some_and(|m| m.is_active(now_millis()))
+}
+
+pub fn muted_ids(metas: &HashMap<String, NoteMeta>) -> HashSet<String> {
+    metas
+        .iter()
+        .filter(|(_, meta)| is_muted(Some(meta)))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Whether a notification about note `id` should be held back; if so it is counted for
+/// the unmute summary.
+pub fn suppress<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, notification: Notification) -> bool {
+    if !is_muted(Some(&meta::get_meta(app, id))) {
+        return false;
+    }
+    println!("Note {} is muted, holding back {:?}", id, notification);
+    let result = meta::stage_meta(app, id, |meta| {
+        if let Some(mute) = meta.mute.as_mut() {
+            match notification {
+                Notification::ExternalChange => mute.suppressed_changes += 1,
+                Notification::Reminder => mute.suppressed_reminders += 1,
+            }
+        }
+    });
+    if let Err(e) = result {
+        println!("Failed to count held back notification for {}: {}", id, e);
+    }
+    true
+}
+
+fn unmute<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    let mut mute = None;
+    meta::update_meta(app, id, |meta| mute = meta.mute.take())?;
+    let Some(mute) = mute else {
+        return Ok(());
+    };
+    let summary = Unmuted {
+        id: id.to_string(),
+        muted_for_ms: now_millis().saturating_sub(mute.since),
+        suppressed_changes: mute.suppressed_changes,
+        suppressed_reminders: mute.suppressed_reminders,
+    };
+    println!("Unmuted {}: {:?}", id, summary);
+    app.emit_note_event(id, "note-unmuted", summary);
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Mutes or unmutes a note. `until` (Unix millis) makes the mute temporary; muting an
+/// already muted note only changes when it ends.
+#[tauri::command]
+pub async fn set_note_muted(id: String, muted: bool, until: Option<u64>, app: tauri::AppHandle) -> Result<(), String> {
+    if !muted {
+        return unmute(&app, &id);
+    }
+    let now = now_millis();
+    if until.is_some_and(|until| until <= now) {
+        return Err("The mute would already have ended".to_string());
+    }
+    meta::update_meta(&app, &id, |meta| match meta.mute.as_mut() {
+        Some(mute) if mute.is_active(now) => mute.until = until,
+        _ => {
+            meta.mute = Some(Mute {
+                since: now,
+                until,
+                ..Default::default()
+            })
+        }
+    })?;
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Ends timed mutes once they run out.
+pub fn spawn_mute_expiry<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(EXPIRY_CHECK_INTERVAL).await;
+            let now = now_millis();
+            let expired: Vec<String> = meta::load_all(&app)
+                .into_iter()
+                .filter(|(_, meta)| meta.mute.as_ref().is_some_and(|m| !m.is_active(now)))
+                .map(|(id, _)| id)
+                .collect();
+            for id in expired {
+                if let Err(e) = unmute(&app, &id) {
+                    println!("Failed to end mute of {}: {}", id, e);
+                }
+            }
+        }
+    });
+}
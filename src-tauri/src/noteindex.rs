@@ -0,0 +1,341 @@
+//! Persistent listing index: title, preview, frontmatter tags, `[[links]]` and file times
+//! per note, kept in `notes_index.json` in the app cache dir so the dashboard lists instantly after
+//! a start. A background rebuild then re-reads only the notes whose size or mtime changed
+//! while we weren't running. From there on the index is kept current by `own_change` for
+//! our own writes and deletes and by the change poller for everyone else's, so listings
+//! never scan the notes folder.
+//!
+//! While the vault is enabled the index lives in memory only, since previews are plain text.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::cache::CachedPreview;
+use crate::flush::{FlushOutcome, Flushable};
+use crate::links;
+use crate::meta;
+use crate::notemenu;
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::tags::frontmatter_tags;
+use crate::tasks::TaskCounts;
+use crate::vault::{self, VaultKey};
+use crate::{notes_dir, preview_of, to_millis, vault_locked_preview};
+
+const FILE_NAME: &str = "notes_index.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct IndexEntry {
+    /// File mtime in Unix millis, with `len` what tells a stale entry
+    modified_at: Option<u64>,
+    len: u64,
+    created_at: Option<u64>,
+    title: String,
+    preview: String,
+    locked: bool,
+    tasks: TaskCounts,
+    /// `None` without frontmatter, where tags come from the note's metadata
+    frontmatter_tags: Option<Vec<String>>,
+    /// Targets of the note's `[[links]]`, as written
+    links: Vec<String>,
+}
+
+impl IndexEntry {
+    pub fn listing(&self) -> CachedPreview {
+        CachedPreview {
+            preview: self.preview.clone(),
+            title: self.title.clone(),
+            locked: self.locked,
+            tasks: self.tasks,
+        }
+    }
+
+    pub fn file_modified_at(&self) -> Option<u64> {
+        self.modified_at
+    }
+
+    pub fn file_created_at(&self) -> Option<u64> {
+        self.created_at
+    }
+
+    pub fn frontmatter_tags(&self) -> Option<&[String]> {
+        self.frontmatter_tags.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+}
+
+pub struct NotesIndex {
+    /// `None` until loaded or built
+    entries: RwLock<Option<HashMap<String, IndexEntry>>>,
+    /// Held while entries are read from disk, so a rebuild can't overwrite a fresher update
+    update: Mutex<()>,
+    dirty: AtomicBool,
+}
+
+fn file_path<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(app.path().app_cache_dir().map_err(|e| e.to_string())?.join(FILE_NAME))
+}
+
+impl NotesIndex {
+    /// The index saved last session; it is checked against the files by `spawn_rebuild`.
+    pub fn load<R: Runtime>(app: &tauri::AppHandle<R>) -> Self {
+        let path = file_path(app).ok();
+        let entries = if vault::is_enabled(app) {
+            // Left behind by a session from before the vault was enabled
+            if let Some(path) = &path {
+                let _ = fs::remove_file(path);
+            }
+            None
+        } else {
+            path.and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|json| serde_json::from_str(&json).ok())
+        };
+        NotesIndex {
+            entries: RwLock::new(entries),
+            update: Mutex::new(()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+}
+
+fn read_entry(path: &Path, vault_key: Option<&VaultKey>) -> Option<IndexEntry> {
+    let metadata = fs::metadata(path).ok()?;
+    // Links can be anywhere in a note, so unlike the preview cache this reads notes whole
+    let stored = String::from_utf8_lossy(&fs::read(path).ok()?).into_owned();
+    let (listing, frontmatter_tags, links) = match vault::decode_with(vault_key, stored) {
+        Ok(content) => (
+            preview_of(&content),
+            frontmatter_tags(&content),
+            links::parse_links(&content),
+        ),
+        Err(_) => (vault_locked_preview(), None, Vec::new()),
+    };
+    Some(IndexEntry {
+        modified_at: to_millis(metadata.modified()),
+        len: metadata.len(),
+        created_at: to_millis(metadata.created()),
+        title: listing.title,
+        preview: listing.preview,
+        locked: listing.locked,
+        tasks: listing.tasks,
+        frontmatter_tags,
+        links,
+    })
+}
+
+fn is_current(entry: &IndexEntry, metadata: &fs::Metadata) -> bool {
+    entry.len == metadata.len() && entry.modified_at == to_millis(metadata.modified())
+}
+
+/// The entries for the notes in `dir`, reusing those in `previous` whose file hasn't
+/// changed. Returns them and whether any had to be read.
+pub fn read_entries(
+    dir: &Path,
+    vault_key: Option<&VaultKey>,
+    previous: &HashMap<String, IndexEntry>,
+) -> Result<(HashMap<String, IndexEntry>, bool), String> {
+    let mut entries = HashMap::new();
+    let mut changed = false;
+    if !dir.exists() {
+        return Ok((entries, changed));
+    }
+    for entry in scan_notes(dir, ScanOptions::default()) {
+        match entry {
+            ScanEntry::Note { id, path, .. } => {
+                let kept = previous
+                    .get(&id)
+                    .filter(|entry| fs::metadata(&path).is_ok_and(|m| is_current(entry, &m)));
+                let entry = match kept {
+                    Some(entry) => Some(entry.clone()),
+                    None => {
+                        changed = true;
+                        read_entry(&path, vault_key)
+                    }
+                };
+                if let Some(entry) = entry {
+                    entries.insert(id, entry);
+                }
+            }
+            // The notes directory itself being unreadable is a real error, anything below it is skipped
+            ScanEntry::Unreadable { path: bad, reason } if bad == dir => return Err(reason),
+            ScanEntry::Unreadable { path: bad, reason } | ScanEntry::Quarantine { path: bad, reason } => {
+                println!("Skipping {:?}: {}", bad, reason);
+            }
+            _ => {}
+        }
+    }
+    Ok((entries, changed))
+}
+
+/// Brings the index in line with the notes folder, reading only new and changed notes.
+/// Returns whether anything changed.
+fn rebuild<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<bool, String> {
+    let state = app.state::<NotesIndex>();
+    let _update = state.update.lock().map_err(|e| e.to_string())?;
+    let dir = notes_dir(app)?;
+    let vault_key = vault::key(app);
+    let previous = state
+        .entries
+        .read()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .unwrap_or_default();
+
+    let (entries, mut changed) = read_entries(&dir, vault_key.as_ref(), &previous)?;
+    changed |= entries.len() != previous.len();
+
+    let mut current = state.entries.write().map_err(|e| e.to_string())?;
+    changed |= current.is_none();
+    *current = Some(entries);
+    if changed {
+        state.dirty.store(true, Ordering::Relaxed);
+    }
+    Ok(changed)
+}
+
+/// Re-reads the entries of notes `ids`, dropping those whose file is gone. Does nothing
+/// before the index is first built; the build picks the changes up.
+pub fn refresh_ids<R: Runtime>(app: &tauri::AppHandle<R>, ids: &[&str]) {
+    let state = app.state::<NotesIndex>();
+    if state.entries.read().map_or(true, |entries| entries.is_none()) {
+        return;
+    }
+    // (id, old title, new title) of notes whose title changed
+    let mut renamed = Vec::new();
+    {
+        let (Ok(_update), Ok(dir)) = (state.update.lock(), notes_dir(app)) else {
+            return;
+        };
+        let vault_key = vault::key(app);
+        let fresh: Vec<(&str, Option<IndexEntry>)> = ids
+            .iter()
+            .map(|id| (*id, read_entry(&dir.join(format!("{}.md", id)), vault_key.as_ref())))
+            .collect();
+        let Ok(mut entries) = state.entries.write() else {
+            return;
+        };
+        let Some(entries) = entries.as_mut() else {
+            return;
+        };
+        for (id, entry) in fresh {
+            let previous = match entry {
+                Some(entry) => {
+                    let title = (!entry.locked).then(|| entry.title.clone());
+                    entries.insert(id.to_string(), entry).map(|old| (old, title))
+                }
+                None => {
+                    entries.remove(id);
+                    None
+                }
+            };
+            if let Some((old, Some(title))) = previous {
+                if !old.locked && old.title != title {
+                    renamed.push((id.to_string(), old.title, title));
+                }
+            }
+        }
+        state.dirty.store(true, Ordering::Relaxed);
+    }
+    for (id, old_title, new_title) in renamed {
+        // A note given a title keeps it whatever its first line says
+        if meta::get_meta(app, &id).title.is_some() {
+            continue;
+        }
+        links::target_renamed(app, &id, &old_title, &new_title);
+        notemenu::title_changed(app, &id);
+    }
+}
+
+/// Every indexed note, building the index first if the background build hasn't yet.
+pub fn listing<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<HashMap<String, IndexEntry>, String> {
+    let state = app.state::<NotesIndex>();
+    if let Some(entries) = state.entries.read().map_err(|e| e.to_string())?.as_ref() {
+        return Ok(entries.clone());
+    }
+    rebuild(app)?;
+    let entries = state
+        .entries
+        .read()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .unwrap_or_default();
+    Ok(entries)
+}
+
+/// Checks the index against the notes folder off the main thread, refreshing listings if
+/// anything was out of date.
+pub fn spawn_rebuild<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        match rebuild(&app) {
+            Ok(true) => app.emit_event("refresh-notes", ()),
+            Ok(false) => {}
+            Err(e) => println!("Failed to index notes: {}", e),
+        }
+    });
+}
+
+/// Throws the index away and builds it again, for when what an entry would hold changed
+/// without the files changing (the vault being locked or unlocked).
+pub fn reset<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if let Ok(mut entries) = app.state::<NotesIndex>().entries.write() {
+        *entries = None;
+    }
+    spawn_rebuild(app.clone());
+}
+
+/// Writes `notes_index.json` if the index changed since the last flush.
+pub fn flush_index<R: Runtime>(app: &tauri::AppHandle<R>) -> FlushOutcome {
+    let state = app.state::<NotesIndex>();
+    if !state.dirty.swap(false, Ordering::Relaxed) {
+        return FlushOutcome::Clean;
+    }
+    let result = file_path(app).and_then(|path| {
+        if vault::is_enabled(app) {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            };
+        }
+        let json = state
+            .entries
+            .read()
+            .map_err(|e| e.to_string())
+            .and_then(|entries| serde_json::to_string(&*entries).map_err(|e| e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, json).map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(()) => FlushOutcome::Wrote,
+        Err(error) => {
+            // Keep it dirty so the next flush retries
+            state.dirty.store(true, Ordering::Relaxed);
+            println!("Failed to flush the notes index: {}", error);
+            FlushOutcome::Failed { error }
+        }
+    }
+}
+
+pub struct IndexFlush;
+
+impl<R: Runtime> Flushable<R> for IndexFlush {
+    fn name(&self) -> &'static str {
+        "notes_index"
+    }
+
+    fn flush(&self, app: &tauri::AppHandle<R>) -> FlushOutcome {
+        flush_index(app)
+    }
+}
@@ -0,0 +1,176 @@
+//! The tray's submenus listing note windows by title: "Notes", whose entries focus an open
+//! note, "Rescue note…" (see `rescue`), and "Recent", the notes whose windows were closed
+//! last, most recent first, which reopen them. They are rebuilt together whenever a note
+//! window opens or closes and when an open note's title changes.
+//!
+//! Recently closed notes are kept in memory only, like a browser's closed tabs;
+//! `reopen_last_closed_note` brings back the latest that still exists.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tauri::menu::{MenuItem, Submenu};
+use tauri::{Manager, Runtime};
+
+use crate::meta;
+use crate::notewindow::NoteWindowOptions;
+use crate::rescue::{self, RescueMenu};
+use crate::tasks::count_tasks;
+use crate::titles::effective_title;
+use crate::{create_note_window, derive_title, notes_dir, read_note, WindowRegistry};
+
+/// Prefix of the tray menu item ids; the note id follows.
+pub const MENU_ID_PREFIX: &str = "focus:";
+/// Prefix of the "Recent" submenu's item ids; the note id follows.
+pub const RECENT_MENU_ID_PREFIX: &str = "recent:";
+const MENU_TITLE_CHARS: usize = 40;
+/// How many closed notes are remembered.
+const RECENT_LIMIT: usize = 10;
+
+/// The tray's "Notes" submenu.
+pub struct NotesMenu<R: Runtime>(pub Submenu<R>);
+
+/// The tray's "Recent" submenu.
+pub struct RecentMenu<R: Runtime>(pub Submenu<R>);
+
+/// Ids of the notes whose windows closed last, most recent first.
+#[derive(Default)]
+pub struct RecentlyClosed(Mutex<VecDeque<String>>);
+
+/// The note's title, shortened, with a done/total badge if it has task list items.
+fn menu_title<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> String {
+    let content = read_note(app, id).unwrap_or_default();
+    let title = effective_title(Some(&meta::get_meta(app, id)), &derive_title(&content));
+    let mut title: String = title.chars().take(MENU_TITLE_CHARS).collect();
+    let tasks = count_tasks(&content);
+    if tasks.open + tasks.done > 0 {
+        title.push_str(&format!(" ({}/{})", tasks.done, tasks.open + tasks.done));
+    }
+    title
+}
+
+fn note_exists<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> bool {
+    notes_dir(app).is_ok_and(|dir| dir.join(format!("{}.md", id)).is_file())
+}
+
+/// Ids and menu titles of the open notes, by title.
+fn open_notes<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<(String, String)> {
+    let mut notes: Vec<(String, String)> = app
+        .state::<WindowRegistry>()
+        .note_labels()
+        .iter()
+        .filter_map(|label| label.strip_prefix("note-"))
+        .map(|id| (id.to_string(), menu_title(app, id)))
+        .collect();
+    notes.sort_by_cached_key(|(id, title)| (title.to_lowercase(), id.clone()));
+    notes
+}
+
+/// Ids of the recently closed notes, most recent first.
+pub fn recently_closed<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<String> {
+    app.state::<RecentlyClosed>()
+        .0
+        .lock()
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Ids and menu titles of the recently closed notes that still exist.
+fn recent_notes<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<(String, String)> {
+    recently_closed(app)
+        .into_iter()
+        .filter(|id| note_exists(app, id))
+        .map(|id| {
+            let title = menu_title(app, &id);
+            (id, title)
+        })
+        .collect()
+}
+
+fn fill<R: Runtime>(app: &tauri::AppHandle<R>, submenu: &Submenu<R>, prefix: &str, notes: &[(String, String)]) {
+    if let Ok(items) = submenu.items() {
+        for item in items {
+            let _ = submenu.remove(&item);
+        }
+    }
+    let _ = submenu.set_enabled(!notes.is_empty());
+    for (id, title) in notes {
+        if let Ok(item) = MenuItem::with_id(app, format!("{}{}", prefix, id), title, true, None::<&str>) {
+            let _ = submenu.append(&item);
+        }
+    }
+}
+
+/// Lists the open notes in the tray's submenus.
+pub fn refresh_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let notes = open_notes(app);
+    if let Some(menu) = app.try_state::<NotesMenu<R>>() {
+        fill(app, &menu.0, MENU_ID_PREFIX, &notes);
+    }
+    if let Some(menu) = app.try_state::<RescueMenu<R>>() {
+        fill(app, &menu.0, rescue::MENU_ID_PREFIX, &notes);
+    }
+    if let Some(menu) = app.try_state::<RecentMenu<R>>() {
+        fill(app, &menu.0, RECENT_MENU_ID_PREFIX, &recent_notes(app));
+    }
+}
+
+/// Note `id`'s window was registered; it is no longer a recently closed note.
+pub fn note_opened<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    if let Ok(mut recent) = app.state::<RecentlyClosed>().0.lock() {
+        recent.retain(|closed| closed != id);
+    }
+    refresh_menus(app);
+}
+
+/// Note `id`'s window was destroyed; it becomes the most recently closed note.
+pub fn note_closed<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    if let Ok(mut recent) = app.state::<RecentlyClosed>().0.lock() {
+        recent.retain(|closed| closed != id);
+        recent.push_front(id.to_string());
+        recent.truncate(RECENT_LIMIT);
+    }
+    refresh_menus(app);
+}
+
+/// Note `id` has a new title; its menu entries follow if its window is open.
+pub fn title_changed<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    let open = app
+        .state::<WindowRegistry>()
+        .0
+        .read()
+        .is_ok_and(|registry| registry.contains_key(&format!("note-{}", id)));
+    if open {
+        refresh_menus(app);
+    }
+}
+
+/// Brings note `id`'s window forward, or opens it again, from the tray's submenus.
+pub fn focus_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    create_note_window(app, NoteWindowOptions::open(id)).map(|_| ())
+}
+
+/// Opens the most recently closed note that still exists. Returns its id, or `None` if
+/// there is none.
+pub fn reopen_last_closed<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<Option<String>, String> {
+    loop {
+        let next = app
+            .state::<RecentlyClosed>()
+            .0
+            .lock()
+            .map_err(|e| e.to_string())?
+            .pop_front();
+        let Some(id) = next else {
+            return Ok(None);
+        };
+        // Deleted or archived since; the next one is what the user means
+        if note_exists(app, &id) {
+            focus_note(app, &id)?;
+            return Ok(Some(id));
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn reopen_last_closed_note(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    reopen_last_closed(&app)
+}
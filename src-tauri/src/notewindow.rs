@@ -0,0 +1,160 @@
+//! How `create_note_window` should build a note window. Start from the constructor
+//! matching the flow (`new_note`, `open`, `restore`) and override from there; anything
+//! not overridden falls back to what the note's saved metadata says.
+
+use uuid::Uuid;
+
+use crate::meta::Rect;
+
+/// Width and height of a note that was never moved or resized.
+pub const DEFAULT_NOTE_SIZE: (f64, f64) = (300.0, 300.0);
+/// Smallest inner size that still leaves the frontend's drag region and close button usable.
+pub const MIN_NOTE_SIZE: (f64, f64) = (160.0, 120.0);
+
+#[derive(Clone, Debug)]
+pub struct NoteWindowOptions {
+    id: Option<String>,
+    save_to_session: bool,
+    show: bool,
+    geometry: Option<Rect>,
+    pinned: Option<bool>,
+}
+
+impl NoteWindowOptions {
+    /// A brand-new note under a fresh id: added to the session and shown.
+    pub fn new_note() -> Self {
+        NoteWindowOptions {
+            id: None,
+            save_to_session: true,
+            show: true,
+            geometry: None,
+            pinned: None,
+        }
+    }
+
+    /// An existing note the user asked to see: added to the session and shown.
+    pub fn open(id: impl Into<String>) -> Self {
+        NoteWindowOptions {
+            id: Some(id.into()),
+            ..Self::new_note()
+        }
+    }
+
+    /// A note coming back from a saved session. It is already in the session order and
+    /// stays hidden; the caller decides when to show it.
+    pub fn restore(id: impl Into<String>) -> Self {
+        NoteWindowOptions {
+            id: Some(id.into()),
+            save_to_session: false,
+            show: false,
+            geometry: None,
+            pinned: None,
+        }
+    }
+
+    /// Logical geometry to use instead of the saved one. Unlike saved geometry it is
+    /// taken as is, without clamping to the displays.
+    pub fn geometry(mut self, rect: Rect) -> Self {
+        self.geometry = Some(rect);
+        self
+    }
+
+    pub fn pinned(mut self, pinned: bool) -> Self {
+        self.pinned = Some(pinned);
+        self
+    }
+
+    /// The note id, generating one for a new note.
+    pub fn resolve_id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| Uuid::new_v4().to_string())
+    }
+
+    /// An explicit geometry wins over the saved one; `None` leaves it to
+    /// `meta::new_note_geometry`.
+    pub fn resolve_geometry(&self, saved: Option<Rect>) -> Option<Rect> {
+        self.geometry.or(saved)
+    }
+
+    pub fn resolve_pinned(&self, saved: bool) -> bool {
+        self.pinned.unwrap_or(saved)
+    }
+
+    pub fn saves_to_session(&self) -> bool {
+        self.save_to_session
+    }
+
+    pub fn shows(&self) -> bool {
+        self.show
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVED: Rect = Rect {
+        x: 10.0,
+        y: 20.0,
+        width: 300.0,
+        height: 300.0,
+    };
+    const EXPLICIT: Rect = Rect {
+        x: 400.0,
+        y: 50.0,
+        width: 200.0,
+        height: 180.0,
+    };
+
+    #[test]
+    fn new_notes_get_a_fresh_id_each_time() {
+        let options = NoteWindowOptions::new_note();
+        let first = options.resolve_id();
+        let second = options.resolve_id();
+        assert_ne!(first, second);
+        assert!(Uuid::parse_str(&first).is_ok());
+        assert!(options.saves_to_session());
+        assert!(options.shows());
+    }
+
+    #[test]
+    fn opened_notes_keep_their_id_and_are_saved_and_shown() {
+        let options = NoteWindowOptions::open("abc");
+        assert_eq!(options.resolve_id(), "abc");
+        assert!(options.saves_to_session());
+        assert!(options.shows());
+    }
+
+    #[test]
+    fn restored_notes_stay_hidden_and_out_of_the_session_order() {
+        let options = NoteWindowOptions::restore("abc");
+        assert_eq!(options.resolve_id(), "abc");
+        assert!(!options.saves_to_session());
+        assert!(!options.shows());
+    }
+
+    #[test]
+    fn saved_state_applies_when_nothing_is_overridden() {
+        for options in [
+            NoteWindowOptions::new_note(),
+            NoteWindowOptions::open("abc"),
+            NoteWindowOptions::restore("abc"),
+        ] {
+            assert_eq!(options.resolve_geometry(Some(SAVED)), Some(SAVED));
+            assert_eq!(options.resolve_geometry(None), None);
+            assert!(options.resolve_pinned(true));
+            assert!(!options.resolve_pinned(false));
+        }
+    }
+
+    #[test]
+    fn overrides_win_over_saved_state() {
+        let options = NoteWindowOptions::restore("abc").geometry(EXPLICIT).pinned(false);
+        assert_eq!(options.resolve_geometry(Some(SAVED)), Some(EXPLICIT));
+        assert_eq!(options.resolve_geometry(None), Some(EXPLICIT));
+        assert!(!options.resolve_pinned(true));
+        assert!(NoteWindowOptions::open("abc").pinned(true).resolve_pinned(false));
+        // Overrides leave the flow's other choices alone
+        assert!(!options.saves_to_session());
+        assert!(!options.shows());
+    }
+}
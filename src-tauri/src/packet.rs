@@ -0,0 +1,358 @@
+//! Single-note packets: a zip holding one note's content, metadata and asset folder,
+//! for handing a note back and forth between machines.
+//!
+//! Layout: `manifest.json`, `content.md`, `meta.json` and `assets/...` (a copy of the
+//! note's `<id>/` folder; its `history/` sub-folder only when asked for).
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri::Runtime;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::annotations;
+use crate::backend::NotesBackend;
+use crate::error::NoteError;
+use crate::flush::flush_now;
+use crate::limits::effective_limits;
+use crate::meta::{self, NoteMeta};
+use crate::scan::is_valid_note_id;
+use crate::vault;
+use crate::{notes_dir, now_millis, write_note};
+
+const PACKET_FORMAT: &str = "sticky-notes-packet";
+/// Bump when the layout changes; importers refuse packets newer than they understand.
+const PACKET_VERSION: u32 = 1;
+pub const HISTORY_DIR: &str = "history";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct PacketManifest {
+    format: String,
+    version: u32,
+    note_id: String,
+    /// Content hash both sides last agreed on, `None` for a note never exchanged before
+    base_hash: Option<String>,
+    content_hash: String,
+    exported_at: u64,
+    has_history: bool,
+}
+
+/// How `import_note_packet` places the incoming note.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    Merge,
+    AsNew,
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// The note did not exist locally (or `as_new` was used) and was created.
+    Created { id: String },
+    /// The local note was still at the packet's base version and now has its content.
+    Applied { id: String },
+    /// Nothing differed; nothing was written.
+    Unchanged { id: String },
+    /// Both sides edited since the base; nothing was written.
+    Diverged {
+        id: String,
+        local: String,
+        incoming: String,
+    },
+}
+
+struct Packet {
+    manifest: PacketManifest,
+    content: String,
+    meta: NoteMeta,
+    /// Paths relative to the note's asset folder
+    assets: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// FNV-1a, so hashes stay stable across builds and platforms.
+pub fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn collect_assets(
+    dir: &Path,
+    relative: &Path,
+    include_history: bool,
+    out: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    // Sorted so the same note always produces the same archive
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = PathBuf::from(entry.file_name());
+        let path = entry.path();
+        if relative.as_os_str().is_empty() && name == Path::new(HISTORY_DIR) && !include_history {
+            continue;
+        }
+        // symlink_metadata so a link can't pull files from outside the note into the packet
+        let file_type = fs::symlink_metadata(&path).map_err(|e| e.to_string())?.file_type();
+        if file_type.is_dir() {
+            collect_assets(&path, &relative.join(&name), include_history, out)?;
+        } else if file_type.is_file() {
+            out.push((path, relative.join(&name)));
+        }
+    }
+    Ok(())
+}
+
+/// Zip entry names always use `/`, whatever the platform.
+fn entry_name(relative: &Path) -> String {
+    let parts: Vec<_> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect();
+    format!("assets/{}", parts.join("/"))
+}
+
+fn read_packet(path: &Path, max_note_bytes: usize) -> Result<Packet, String> {
+    let invalid = |reason: String| -> String { NoteError::InvalidPacket { reason }.into() };
+
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+
+    let manifest: PacketManifest = {
+        let entry = archive
+            .by_name("manifest.json")
+            .map_err(|_| invalid("missing manifest.json".to_string()))?;
+        serde_json::from_reader(entry).map_err(|e| invalid(format!("bad manifest: {}", e)))?
+    };
+    if manifest.format != PACKET_FORMAT {
+        return Err(invalid(format!("unknown format {:?}", manifest.format)));
+    }
+    if manifest.version > PACKET_VERSION {
+        return Err(invalid(format!(
+            "packet version {} is newer than supported version {}",
+            manifest.version, PACKET_VERSION
+        )));
+    }
+    if !is_valid_note_id(&manifest.note_id) {
+        return Err(invalid(format!("invalid note id {:?}", manifest.note_id)));
+    }
+
+    let content = {
+        let entry = archive
+            .by_name("content.md")
+            .map_err(|_| invalid("missing content.md".to_string()))?;
+        if entry.size() as usize > max_note_bytes {
+            return Err(NoteError::TooLarge {
+                size: entry.size() as usize,
+                limit: max_note_bytes,
+            }
+            .into());
+        }
+        let mut content = String::new();
+        entry
+            .take(max_note_bytes as u64 + 1)
+            .read_to_string(&mut content)
+            .map_err(|e| invalid(format!("unreadable content.md: {}", e)))?;
+        content
+    };
+    if content_hash(&content) != manifest.content_hash {
+        return Err(invalid("content does not match the manifest hash".to_string()));
+    }
+
+    let meta = match archive.by_name("meta.json") {
+        Ok(entry) => serde_json::from_reader(entry).map_err(|e| invalid(format!("bad meta.json: {}", e)))?,
+        Err(_) => NoteMeta::default(),
+    };
+
+    let mut assets = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| invalid(e.to_string()))?;
+        if entry.is_dir() || !entry.name().starts_with("assets/") {
+            continue;
+        }
+        // enclosed_name rejects absolute paths and `..`, so entries can't escape the note folder
+        let relative = entry
+            .enclosed_name()
+            .and_then(|name| name.strip_prefix("assets").ok().map(Path::to_path_buf))
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .ok_or_else(|| invalid(format!("unsafe asset path {:?}", entry.name())))?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).map_err(|e| invalid(e.to_string()))?;
+        assets.push((relative, bytes));
+    }
+
+    Ok(Packet {
+        manifest,
+        content,
+        meta,
+        assets,
+    })
+}
+
+fn write_assets<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    asset_dir: &Path,
+    assets: &[(PathBuf, Vec<u8>)],
+) -> Result<(), String> {
+    for (relative, bytes) in assets {
+        let path = asset_dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        if relative.starts_with(HISTORY_DIR) {
+            let snapshot = vault::encode(app, &String::from_utf8_lossy(bytes))?;
+            fs::write(path, snapshot).map_err(|e| e.to_string())?;
+            continue;
+        }
+        fs::write(path, bytes).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Writes the packet's note under `id` and records its hash as the new common base.
+fn apply_packet<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, packet: &Packet) -> Result<(), String> {
+    write_note(app, id, &packet.content)?;
+    write_assets(app, &notes_dir(app)?.join(id), &packet.assets)?;
+    // Geometry and pinning describe the sender's desk, so only color, tags and annotations travel
+    meta::update_meta(app, id, |meta| {
+        meta.color = packet.meta.color.clone();
+        meta.tags = packet.meta.tags.clone();
+        annotations::merge(&mut meta.annotations, &packet.meta.annotations);
+        meta.last_exchanged_hash = Some(packet.manifest.content_hash.clone());
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn export_note_packet(
+    id: String,
+    destination: String,
+    include_history: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    flush_now(&app, "export");
+    let include_history = include_history.unwrap_or(false);
+    let dir = notes_dir(&app)?;
+    let content = vault::read_file(&app, &dir.join(format!("{}.md", id)))?;
+    let note_meta = meta::get_meta(&app, &id);
+    let hash = content_hash(&content);
+
+    let mut assets = Vec::new();
+    let asset_dir = dir.join(&id);
+    if asset_dir.is_dir() {
+        collect_assets(&asset_dir, Path::new(""), include_history, &mut assets)?;
+    }
+
+    let manifest = PacketManifest {
+        format: PACKET_FORMAT.to_string(),
+        version: PACKET_VERSION,
+        note_id: id.clone(),
+        base_hash: note_meta.last_exchanged_hash.clone(),
+        content_hash: hash.clone(),
+        exported_at: now_millis(),
+        has_history: include_history && asset_dir.join(HISTORY_DIR).is_dir(),
+    };
+
+    let file = fs::File::create(&destination).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut add = |name: &str, bytes: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(bytes).map_err(|e| e.to_string())
+    };
+    add(
+        "manifest.json",
+        &serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?,
+    )?;
+    add("content.md", content.as_bytes())?;
+    add(
+        "meta.json",
+        &serde_json::to_vec_pretty(&note_meta).map_err(|e| e.to_string())?,
+    )?;
+    for (path, relative) in &assets {
+        // Packets carry plain text; snapshots are re-encrypted on import if need be
+        let bytes = if relative.starts_with(HISTORY_DIR) {
+            vault::read_file(&app, path)?.into_bytes()
+        } else {
+            fs::read(path).map_err(|e| e.to_string())?
+        };
+        add(&entry_name(relative), &bytes)?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    // What we just sent is now the version the receiver will build on
+    meta::update_meta(&app, &id, |meta| meta.last_exchanged_hash = Some(hash))?;
+    Ok(())
+}
+
+/// `merge` only overwrites a local note still at the packet's base version; `as_new`
+/// always imports under a fresh id. A packet identical to the local note writes nothing.
+#[tauri::command]
+pub async fn import_note_packet(
+    path: String,
+    mode: ImportMode,
+    app: tauri::AppHandle,
+) -> Result<ImportOutcome, String> {
+    let packet = read_packet(Path::new(&path), effective_limits(&app).max_note_bytes)?;
+
+    let outcome = match mode {
+        ImportMode::AsNew => {
+            let id = Uuid::new_v4().to_string();
+            apply_packet(&app, &id, &packet)?;
+            ImportOutcome::Created { id }
+        }
+        ImportMode::Merge => {
+            let id = packet.manifest.note_id.clone();
+            let dir = notes_dir(&app)?;
+            let note_path = dir.join(format!("{}.md", id));
+            if !note_path.is_file() {
+                apply_packet(&app, &id, &packet)?;
+                ImportOutcome::Created { id }
+            } else {
+                let local = vault::read_file(&app, &note_path)?;
+                let local_hash = content_hash(&local);
+                let assets_match = packet
+                    .assets
+                    .iter()
+                    .all(|(relative, bytes)| fs::read(dir.join(&id).join(relative)).ok().as_ref() == Some(bytes));
+                let local_meta = meta::get_meta(&app, &id);
+                let meta_matches = local_meta.color == packet.meta.color
+                    && packet
+                        .meta
+                        .annotations
+                        .iter()
+                        .all(|incoming| local_meta.annotations.iter().any(|a| a.id == incoming.id));
+
+                if local_hash == packet.manifest.content_hash && assets_match && meta_matches {
+                    ImportOutcome::Unchanged { id }
+                } else if packet.manifest.base_hash.as_deref() == Some(local_hash.as_str())
+                    || local_hash == packet.manifest.content_hash
+                {
+                    apply_packet(&app, &id, &packet)?;
+                    ImportOutcome::Applied { id }
+                } else {
+                    ImportOutcome::Diverged {
+                        id,
+                        local,
+                        incoming: packet.content,
+                    }
+                }
+            }
+        }
+    };
+
+    if matches!(outcome, ImportOutcome::Created { .. } | ImportOutcome::Applied { .. }) {
+        app.emit_event("refresh-notes", ());
+    }
+    Ok(outcome)
+}
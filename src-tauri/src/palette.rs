@@ -0,0 +1,242 @@
+//! The command palette. `palette_query(text)` fuzzily matches the text against note
+//! titles, the app's commands and the templates and returns the best matches first, each
+//! with the action to run when it is picked; the frontend passes that action to
+//! `palette_run`, which does it.
+//!
+//! Picks are remembered (the last `HISTORY_LIMIT`, in session.bin `palette_history`) and
+//! rank above other matches, most recent first; so do notes closed recently. With no
+//! text the palette lists just those, then the commands.
+
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::daily;
+use crate::meta;
+use crate::noteindex;
+use crate::notemenu;
+use crate::notewindow::NoteWindowOptions;
+use crate::quickcapture;
+use crate::templates;
+use crate::titles::effective_title;
+use crate::{create_note_window, show_dashboard};
+
+const HISTORY_KEY: &str = "palette_history";
+const HISTORY_LIMIT: usize = 20;
+const MAX_RESULTS: usize = 20;
+/// Added to a match's score per place from the end of the history, so recent picks lead.
+const HISTORY_BOOST: i64 = 4;
+/// Added to the score of a recently closed note.
+const RECENT_BOOST: i64 = 10;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PaletteAction {
+    OpenNote { id: String },
+    NewNote,
+    NewFromTemplate { name: String },
+    OpenDailyNote,
+    QuickCapture,
+    ReopenClosedNote,
+    OpenDashboard,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct PaletteResult {
+    title: String,
+    action: PaletteAction,
+    /// Picked from the palette or closed recently
+    recent: bool,
+}
+
+/// The app's own commands and their palette titles.
+const COMMANDS: &[(&str, PaletteAction)] = &[
+    ("New note", PaletteAction::NewNote),
+    ("Today's note", PaletteAction::OpenDailyNote),
+    ("Quick capture", PaletteAction::QuickCapture),
+    ("Reopen closed note", PaletteAction::ReopenClosedNote),
+    ("Open dashboard", PaletteAction::OpenDashboard),
+];
+
+/// Scores `candidate` for `text`, higher for a better match, or `None` if the characters
+/// of `text` (spaces aside) don't all appear in it in order. Characters matched at the
+/// start of a word or right after the previous match count most, skipped ones count
+/// against it. Case is ignored.
+fn fuzzy_score(text: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in text.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase) {
+        let found = (next..candidate.len()).find(|&i| candidate[i] == wanted)?;
+        score += 1;
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if found > 0 && previous == Some(found - 1) {
+            score += 5;
+        }
+        score -= (found - next).min(5) as i64;
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+fn history(backend: &impl NotesBackend) -> Vec<PaletteAction> {
+    backend
+        .read_store("session.bin", HISTORY_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn remember(backend: &impl NotesBackend, action: &PaletteAction) {
+    let mut history = history(backend);
+    history.retain(|picked| picked != action);
+    history.insert(0, action.clone());
+    history.truncate(HISTORY_LIMIT);
+    let saved = serde_json::to_value(history)
+        .map_err(|e| e.to_string())
+        .and_then(|value| backend.write_store("session.bin", HISTORY_KEY, value));
+    if let Err(e) = saved {
+        println!("Failed to save palette history: {}", e);
+    }
+}
+
+/// Everything the palette can offer, with its title.
+fn candidates<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<(String, PaletteAction)> {
+    let metas = meta::load_all(app);
+    let mut notes: Vec<(String, PaletteAction)> = noteindex::listing(app)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, entry)| {
+            let title = effective_title(metas.get(&id), entry.title());
+            (title, PaletteAction::OpenNote { id })
+        })
+        .collect();
+    notes.sort_by_cached_key(|(title, _)| title.to_lowercase());
+    let commands = COMMANDS
+        .iter()
+        .map(|(title, action)| (title.to_string(), action.clone()));
+    let templates = templates::template_names(app)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| {
+            let title = format!("New from template: {}", name);
+            (title, PaletteAction::NewFromTemplate { name })
+        });
+    commands.chain(templates).chain(notes).collect()
+}
+
+/// How much being recent adds to a candidate's score, if it is.
+fn recency_boost(action: &PaletteAction, history: &[PaletteAction], recently_closed: &[String]) -> Option<i64> {
+    if let Some(place) = history.iter().position(|picked| picked == action) {
+        return Some((HISTORY_LIMIT - place) as i64 * HISTORY_BOOST);
+    }
+    match action {
+        PaletteAction::OpenNote { id } if recently_closed.contains(id) => Some(RECENT_BOOST),
+        _ => None,
+    }
+}
+
+fn query<R: Runtime>(app: &tauri::AppHandle<R>, text: &str) -> Vec<PaletteResult> {
+    let history = history(app);
+    let recently_closed = notemenu::recently_closed(app);
+    let text = text.trim();
+    let mut scored: Vec<(i64, PaletteResult)> = candidates(app)
+        .into_iter()
+        .filter_map(|(title, action)| {
+            let boost = recency_boost(&action, &history, &recently_closed);
+            let score = match (text.is_empty(), boost) {
+                (false, _) => fuzzy_score(text, &title)? + boost.unwrap_or(0),
+                (true, Some(boost)) => boost,
+                // After the recent items; other notes and the templates wait for some text
+                (true, None) if COMMANDS.iter().any(|(_, command)| *command == action) => 0,
+                (true, None) => return None,
+            };
+            let result = PaletteResult {
+                title,
+                action,
+                recent: boost.is_some(),
+            };
+            Some((score, result))
+        })
+        .collect();
+    // Stable, so equal scores keep the candidates' order
+    scored.sort_by_key(|(score, _)| -score);
+    scored.into_iter().take(MAX_RESULTS).map(|(_, result)| result).collect()
+}
+
+/// The best matches for `text`, best first; see the module docs.
+#[tauri::command]
+pub async fn palette_query(text: String, app: tauri::AppHandle) -> Result<Vec<PaletteResult>, String> {
+    Ok(query(&app, &text))
+}
+
+/// Runs a result's action and remembers it.
+#[tauri::command]
+pub async fn palette_run(action: PaletteAction, app: tauri::AppHandle) -> Result<(), String> {
+    remember(&app, &action);
+    match action {
+        PaletteAction::OpenNote { id } => notemenu::focus_note(&app, &id),
+        PaletteAction::NewNote => create_note_window(&app, NoteWindowOptions::new_note()).map(|_| ()),
+        PaletteAction::NewFromTemplate { name } => templates::new_note_from_template(&app, &name).map(|_| ()),
+        PaletteAction::OpenDailyNote => daily::open_daily(&app).map(|_| ()),
+        PaletteAction::QuickCapture => quickcapture::show_popup(&app),
+        PaletteAction::ReopenClosedNote => notemenu::reopen_last_closed(&app).map(|_| ()),
+        PaletteAction::OpenDashboard => {
+            show_dashboard(&app);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    fn open(id: &str) -> PaletteAction {
+        PaletteAction::OpenNote { id: id.to_string() }
+    }
+
+    #[test]
+    fn matches_need_every_character_in_order() {
+        assert!(fuzzy_score("nn", "New note").is_some());
+        assert!(fuzzy_score("NEW NOTE", "new note").is_some());
+        assert_eq!(fuzzy_score("np", "New note"), None);
+        assert_eq!(fuzzy_score("ton", "note"), None);
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn word_starts_and_runs_score_higher() {
+        assert!(fuzzy_score("nn", "New note") > fuzzy_score("nn", "Reopen closed note"));
+        assert!(fuzzy_score("groc", "Groceries") > fuzzy_score("groc", "Go round the clock"));
+    }
+
+    #[test]
+    fn recent_picks_rank_by_place() {
+        let history = [open("b"), PaletteAction::NewNote, open("a")];
+        let closed = ["a".to_string(), "c".to_string()];
+        let boost = |action: &PaletteAction| recency_boost(action, &history, &closed);
+        assert!(boost(&open("b")) > boost(&PaletteAction::NewNote));
+        assert!(boost(&PaletteAction::NewNote) > boost(&open("a")));
+        assert_eq!(boost(&open("c")), Some(RECENT_BOOST));
+        assert_eq!(boost(&open("d")), None);
+        assert_eq!(boost(&PaletteAction::QuickCapture), None);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_pick_once() {
+        let backend = TempBackend::new();
+        for id in 0..HISTORY_LIMIT + 5 {
+            remember(&backend, &open(&id.to_string()));
+        }
+        remember(&backend, &open("3"));
+        let history = history(&backend);
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history[0], open("3"));
+        assert_eq!(history[1], open(&(HISTORY_LIMIT + 4).to_string()));
+        assert_eq!(history.iter().filter(|picked| **picked == open("3")).count(), 1);
+    }
+}
@@ -0,0 +1,568 @@
+//! A small PDF writer for note export: text blocks in the PDF base fonts, wrapped and
+//! paginated onto A4. The base fonts need no font files, at the price of covering only
+//! WinAnsi (roughly Latin-1); other characters print as `?`.
+
+use std::fmt::Write;
+
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 56.0;
+/// Baseline-to-baseline distance as a multiple of the font size.
+const LINE_HEIGHT: f64 = 1.35;
+
+/// Helvetica advance widths for ASCII 32..=126, in 1/1000 em.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // '0'..'?'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // '@'..'O'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // 'P'..'_'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // '`'..'o'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // 'p'..'~'
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Font {
+    Regular,
+    Bold,
+    Italic,
+    Mono,
+}
+
+impl Font {
+    const ALL: [Font; 4] = [Font::Regular, Font::Bold, Font::Italic, Font::Mono];
+
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Italic => "F3",
+            Font::Mono => "F4",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Italic => "Helvetica-Oblique",
+            Font::Mono => "Courier",
+        }
+    }
+
+    fn char_width(self, c: char, size: f64) -> f64 {
+        let width = match self {
+            Font::Mono => 600.0,
+            _ => {
+                let base = match c as u32 {
+                    code @ 32..=126 => HELVETICA_WIDTHS[(code - 32) as usize] as f64,
+                    _ => 556.0,
+                };
+                // Bold runs a little wider; erring wide only wraps a word early
+                if self == Font::Bold {
+                    base * 1.08
+                } else {
+                    base
+                }
+            }
+        };
+        width * size / 1000.0
+    }
+
+    fn text_width(self, text: &str, size: f64) -> f64 {
+        text.chars().map(|c| self.char_width(c, size)).sum()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub font: Font,
+    /// `\n` forces a line break
+    pub text: String,
+}
+
+/// How a block of text is set.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockStyle {
+    pub size: f64,
+    /// Points from the left margin
+    pub indent: f64,
+    /// Extra space above the block, in points
+    pub space_before: f64,
+}
+
+/// One placed line: runs of text starting at `x`, with its baseline at `y`.
+struct Line {
+    x: f64,
+    y: f64,
+    size: f64,
+    runs: Vec<(Font, String)>,
+}
+
+enum Mark {
+    Text(Line),
+    Rule { y: f64 },
+}
+
+pub struct Document {
+    title: String,
+    pages: Vec<Vec<Mark>>,
+    /// Baseline of the next line on the last page
+    cursor: f64,
+}
+
+/// WinAnsi code for `c`, or `?`.
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '\t' => b' ',
+        _ => b'?',
+    }
+}
+
+impl Document {
+    pub fn new(title: &str) -> Self {
+        Document {
+            title: title.to_string(),
+            pages: vec![Vec::new()],
+            cursor: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Moves down by `height`, starting a new page if that would cross the bottom margin,
+    /// and returns the new baseline.
+    fn advance(&mut self, height: f64) -> f64 {
+        if self.cursor - height < MARGIN {
+            self.pages.push(Vec::new());
+            self.cursor = PAGE_HEIGHT - MARGIN;
+        }
+        self.cursor -= height;
+        self.cursor
+    }
+
+    fn at_page_top(&self) -> bool {
+        self.cursor >= PAGE_HEIGHT - MARGIN
+    }
+
+    fn place(&mut self, mark: Mark) {
+        if let Some(page) = self.pages.last_mut() {
+            page.push(mark);
+        }
+    }
+
+    fn space(&mut self, points: f64) {
+        if !self.at_page_top() {
+            self.cursor -= points;
+        }
+    }
+
+    fn emit_line(&mut self, style: BlockStyle, runs: Vec<(Font, String)>) {
+        let y = self.advance(style.size * LINE_HEIGHT);
+        self.place(Mark::Text(Line {
+            x: MARGIN + style.indent,
+            y,
+            size: style.size,
+            runs,
+        }));
+    }
+
+    /// Sets `spans` as a paragraph, wrapped at word boundaries to the text width. A
+    /// `prefix` (a list bullet, say) starts the first line and later lines hang below
+    /// the text after it.
+    pub fn paragraph(&mut self, spans: &[Span], style: BlockStyle, prefix: Option<Span>) {
+        self.space(style.space_before);
+        let hang = prefix
+            .as_ref()
+            .map(|p| p.font.text_width(&p.text, style.size))
+            .unwrap_or(0.0);
+        let width = PAGE_WIDTH - 2.0 * MARGIN - style.indent;
+        let mut line = Vec::new();
+        let mut used = 0.0;
+        let mut first = true;
+        if let Some(prefix) = prefix {
+            line.push((prefix.font, prefix.text));
+            used = hang;
+        }
+
+        // Sets the current line and starts the next, which hangs under the prefix
+        let mut flush = |doc: &mut Document, line: &mut Vec<(Font, String)>, used: &mut f64| {
+            let indent = if first { 0.0 } else { hang };
+            doc.emit_line(
+                BlockStyle {
+                    indent: style.indent + indent,
+                    ..style
+                },
+                std::mem::take(line),
+            );
+            *used = hang;
+            first = false;
+        };
+
+        let mut line_has_words = false;
+        for span in spans {
+            for (i, segment) in span.text.split('\n').enumerate() {
+                if i > 0 {
+                    flush(self, &mut line, &mut used);
+                    line_has_words = false;
+                }
+                for word in segment.split(' ').filter(|w| !w.is_empty()) {
+                    let mut space = if line_has_words {
+                        span.font.char_width(' ', style.size)
+                    } else {
+                        0.0
+                    };
+                    let mut rest = word;
+                    loop {
+                        let rest_width = span.font.text_width(rest, style.size);
+                        if used + space + rest_width <= width {
+                            if space > 0.0 {
+                                push_run(&mut line, span.font, " ");
+                            }
+                            push_run(&mut line, span.font, rest);
+                            used += space + rest_width;
+                            line_has_words = true;
+                            break;
+                        }
+                        if line_has_words {
+                            flush(self, &mut line, &mut used);
+                            line_has_words = false;
+                            space = 0.0;
+                            continue;
+                        }
+                        // Alone on its line and still too long: break it where it runs out
+                        let mut cut = 0;
+                        let mut cut_width = used;
+                        for (index, c) in rest.char_indices() {
+                            let w = span.font.char_width(c, style.size);
+                            if cut > 0 && cut_width + w > width {
+                                break;
+                            }
+                            cut_width += w;
+                            cut = index + c.len_utf8();
+                        }
+                        push_run(&mut line, span.font, &rest[..cut]);
+                        rest = &rest[cut..];
+                        if rest.is_empty() {
+                            used = cut_width;
+                            line_has_words = true;
+                            break;
+                        }
+                        flush(self, &mut line, &mut used);
+                    }
+                }
+            }
+        }
+        if !line.is_empty() {
+            flush(self, &mut line, &mut used);
+        }
+    }
+
+    /// Sets preformatted lines (code) as they are, breaking only lines too long to fit.
+    pub fn preformatted(&mut self, text: &str, style: BlockStyle) {
+        self.space(style.space_before);
+        let width = PAGE_WIDTH - 2.0 * MARGIN - style.indent;
+        let per_line = ((width / Font::Mono.char_width(' ', style.size)) as usize).max(1);
+        for line in text.trim_end_matches('\n').split('\n') {
+            let chars: Vec<char> = line.replace('\t', "    ").chars().collect();
+            if chars.is_empty() {
+                self.emit_line(style, Vec::new());
+            }
+            for chunk in chars.chunks(per_line) {
+                self.emit_line(style, vec![(Font::Mono, chunk.iter().collect())]);
+            }
+        }
+    }
+
+    /// A horizontal rule across the text width.
+    pub fn rule(&mut self, space_before: f64) {
+        self.space(space_before);
+        let y = self.advance(8.0);
+        self.place(Mark::Rule { y: y + 4.0 });
+    }
+
+    /// The finished PDF file.
+    pub fn render(&self) -> Vec<u8> {
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        // 1: catalog, 2: page tree, 3: info, 4..: fonts, then a page and its contents per page
+        let font_base = 4;
+        let page_base = font_base + Font::ALL.len();
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|i| format!("{} 0 R", page_base + 2 * i))
+            .collect();
+
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} /MediaBox [0 0 {} {}] >>",
+                kids.join(" "),
+                self.pages.len(),
+                PAGE_WIDTH,
+                PAGE_HEIGHT
+            )
+            .into_bytes(),
+        );
+        objects.push(format!("<< /Title {} /Producer (Sticky Notes) >>", utf16_string(&self.title)).into_bytes());
+        for font in Font::ALL {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                    font.base_font()
+                )
+                .into_bytes(),
+            );
+        }
+        let fonts: Vec<String> = Font::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, font)| format!("/{} {} 0 R", font.resource(), font_base + i))
+            .collect();
+
+        for (i, page) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                    fonts.join(" "),
+                    page_base + 2 * i + 1
+                )
+                .into_bytes(),
+            );
+            let stream = page_stream(page);
+            let mut contents = format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+            contents.extend_from_slice(&stream);
+            contents.extend_from_slice(b"\nendstream");
+            objects.push(contents);
+        }
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        out.extend_from_slice(table.as_bytes());
+        out
+    }
+}
+
+/// Appends `text` to the last run if it has the same font.
+fn push_run(runs: &mut Vec<(Font, String)>, font: Font, text: &str) {
+    match runs.last_mut() {
+        Some((last, existing)) if *last == font => existing.push_str(text),
+        _ => runs.push((font, text.to_string())),
+    }
+}
+
+fn hex_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2 + 2);
+    out.push('<');
+    for c in text.chars() {
+        let _ = write!(out, "{:02X}", win_ansi(c));
+    }
+    out.push('>');
+    out
+}
+
+/// A text string outside content streams, which may hold any Unicode as UTF-16BE.
+fn utf16_string(text: &str) -> String {
+    let mut out = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        let _ = write!(out, "{:04X}", unit);
+    }
+    out.push('>');
+    out
+}
+
+fn page_stream(marks: &[Mark]) -> Vec<u8> {
+    let mut out = String::new();
+    for mark in marks {
+        match mark {
+            Mark::Text(line) => {
+                if line.runs.is_empty() {
+                    continue;
+                }
+                let _ = write!(out, "BT {:.2} {:.2} Td", line.x, line.y);
+                for (font, text) in &line.runs {
+                    let _ = write!(out, " /{} {:.2} Tf {} Tj", font.resource(), line.size, hex_string(text));
+                }
+                out.push_str(" ET\n");
+            }
+            Mark::Rule { y } => {
+                let _ = writeln!(
+                    out,
+                    "0.5 w 0.6 G {:.2} {:.2} m {:.2} {:.2} l S 0 G",
+                    MARGIN,
+                    y,
+                    PAGE_WIDTH - MARGIN,
+                    y
+                );
+            }
+        }
+    }
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: BlockStyle = BlockStyle {
+        size: 11.0,
+        indent: 0.0,
+        space_before: 6.0,
+    };
+
+    fn text(words: &str) -> Vec<Span> {
+        vec![Span {
+            font: Font::Regular,
+            text: words.to_string(),
+        }]
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    /// The number after `key` in the trailer or an object dictionary.
+    fn number_after(pdf: &[u8], key: &str) -> usize {
+        let start = find(pdf, key.as_bytes()).unwrap() + key.len();
+        let digits: String = pdf[start..]
+            .iter()
+            .map(|b| *b as char)
+            .skip_while(|c| c.is_whitespace())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().unwrap()
+    }
+
+    /// Checks that the xref table points at every object and that stream lengths hold.
+    fn check_structure(pdf: &[u8]) {
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let xref = number_after(pdf, "startxref\n");
+        assert!(pdf[xref..].starts_with(b"xref\n"));
+
+        let table = String::from_utf8_lossy(&pdf[xref..]).into_owned();
+        let mut lines = table.lines().skip(1);
+        let count: usize = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        assert_eq!(count, number_after(pdf, "/Size "));
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        for object in 1..count {
+            let entry = lines.next().unwrap();
+            assert!(entry.ends_with(" 00000 n "), "{:?}", entry);
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(
+                pdf[offset..].starts_with(format!("{} 0 obj\n", object).as_bytes()),
+                "object {}",
+                object
+            );
+        }
+
+        let mut rest = pdf;
+        while let Some(at) = find(rest, b"/Length ") {
+            let length = number_after(&rest[at..], "/Length ");
+            let start = at + find(&rest[at..], b"stream\n").unwrap() + b"stream\n".len();
+            assert!(rest[start + length..].starts_with(b"\nendstream"));
+            rest = &rest[start + length..];
+        }
+    }
+
+    #[test]
+    fn an_empty_document_is_a_valid_pdf() {
+        let pdf = Document::new("Empty").render();
+        check_structure(&pdf);
+        assert_eq!(number_after(&pdf, "/Count "), 1);
+    }
+
+    #[test]
+    fn long_documents_paginate_with_a_consistent_xref() {
+        let mut doc = Document::new("Long");
+        for i in 0..120 {
+            doc.paragraph(&text(&format!("Paragraph {} with a few words in it.", i)), BODY, None);
+        }
+        doc.preformatted("fn main() {\n\tprintln!(\"hi\");\n}\n", BODY);
+        doc.rule(6.0);
+        assert!(doc.pages.len() > 1);
+
+        let pdf = doc.render();
+        check_structure(&pdf);
+        assert_eq!(number_after(&pdf, "/Count "), doc.pages.len());
+    }
+
+    #[test]
+    fn lines_stay_within_the_margins() {
+        let mut doc = Document::new("Wrap");
+        let long_word = "x".repeat(400);
+        doc.paragraph(
+            &text(&format!("{} {} end", "lorem ipsum dolor ".repeat(40), long_word)),
+            BODY,
+            Some(Span {
+                font: Font::Bold,
+                text: "• ".to_string(),
+            }),
+        );
+        doc.preformatted(&"y".repeat(300), BODY);
+
+        let right = PAGE_WIDTH - MARGIN + 0.001;
+        let mut lines = 0;
+        for mark in doc.pages.iter().flatten() {
+            if let Mark::Text(line) = mark {
+                let width: f64 = line
+                    .runs
+                    .iter()
+                    .map(|(font, text)| font.text_width(text, line.size))
+                    .sum();
+                assert!(line.x + width <= right, "{} + {} runs past the margin", line.x, width);
+                assert!(line.y >= MARGIN);
+                lines += 1;
+            }
+        }
+        assert!(lines > 5);
+        // Nothing was dropped while wrapping
+        let set: String = doc
+            .pages
+            .iter()
+            .flatten()
+            .filter_map(|mark| match mark {
+                Mark::Text(line) => Some(line.runs.iter().map(|(_, t)| t.as_str()).collect::<String>()),
+                Mark::Rule { .. } => None,
+            })
+            .collect();
+        assert_eq!(set.matches('x').count(), 400);
+        assert_eq!(set.matches('y').count(), 300);
+    }
+
+    #[test]
+    fn forced_breaks_start_new_lines() {
+        let mut doc = Document::new("Breaks");
+        doc.paragraph(&text("one\ntwo\nthree"), BODY, None);
+        assert_eq!(doc.pages[0].len(), 3);
+    }
+
+    #[test]
+    fn text_outside_win_ansi_prints_as_question_marks() {
+        assert_eq!(hex_string("A é€—日"), "<4120E980973F>");
+        assert_eq!(utf16_string("Ä日"), "<FEFF00C465E5>");
+    }
+}
@@ -0,0 +1,194 @@
+use tauri::{Manager, Runtime, WebviewWindowBuilder};
+use tauri_plugin_store::StoreExt;
+
+use crate::applock;
+use crate::backend::NotesBackend;
+use crate::events;
+use crate::meta;
+use crate::theme;
+use crate::vault;
+use crate::{notes_dir, read_note_info, NoteInfo, PreviewCache, WindowKind, WindowRegistry};
+
+pub const PINBOARD_LABEL: &str = "pinboard";
+
+/// Logical-pixel geometry, so it survives DPI changes between sessions.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+struct WindowGeometry {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+pub fn get_pinboard_ids<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<String> {
+    app.store("settings.bin")
+        .ok()
+        .and_then(|store| store.get("pinboard_ids"))
+        .and_then(|v| serde_json::from_value::<Vec<String>>(v).ok())
+        .unwrap_or_default()
+}
+
+fn set_pinboard_ids<R: Runtime>(app: &tauri::AppHandle<R>, ids: Vec<String>) -> Result<(), String> {
+    let store = app.store("settings.bin").map_err(|e| e.to_string())?;
+    store.set("pinboard_ids", serde_json::to_value(ids).map_err(|e| e.to_string())?);
+    store.save().map_err(|e| e.to_string())
+}
+
+fn save_geometry<R: Runtime>(window: &tauri::WebviewWindow<R>) {
+    let (Ok(scale), Ok(position), Ok(size)) = (
+        window.scale_factor(),
+        window.outer_position(),
+        window.inner_size(),
+    ) else {
+        return;
+    };
+    let position = position.to_logical::<f64>(scale);
+    let size = size.to_logical::<f64>(scale);
+    let geometry = WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+
+    // Moves arrive per pixel; the store's auto-save debounces the actual disk write
+    if let (Ok(store), Ok(value)) = (window.app_handle().store("session.bin"), serde_json::to_value(geometry)) {
+        store.set("pinboard_geometry", value);
+    }
+}
+
+fn build_pinboard<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<tauri::WebviewWindow<R>, String> {
+    let geometry = app
+        .store("session.bin")
+        .ok()
+        .and_then(|store| store.get("pinboard_geometry"))
+        .and_then(|v| serde_json::from_value::<WindowGeometry>(v).ok());
+
+    let mut builder = WebviewWindowBuilder::new(app, PINBOARD_LABEL, tauri::WebviewUrl::App("index.html".into()))
+        .title("Pinboard")
+        .resizable(true)
+        .decorations(false)
+        .transparent(true)
+        .theme(theme::native(app))
+        .skip_taskbar(false)
+        .visible(false);
+    builder = match geometry {
+        Some(g) => builder.position(g.x, g.y).inner_size(g.width, g.height),
+        None => builder.inner_size(640.0, 480.0),
+    };
+    let window = builder.build().map_err(|e| e.to_string())?;
+
+    if let Ok(mut registry) = app.state::<WindowRegistry>().0.write() {
+        registry.insert(PINBOARD_LABEL.to_string(), WindowKind::Utility);
+    }
+
+    let window_for_events = window.clone();
+    window.on_window_event(move |event| match event {
+        // Like the dashboard, closing only hides so the pinboard can come back instantly
+        tauri::WindowEvent::CloseRequested { api, .. } => {
+            save_geometry(&window_for_events);
+            let _ = window_for_events.hide();
+            api.prevent_close();
+        }
+        tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => {
+            save_geometry(&window_for_events);
+        }
+        tauri::WindowEvent::Destroyed => {
+            if let Ok(mut registry) = window_for_events.app_handle().state::<WindowRegistry>().0.write() {
+                registry.remove(PINBOARD_LABEL);
+            }
+            events::unsubscribe(window_for_events.app_handle(), PINBOARD_LABEL);
+        }
+        _ => {}
+    });
+
+    Ok(window)
+}
+
+/// Shows the pinboard (creating it on first use) or hides it if it is already visible.
+/// Returns whether the pinboard is visible afterwards.
+pub fn toggle_pinboard_window<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<bool, String> {
+    applock::ensure_unlocked(app)?;
+    if let Some(window) = app.get_webview_window(PINBOARD_LABEL) {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+            return Ok(false);
+        }
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+        return Ok(true);
+    }
+
+    let window = build_pinboard(app)?;
+    let _ = window.show();
+    let _ = window.set_focus();
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn toggle_pinboard(app: tauri::AppHandle) -> Result<bool, String> {
+    toggle_pinboard_window(&app)
+}
+
+/// Notes shown on the pinboard, in the order they were added.
+#[tauri::command]
+pub async fn get_pinboard_notes(app: tauri::AppHandle) -> Result<Vec<NoteInfo>, String> {
+    let dir = notes_dir(&app)?;
+    let cache = app.state::<PreviewCache>();
+    let vault_key = vault::key(&app);
+    let metas = meta::load_all(&app);
+    Ok(get_pinboard_ids(&app)
+        .into_iter()
+        .filter_map(|id| {
+            let path = dir.join(format!("{}.md", id));
+            path.is_file().then(|| read_note_info(&cache, vault_key.as_ref(), &metas, id, &path))
+        })
+        .collect())
+}
+
+/// Keeps the pinboard pointing at a note whose id changed.
+pub fn rename_pinboard_id<R: Runtime>(app: &tauri::AppHandle<R>, old: &str, new: &str) -> Result<(), String> {
+    let ids = get_pinboard_ids(app);
+    if !ids.iter().any(|id| id == old) {
+        return Ok(());
+    }
+    set_pinboard_ids(
+        app,
+        ids.into_iter()
+            .map(|id| if id == old { new.to_string() } else { id })
+            .collect(),
+    )
+}
+
+pub fn add_id<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    let mut ids = get_pinboard_ids(app);
+    if !ids.iter().any(|existing| existing == id) {
+        ids.push(id.to_string());
+        set_pinboard_ids(app, ids)?;
+    }
+    Ok(())
+}
+
+pub fn remove_id<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    let mut ids = get_pinboard_ids(app);
+    if ids.iter().any(|existing| existing == id) {
+        ids.retain(|existing| existing != id);
+        set_pinboard_ids(app, ids)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_to_pinboard(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    add_id(&app, &id)?;
+    app.emit_event("pinboard-changed", ());
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_from_pinboard(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    remove_id(&app, &id)?;
+    app.emit_event("pinboard-changed", ());
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! The quick-capture popup: a small frameless input that opens at the cursor, turns what
+//! is typed into a new note and goes away, without opening a note window. Losing focus
+//! only hides it, so a half-typed thought is still there the next time it is opened.
+
+use tauri::{Manager, Runtime, WebviewWindowBuilder};
+use uuid::Uuid;
+
+use crate::applock;
+use crate::backend::NotesBackend;
+use crate::capture::at_cursor;
+use crate::events;
+use crate::theme;
+use crate::{write_note, WindowKind, WindowRegistry};
+
+pub const QUICK_CAPTURE_LABEL: &str = "quick-capture";
+/// Logical size of the popup: one line of input with some padding.
+const POPUP_SIZE: (f64, f64) = (420.0, 64.0);
+
+fn build_popup<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<tauri::WebviewWindow<R>, String> {
+    let mut builder = WebviewWindowBuilder::new(app, QUICK_CAPTURE_LABEL, tauri::WebviewUrl::App("index.html".into()))
+        .title("Quick capture")
+        .resizable(false)
+        .decorations(false)
+        .transparent(true)
+        .theme(theme::native(app))
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false);
+    builder = match at_cursor(app, POPUP_SIZE) {
+        Some(rect) => builder.position(rect.x, rect.y).inner_size(rect.width, rect.height),
+        None => builder.inner_size(POPUP_SIZE.0, POPUP_SIZE.1).center(),
+    };
+    let window = builder.build().map_err(|e| e.to_string())?;
+
+    if let Ok(mut registry) = app.state::<WindowRegistry>().0.write() {
+        registry.insert(QUICK_CAPTURE_LABEL.to_string(), WindowKind::Utility);
+    }
+
+    let window_for_events = window.clone();
+    window.on_window_event(move |event| match event {
+        tauri::WindowEvent::Focused(false) => {
+            let _ = window_for_events.hide();
+        }
+        tauri::WindowEvent::Destroyed => {
+            if let Ok(mut registry) = window_for_events.app_handle().state::<WindowRegistry>().0.write() {
+                registry.remove(QUICK_CAPTURE_LABEL);
+            }
+            events::unsubscribe(window_for_events.app_handle(), QUICK_CAPTURE_LABEL);
+        }
+        _ => {}
+    });
+
+    Ok(window)
+}
+
+/// Shows the popup at the cursor, creating it on first use.
+pub fn show_popup<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
+    applock::ensure_unlocked(app)?;
+    let window = match app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        Some(window) => {
+            if let Some(rect) = at_cursor(app, POPUP_SIZE) {
+                let _ = window.set_position(tauri::LogicalPosition::new(rect.x, rect.y));
+            }
+            window
+        }
+        None => build_popup(app)?,
+    };
+    let _ = window.show();
+    let _ = window.set_focus();
+    Ok(())
+}
+
+fn close_popup<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        let _ = window.close();
+    }
+}
+
+#[tauri::command]
+pub async fn quick_capture(app: tauri::AppHandle) -> Result<(), String> {
+    show_popup(&app)
+}
+
+/// Saves `text` as a new note and closes the popup. The note isn't opened; it shows up
+/// on the dashboard. Blank text just closes the popup. Returns the new note's id.
+#[tauri::command]
+pub async fn submit_quick_capture(text: String, app: tauri::AppHandle) -> Result<Option<String>, String> {
+    if text.trim().is_empty() {
+        close_popup(&app);
+        return Ok(None);
+    }
+    let id = Uuid::new_v4().to_string();
+    // On failure the popup stays open with the text, so nothing typed is lost
+    write_note(&app, &id, &text)?;
+    close_popup(&app);
+    app.emit_event("refresh-notes", ());
+    Ok(Some(id))
+}
+
+/// Closes the popup, dropping whatever was typed.
+#[tauri::command]
+pub async fn cancel_quick_capture(app: tauri::AppHandle) -> Result<(), String> {
+    close_popup(&app);
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{Manager, Runtime};
+use uuid::Uuid;
+
+use crate::annotations;
+use crate::backend::NotesBackend;
+use crate::changes::own_change;
+use crate::meta;
+use crate::notewindow::NoteWindowOptions;
+use crate::rekey::note_paths;
+use crate::safepath::{Root, SafePath};
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::settings;
+use crate::vault;
+use crate::{create_note_window, notes_dir, read_note_info, NoteInfo, PreviewCache};
+
+/// Characters returned by `peek_recycled`.
+const PEEK_CHARS: usize = 2000;
+/// How long trashed notes are kept before the startup purge removes them, unless
+/// the `trash_retention_days` setting says otherwise (0 keeps them forever).
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+/// Deletion timestamps live next to the recycled files; the leading dot keeps the scanner away.
+const INDEX_FILE: &str = ".index.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RecycledKind {
+    Trash,
+    Archive,
+}
+
+#[derive(serde::Serialize)]
+pub struct RecycledNoteInfo {
+    #[serde(flatten)]
+    info: NoteInfo,
+    deleted_at: Option<u64>,
+}
+
+impl RecycledKind {
+    fn root(self) -> Root {
+        match self {
+            RecycledKind::Trash => Root::Trash,
+            RecycledKind::Archive => Root::Archive,
+        }
+    }
+}
+
+pub fn recycled_dir(backend: &impl NotesBackend, kind: RecycledKind) -> Result<PathBuf, String> {
+    kind.root().dir(backend)
+}
+
+fn read_index(dir: &Path) -> HashMap<String, u64> {
+    fs::read_to_string(dir.join(INDEX_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_index(dir: &Path, index: &HashMap<String, u64>) -> Result<(), String> {
+    let json = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    fs::write(dir.join(INDEX_FILE), json).map_err(|e| e.to_string())
+}
+
+fn validate_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(format!("Invalid note id: {}", id));
+    }
+    Ok(())
+}
+
+/// `move_to_recycled` apart from the preview cache. Returns the paths moved from and to.
+fn move_files(backend: &impl NotesBackend, kind: RecycledKind, id: &str) -> Result<Vec<PathBuf>, String> {
+    validate_id(id)?;
+    let dir = recycled_dir(backend, kind)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut moved = Vec::new();
+    for (from, to) in note_paths(&notes_dir(backend)?, id)
+        .into_iter()
+        .zip(note_paths(&dir, id))
+    {
+        if from.exists() {
+            let to = SafePath::new(backend, kind.root(), to)?;
+            // A previous recycle of the same id is superseded
+            if to.path().is_dir() {
+                let _ = to.remove();
+            }
+            let from = SafePath::new(backend, Root::Notes, from)?;
+            backend.own_change(&[id], || from.rename_to(&to))?;
+            moved.push(from.path().to_path_buf());
+            moved.push(to.path().to_path_buf());
+        }
+    }
+
+    let mut index = read_index(&dir);
+    index.insert(id.to_string(), crate::now_millis());
+    write_index(&dir, &index)?;
+
+    backend.emit_event("recycled-changed", kind);
+    Ok(moved)
+}
+
+/// Moves a note's file, sidecar and asset directory from the live notes directory into
+/// the trash or archive, recording when it happened.
+pub fn move_to_recycled<R: Runtime>(app: &tauri::AppHandle<R>, kind: RecycledKind, id: &str) -> Result<(), String> {
+    // The rename keeps the mtime, so an entry for an earlier recycle of this id could pass
+    let cache = app.state::<PreviewCache>();
+    for path in move_files(app, kind, id)? {
+        cache.remove(&path);
+    }
+    Ok(())
+}
+
+/// Puts `content` into the trash or archive as a note of its own under a fresh id, for
+/// versions that never were a live note (e.g. the losing side of a sync conflict).
+pub fn recycle_content<R: Runtime>(app: &tauri::AppHandle<R>, kind: RecycledKind, content: &str) -> Result<String, String> {
+    let dir = recycled_dir(app, kind)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let id = Uuid::new_v4().to_string();
+    fs::write(dir.join(format!("{}.md", id)), vault::encode(app, content)?).map_err(|e| e.to_string())?;
+
+    let mut index = read_index(&dir);
+    index.insert(id.clone(), crate::now_millis());
+    write_index(&dir, &index)?;
+
+    app.emit_event("recycled-changed", kind);
+    Ok(id)
+}
+
+/// Lists trashed or archived notes, most recently recycled first. `text_filter` matches the
+/// preview only unless `deep` is set, in which case full contents are searched. Annotations
+/// are only searched with `include_annotations`.
+#[tauri::command]
+pub async fn query_recycled(
+    kind: RecycledKind,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    text_filter: Option<String>,
+    deep: Option<bool>,
+    include_annotations: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<Vec<RecycledNoteInfo>, String> {
+    let dir = recycled_dir(&app, kind)?;
+    let index = read_index(&dir);
+    let needle = text_filter
+        .map(|f| f.trim().to_lowercase())
+        .filter(|f| !f.is_empty());
+    let deep = deep.unwrap_or(false);
+    let include_annotations = include_annotations.unwrap_or(false);
+    let cache = app.state::<PreviewCache>();
+    let vault_key = vault::key(&app);
+    let metas = meta::load_all(&app);
+    let annotated = |id: &str, needle: &str| {
+        include_annotations && metas.get(id).is_some_and(|m| annotations::matches(m, needle))
+    };
+
+    let mut notes: Vec<RecycledNoteInfo> = scan_notes(&dir, ScanOptions::default())
+        .filter_map(|entry| match entry {
+            ScanEntry::Note { id, path, .. } => Some((id, path)),
+            _ => None,
+        })
+        .filter(|(id, path)| match (&needle, deep) {
+            (Some(needle), true) => {
+                annotated(id, needle)
+                    || vault::read_file(&app, path)
+                        .map(|content| content.to_lowercase().contains(needle))
+                        .unwrap_or(false)
+            }
+            _ => true,
+        })
+        .map(|(id, path)| RecycledNoteInfo {
+            deleted_at: index.get(&id).copied(),
+            info: read_note_info(&cache, vault_key.as_ref(), &metas, id, &path),
+        })
+        .filter(|note| match (&needle, deep) {
+            (Some(needle), false) => {
+                note.info.preview.to_lowercase().contains(needle) || annotated(&note.info.id, needle)
+            }
+            _ => true,
+        })
+        .collect();
+
+    notes.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then_with(|| a.info.id.cmp(&b.info.id))
+    });
+
+    Ok(notes
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect())
+}
+
+/// A bounded look at a recycled note without restoring it.
+#[tauri::command]
+pub async fn peek_recycled(kind: RecycledKind, id: String, app: tauri::AppHandle) -> Result<String, String> {
+    validate_id(&id)?;
+    let path = recycled_dir(&app, kind)?.join(format!("{}.md", id));
+    let content = vault::read_file(&app, &path)?;
+    Ok(content.chars().take(PEEK_CHARS).collect())
+}
+
+/// Moves a recycled note back into the live notes directory and returns its id, which is
+/// a fresh one if the original id has been reused by a live note in the meantime.
+#[tauri::command]
+pub async fn restore_recycled(
+    kind: RecycledKind,
+    id: String,
+    reopen_window: bool,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    validate_id(&id)?;
+    let dir = recycled_dir(&app, kind)?;
+    let live = notes_dir(&app)?;
+    let sources = note_paths(&dir, &id);
+    if !sources[0].is_file() {
+        return Err(format!("Note {} is not in the {:?}", id, kind));
+    }
+
+    let final_id = if note_paths(&live, &id).iter().any(|p| p.exists()) {
+        Uuid::new_v4().to_string()
+    } else {
+        id.clone()
+    };
+
+    fs::create_dir_all(&live).map_err(|e| e.to_string())?;
+    for (from, to) in sources.into_iter().zip(note_paths(&live, &final_id)) {
+        if from.exists() {
+            let (from, to) = (SafePath::new(&app, kind.root(), from)?, SafePath::new(&app, Root::Notes, to)?);
+            own_change(&app, &[&final_id], || from.rename_to(&to))?;
+        }
+    }
+
+    if final_id != id {
+        // The live note under `id` keeps its metadata; the restored one gets a copy
+        let restored = meta::NoteMeta {
+            follow: false,
+            ..meta::get_meta(&app, &id)
+        };
+        meta::update_meta(&app, &final_id, |meta| *meta = restored)?;
+    }
+
+    let mut index = read_index(&dir);
+    index.remove(&id);
+    write_index(&dir, &index)?;
+
+    if reopen_window {
+        if let Err(e) = create_note_window(&app, NoteWindowOptions::open(final_id.clone())) {
+            println!("Restored {} but could not open its window: {}", final_id, e);
+        }
+    }
+
+    app.emit_event("recycled-changed", kind);
+    app.emit_event("refresh-notes", ());
+    Ok(final_id)
+}
+
+/// Archived notes, most recently archived first; a shorthand for `query_recycled`.
+#[tauri::command]
+pub async fn list_archived_notes(
+    text_filter: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Vec<RecycledNoteInfo>, String> {
+    query_recycled(RecycledKind::Archive, None, None, text_filter, None, None, app).await
+}
+
+/// Brings an archived note back to the dashboard and returns its id (see `restore_recycled`).
+#[tauri::command]
+pub async fn unarchive_note(id: String, reopen_window: Option<bool>, app: tauri::AppHandle) -> Result<String, String> {
+    restore_recycled(RecycledKind::Archive, id, reopen_window.unwrap_or(false), app).await
+}
+
+fn purge_one<R: Runtime>(app: &tauri::AppHandle<R>, kind: RecycledKind, dir: &Path, id: &str) -> Result<(), String> {
+    for path in note_paths(dir, id).into_iter().filter(|p| p.exists()) {
+        SafePath::new(app, kind.root(), path)?.remove()?;
+    }
+    // Metadata stays with a trashed note for its restore; a live note may own the id by now
+    if !note_paths(&notes_dir(app)?, id).iter().any(|p| p.exists()) {
+        meta::remove_meta(app, id);
+    }
+    Ok(())
+}
+
+/// Permanently removes a recycled note and everything belonging to it.
+#[tauri::command]
+pub async fn purge_recycled(kind: RecycledKind, id: String, app: tauri::AppHandle) -> Result<(), String> {
+    validate_id(&id)?;
+    let dir = recycled_dir(&app, kind)?;
+    purge_one(&app, kind, &dir, &id)?;
+
+    let mut index = read_index(&dir);
+    index.remove(&id);
+    write_index(&dir, &index)?;
+
+    app.emit_event("recycled-changed", kind);
+    Ok(())
+}
+
+/// Records `now` as the deletion time of every note in `dir` the index has none for (put
+/// there by hand, or by a version that didn't keep the index). Returns whether any was added.
+fn stamp_unrecorded(dir: &Path, index: &mut HashMap<String, u64>, now: u64) -> bool {
+    let mut added = false;
+    for entry in scan_notes(dir, ScanOptions::default()) {
+        if let ScanEntry::Note { id, .. } = entry {
+            if let Entry::Vacant(slot) = index.entry(id) {
+                slot.insert(now);
+                added = true;
+            }
+        }
+    }
+    added
+}
+
+/// Purges trashed notes deleted more than `days` days ago and returns how many. Notes
+/// without a recorded deletion time count as deleted now: they are stamped with the
+/// current time and go once that is `days` old.
+fn purge_trash_older_than<R: Runtime>(app: &tauri::AppHandle<R>, days: u64) -> Result<usize, String> {
+    let kind = RecycledKind::Trash;
+    let dir = recycled_dir(app, kind)?;
+    let mut index = read_index(&dir);
+    if dir.is_dir() && stamp_unrecorded(&dir, &mut index, crate::now_millis()) {
+        write_index(&dir, &index)?;
+    }
+    let cutoff = crate::now_millis().saturating_sub(days.saturating_mul(DAY_MS));
+    let expired: Vec<String> = index
+        .iter()
+        .filter(|(_, deleted_at)| **deleted_at < cutoff)
+        .map(|(id, _)| id.clone())
+        .collect();
+    if expired.is_empty() {
+        return Ok(0);
+    }
+
+    let mut purged = 0;
+    for id in &expired {
+        match purge_one(app, kind, &dir, id) {
+            Ok(()) => {
+                index.remove(id);
+                purged += 1;
+            }
+            Err(e) => println!("Failed to purge trashed note {}: {}", id, e),
+        }
+    }
+    write_index(&dir, &index)?;
+    app.emit_event("recycled-changed", kind);
+    Ok(purged)
+}
+
+/// Empties the trash of notes deleted more than `older_than_days` days ago.
+#[tauri::command]
+pub async fn purge_trash(older_than_days: u64, app: tauri::AppHandle) -> Result<usize, String> {
+    purge_trash_older_than(&app, older_than_days)
+}
+
+/// Applies `trash_retention_days` once at startup.
+pub fn spawn_trash_purge<R: Runtime>(app: tauri::AppHandle<R>) {
+    let days = settings::current(&app).trash_retention_days;
+    if days == 0 {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        match purge_trash_older_than(&app, days) {
+            Ok(0) => {}
+            Ok(purged) => println!("Purged {} notes trashed more than {} days ago", purged, days),
+            Err(e) => println!("Trash purge failed: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    /// Note `id` with a sidecar and an attachment; returns the notes dir.
+    fn put_note_with_assets(backend: &TempBackend, id: &str, content: &str) -> PathBuf {
+        let notes = backend.put_note(id, content).parent().unwrap().to_path_buf();
+        fs::write(notes.join(format!("{}.meta.json", id)), "{}").unwrap();
+        fs::create_dir_all(notes.join(id)).unwrap();
+        fs::write(notes.join(id).join(format!("{}.png", content)), [0u8; 4]).unwrap();
+        notes
+    }
+
+    #[test]
+    fn trashing_moves_every_file_of_the_note() {
+        let backend = TempBackend::new();
+        let notes = put_note_with_assets(&backend, "a", "Alpha");
+
+        move_files(&backend, RecycledKind::Trash, "a").unwrap();
+
+        let trash = recycled_dir(&backend, RecycledKind::Trash).unwrap();
+        assert!(note_paths(&notes, "a").iter().all(|p| !p.exists()));
+        assert!(note_paths(&trash, "a").iter().all(|p| p.exists()));
+        assert_eq!(fs::read_to_string(trash.join("a.md")).unwrap(), "Alpha");
+        assert!(read_index(&trash).contains_key("a"));
+        assert_eq!(backend.own_changes(), ["a", "a", "a"]);
+        let events = backend.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "recycled-changed");
+        assert_eq!(events[0].note, None);
+        assert_eq!(events[0].payload, serde_json::json!("trash"));
+    }
+
+    #[test]
+    fn trashing_an_id_again_supersedes_the_earlier_copy() {
+        let backend = TempBackend::new();
+        put_note_with_assets(&backend, "a", "Alpha");
+        move_files(&backend, RecycledKind::Trash, "a").unwrap();
+        put_note_with_assets(&backend, "a", "Again");
+        move_files(&backend, RecycledKind::Trash, "a").unwrap();
+
+        let trash = recycled_dir(&backend, RecycledKind::Trash).unwrap();
+        assert_eq!(fs::read_to_string(trash.join("a.md")).unwrap(), "Again");
+        assert!(trash.join("a").join("Again.png").exists());
+        assert!(!trash.join("a").join("Alpha.png").exists());
+    }
+
+    #[test]
+    fn archive_and_trash_are_kept_apart() {
+        let backend = TempBackend::new();
+        backend.put_note("a", "Alpha");
+        backend.put_note("b", "Beta");
+        move_files(&backend, RecycledKind::Trash, "a").unwrap();
+        move_files(&backend, RecycledKind::Archive, "b").unwrap();
+
+        let trash = recycled_dir(&backend, RecycledKind::Trash).unwrap();
+        let archive = recycled_dir(&backend, RecycledKind::Archive).unwrap();
+        assert!(trash.join("a.md").exists() && !trash.join("b.md").exists());
+        assert!(archive.join("b.md").exists() && !archive.join("a.md").exists());
+        assert_eq!(read_index(&archive).into_keys().collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn invalid_ids_are_refused() {
+        let backend = TempBackend::new();
+        backend.put_note("a", "Alpha");
+        for id in ["", ".index", "../a", "x/../../a", "a\\b"] {
+            assert!(move_files(&backend, RecycledKind::Trash, id).is_err(), "{:?}", id);
+        }
+        assert!(backend.notes_dir().unwrap().join("a.md").exists());
+        assert!(backend.event_names().is_empty());
+    }
+
+    #[test]
+    fn notes_without_a_deletion_time_are_stamped_now() {
+        let backend = TempBackend::new();
+        put_note_with_assets(&backend, "a", "Alpha");
+        move_files(&backend, RecycledKind::Trash, "a").unwrap();
+        let trash = recycled_dir(&backend, RecycledKind::Trash).unwrap();
+        fs::write(trash.join("b.md"), "Dropped in by hand").unwrap();
+
+        let mut index = read_index(&trash);
+        let trashed_at = index["a"];
+        assert!(stamp_unrecorded(&trash, &mut index, trashed_at + 1));
+        let expected = HashMap::from([("a".to_string(), trashed_at), ("b".to_string(), trashed_at + 1)]);
+        assert_eq!(index, expected);
+        assert!(!stamp_unrecorded(&trash, &mut index, trashed_at + 2));
+    }
+}
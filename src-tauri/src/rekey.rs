@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::changes::own_change;
+use crate::encryption;
+use crate::links;
+use crate::localstate::rename_local_state;
+use crate::meta::{self, Rect};
+use crate::noteindex::{self, IndexEntry};
+use crate::notewindow::NoteWindowOptions;
+use crate::pinboard::rename_pinboard_id;
+use crate::safepath::{Root, SafePath};
+use crate::scan::is_valid_note_id;
+use crate::usage::record_usage;
+use crate::{create_note_window, get_session_order, notes_dir, read_note, write_note};
+
+#[derive(serde::Serialize, Clone)]
+struct NoteRekeyed {
+    old: String,
+    new: String,
+}
+
+/// Everything on disk that belongs to a note, whether or not it exists yet.
+pub fn note_paths(dir: &Path, id: &str) -> [PathBuf; 3] {
+    [
+        dir.join(format!("{}.md", id)),
+        dir.join(format!("{}.meta.json", id)),
+        // Asset directory, which also holds the note's history
+        dir.join(id),
+    ]
+}
+
+/// Renames every `(from, to)` pair in order, undoing the completed ones if any step fails.
+fn rename_all(moves: &[(SafePath, SafePath)]) -> Result<(), String> {
+    for (done, (from, to)) in moves.iter().enumerate() {
+        if let Err(e) = from.rename_to(to) {
+            for (undo_from, undo_to) in moves[..done].iter().rev() {
+                if let Err(undo_err) = undo_to.rename_to(undo_from) {
+                    println!("Rekey rollback failed for {:?}: {}", undo_to.path(), undo_err);
+                }
+            }
+            return Err(format!("Failed to rename {:?}: {}", from.path(), e));
+        }
+    }
+    Ok(())
+}
+
+fn rename_in_session_order<R: Runtime>(app: &tauri::AppHandle<R>, old: &str, new: &str) {
+    let order: Vec<String> = get_session_order(app)
+        .into_iter()
+        .map(|id| if id == old { new.to_string() } else { id })
+        .collect();
+    if let Ok(store) = app.store("session.bin") {
+        store.set("open_notes", serde_json::to_value(order).unwrap());
+        let _ = store.save();
+    }
+}
+
+/// New content for every note with a `[[old]]` link, read after the rename (so a note
+/// linking to itself is found under `new`). Locked notes are left alone: their links
+/// aren't indexed and their text can't be rewritten without the password.
+fn repointed_links(
+    backend: &impl NotesBackend,
+    entries: &HashMap<String, IndexEntry>,
+    old: &str,
+    new: &str,
+) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .filter(|(_, entry)| entry.links().iter().any(|target| target == old))
+        .map(|(id, _)| if id == old { new.to_string() } else { id.clone() })
+        .filter_map(|id| {
+            let content = read_note(backend, &id).ok()?;
+            if encryption::is_locked(&content) {
+                return None;
+            }
+            Some((id, links::repoint(&content, old, new)?))
+        })
+        .collect()
+}
+
+/// Moves an open note window over to its new label, keeping geometry, pin and visibility.
+fn reopen_window<R: Runtime>(app: &tauri::AppHandle<R>, old: &str, new: &str) {
+    let Some(window) = app.get_webview_window(&format!("note-{}", old)) else {
+        return;
+    };
+    let geometry = match (window.scale_factor(), window.outer_position(), window.inner_size()) {
+        (Ok(scale), Ok(position), Ok(size)) => {
+            let position = position.to_logical::<f64>(scale);
+            let size = size.to_logical::<f64>(scale);
+            Some(Rect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            })
+        }
+        _ => None,
+    };
+    let pinned = window.is_always_on_top().unwrap_or(false);
+    let visible = window.is_visible().unwrap_or(true);
+    let _ = window.close();
+
+    let mut options = NoteWindowOptions::restore(new).pinned(pinned);
+    if let Some(rect) = geometry {
+        options = options.geometry(rect);
+    }
+    match create_note_window(app, options) {
+        Ok(window) => {
+            if visible {
+                let _ = window.show();
+            }
+        }
+        Err(e) => println!("Rekey: failed to reopen window for {}: {}", new, e),
+    }
+}
+
+/// Gives a note a new id (a fresh UUID unless `new_id` is supplied) and returns it.
+/// Files are renamed first and rolled back completely if any rename fails; only then
+/// are the session order, pinboard, `[[old-id]]` links and open window pointed at the
+/// new id.
+#[tauri::command]
+pub async fn rekey_note(
+    old_id: String,
+    new_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    // The old id may be a sync conflict copy like `abc (1)`, so only reject path tricks
+    if old_id.is_empty() || old_id.starts_with('.') || old_id.contains(['/', '\\']) {
+        return Err(format!("Invalid note id: {}", old_id));
+    }
+    let new_id = match new_id {
+        Some(id) if !is_valid_note_id(&id) => return Err(format!("Invalid note id: {}", id)),
+        Some(id) => id,
+        None => Uuid::new_v4().to_string(),
+    };
+    if new_id == old_id {
+        return Ok(new_id);
+    }
+    record_usage(&app, "rekey");
+
+    let dir = notes_dir(&app)?;
+    let sources = note_paths(&dir, &old_id);
+    let targets = note_paths(&dir, &new_id);
+    if !sources[0].is_file() {
+        return Err(format!("Note {} does not exist", old_id));
+    }
+    if targets.iter().any(|p| p.exists()) {
+        return Err(format!("Note {} already exists", new_id));
+    }
+
+    let moves = sources
+        .into_iter()
+        .zip(targets)
+        .filter(|(from, _)| from.exists())
+        .map(|(from, to)| {
+            Ok((
+                SafePath::new(&app, Root::Notes, from)?,
+                SafePath::new(&app, Root::Notes, to)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let entries = noteindex::listing(&app)?;
+    own_change(&app, &[&old_id, &new_id], || rename_all(&moves))?;
+
+    for (id, content) in repointed_links(&app, &entries, &old_id, &new_id) {
+        if let Err(e) = write_note(&app, &id, &content) {
+            println!("Rekey: failed to repoint links in {}: {}", id, e);
+        }
+    }
+
+    rename_in_session_order(&app, &old_id, &new_id);
+    meta::rename_meta(&app, &old_id, &new_id);
+    rename_local_state(&app, &old_id, &new_id);
+    if let Err(e) = rename_pinboard_id(&app, &old_id, &new_id) {
+        println!("Rekey: failed to update pinboard: {}", e);
+    }
+
+    // Tell frontends before the window swap so none of them saves under the old id
+    app.emit_event(
+        "note-rekeyed",
+        NoteRekeyed {
+            old: old_id.clone(),
+            new: new_id.clone(),
+        },
+    );
+    reopen_window(&app, &old_id, &new_id);
+    app.emit_event("refresh-notes", ());
+
+    Ok(new_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempBackend;
+
+    #[test]
+    fn links_by_id_follow_the_rekey() {
+        let backend = TempBackend::new();
+        backend.put_note("a", "# A\nSee [[old|the plan]] and [[Plan]]");
+        backend.put_note("b", "# B\nNothing here");
+        backend.put_note("old", "# Plan\nBack to [[old]]");
+        let dir = backend.notes_dir().unwrap();
+        let entries = noteindex::read_entries(&dir, None, &HashMap::new()).unwrap().0;
+        std::fs::rename(dir.join("old.md"), dir.join("new.md")).unwrap();
+
+        let mut repointed = repointed_links(&backend, &entries, "old", "new");
+        repointed.sort();
+        assert_eq!(
+            repointed,
+            [
+                ("a".to_string(), "# A\nSee [[new|the plan]] and [[Plan]]".to_string()),
+                ("new".to_string(), "# Plan\nBack to [[new]]".to_string()),
+            ]
+        );
+    }
+}
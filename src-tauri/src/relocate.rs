@@ -0,0 +1,193 @@
+//! A notes folder chosen by the user (settings.bin `notes_directory`), e.g. inside
+//! Dropbox or Syncthing, instead of `notes/` in the app data dir. Every command resolves
+//! the folder through `notes_dir`, so switching only has to move the files and restart
+//! what watches them.
+//!
+//! Moving copies the note files and asset folders first, switches the setting, and only
+//! then removes the originals, so a failure part way leaves the old folder in use and
+//! intact. Note metadata, trash, archive and the other stores stay in the app data dir.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::cache::PreviewCache;
+use crate::changes;
+use crate::flush::flush_now;
+use crate::noteindex;
+use crate::safepath::{copy_recursively, Root, SafePath};
+use crate::storage::{self, ensure_writes_allowed, StorageAccess};
+
+const SETTING_KEY: &str = "notes_directory";
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct NotesDirectory {
+    path: String,
+    /// Whether the user chose it, rather than it being the default under the app data dir
+    custom: bool,
+}
+
+/// The folder the user chose, if any.
+pub fn configured(backend: &impl NotesBackend) -> Option<PathBuf> {
+    backend
+        .read_store("settings.bin", SETTING_KEY)
+        .and_then(|v| v.as_str().map(PathBuf::from))
+        .filter(|path| path.is_absolute())
+}
+
+fn default_dir<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("notes"))
+}
+
+/// What a relocation would move: everything in the notes folder except hidden entries
+/// (temp files, write probes), but with the Git repository if there is one.
+fn movable_entries(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') || name == ".git" || name == ".gitignore" {
+            entries.push(entry.path());
+        }
+    }
+    Ok(entries)
+}
+
+/// Checks `target` can take over from `current` and returns it canonicalized. It is
+/// created if missing.
+fn validate_target<R: Runtime>(app: &tauri::AppHandle<R>, current: &Path, target: &Path) -> Result<PathBuf, String> {
+    if !target.is_absolute() {
+        return Err(format!("{:?} is not an absolute path", target));
+    }
+    if target.exists() && !target.is_dir() {
+        return Err(format!("{:?} exists and is not a folder", target));
+    }
+    if storage::probe_access(target) != StorageAccess::Writable {
+        return Err(format!("{:?} can't be written to", target));
+    }
+    let target = target.canonicalize().map_err(|e| e.to_string())?;
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let data_dir = data_dir.canonicalize().unwrap_or(data_dir);
+    let current = current.canonicalize().unwrap_or_else(|_| current.to_path_buf());
+
+    if target == current {
+        return Err("That is already the notes folder".to_string());
+    }
+    if target.starts_with(&current) || current.starts_with(&target) {
+        return Err("The new notes folder can't contain, or be inside, the current one".to_string());
+    }
+    // Reverting to the default is the only place under the app data dir that makes sense
+    let default = default_dir(app)?;
+    let is_default = default.canonicalize().is_ok_and(|default| default == target);
+    if data_dir.starts_with(&target) || (target.starts_with(&data_dir) && !is_default) {
+        return Err("The notes folder can't contain, or be inside, the app's data folder".to_string());
+    }
+    Ok(target)
+}
+
+/// Removes the copies of `entries` from `target` again.
+fn remove_copies(entries: &[PathBuf], target: &Path) {
+    for name in entries.iter().filter_map(|from| from.file_name()) {
+        if let Ok(copy) = SafePath::in_former_root(Root::Notes, target, target.join(name)) {
+            let _ = copy.remove();
+        }
+    }
+}
+
+/// Copies `entries` into `target`, undoing the copy if any of them fails.
+fn copy_all(entries: &[PathBuf], target: &Path) -> Result<(), String> {
+    for (done, from) in entries.iter().enumerate() {
+        let Some(name) = from.file_name() else {
+            continue;
+        };
+        if let Err(e) = copy_recursively(from, &target.join(name)) {
+            remove_copies(&entries[..=done], target);
+            return Err(format!("Copying {:?} failed: {}", from, e));
+        }
+    }
+    Ok(())
+}
+
+fn notes_directory<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<NotesDirectory, String> {
+    Ok(NotesDirectory {
+        path: app.notes_dir()?.to_string_lossy().into_owned(),
+        custom: configured(app).is_some(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_notes_directory(app: tauri::AppHandle) -> Result<NotesDirectory, String> {
+    notes_directory(&app)
+}
+
+/// Moves the notes to `path` and keeps them there from now on; without a path they move
+/// back to the default folder. Notes already in the target folder (another machine's,
+/// synced there) are kept and show up next to the moved ones, but a file or folder that
+/// exists on both sides is refused rather than overwritten.
+#[tauri::command]
+pub async fn set_notes_directory(path: Option<String>, app: tauri::AppHandle) -> Result<NotesDirectory, String> {
+    ensure_writes_allowed(&app)?;
+    let current = app.notes_dir()?;
+    let target = match path.as_deref().map(str::trim) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => default_dir(&app)?,
+    };
+    let target = validate_target(&app, &current, &target)?;
+    flush_now(&app, "notes directory");
+
+    let entries = changes::repoint(&app, || -> Result<Vec<PathBuf>, String> {
+        let entries = movable_entries(&current)?;
+        let clashes: Vec<&PathBuf> = entries
+            .iter()
+            .filter(|from| from.file_name().is_some_and(|name| target.join(name).exists()))
+            .collect();
+        if let Some(first) = clashes.first() {
+            return Err(format!(
+                "{} entries already exist in the new folder, first: {:?}",
+                clashes.len(),
+                first.file_name().unwrap_or_default()
+            ));
+        }
+        copy_all(&entries, &target)?;
+        let to_default = default_dir(&app)?.canonicalize().is_ok_and(|default| default == target);
+        let setting = if to_default {
+            serde_json::Value::Null
+        } else {
+            serde_json::Value::String(target.to_string_lossy().into_owned())
+        };
+        if let Err(e) = app.write_store("settings.bin", SETTING_KEY, setting) {
+            remove_copies(&entries, &target);
+            return Err(e);
+        }
+        Ok(entries)
+    })?;
+
+    // The copies are in use now; what's left behind is only a duplicate
+    let mut left_behind = 0;
+    for from in &entries {
+        let removed = SafePath::in_former_root(Root::Notes, &current, from.clone()).and_then(|safe| safe.remove());
+        if let Err(e) = removed {
+            println!("Could not remove {:?} from the old notes folder: {}", from, e);
+            left_behind += 1;
+        }
+    }
+    println!(
+        "Notes folder moved from {:?} to {:?}, {} entries ({} left behind)",
+        current,
+        target,
+        entries.len(),
+        left_behind
+    );
+
+    app.state::<PreviewCache>().clear();
+    noteindex::reset(&app);
+    storage::notes_dir_changed(&app);
+    let directory = notes_directory(&app)?;
+    app.emit_event("notes-directory-changed", directory.clone());
+    app.emit_event("refresh-notes", ());
+    Ok(directory)
+}
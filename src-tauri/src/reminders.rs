@@ -0,0 +1,236 @@
+//! Reminders on notes. Each one is kept in session.bin `reminders` until it fires; the
+//! scheduler sleeps until the earliest is due (or the list changes), then shows a desktop
+//! notification and opens the note. Reminders that came due while the app was closed
+//! fire on the next start. A muted note's reminders are held back, see `mute.rs`. The
+//! pending ones are also written out for calendar apps, see `calendar.rs`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use tauri::{Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::calendar::{self, CalendarEvent};
+use crate::mute::{self, Notification};
+use crate::notewindow::NoteWindowOptions;
+use crate::scan::is_valid_note_id;
+use crate::{create_note_window, derive_title, notes_dir, now_millis, read_note};
+
+const REMINDERS_KEY: &str = "reminders";
+/// Longest the scheduler sleeps, so a changed system clock or a suspended machine
+/// delays a reminder by at most this much.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Reminder {
+    /// What `cancel_reminder` takes
+    id: String,
+    note_id: String,
+    /// Unix millis
+    due_at: u64,
+    message: Option<String>,
+    /// Unix millis
+    created_at: u64,
+}
+
+#[derive(Default)]
+pub struct ReminderEngine {
+    /// Held for every read-modify-write of the stored list
+    lock: Mutex<()>,
+    /// Signalled when the list changes, so the scheduler picks up a new earliest reminder
+    wake: tokio::sync::Notify,
+}
+
+fn load(backend: &impl NotesBackend) -> Vec<Reminder> {
+    backend
+        .read_store("session.bin", REMINDERS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn save(backend: &impl NotesBackend, reminders: &[Reminder]) -> Result<(), String> {
+    let value = serde_json::to_value(reminders).map_err(|e| e.to_string())?;
+    backend.write_store("session.bin", REMINDERS_KEY, value)
+}
+
+/// Applies `change` to the stored reminders and wakes the scheduler.
+fn update<R: Runtime, T>(
+    app: &tauri::AppHandle<R>,
+    change: impl FnOnce(&mut Vec<Reminder>) -> Result<T, String>,
+) -> Result<T, String> {
+    let engine = app.state::<ReminderEngine>();
+    let result = {
+        let _guard = engine.lock.lock().map_err(|e| e.to_string())?;
+        let mut reminders = load(app);
+        let result = change(&mut reminders)?;
+        save(app, &reminders)?;
+        result
+    };
+    engine.wake.notify_one();
+    Ok(result)
+}
+
+/// RFC 3339 (`2026-03-01T09:00:00+01:00`), or a local date and time without an offset
+/// (`2026-03-01T09:00`), as a `datetime-local` input sends it.
+fn parse_datetime(datetime: &str) -> Result<u64, String> {
+    let parsed = DateTime::parse_from_rfc3339(datetime)
+        .map(|d| d.timestamp_millis())
+        .or_else(|_| {
+            ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(datetime, format).ok())
+                .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+                .map(|d| d.timestamp_millis())
+                .ok_or_else(|| format!("Can't read {:?} as a date and time", datetime))
+        })?;
+    u64::try_from(parsed).map_err(|_| format!("{:?} is before 1970", datetime))
+}
+
+/// Rewrites the calendar file from the stored reminders.
+pub fn write_calendar(backend: &impl NotesBackend) {
+    let reminders = load(backend);
+    let events: Vec<CalendarEvent> = reminders
+        .iter()
+        .map(|reminder| CalendarEvent {
+            uid: &reminder.id,
+            note_id: &reminder.note_id,
+            summary: read_note(backend, &reminder.note_id)
+                .map(|content| derive_title(&content))
+                .unwrap_or_else(|_| "Note".to_string()),
+            message: reminder.message.as_deref(),
+            due_at: reminder.due_at,
+            created_at: reminder.created_at,
+        })
+        .collect();
+    if let Err(e) = calendar::write(backend, &events) {
+        println!("Failed to write the calendar file: {}", e);
+    }
+}
+
+/// Tells windows the reminders changed and rewrites the calendar file.
+fn changed<R: Runtime>(app: &tauri::AppHandle<R>) {
+    app.emit_event("reminders-changed", ());
+    write_calendar(app);
+}
+
+fn fire<R: Runtime>(app: &tauri::AppHandle<R>, reminder: &Reminder) {
+    let id = &reminder.note_id;
+    let exists = notes_dir(app).is_ok_and(|dir| dir.join(format!("{}.md", id)).is_file());
+    if !exists {
+        println!("Dropping reminder {} for missing note {}", reminder.id, id);
+        return;
+    }
+    if mute::suppress(app, id, Notification::Reminder) {
+        return;
+    }
+    println!("Reminder {} for {} is due", reminder.id, id);
+
+    let title = read_note(app, id).map(|c| derive_title(&c)).unwrap_or_default();
+    let body = reminder.message.clone().unwrap_or_else(|| "Reminder".to_string());
+    if let Err(e) = app.notification().builder().title(&title).body(&body).show() {
+        println!("Failed to show notification for {}: {}", id, e);
+    }
+    if let Err(e) = create_note_window(app, NoteWindowOptions::open(id.clone())) {
+        println!("Failed to open note {} for its reminder: {}", id, e);
+    }
+    app.emit_note_event(id, "reminder-fired", reminder.clone());
+}
+
+/// Takes the due reminders off the list and fires them. Returns when the next one is due.
+fn fire_due<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<u64> {
+    let now = now_millis();
+    let (due, next) = {
+        let engine = app.state::<ReminderEngine>();
+        let _guard = engine.lock.lock().ok()?;
+        let (due, pending): (Vec<Reminder>, Vec<Reminder>) = load(app).into_iter().partition(|r| r.due_at <= now);
+        if !due.is_empty() {
+            if let Err(e) = save(app, &pending) {
+                // Firing anyway would repeat these every tick
+                println!("Failed to save reminders, not firing: {}", e);
+                return Some(now + MAX_SLEEP.as_millis() as u64);
+            }
+        }
+        (due, pending.iter().map(|r| r.due_at).min())
+    };
+    for reminder in &due {
+        fire(app, reminder);
+    }
+    if !due.is_empty() {
+        changed(app);
+    }
+    next
+}
+
+pub fn spawn_reminder_scheduler<R: Runtime>(app: tauri::AppHandle<R>) {
+    write_calendar(&app);
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let wait = match fire_due(&app) {
+                Some(due_at) => Duration::from_millis(due_at.saturating_sub(now_millis())).min(MAX_SLEEP),
+                None => MAX_SLEEP,
+            };
+            let _ = tokio::time::timeout(wait, app.state::<ReminderEngine>().wake.notified()).await;
+        }
+    });
+}
+
+/// Reminds about note `id` at `datetime` (see `parse_datetime`), with an optional message
+/// for the notification.
+#[tauri::command]
+pub async fn set_reminder(
+    id: String,
+    datetime: String,
+    message: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Reminder, String> {
+    if !is_valid_note_id(&id) {
+        return Err(format!("Invalid note id {:?}", id));
+    }
+    let due_at = parse_datetime(datetime.trim())?;
+    let now = now_millis();
+    if due_at <= now {
+        return Err(format!("{} is in the past", datetime));
+    }
+    let reminder = Reminder {
+        id: Uuid::new_v4().to_string(),
+        note_id: id,
+        due_at,
+        message: message.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        created_at: now,
+    };
+    update(&app, |reminders| {
+        reminders.push(reminder.clone());
+        Ok(())
+    })?;
+    println!("Reminder {} set for {} at {}", reminder.id, reminder.note_id, datetime);
+    changed(&app);
+    Ok(reminder)
+}
+
+/// Pending reminders, soonest first; only note `id`'s when given.
+#[tauri::command]
+pub async fn list_reminders(id: Option<String>, app: tauri::AppHandle) -> Result<Vec<Reminder>, String> {
+    let mut reminders: Vec<Reminder> = load(&app)
+        .into_iter()
+        .filter(|r| id.as_ref().is_none_or(|id| r.note_id == *id))
+        .collect();
+    reminders.sort_by_key(|r| r.due_at);
+    Ok(reminders)
+}
+
+#[tauri::command]
+pub async fn cancel_reminder(reminder_id: String, app: tauri::AppHandle) -> Result<(), String> {
+    update(&app, |reminders| {
+        let before = reminders.len();
+        reminders.retain(|r| r.id != reminder_id);
+        if reminders.len() == before {
+            return Err(format!("No reminder {}", reminder_id));
+        }
+        Ok(())
+    })?;
+    changed(&app);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Getting a note window back into a usable state: default size, centered, not pinned,
+//! not click-through, focused. Only backend window calls are used, since a window
+//! that needs rescuing usually can't be clicked or isn't on any screen.
+
+use tauri::{menu::Submenu, LogicalPosition, LogicalSize, Manager, Runtime};
+
+use crate::applock;
+use crate::create_note_window;
+use crate::meta::{self, Rect};
+use crate::notewindow::{NoteWindowOptions, DEFAULT_NOTE_SIZE};
+
+/// Prefix of the tray menu item ids; the note id follows.
+pub const MENU_ID_PREFIX: &str = "rescue:";
+
+/// The tray's "Rescue note…" submenu, rebuilt by `notemenu` as note windows open and close.
+pub struct RescueMenu<R: Runtime>(pub Submenu<R>);
+
+/// Default-sized rectangle centered on `display`.
+pub fn centered_default(display: Rect) -> Rect {
+    let (width, height) = DEFAULT_NOTE_SIZE;
+    Rect {
+        x: display.x + (display.width - width) / 2.0,
+        y: display.y + (display.height - height) / 2.0,
+        width,
+        height,
+    }
+}
+
+fn rescue_window<R: Runtime>(window: &tauri::WebviewWindow<R>, id: &str) -> Result<(), String> {
+    let app = window.app_handle();
+    // The monitor the window is on, or the primary one if it's on none
+    let display = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .map(|m| {
+            let scale = m.scale_factor();
+            let position = m.position().to_logical::<f64>(scale);
+            let size = m.size().to_logical::<f64>(scale);
+            Rect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            }
+        })
+        .or_else(|| meta::display_rects(app).first().copied());
+
+    let _ = window.set_ignore_cursor_events(false);
+    window.set_always_on_top(false).map_err(|e| e.to_string())?;
+    let _ = window.unminimize();
+    window
+        .set_size(LogicalSize::new(DEFAULT_NOTE_SIZE.0, DEFAULT_NOTE_SIZE.1))
+        .map_err(|e| e.to_string())?;
+    match display {
+        Some(display) => {
+            let rect = centered_default(display);
+            window
+                .set_position(LogicalPosition::new(rect.x, rect.y))
+                .map_err(|e| e.to_string())?;
+        }
+        None => window.center().map_err(|e| e.to_string())?,
+    }
+    window.show().map_err(|e| e.to_string())?;
+    let _ = window.set_focus();
+
+    // Geometry is saved by the Moved/Resized handler; the pin is cleared for good too
+    meta::update_meta(app, id, |meta| meta.pinned = false)?;
+    println!("Rescued note window {}", id);
+    Ok(())
+}
+
+/// Rescues the note's window, opening it first if it isn't open.
+pub fn rescue_note<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Result<(), String> {
+    applock::ensure_unlocked(app)?;
+    let window = match app.get_webview_window(&format!("note-{}", id)) {
+        Some(window) => window,
+        None => create_note_window(app, NoteWindowOptions::open(id))?,
+    };
+    rescue_window(&window, id)
+}
+
+#[tauri::command]
+pub async fn rescue_note_window(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    rescue_note(&app, &id)
+}
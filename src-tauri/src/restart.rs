@@ -0,0 +1,224 @@
+//! Scheduled self-restart for unattended (kiosk-style) machines.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime};
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::backend::NotesBackend;
+use crate::{prepare_exit, ChunkedSaves};
+
+const TICK: Duration = Duration::from_secs(30);
+const WARNING_LEAD: chrono::Duration = chrono::Duration::minutes(2);
+/// A note focused within this window means someone is at the machine.
+const IDLE_REQUIRED: Duration = Duration::from_secs(15 * 60);
+const DEFER_STEP: chrono::Duration = chrono::Duration::minutes(15);
+const MAX_AUTO_DEFERRALS: u32 = 8;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct ScheduledRestart {
+    pub enabled: bool,
+    /// Local time of day, `HH:MM`
+    pub time: String,
+    /// 0 = Sunday .. 6 = Saturday
+    pub days: Vec<u32>,
+}
+
+impl Default for ScheduledRestart {
+    fn default() -> Self {
+        ScheduledRestart {
+            enabled: false,
+            time: "03:00".to_string(),
+            days: (0..7).collect(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct RestartImminent {
+    at: i64,
+}
+
+struct Pending {
+    deferred_until: Option<DateTime<Local>>,
+    auto_deferrals: u32,
+    warned_for: Option<DateTime<Local>>,
+    /// Today's slot once it has run out of deferrals, so it isn't retried
+    skipped_for: Option<DateTime<Local>>,
+}
+
+pub struct RestartState {
+    started_at: DateTime<Local>,
+    last_focus: Mutex<Instant>,
+    dirty_editors: Mutex<HashSet<String>>,
+    pending: Mutex<Pending>,
+}
+
+impl Default for RestartState {
+    fn default() -> Self {
+        RestartState {
+            started_at: Local::now(),
+            last_focus: Mutex::new(Instant::now()),
+            dirty_editors: Mutex::new(HashSet::new()),
+            pending: Mutex::new(Pending {
+                deferred_until: None,
+                auto_deferrals: 0,
+                warned_for: None,
+                skipped_for: None,
+            }),
+        }
+    }
+}
+
+/// Called from note focus handlers.
+pub fn note_focused<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if let Ok(mut last_focus) = app.state::<RestartState>().last_focus.lock() {
+        *last_focus = Instant::now();
+    }
+}
+
+fn get_settings<R: Runtime>(app: &tauri::AppHandle<R>) -> ScheduledRestart {
+    app.store("settings.bin")
+        .ok()
+        .and_then(|store| store.get("scheduled_restart"))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Today's slot, if today is a scheduled day. Slots before this process started don't
+/// count, which is also what stops a freshly restarted app from restarting again.
+fn todays_slot(settings: &ScheduledRestart, now: DateTime<Local>, started_at: DateTime<Local>) -> Option<DateTime<Local>> {
+    if !settings.enabled || !settings.days.contains(&now.weekday().num_days_from_sunday()) {
+        return None;
+    }
+    let time = NaiveTime::parse_from_str(&settings.time, "%H:%M").ok()?;
+    let slot = now.date_naive().and_time(time).and_local_timezone(Local).earliest()?;
+    (slot > started_at).then_some(slot)
+}
+
+fn is_busy<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    let state = app.state::<RestartState>();
+    let recently_focused = state
+        .last_focus
+        .lock()
+        .map(|t| t.elapsed() < IDLE_REQUIRED)
+        .unwrap_or(true);
+    let dirty = state.dirty_editors.lock().map(|d| !d.is_empty()).unwrap_or(true);
+    let uploading = app
+        .state::<ChunkedSaves>()
+        .0
+        .lock()
+        .map(|p| !p.is_empty())
+        .unwrap_or(true);
+    recently_focused || dirty || uploading
+}
+
+fn tick<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let settings = get_settings(app);
+    let state = app.state::<RestartState>();
+    let now = Local::now();
+    let Ok(mut pending) = state.pending.lock() else {
+        return;
+    };
+
+    let slot = todays_slot(&settings, now, state.started_at).filter(|slot| pending.skipped_for != Some(*slot));
+    let Some(due) = pending.deferred_until.or(slot) else {
+        return;
+    };
+
+    if now + WARNING_LEAD >= due && pending.warned_for != Some(due) {
+        pending.warned_for = Some(due);
+        app.emit_event(
+            "scheduled-restart-imminent",
+            RestartImminent { at: due.timestamp_millis() },
+        );
+    }
+    if now < due {
+        return;
+    }
+
+    if is_busy(app) {
+        if pending.auto_deferrals < MAX_AUTO_DEFERRALS {
+            pending.auto_deferrals += 1;
+            pending.deferred_until = Some(now + DEFER_STEP);
+            println!("Scheduled restart deferred, the app is in use ({} so far)", pending.auto_deferrals);
+        } else {
+            println!("Scheduled restart skipped after {} deferrals", pending.auto_deferrals);
+            pending.skipped_for = slot;
+            pending.deferred_until = None;
+            pending.auto_deferrals = 0;
+        }
+        return;
+    }
+
+    println!("Performing scheduled restart");
+    drop(pending);
+    prepare_exit(app);
+    app.restart();
+}
+
+pub fn spawn_restart_scheduler<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(TICK).await;
+            tick(&app);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_scheduled_restart(app: tauri::AppHandle) -> Result<ScheduledRestart, String> {
+    Ok(get_settings(&app))
+}
+
+#[tauri::command]
+pub async fn set_scheduled_restart(settings: ScheduledRestart, app: tauri::AppHandle) -> Result<(), String> {
+    NaiveTime::parse_from_str(&settings.time, "%H:%M").map_err(|_| format!("Invalid time: {}", settings.time))?;
+    if settings.days.iter().any(|d| *d > 6) {
+        return Err("Days must be 0 (Sunday) to 6 (Saturday)".to_string());
+    }
+    let store = app.store("settings.bin").map_err(|e| e.to_string())?;
+    store.set("scheduled_restart", serde_json::to_value(settings).map_err(|e| e.to_string())?);
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Lets a person at the machine push the pending (or next) restart back.
+#[tauri::command]
+pub async fn defer_scheduled_restart(minutes: u32, app: tauri::AppHandle) -> Result<(), String> {
+    let settings = get_settings(&app);
+    let state = app.state::<RestartState>();
+    let now = Local::now();
+    let mut pending = state.pending.lock().map_err(|e| e.to_string())?;
+    let base = pending
+        .deferred_until
+        .or_else(|| todays_slot(&settings, now, state.started_at))
+        .unwrap_or(now)
+        .max(now);
+    pending.deferred_until = Some(base + chrono::Duration::minutes(minutes as i64));
+    Ok(())
+}
+
+pub fn is_editor_dirty<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> bool {
+    app.state::<RestartState>()
+        .dirty_editors
+        .lock()
+        .map(|editors| editors.contains(id))
+        .unwrap_or(false)
+}
+
+/// Editors report unsaved buffers so a restart never discards typing.
+#[tauri::command]
+pub async fn set_editor_dirty(id: String, dirty: bool, app: tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<RestartState>();
+    let mut editors = state.dirty_editors.lock().map_err(|e| e.to_string())?;
+    if dirty {
+        editors.insert(id);
+    } else {
+        editors.remove(&id);
+    }
+    Ok(())
+}
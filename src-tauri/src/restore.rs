@@ -0,0 +1,163 @@
+//! Startup session restore.
+//!
+//! Windows are created strictly one at a time, each bounded by a timeout, because on a
+//! cold Windows boot the first WebView2 instance can take many seconds (or fail) while
+//! the runtime updates itself; firing off every window at once just multiplies the stall.
+
+use std::time::{Duration, Instant};
+use tauri::{Manager, Runtime};
+
+use crate::applock;
+use crate::autostart;
+use crate::backend::NotesBackend;
+use crate::notewindow::NoteWindowOptions;
+use crate::settings;
+use crate::suspect;
+use crate::{create_note_window, show_dashboard};
+
+pub const DEFAULT_CREATE_TIMEOUT_MS: u64 = 15_000;
+
+#[derive(serde::Serialize, Clone)]
+struct RestoreProgress {
+    total: usize,
+    completed: usize,
+    failed: Vec<String>,
+    done: bool,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct WebviewUnhealthy {
+    error: String,
+}
+
+fn create_timeout<R: Runtime>(app: &tauri::AppHandle<R>) -> Duration {
+    Duration::from_millis(settings::current(app).window_create_timeout_ms)
+}
+
+/// Builds one restored (hidden) note window, giving up after `timeout`. A build that
+/// finishes after we gave up is harmless: the retry finds and reuses the window.
+async fn create_with_timeout<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    id: &str,
+    timeout: Duration,
+) -> Result<tauri::WebviewWindow<R>, String> {
+    let handle = app.clone();
+    let id_for_task = id.to_string();
+    let started = Instant::now();
+    let task = tauri::async_runtime::spawn_blocking(move || {
+        create_note_window(&handle, NoteWindowOptions::restore(id_for_task))
+    });
+
+    let result = match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("timed out after {:?}", timeout)),
+    };
+    println!(
+        "Restore: window for {} {} in {:?}",
+        id,
+        if result.is_ok() { "created" } else { "failed" },
+        started.elapsed()
+    );
+    result
+}
+
+fn emit_progress<R: Runtime>(app: &tauri::AppHandle<R>, progress: &RestoreProgress) {
+    app.emit_event("restore-progress", progress.clone());
+}
+
+/// Restores the saved session, or opens a first note when there is none. A launch at
+/// login restores the notes hidden and opens no first note.
+/// Locked, it is left for `unlock_app` to run.
+pub async fn restore_session<R: Runtime>(app: tauri::AppHandle<R>, notes: Vec<String>) {
+    let hidden = autostart::start_hidden(&app);
+    if applock::defer_restore(&app) {
+        if !hidden {
+            show_dashboard(&app);
+        }
+        return;
+    }
+    if notes.is_empty() {
+        if hidden {
+            return;
+        }
+        if let Err(e) = create_note_window(&app, NoteWindowOptions::new_note()) {
+            report_unhealthy(&app, e);
+        }
+        return;
+    }
+
+    let timeout = create_timeout(&app);
+    let mut progress = RestoreProgress {
+        total: notes.len(),
+        completed: 0,
+        failed: Vec::new(),
+        done: false,
+    };
+    let mut restored = Vec::new();
+    let mut deferred = Vec::new();
+    let mut last_error = None;
+
+    for id in notes {
+        // Before the window loads the content and its first autosave can overwrite it
+        suspect::check(&app, &id);
+        match create_with_timeout(&app, &id, timeout).await {
+            Ok(window) => {
+                restored.push(window);
+                progress.completed += 1;
+            }
+            Err(e) => {
+                last_error = Some(e);
+                deferred.push(id);
+            }
+        }
+        emit_progress(&app, &progress);
+    }
+
+    // Anything that failed gets one more try, but only once we know WebViews work at all
+    if !restored.is_empty() {
+        for id in deferred {
+            match create_with_timeout(&app, &id, timeout).await {
+                Ok(window) => {
+                    restored.push(window);
+                    progress.completed += 1;
+                }
+                Err(e) => {
+                    last_error = Some(e);
+                    progress.failed.push(id);
+                }
+            }
+        }
+    } else {
+        progress.failed = deferred;
+    }
+
+    progress.done = true;
+    emit_progress(&app, &progress);
+
+    if restored.is_empty() {
+        report_unhealthy(&app, last_error.unwrap_or_else(|| "unknown error".to_string()));
+        return;
+    }
+
+    if hidden {
+        println!("Restore: launched at login, {} notes left in the tray", restored.len());
+        return;
+    }
+
+    // Batch show all restored windows at once
+    for window in restored {
+        let _ = window.show();
+    }
+}
+
+/// No note window could be created: fall back to the dashboard so the user sees guidance
+/// instead of a lone tray icon.
+fn report_unhealthy<R: Runtime>(app: &tauri::AppHandle<R>, error: String) {
+    println!("Restore: no note window could be created, last error: {}", error);
+    if let Some(main_win) = app.get_webview_window("main") {
+        let _ = main_win.show();
+        let _ = main_win.set_focus();
+    }
+    app.emit_event("webview-unhealthy", WebviewUnhealthy { error });
+}
@@ -0,0 +1,377 @@
+//! Every delete, rename and move in the crate goes through `SafePath`, which refuses to
+//! touch anything that isn't strictly inside one of the app's data roots. It is a
+//! guardrail against bugs in bulk features, not a permission system.
+//!
+//! The notes root follows the notes directory setting (`relocate.rs`); the others are
+//! always under the app data dir.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::backend::NotesBackend;
+use crate::error::NoteError;
+use crate::notes_dir;
+
+/// The directories destructive operations may work in.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Root {
+    Notes,
+    Trash,
+    Archive,
+    Journal,
+    Templates,
+}
+
+const ROOTS: [Root; 5] = [Root::Notes, Root::Trash, Root::Archive, Root::Journal, Root::Templates];
+
+/// Destructive calls made so far, indexed like `ROOTS`.
+static DESTRUCTIVE_CALLS: [AtomicU64; 5] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+impl Root {
+    fn dir_name(self) -> &'static str {
+        match self {
+            Root::Notes => "notes",
+            Root::Trash => "trash",
+            Root::Archive => "archive",
+            Root::Journal => "journal",
+            Root::Templates => "templates",
+        }
+    }
+
+    pub fn dir(self, backend: &impl NotesBackend) -> Result<PathBuf, String> {
+        if self == Root::Notes {
+            return notes_dir(backend);
+        }
+        Ok(backend.data_dir()?.join(self.dir_name()))
+    }
+
+    fn count(self) {
+        let index = ROOTS.iter().position(|root| *root == self).unwrap_or(0);
+        DESTRUCTIVE_CALLS[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Destructive calls per root since start.
+pub fn destructive_calls() -> Vec<(Root, u64)> {
+    ROOTS
+        .iter()
+        .zip(DESTRUCTIVE_CALLS.iter())
+        .map(|(root, calls)| (*root, calls.load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// A path verified to lie strictly inside `root`.
+#[derive(Debug)]
+pub struct SafePath {
+    root: Root,
+    path: PathBuf,
+}
+
+impl SafePath {
+    pub fn new(backend: &impl NotesBackend, root: Root, path: impl Into<PathBuf>) -> Result<Self, String> {
+        Self::check(root, &root.dir(backend)?, path.into())
+    }
+
+    /// Like `new`, but against `root_dir` instead of where `root` is now. For the notes
+    /// folder a relocation has just moved away from.
+    pub fn in_former_root(root: Root, root_dir: &Path, path: impl Into<PathBuf>) -> Result<Self, String> {
+        Self::check(root, root_dir, path.into())
+    }
+
+    fn check(root: Root, root_dir: &Path, path: PathBuf) -> Result<Self, String> {
+        let unsafe_path = |reason: &str| -> String {
+            NoteError::UnsafePath {
+                path: path.display().to_string(),
+                reason: reason.to_string(),
+            }
+            .into()
+        };
+
+        if path.components().any(|c| matches!(c, Component::ParentDir)) {
+            return Err(unsafe_path("contains '..'"));
+        }
+        match path.strip_prefix(root_dir) {
+            Ok(relative) if relative.as_os_str().is_empty() => return Err(unsafe_path("is the root itself")),
+            Ok(_) => {}
+            Err(_) => return Err(unsafe_path("is outside its root")),
+        }
+
+        // Lexically fine; now make sure no symlink along the way leads somewhere else
+        let canonical_root = root_dir
+            .canonicalize()
+            .map_err(|_| unsafe_path("root does not exist"))?;
+        let canonical_parent = path
+            .parent()
+            .and_then(|parent| parent.canonicalize().ok())
+            .ok_or_else(|| unsafe_path("parent directory does not exist"))?;
+        if !canonical_parent.starts_with(&canonical_root) {
+            return Err(unsafe_path("parent escapes its root through a symlink"));
+        }
+        if fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink()) {
+            // A dangling link can't lead anywhere, so removing it is fine
+            if let Ok(target) = path.canonicalize() {
+                if !target.starts_with(&canonical_root) || target == canonical_root {
+                    return Err(unsafe_path("symlink escapes its root"));
+                }
+            }
+        }
+
+        Ok(SafePath { root, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes a file, symlink or whole directory. Missing paths are not an error.
+    pub fn remove(&self) -> Result<(), String> {
+        let Ok(metadata) = fs::symlink_metadata(&self.path) else {
+            return Ok(());
+        };
+        self.root.count();
+        println!("Removing {:?} ({:?})", self.path, self.root);
+        if metadata.is_dir() {
+            fs::remove_dir_all(&self.path).map_err(|e| e.to_string())
+        } else {
+            fs::remove_file(&self.path).map_err(|e| e.to_string())
+        }
+    }
+
+    /// Renames over `to`, which may be in a different root (e.g. notes -> trash).
+    pub fn rename_to(&self, to: &SafePath) -> Result<(), String> {
+        self.root.count();
+        if to.root != self.root {
+            to.root.count();
+        }
+        println!(
+            "Renaming {:?} ({:?}) to {:?} ({:?})",
+            self.path, self.root, to.path, to.root
+        );
+        match fs::rename(&self.path, &to.path) {
+            Ok(()) => Ok(()),
+            // A relocated notes folder can be on another volume than trash and archive. Any
+            // other failure is reported: copying over what's there could merge or clobber it
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                copy_recursively(&self.path, &to.path).map_err(|e| e.to_string())?;
+                self.remove()
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Copies a file, or a directory with everything in it. Symlinks are skipped rather than
+/// followed.
+pub fn copy_recursively(from: &Path, to: &Path) -> std::io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+    if file_type.is_file() {
+        fs::copy(from, to)?;
+    } else if file_type.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const MAX_FILE_STEM_CHARS: usize = 100;
+
+/// Turns a user-chosen name into a file stem that is valid on every platform: characters
+/// Windows forbids become `_`, leading dots and trailing dots/spaces go, and device names
+/// and names left empty are refused.
+pub fn sanitize_file_name(name: &str) -> Result<String, String> {
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_FILE_STEM_CHARS)
+        .collect();
+    let stem = replaced
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' '])
+        .to_string();
+    if stem.is_empty() {
+        return Err(format!("Invalid file name: {:?}", name));
+    }
+    let device = stem.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(device)) {
+        return Err(format!("{:?} is a reserved file name", stem));
+    }
+    Ok(stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A notes root and a trash root inside one temp dir, and something outside both.
+    struct Roots {
+        _dir: TempDir,
+        notes: PathBuf,
+        trash: PathBuf,
+        outside: PathBuf,
+    }
+
+    fn roots() -> Roots {
+        let dir = TempDir::new().unwrap();
+        let [notes, trash, outside] = ["notes", "trash", "outside"].map(|name| dir.path().join(name));
+        for dir in [&notes, &trash, &outside] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        Roots {
+            _dir: dir,
+            notes,
+            trash,
+            outside,
+        }
+    }
+
+    fn in_notes(roots: &Roots, path: impl Into<PathBuf>) -> Result<SafePath, String> {
+        SafePath::in_former_root(Root::Notes, &roots.notes, path)
+    }
+
+    fn refusal(result: Result<SafePath, String>) -> String {
+        let error = result.unwrap_err();
+        assert!(error.starts_with("UnsafePath"), "{}", error);
+        error
+    }
+
+    #[test]
+    fn paths_inside_the_root_are_accepted() {
+        let roots = roots();
+        fs::create_dir_all(roots.notes.join("a")).unwrap();
+        for path in [
+            roots.notes.join("a.md"),
+            roots.notes.join("a"),
+            roots.notes.join("a").join("1.png"),
+        ] {
+            assert_eq!(in_notes(&roots, path.clone()).unwrap().path(), path);
+        }
+    }
+
+    #[test]
+    fn parent_components_are_refused() {
+        let roots = roots();
+        fs::create_dir_all(roots.notes.join("a")).unwrap();
+        for crafted in ["..", "../outside/x.md", "a/../../outside/x.md", "a/../a.md", "a/.."] {
+            let error = refusal(in_notes(&roots, roots.notes.join(crafted)));
+            assert!(error.contains("'..'"), "{}: {}", crafted, error);
+        }
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_refused() {
+        let roots = roots();
+        let absolute_id = roots.notes.join(format!("{}.md", roots.outside.join("x").display()));
+        for path in [
+            roots.outside.join("x.md"),
+            roots.trash.join("a.md"),
+            absolute_id,
+            PathBuf::from("a.md"),
+        ] {
+            let error = refusal(in_notes(&roots, path.clone()));
+            assert!(error.contains("outside its root"), "{:?}: {}", path, error);
+        }
+        let error = refusal(in_notes(&roots, roots.notes.clone()));
+        assert!(error.contains("root itself"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_refused() {
+        use std::os::unix::fs::symlink;
+
+        let roots = roots();
+        fs::write(roots.outside.join("x.md"), "not ours").unwrap();
+        symlink(&roots.outside, roots.notes.join("linked")).unwrap();
+        symlink(roots.outside.join("x.md"), roots.notes.join("b.md")).unwrap();
+        symlink(&roots.notes, roots.notes.join("self")).unwrap();
+
+        let error = refusal(in_notes(&roots, roots.notes.join("linked").join("x.md")));
+        assert!(error.contains("parent escapes"), "{}", error);
+        let error = refusal(in_notes(&roots, roots.notes.join("b.md")));
+        assert!(error.contains("symlink escapes"), "{}", error);
+        // A link to the root itself would have `remove` take the root with it
+        let error = refusal(in_notes(&roots, roots.notes.join("self")));
+        assert!(error.contains("symlink escapes"), "{}", error);
+        assert!(roots.outside.join("x.md").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_within_the_root_and_dangling_ones_are_accepted() {
+        use std::os::unix::fs::symlink;
+
+        let roots = roots();
+        fs::write(roots.notes.join("a.md"), "ours").unwrap();
+        symlink(roots.notes.join("a.md"), roots.notes.join("b.md")).unwrap();
+        symlink(roots.outside.join("gone.md"), roots.notes.join("c.md")).unwrap();
+
+        assert!(in_notes(&roots, roots.notes.join("b.md")).is_ok());
+        let dangling = in_notes(&roots, roots.notes.join("c.md")).unwrap();
+        dangling.remove().unwrap();
+        assert!(fs::symlink_metadata(roots.notes.join("c.md")).is_err());
+    }
+
+    #[test]
+    fn rename_moves_between_roots() {
+        let roots = roots();
+        fs::write(roots.notes.join("a.md"), "Alpha").unwrap();
+        let from = in_notes(&roots, roots.notes.join("a.md")).unwrap();
+        let to = SafePath::in_former_root(Root::Trash, &roots.trash, roots.trash.join("a.md")).unwrap();
+
+        from.rename_to(&to).unwrap();
+        assert!(!roots.notes.join("a.md").exists());
+        assert_eq!(fs::read_to_string(roots.trash.join("a.md")).unwrap(), "Alpha");
+    }
+
+    #[test]
+    fn failed_rename_does_not_fall_back_to_copying() {
+        let roots = roots();
+        for root in [&roots.notes, &roots.trash] {
+            fs::create_dir_all(root.join("a")).unwrap();
+            fs::write(root.join("a").join("1.png"), root.display().to_string()).unwrap();
+        }
+        let from = in_notes(&roots, roots.notes.join("a")).unwrap();
+        let to = SafePath::in_former_root(Root::Trash, &roots.trash, roots.trash.join("a")).unwrap();
+
+        // Renaming onto a non-empty directory fails; copying would merge into it
+        assert!(from.rename_to(&to).is_err());
+        assert!(roots.notes.join("a").join("1.png").exists());
+        let kept = fs::read_to_string(roots.trash.join("a").join("1.png")).unwrap();
+        assert_eq!(kept, roots.trash.display().to_string());
+    }
+
+    #[test]
+    fn file_names_are_sanitized() {
+        assert_eq!(sanitize_file_name("  ..My: note?. ").unwrap(), "My_ note_");
+        assert_eq!(sanitize_file_name("a/b\\c").unwrap(), "a_b_c");
+        assert_eq!(sanitize_file_name(&"x".repeat(150)).unwrap().len(), MAX_FILE_STEM_CHARS);
+        for refused in ["", " . ", "CON", "nul.txt", "Com1 .md"] {
+            assert!(sanitize_file_name(refused).is_err(), "{:?}", refused);
+        }
+    }
+}
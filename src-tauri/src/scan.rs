@@ -0,0 +1,399 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Controls how far `scan_notes` descends below the notes directory.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanOptions {
+    /// Levels of sub-folders to walk into; 0 scans only the notes directory itself.
+    pub max_depth: usize,
+}
+
+/// Everything the scanner finds is reported as exactly one of these.
+#[derive(Debug)]
+pub enum ScanEntry {
+    /// A `<id>.md` note.
+    Note { id: String, path: PathBuf },
+    /// A `<id>.meta.json` sidecar, which belongs with its note.
+    Meta,
+    /// A `<id>/` directory belonging to a note (history, attachments, ...). Not descended into.
+    AssetDir,
+    /// Something that lives in the notes directory but isn't a usable note,
+    /// e.g. a sync conflict copy like `id (1).md`.
+    Quarantine { path: PathBuf, reason: String },
+    /// An entry we could not inspect at all.
+    Unreadable { path: PathBuf, reason: String },
+}
+
+/// Note ids double as file names, so only allow characters that are safe everywhere.
+pub fn is_valid_note_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Editor swap files, OS metadata and our own in-flight temp files.
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('.')
+        || name.starts_with("~$")
+        || name.ends_with('~')
+        || name.ends_with(".tmp")
+        || name.ends_with(".swp")
+}
+
+/// Lazily walks `dir`, yielding one `ScanEntry` per interesting entry.
+/// Never panics: anything odd comes back as `Quarantine` or `Unreadable`.
+pub fn scan_notes(dir: &Path, options: ScanOptions) -> NoteScanner {
+    let mut scanner = NoteScanner {
+        options,
+        pending: VecDeque::new(),
+        current: None,
+        visited: HashSet::new(),
+    };
+
+    if dir.exists() {
+        if let Ok(canonical) = dir.canonicalize() {
+            scanner.visited.insert(canonical);
+        }
+        scanner.pending.push_back((dir.to_path_buf(), 0));
+    }
+    scanner
+}
+
+pub struct NoteScanner {
+    options: ScanOptions,
+    pending: VecDeque<(PathBuf, usize)>,
+    current: Option<(fs::ReadDir, PathBuf, usize)>,
+    // Canonical paths of every directory already queued, so symlink loops end
+    visited: HashSet<PathBuf>,
+}
+
+impl NoteScanner {
+    fn classify(&mut self, path: PathBuf, dir: &Path, depth: usize) -> Option<ScanEntry> {
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => {
+                return Some(ScanEntry::Quarantine {
+                    path,
+                    reason: "file name is not valid UTF-8".to_string(),
+                })
+            }
+        };
+
+        if is_ignored(&name) {
+            return None;
+        }
+
+        // fs::metadata follows symlinks, so a dangling link ends up here as unreadable
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                return Some(ScanEntry::Unreadable {
+                    path,
+                    reason: e.to_string(),
+                })
+            }
+        };
+
+        if metadata.is_dir() {
+            if is_valid_note_id(&name) && dir.join(format!("{}.md", name)).is_file() {
+                return Some(ScanEntry::AssetDir);
+            }
+            if depth >= self.options.max_depth {
+                return None;
+            }
+            let canonical = match path.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    return Some(ScanEntry::Unreadable {
+                        path,
+                        reason: e.to_string(),
+                    })
+                }
+            };
+            if !self.visited.insert(canonical) {
+                return Some(ScanEntry::Unreadable {
+                    path,
+                    reason: "directory already visited (symlink loop)".to_string(),
+                });
+            }
+            self.pending.push_back((path, depth + 1));
+            return None;
+        }
+
+        if !metadata.is_file() {
+            return Some(ScanEntry::Quarantine {
+                path,
+                reason: "not a regular file".to_string(),
+            });
+        }
+
+        if let Some(id) = name.strip_suffix(".meta.json") {
+            if is_valid_note_id(id) {
+                return Some(ScanEntry::Meta);
+            }
+            return Some(ScanEntry::Quarantine {
+                path,
+                reason: "sidecar name is not a valid note id".to_string(),
+            });
+        }
+
+        if let Some(id) = name.strip_suffix(".md") {
+            if is_valid_note_id(id) {
+                return Some(ScanEntry::Note {
+                    id: id.to_string(),
+                    path,
+                });
+            }
+            return Some(ScanEntry::Quarantine {
+                path,
+                reason: "file name is not a valid note id".to_string(),
+            });
+        }
+
+        Some(ScanEntry::Quarantine {
+            path,
+            reason: "unrecognised file".to_string(),
+        })
+    }
+}
+
+impl Iterator for NoteScanner {
+    type Item = ScanEntry;
+
+    fn next(&mut self) -> Option<ScanEntry> {
+        loop {
+            if self.current.is_none() {
+                let (dir, depth) = self.pending.pop_front()?;
+                match fs::read_dir(&dir) {
+                    Ok(read_dir) => self.current = Some((read_dir, dir, depth)),
+                    Err(e) => {
+                        return Some(ScanEntry::Unreadable {
+                            path: dir,
+                            reason: e.to_string(),
+                        })
+                    }
+                }
+            }
+
+            let (read_dir, dir, depth) = self.current.as_mut()?;
+            let depth = *depth;
+            match read_dir.next() {
+                None => self.current = None,
+                Some(Err(e)) => {
+                    return Some(ScanEntry::Unreadable {
+                        path: dir.clone(),
+                        reason: e.to_string(),
+                    })
+                }
+                Some(Ok(entry)) => {
+                    let dir = dir.clone();
+                    if let Some(found) = self.classify(entry.path(), &dir, depth) {
+                        return Some(found);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// xorshift64, so generated trees are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+
+    /// What a scan entry says, comparable: its kind and, where it has one, its path.
+    type Found = (&'static str, Option<PathBuf>);
+
+    fn found(entry: ScanEntry) -> Found {
+        match entry {
+            ScanEntry::Note { id, path } => {
+                assert_eq!(path.file_name().unwrap().to_str().unwrap(), format!("{}.md", id));
+                ("note", Some(path))
+            }
+            ScanEntry::Meta => ("meta", None),
+            ScanEntry::AssetDir => ("asset_dir", None),
+            ScanEntry::Quarantine { path, .. } => ("quarantine", Some(path)),
+            ScanEntry::Unreadable { path, .. } => ("unreadable", Some(path)),
+        }
+    }
+
+    fn scan(dir: &Path, max_depth: usize) -> Vec<Found> {
+        let mut found: Vec<Found> = scan_notes(dir, ScanOptions { max_depth }).map(found).collect();
+        found.sort();
+        found
+    }
+
+    fn touch(path: PathBuf) -> PathBuf {
+        fs::write(&path, "x").unwrap();
+        path
+    }
+
+    /// Fills `dir` with a random mix of every kind of entry, recording what a scan reaching
+    /// `depth` should report.
+    fn fill(rng: &mut Rng, dir: &Path, depth: usize, expected: &mut Vec<(usize, Found)>) {
+        fs::create_dir_all(dir).unwrap();
+        for i in 0..rng.below(10) {
+            let id = format!("n{}-{}", depth, i);
+            match rng.below(8) {
+                0 => expected.push((depth, ("note", Some(touch(dir.join(format!("{}.md", id))))))),
+                1 => {
+                    touch(dir.join(format!("{}.meta.json", id)));
+                    expected.push((depth, ("meta", None)));
+                }
+                2 => {
+                    let note = touch(dir.join(format!("{}.md", id)));
+                    fs::create_dir_all(dir.join(&id)).unwrap();
+                    // Never reported: asset dirs aren't descended into
+                    touch(dir.join(&id).join("n9.md"));
+                    expected.push((depth, ("note", Some(note))));
+                    expected.push((depth, ("asset_dir", None)));
+                }
+                3 => {
+                    let names = [
+                        format!(".{}", id),
+                        format!(".{}.md.1234.tmp", id),
+                        format!("~${}.md", id),
+                        format!("{}.md~", id),
+                        format!("{}.md.swp", id),
+                    ];
+                    touch(dir.join(&names[rng.below(5) as usize]));
+                }
+                4 => {
+                    let copy = touch(dir.join(format!("{} (1).md", id)));
+                    expected.push((depth, ("quarantine", Some(copy))));
+                }
+                5 => {
+                    let other = touch(dir.join(format!("{}.txt", id)));
+                    expected.push((depth, ("quarantine", Some(other))));
+                }
+                6 => {
+                    let sidecar = touch(dir.join(format!("{} (1).meta.json", id)));
+                    expected.push((depth, ("quarantine", Some(sidecar))));
+                }
+                _ if depth < 3 => fill(rng, &dir.join(format!("d{}-{}", depth, i)), depth + 1, expected),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn generated_trees_are_classified_completely() {
+        for seed in 1..=64u64 {
+            let root = TempDir::new().unwrap();
+            let mut rng = Rng(seed.wrapping_mul(0x9e3779b97f4a7c15));
+            let mut expected = Vec::new();
+            fill(&mut rng, root.path(), 0, &mut expected);
+
+            for max_depth in 0..=4 {
+                let mut reachable: Vec<Found> = expected
+                    .iter()
+                    .filter(|(depth, _)| *depth <= max_depth)
+                    .map(|(_, found)| found.clone())
+                    .collect();
+                reachable.sort();
+                let found = scan(root.path(), max_depth);
+                assert_eq!(found, reachable, "seed {} depth {}", seed, max_depth);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_directory_scans_empty() {
+        let root = TempDir::new().unwrap();
+        assert!(scan(&root.path().join("nowhere"), 2).is_empty());
+    }
+
+    #[test]
+    fn long_names_are_quarantined() {
+        let root = TempDir::new().unwrap();
+        let longest = "a".repeat(128);
+        let note = touch(root.path().join(format!("{}.md", longest)));
+        let too_long = touch(root.path().join(format!("{}.md", "a".repeat(129))));
+        // Close to the 255 byte limit most filesystems have
+        let longer = touch(root.path().join(format!("{}.md", "b".repeat(250))));
+
+        let mut expected = vec![
+            ("note", Some(note)),
+            ("quarantine", Some(too_long)),
+            ("quarantine", Some(longer)),
+        ];
+        expected.sort();
+        assert_eq!(scan(root.path(), 0), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_end() {
+        use std::os::unix::fs::symlink;
+
+        let root = TempDir::new().unwrap();
+        let sub = root.path().join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        let note = touch(sub.join("a.md"));
+        symlink(root.path(), sub.join("up")).unwrap();
+        symlink(&sub, sub.join("again")).unwrap();
+
+        let found = scan(root.path(), 10);
+        let mut expected = vec![
+            ("note", Some(note)),
+            ("unreadable", Some(sub.join("again"))),
+            ("unreadable", Some(sub.join("up"))),
+        ];
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_entries_are_reported_not_fatal() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = TempDir::new().unwrap();
+        let note = touch(root.path().join("a.md"));
+        let dangling = root.path().join("b.md");
+        symlink(root.path().join("gone"), &dangling).unwrap();
+        let locked = root.path().join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        touch(locked.join("c.md"));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let found = scan(root.path(), 1);
+        // Permissions don't stop root, which some CI containers run tests as
+        let locked_readable = fs::read_dir(&locked).is_ok();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut expected = vec![("note", Some(note)), ("unreadable", Some(dangling))];
+        if locked_readable {
+            expected.push(("note", Some(locked.join("c.md"))));
+        } else {
+            expected.push(("unreadable", Some(locked)));
+        }
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn note_ids_are_file_name_safe() {
+        for id in ["a", "2026-10-16", "a_b-C9", &"x".repeat(128)] {
+            assert!(is_valid_note_id(id), "{}", id);
+        }
+        for id in ["", "a b", "a.b", "a/b", "..", "é", &"x".repeat(129)] {
+            assert!(!is_valid_note_id(id), "{}", id);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! App-wide preferences as one typed `Settings`, kept in settings.bin `app_settings`:
+//! appearance (theme, font) and the behavior switches and limits that used to be
+//! separate top-level keys. `get_settings` returns them all; `update_settings` takes a
+//! patch of just the fields to change, validates the result and sends
+//! `settings-changed` to every window so they apply it straight away.
+//!
+//! Starting after an upgrade moves the old top-level keys in (see `migrate`). Features
+//! with a settings object and commands of their own (sync, backups, the HTTP API,
+//! shortcuts, disk thresholds, scheduled restarts, ...) keep their own keys, as do lists
+//! of note ids like the manual order and the pinboard.
+
+use serde_json::{Map, Value};
+use tauri::Runtime;
+use tauri_plugin_store::StoreExt;
+
+use crate::backend::NotesBackend;
+use crate::dimming::{self, FocusDimming};
+use crate::limits::{DEFAULT_MAX_ATTACHMENT_BYTES, DEFAULT_MAX_NOTE_BYTES};
+use crate::sort::SortSettings;
+use crate::{appearance, batch, cache, flush, history, recycle, reminders, restore, suspect, theme, usage};
+
+/// The settings.bin key holding `Settings`.
+pub const SETTINGS_KEY: &str = "app_settings";
+pub const MIN_FONT_SIZE: f64 = 8.0;
+pub const MAX_FONT_SIZE: f64 = 48.0;
+pub const MIN_ZOOM: f64 = 0.5;
+pub const MAX_ZOOM: f64 = 3.0;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Follows the OS
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: Theme,
+    /// `None` for the frontend's default
+    pub font_family: Option<String>,
+    /// CSS pixels
+    pub font_size: f64,
+    /// Of note windows, 1.0 is 100%. Notes can override these three, see `appearance.rs`
+    pub zoom: f64,
+    /// Tidy markdown on every save, see `tidy.rs`
+    pub tidy_on_save: bool,
+    /// Count feature use locally, see `usage.rs`
+    pub collect_local_usage: bool,
+    /// Flush note writes to the device before they count as saved
+    pub fsync_saves: bool,
+    /// Seconds without activity before batched writes are flushed
+    pub idle_flush_secs: u64,
+    /// Trashed notes older than this are purged at startup; 0 keeps them
+    pub trash_retention_days: u64,
+    pub history_max_snapshots: usize,
+    pub max_note_bytes: usize,
+    pub max_attachment_bytes: usize,
+    /// A file shrinking below this fraction of its size outside the app is suspect
+    pub suspect_shrink_fraction: f64,
+    /// Also send events to windows that never subscribed, for older frontends
+    pub broadcast_unsubscribed_events: bool,
+    /// How long a restored window may take to build; read at launch
+    pub window_create_timeout_ms: u64,
+    /// Longest a show-all pass holds back focus events; read at launch
+    pub batch_focus_max_ms: u64,
+    /// Preview cache bounds; read at launch
+    pub preview_cache_max_entries: usize,
+    pub preview_cache_max_bytes: usize,
+    /// Default order of note listings, see `sort.rs`
+    pub note_sort: SortSettings,
+    /// Fading unfocused notes, see `dimming.rs`
+    pub focus_dimming: FocusDimming,
+    /// Keep the reminders calendar file, see `calendar.rs`
+    pub calendar_file: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            theme: Theme::System,
+            font_family: None,
+            font_size: 14.0,
+            zoom: 1.0,
+            tidy_on_save: false,
+            collect_local_usage: false,
+            fsync_saves: true,
+            idle_flush_secs: flush::DEFAULT_IDLE_SECS,
+            trash_retention_days: recycle::DEFAULT_TRASH_RETENTION_DAYS,
+            history_max_snapshots: history::DEFAULT_MAX_SNAPSHOTS,
+            max_note_bytes: DEFAULT_MAX_NOTE_BYTES,
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            suspect_shrink_fraction: suspect::DEFAULT_SHRINK_FRACTION,
+            broadcast_unsubscribed_events: true,
+            window_create_timeout_ms: restore::DEFAULT_CREATE_TIMEOUT_MS,
+            batch_focus_max_ms: batch::DEFAULT_MAX_BATCH_MS,
+            preview_cache_max_entries: cache::DEFAULT_MAX_ENTRIES,
+            preview_cache_max_bytes: cache::DEFAULT_MAX_BYTES,
+            note_sort: SortSettings::default(),
+            focus_dimming: FocusDimming::default(),
+            calendar_file: true,
+        }
+    }
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), String> {
+        if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&self.font_size) {
+            return Err(format!("font_size must be {} to {}", MIN_FONT_SIZE, MAX_FONT_SIZE));
+        }
+        if !(MIN_ZOOM..=MAX_ZOOM).contains(&self.zoom) {
+            return Err(format!("zoom must be {} to {}", MIN_ZOOM, MAX_ZOOM));
+        }
+        if self
+            .font_family
+            .as_deref()
+            .is_some_and(|family| family.trim().is_empty())
+        {
+            return Err("font_family must not be blank; use null for the default".to_string());
+        }
+        if self.idle_flush_secs == 0 {
+            return Err("idle_flush_secs must be at least 1".to_string());
+        }
+        if self.max_note_bytes < 1024 || self.max_attachment_bytes < 1024 {
+            return Err("Note and attachment limits must be at least 1024 bytes".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.suspect_shrink_fraction) {
+            return Err("suspect_shrink_fraction must be between 0 and 1".to_string());
+        }
+        if self.window_create_timeout_ms < 1000 {
+            return Err("window_create_timeout_ms must be at least 1000".to_string());
+        }
+        if !(0.1..=1.0).contains(&self.focus_dimming.level) {
+            return Err(format!(
+                "Dim level must be between 0.1 and 1.0, got {}",
+                self.focus_dimming.level
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The settings, with defaults for anything missing.
+pub fn current(backend: &impl NotesBackend) -> Settings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// `settings` with `patch` applied field by field. Fields that don't exist are refused,
+/// so a typo doesn't pass for a change.
+fn patched(settings: &Settings, patch: Map<String, Value>) -> Result<Settings, String> {
+    let mut value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    let fields = value.as_object_mut().ok_or("Settings are not an object")?;
+    for (key, field) in patch {
+        if !fields.contains_key(&key) {
+            return Err(format!("Unknown setting {:?}", key));
+        }
+        fields.insert(key, field);
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Applies `patch`, saves and tells every window; a new theme is applied to them too.
+pub fn update<R: Runtime>(app: &tauri::AppHandle<R>, patch: Map<String, Value>) -> Result<Settings, String> {
+    let previous = current(app);
+    let settings = patched(&previous, patch)?;
+    settings.validate()?;
+    let value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+    app.write_store("settings.bin", SETTINGS_KEY, value)?;
+    usage::set_enabled(app, settings.collect_local_usage);
+    if settings.theme != previous.theme {
+        theme::apply(app);
+    }
+    if (&settings.font_family, settings.font_size, settings.zoom)
+        != (&previous.font_family, previous.font_size, previous.zoom)
+    {
+        appearance::refresh_all(app);
+    }
+    if settings.focus_dimming != previous.focus_dimming {
+        dimming::refresh_all(app);
+    }
+    if settings.calendar_file != previous.calendar_file {
+        reminders::write_calendar(app);
+    }
+    app.emit_event("settings-changed", settings.clone());
+    Ok(settings)
+}
+
+/// `settings` with the top-level keys `old` has for any of its fields moved in, and the
+/// keys that were found. Values of the wrong type are dropped for what `settings` had.
+fn with_legacy_keys(mut settings: Settings, old: impl Fn(&str) -> Option<Value>) -> (Settings, Vec<String>) {
+    let keys: Vec<String> = match serde_json::to_value(&settings) {
+        Ok(Value::Object(fields)) => fields.into_iter().map(|(key, _)| key).collect(),
+        _ => return (settings, vec![]),
+    };
+    let mut moved = Vec::new();
+    for key in keys {
+        let Some(value) = old(&key) else {
+            continue;
+        };
+        match patched(&settings, Map::from_iter([(key.clone(), value)])) {
+            Ok(with_old) => settings = with_old,
+            Err(e) => println!("Settings: dropping old {} ({})", key, e),
+        }
+        moved.push(key);
+    }
+    (settings, moved)
+}
+
+/// Moves top-level keys named like a field into `app_settings`: all of them the first
+/// time, and later ones like `note_sort` and `focus_dimming` whenever a switch that had
+/// its own key joins `Settings`. Runs before anything reads a setting.
+pub fn migrate<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let existing = app.read_store("settings.bin", SETTINGS_KEY).is_some();
+    let Ok(store) = app.store("settings.bin") else {
+        return;
+    };
+    let (settings, moved) = with_legacy_keys(current(app), |key| store.get(key));
+    if existing && moved.is_empty() {
+        return;
+    }
+    let value = match serde_json::to_value(&settings) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Settings: migration failed: {}", e);
+            return;
+        }
+    };
+    store.set(SETTINGS_KEY, value);
+    for key in &moved {
+        store.delete(key);
+    }
+    match store.save() {
+        Ok(()) => println!("Settings: moved {} old keys into {}", moved.len(), SETTINGS_KEY),
+        Err(e) => println!("Settings: failed to save migrated settings: {}", e),
+    }
+}
+
+#[tauri::command]
+pub async fn get_settings(app: tauri::AppHandle) -> Result<Settings, String> {
+    Ok(current(&app))
+}
+
+/// Changes the fields in `patch` (e.g. `{"theme": "dark"}`) and returns all settings.
+#[tauri::command]
+pub async fn update_settings(patch: Map<String, Value>, app: tauri::AppHandle) -> Result<Settings, String> {
+    update(&app, patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+    use serde_json::json;
+
+    fn patch(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(fields) => fields,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn patches_change_only_their_fields() {
+        let settings = patched(
+            &Settings::default(),
+            patch(json!({ "theme": "dark", "font_size": 16.0 })),
+        )
+        .unwrap();
+        assert_eq!(settings.theme, Theme::Dark);
+        assert_eq!(settings.font_size, 16.0);
+        assert_eq!(settings.zoom, Settings::default().zoom);
+        assert!(settings.fsync_saves);
+    }
+
+    #[test]
+    fn unknown_and_mistyped_fields_are_refused() {
+        let error = patched(&Settings::default(), patch(json!({ "font_szie": 16.0 }))).unwrap_err();
+        assert!(error.contains("font_szie"), "{}", error);
+        assert!(patched(&Settings::default(), patch(json!({ "zoom": "big" }))).is_err());
+        assert!(patched(&Settings::default(), patch(json!({ "theme": "sepia" }))).is_err());
+    }
+
+    #[test]
+    fn validation_checks_ranges() {
+        assert!(Settings::default().validate().is_ok());
+        for bad in [
+            json!({ "font_size": 4.0 }),
+            json!({ "zoom": 5.0 }),
+            json!({ "font_family": "  " }),
+            json!({ "idle_flush_secs": 0 }),
+            json!({ "max_note_bytes": 10 }),
+            json!({ "suspect_shrink_fraction": 1.5 }),
+            json!({ "window_create_timeout_ms": 10 }),
+            json!({ "focus_dimming": { "enabled": true, "level": 0.0 } }),
+        ] {
+            let settings = patched(&Settings::default(), patch(bad.clone())).unwrap();
+            assert!(settings.validate().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn legacy_keys_move_into_existing_settings() {
+        let settings = Settings {
+            zoom: 1.5,
+            ..Default::default()
+        };
+        let old = |key: &str| match key {
+            "note_sort" => Some(json!({ "sort": "title_asc", "pinned_first": true })),
+            "focus_dimming" => Some(json!({ "enabled": true, "level": "dim" })),
+            "manual_order" => Some(json!(["a", "b"])),
+            _ => None,
+        };
+
+        let (settings, mut moved) = with_legacy_keys(settings, old);
+        moved.sort();
+        assert_eq!(moved, ["focus_dimming", "note_sort"]);
+        assert_eq!(settings.note_sort.sort, crate::sort::NoteSort::TitleAsc);
+        assert!(settings.note_sort.pinned_first);
+        // Mistyped, so left at the default
+        assert!(!settings.focus_dimming.enabled);
+        assert_eq!(settings.zoom, 1.5);
+    }
+
+    #[test]
+    fn stored_settings_fill_in_defaults() {
+        let backend = TempBackend::new();
+        assert_eq!(current(&backend).font_size, Settings::default().font_size);
+        backend
+            .write_store("settings.bin", SETTINGS_KEY, json!({ "theme": "light", "zoom": 1.5 }))
+            .unwrap();
+        let settings = current(&backend);
+        assert_eq!((settings.theme, settings.zoom), (Theme::Light, 1.5));
+        assert_eq!(settings.idle_flush_secs, flush::DEFAULT_IDLE_SECS);
+    }
+}
@@ -0,0 +1,170 @@
+//! Sharing a note with a phone on the same network: `share_note` renders the note as a
+//! web page and serves it from a temporary HTTP endpoint on this machine's LAN address,
+//! under an unguessable path. The URL comes back with a QR code of it to scan.
+//!
+//! The page is what the note said when it was shared. It is served until `SHARE_TTL`
+//! runs out, `stop_sharing_note` is called, or the note is shared again.
+
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::collections::HashMap;
+use std::net::{IpAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
+use tiny_http::{Header, Response, Server};
+use uuid::Uuid;
+
+use crate::applock::AppLock;
+use crate::backend::NotesBackend;
+use crate::encryption;
+use crate::export::{document_title, render_html};
+use crate::usage::record_usage;
+use crate::{now_millis, read_note};
+
+const SHARE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The servers of the notes being shared, by note id.
+#[derive(Default)]
+pub struct NoteShares(Mutex<HashMap<String, Arc<Server>>>);
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct SharedNote {
+    /// What the QR code encodes
+    url: String,
+    /// The QR code as an SVG image
+    qr_svg: String,
+    /// Unix milliseconds
+    expires_at: u64,
+}
+
+/// The address other devices on the network reach this machine at. Connecting a UDP
+/// socket sends nothing; it only makes the OS pick the interface it would route through.
+fn lan_address() -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    socket
+        .connect("192.0.2.1:80")
+        .and_then(|_| socket.local_addr())
+        .map(|addr| addr.ip())
+        .ok()
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        .ok_or_else(|| "Not connected to a network".to_string())
+}
+
+fn serve(server: Arc<Server>, path: String, page: String) {
+    for request in server.incoming_requests() {
+        let response = if request.url() == path {
+            let content_type = Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..]).ok();
+            let mut response = Response::from_string(page.clone());
+            if let Some(header) = content_type {
+                response = response.with_header(header);
+            }
+            response
+        } else {
+            Response::from_string("Not found").with_status_code(404)
+        };
+        let _ = request.respond(response);
+    }
+}
+
+/// Note `id` as stored. Refused while the app is locked, as `load_note` is: a share would
+/// hand the note to anyone on the network.
+fn read_for_sharing(lock: Option<&AppLock>, backend: &impl NotesBackend, id: &str) -> Result<String, String> {
+    if let Some(lock) = lock {
+        lock.ensure_unlocked()?;
+    }
+    read_note(backend, id)
+}
+
+/// Stops serving note `id`, returning whether it was being shared.
+fn stop<R: tauri::Runtime>(app: &tauri::AppHandle<R>, id: &str) -> bool {
+    let state = app.state::<NoteShares>();
+    let Ok(mut shares) = state.0.lock() else {
+        return false;
+    };
+    match shares.remove(id) {
+        Some(server) => {
+            server.unblock();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+pub async fn share_note(id: String, app: tauri::AppHandle) -> Result<SharedNote, String> {
+    let stored = read_for_sharing(app.try_state::<AppLock>().as_deref(), &app, &id)?;
+    let content = encryption::open(&app, &id, stored)?;
+    let page = render_html(&document_title(&content), &content);
+    let ip = lan_address()?;
+
+    stop(&app, &id);
+    let server = Server::http((ip, 0)).map(Arc::new).map_err(|e| e.to_string())?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or("Could not tell which port the share is on")?;
+    let path = format!("/{}", Uuid::new_v4().simple());
+    let url = format!("http://{}/{}", std::net::SocketAddr::new(ip, port), &path[1..]);
+    let qr_svg = QrCode::new(url.as_bytes())
+        .map_err(|e| e.to_string())?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+
+    if let Ok(mut shares) = app.state::<NoteShares>().0.lock() {
+        shares.insert(id.clone(), server.clone());
+    }
+    let serving = server.clone();
+    std::thread::spawn(move || serve(serving, path, page));
+
+    // Ends this share when it expires, unless it was stopped or replaced since
+    let handle = app.clone();
+    let note_id = id.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SHARE_TTL).await;
+        let state = handle.state::<NoteShares>();
+        let Ok(mut shares) = state.0.lock() else {
+            return;
+        };
+        if shares
+            .get(&note_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &server))
+        {
+            shares.remove(&note_id);
+            server.unblock();
+        }
+    });
+
+    record_usage(&app, "share_note");
+    println!("Sharing {} at {} for {:?}", id, url, SHARE_TTL);
+    Ok(SharedNote {
+        url,
+        qr_svg,
+        expires_at: now_millis() + SHARE_TTL.as_millis() as u64,
+    })
+}
+
+/// Stops sharing note `id`. Returns whether it was being shared.
+#[tauri::command]
+pub async fn stop_sharing_note(id: String, app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(stop(&app, &id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempBackend;
+
+    #[test]
+    fn sharing_is_refused_while_the_app_is_locked() {
+        let backend = TempBackend::new();
+        backend.put_note("a", "# Groceries");
+
+        let error = read_for_sharing(Some(&AppLock::new(true)), &backend, "a").unwrap_err();
+        assert!(error.starts_with("AppLocked"), "{}", error);
+        let shared = read_for_sharing(Some(&AppLock::new(false)), &backend, "a").unwrap();
+        assert_eq!(shared, "# Groceries");
+    }
+}
@@ -0,0 +1,588 @@
+//! Global shortcuts. Features declare their actions here (with an optional default
+//! accelerator) instead of talking to the plugin; the user's rebindings are kept in
+//! settings.bin `shortcut_bindings`. `apply` brings the plugin's registrations and the
+//! dispatch map in line with that table, and is safe to call again whenever the two may
+//! have drifted (after a rebind, or once the plugin lost its registrations).
+//!
+//! An accelerator always maps to one action: `apply` registers what's missing, swaps
+//! the dispatch map in one step, then unregisters what's stale, so a key moved from one
+//! action to another never fires both.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::{Manager, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcut, GlobalShortcutExt, Shortcut};
+
+use crate::backend::NotesBackend;
+use crate::capture;
+use crate::clickthrough;
+use crate::daily;
+use crate::error::NoteError;
+use crate::notemenu;
+use crate::notewindow::NoteWindowOptions;
+use crate::quickcapture;
+use crate::usage::record_usage;
+use crate::{create_note_window, show_dashboard, tray};
+
+const BINDINGS_KEY: &str = "shortcut_bindings";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShortcutAction {
+    NewNote,
+    ShowAll,
+    /// Hides every note, or shows them all when all are hidden
+    ToggleAll,
+    Dashboard,
+    /// A new note holding the clipboard's text or image, opened at the cursor
+    NewNoteFromClipboard,
+    /// The quick-capture popup, for a note without a note window
+    QuickCapture,
+    /// Reopens the note whose window was closed last
+    ReopenLastClosed,
+    /// Makes every click-through note take the mouse again
+    ReleaseClickThrough,
+    /// Opens today's daily note, making it if needed
+    OpenDailyNote,
+    /// Opens (or focuses) one note
+    OpenNote {
+        id: String,
+    },
+}
+
+impl ShortcutAction {
+    fn describe(&self) -> String {
+        match self {
+            ShortcutAction::NewNote => "new note".to_string(),
+            ShortcutAction::ShowAll => "show all".to_string(),
+            ShortcutAction::ToggleAll => "hide/show all".to_string(),
+            ShortcutAction::Dashboard => "dashboard".to_string(),
+            ShortcutAction::NewNoteFromClipboard => "new note from clipboard".to_string(),
+            ShortcutAction::QuickCapture => "quick capture".to_string(),
+            ShortcutAction::ReopenLastClosed => "reopen last closed note".to_string(),
+            ShortcutAction::ReleaseClickThrough => "make notes clickable".to_string(),
+            ShortcutAction::OpenDailyNote => "today's note".to_string(),
+            ShortcutAction::OpenNote { id } => format!("note {}", id),
+        }
+    }
+
+    fn usage_key(&self) -> &'static str {
+        match self {
+            ShortcutAction::NewNote => "new_note_shortcut",
+            ShortcutAction::ShowAll => "show_all_shortcut",
+            ShortcutAction::ToggleAll => "toggle_all_shortcut",
+            ShortcutAction::Dashboard => "dashboard_shortcut",
+            ShortcutAction::NewNoteFromClipboard => "clipboard_note_shortcut",
+            ShortcutAction::QuickCapture => "quick_capture_shortcut",
+            ShortcutAction::ReopenLastClosed => "reopen_closed_shortcut",
+            ShortcutAction::ReleaseClickThrough => "release_click_through_shortcut",
+            ShortcutAction::OpenDailyNote => "daily_note_shortcut",
+            ShortcutAction::OpenNote { .. } => "open_note_shortcut",
+        }
+    }
+}
+
+/// A binding the user changed; `accelerator: None` unbinds a declared default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+struct StoredBinding {
+    action: ShortcutAction,
+    accelerator: Option<String>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ShortcutBinding {
+    action: ShortcutAction,
+    accelerator: Option<String>,
+    default_accelerator: Option<String>,
+    /// Registered with the OS and dispatching
+    active: bool,
+    /// Why a bound accelerator isn't active (conflict, unparsable, taken by another app)
+    error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct ShortcutManager {
+    /// Actions features offer, with their default accelerators, in declaration order
+    declared: Mutex<Vec<(ShortcutAction, Option<String>)>>,
+    /// What the handler dispatches on; replaced whole by `apply`
+    dispatch: RwLock<Arc<HashMap<Shortcut, ShortcutAction>>>,
+    /// Why bound actions aren't active, from the last `apply`
+    errors: Mutex<HashMap<ShortcutAction, String>>,
+    /// One `apply` at a time
+    applying: Mutex<()>,
+}
+
+/// Offers `action` as a shortcut target, bound to `default` unless the user rebound it.
+pub fn declare<R: Runtime>(app: &tauri::AppHandle<R>, action: ShortcutAction, default: Option<&str>) {
+    let state = app.state::<ShortcutManager>();
+    let Ok(mut declared) = state.declared.lock() else {
+        return;
+    };
+    if !declared.iter().any(|(a, _)| *a == action) {
+        declared.push((action, default.map(str::to_string)));
+    }
+}
+
+fn load_stored(backend: &impl NotesBackend) -> Vec<StoredBinding> {
+    backend
+        .read_store("settings.bin", BINDINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn save_stored(backend: &impl NotesBackend, stored: &[StoredBinding]) -> Result<(), String> {
+    let value = serde_json::to_value(stored).map_err(|e| e.to_string())?;
+    backend.write_store("settings.bin", BINDINGS_KEY, value)
+}
+
+/// Declared actions with their defaults, then per-note bindings, with the user's
+/// rebindings applied; the order decides which side of a conflict loses.
+fn desired<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<(ShortcutAction, Option<String>, Option<String>)> {
+    let declared = app
+        .state::<ShortcutManager>()
+        .declared
+        .lock()
+        .map(|d| d.clone())
+        .unwrap_or_default();
+    let stored = load_stored(app);
+    let mut table: Vec<_> = declared
+        .into_iter()
+        .map(|(action, default)| {
+            let accelerator = match stored.iter().find(|b| b.action == action) {
+                Some(binding) => binding.accelerator.clone(),
+                None => default.clone(),
+            };
+            (action, accelerator, default)
+        })
+        .collect();
+    for binding in stored {
+        if !table.iter().any(|(action, _, _)| *action == binding.action) {
+            table.push((binding.action, binding.accelerator, None));
+        }
+    }
+    table
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    Shortcut::from_str(accelerator).map_err(|e| format!("Invalid accelerator {:?}: {}", accelerator, e))
+}
+
+/// The OS side of the bindings: the plugin, or a stand-in in tests.
+trait Registrar {
+    fn is_registered(&self, shortcut: Shortcut) -> bool;
+    fn register(&self, shortcut: Shortcut) -> Result<(), String>;
+    fn unregister(&self, shortcut: Shortcut) -> Result<(), String>;
+}
+
+impl<R: Runtime> Registrar for GlobalShortcut<R> {
+    fn is_registered(&self, shortcut: Shortcut) -> bool {
+        GlobalShortcut::is_registered(self, shortcut)
+    }
+
+    fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+        GlobalShortcut::register(self, shortcut).map_err(|e| e.to_string())
+    }
+
+    fn unregister(&self, shortcut: Shortcut) -> Result<(), String> {
+        GlobalShortcut::unregister(self, shortcut).map_err(|e| e.to_string())
+    }
+}
+
+/// Makes the plugin's registrations and the dispatch map match the binding table.
+pub fn apply<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let state = app.state::<ShortcutManager>();
+    let Ok(_applying) = state.applying.lock() else {
+        return;
+    };
+    apply_table(&state, app.global_shortcut(), desired(app));
+}
+
+/// `apply` for a given table; the caller holds `applying`.
+fn apply_table(
+    state: &ShortcutManager,
+    registrar: &impl Registrar,
+    table: Vec<(ShortcutAction, Option<String>, Option<String>)>,
+) {
+    let mut wanted: HashMap<Shortcut, ShortcutAction> = HashMap::new();
+    let mut errors = HashMap::new();
+    for (action, accelerator, _) in table {
+        let Some(accelerator) = accelerator else {
+            continue;
+        };
+        let shortcut = match parse(&accelerator) {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                errors.insert(action, e);
+                continue;
+            }
+        };
+        if let Some(taken_by) = wanted.get(&shortcut) {
+            let e = NoteError::ShortcutConflict {
+                accelerator,
+                action: taken_by.describe(),
+            };
+            errors.insert(action, e.to_string());
+            continue;
+        }
+        if !registrar.is_registered(shortcut) {
+            if let Err(e) = registrar.register(shortcut) {
+                errors.insert(action, format!("Could not register {}: {}", accelerator, e));
+                continue;
+            }
+        }
+        wanted.insert(shortcut, action);
+    }
+
+    let previous = match state.dispatch.write() {
+        Ok(mut dispatch) => std::mem::replace(&mut *dispatch, Arc::new(wanted.clone())),
+        Err(_) => return,
+    };
+    for shortcut in previous.keys().filter(|s| !wanted.contains_key(*s)) {
+        if let Err(e) = registrar.unregister(*shortcut) {
+            println!("Failed to unregister shortcut {:?}: {}", shortcut, e);
+        }
+    }
+
+    for (action, e) in &errors {
+        println!("Shortcut for {:?} not active: {}", action, e);
+    }
+    if let Ok(mut last) = state.errors.lock() {
+        *last = errors;
+    }
+}
+
+/// Runs the action bound to `shortcut`; called from the plugin's handler on key press.
+pub fn dispatch<R: Runtime>(app: &tauri::AppHandle<R>, shortcut: &Shortcut) {
+    let action = match app.state::<ShortcutManager>().dispatch.read() {
+        Ok(dispatch) => dispatch.get(shortcut).cloned(),
+        Err(_) => None,
+    };
+    let Some(action) = action else {
+        return;
+    };
+    record_usage(app, action.usage_key());
+    let result = match &action {
+        ShortcutAction::NewNote => create_note_window(app, NoteWindowOptions::new_note()).map(|_| ()),
+        ShortcutAction::ShowAll => {
+            tray::show_all(app);
+            Ok(())
+        }
+        ShortcutAction::ToggleAll => {
+            tray::toggle_all(app);
+            Ok(())
+        }
+        ShortcutAction::Dashboard => {
+            show_dashboard(app);
+            Ok(())
+        }
+        ShortcutAction::NewNoteFromClipboard => capture::note_from_clipboard(app).map(|_| ()),
+        ShortcutAction::QuickCapture => quickcapture::show_popup(app),
+        ShortcutAction::ReopenLastClosed => notemenu::reopen_last_closed(app).map(|_| ()),
+        ShortcutAction::ReleaseClickThrough => clickthrough::release_all(app).map(|_| ()),
+        ShortcutAction::OpenDailyNote => daily::open_daily(app).map(|_| ()),
+        ShortcutAction::OpenNote { id } => create_note_window(app, NoteWindowOptions::open(id)).map(|_| ()),
+    };
+    if let Err(e) = result {
+        println!("Shortcut action {:?} failed: {}", action, e);
+    }
+}
+
+fn bindings<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<ShortcutBinding> {
+    let state = app.state::<ShortcutManager>();
+    let errors = state.errors.lock().map(|e| e.clone()).unwrap_or_default();
+    let active: HashSet<ShortcutAction> = state
+        .dispatch
+        .read()
+        .map(|d| d.values().cloned().collect())
+        .unwrap_or_default();
+    desired(app)
+        .into_iter()
+        .map(|(action, accelerator, default_accelerator)| ShortcutBinding {
+            active: active.contains(&action),
+            error: errors.get(&action).cloned(),
+            action,
+            accelerator,
+            default_accelerator,
+        })
+        .collect()
+}
+
+/// Every shortcut action with its accelerator and whether it is working.
+#[tauri::command]
+pub async fn get_shortcut_bindings(app: tauri::AppHandle) -> Result<Vec<ShortcutBinding>, String> {
+    Ok(bindings(&app))
+}
+
+/// Binds `action` to `accelerator` (e.g. "Alt+Shift+N"), or unbinds it with `None`.
+/// Fails with `ShortcutConflict` if another action already has the accelerator.
+#[tauri::command]
+pub async fn set_shortcut_binding(
+    action: ShortcutAction,
+    accelerator: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Vec<ShortcutBinding>, String> {
+    if let Some(accelerator) = &accelerator {
+        let shortcut = parse(accelerator)?;
+        for (other, other_accelerator, _) in desired(&app) {
+            if other == action {
+                continue;
+            }
+            if other_accelerator.and_then(|a| parse(&a).ok()) == Some(shortcut) {
+                return Err(NoteError::ShortcutConflict {
+                    accelerator: accelerator.clone(),
+                    action: other.describe(),
+                }
+                .into());
+            }
+        }
+    }
+
+    let previous = load_stored(&app);
+    let mut stored = previous.clone();
+    stored.retain(|b| b.action != action);
+    let is_per_note = matches!(action, ShortcutAction::OpenNote { .. });
+    // Unbinding a per-note shortcut just forgets it
+    if accelerator.is_some() || !is_per_note {
+        stored.push(StoredBinding {
+            action: action.clone(),
+            accelerator,
+        });
+    }
+    save_stored(&app, &stored)?;
+    apply(&app);
+
+    // The OS may refuse an accelerator another app holds; keep the old binding then
+    let refused = app
+        .state::<ShortcutManager>()
+        .errors
+        .lock()
+        .ok()
+        .and_then(|errors| errors.get(&action).cloned());
+    if let Some(e) = refused {
+        save_stored(&app, &previous)?;
+        apply(&app);
+        return Err(e);
+    }
+    Ok(bindings(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const K1: &str = "Alt+Shift+N";
+    const K2: &str = "Alt+Shift+D";
+    const K3: &str = "Alt+Shift+J";
+
+    /// Records, at every call the pass makes, what each key would do if pressed then.
+    struct MockRegistrar<'a> {
+        state: &'a ShortcutManager,
+        registered: RefCell<HashSet<Shortcut>>,
+        log: RefCell<Vec<(String, HashMap<Shortcut, ShortcutAction>)>>,
+    }
+
+    impl<'a> MockRegistrar<'a> {
+        fn new(state: &'a ShortcutManager) -> Self {
+            MockRegistrar {
+                state,
+                registered: RefCell::new(HashSet::new()),
+                log: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// Keys that reach an action: registered with the OS and in the dispatch map.
+        fn live(&self) -> HashMap<Shortcut, ShortcutAction> {
+            let dispatch = self.state.dispatch.read().unwrap().clone();
+            let registered = self.registered.borrow();
+            dispatch
+                .iter()
+                .filter(|(shortcut, _)| registered.contains(*shortcut))
+                .map(|(shortcut, action)| (*shortcut, action.clone()))
+                .collect()
+        }
+
+        fn record(&self, call: &str, shortcut: Shortcut) {
+            let live = self.live();
+            self.log.borrow_mut().push((format!("{} {:?}", call, shortcut), live));
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.log.borrow().iter().map(|(call, _)| call.clone()).collect()
+        }
+    }
+
+    impl Registrar for MockRegistrar<'_> {
+        fn is_registered(&self, shortcut: Shortcut) -> bool {
+            self.registered.borrow().contains(&shortcut)
+        }
+
+        fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+            self.record("register", shortcut);
+            self.registered.borrow_mut().insert(shortcut);
+            Ok(())
+        }
+
+        fn unregister(&self, shortcut: Shortcut) -> Result<(), String> {
+            self.record("unregister", shortcut);
+            self.registered.borrow_mut().remove(&shortcut);
+            Ok(())
+        }
+    }
+
+    fn key(accelerator: &str) -> Shortcut {
+        parse(accelerator).unwrap()
+    }
+
+    fn bind(action: ShortcutAction, accelerator: Option<&str>) -> (ShortcutAction, Option<String>, Option<String>) {
+        (action, accelerator.map(str::to_string), None)
+    }
+
+    fn fired_by(live: &HashMap<Shortcut, ShortcutAction>, accelerator: &str) -> Option<ShortcutAction> {
+        live.get(&key(accelerator)).cloned()
+    }
+
+    #[test]
+    fn moving_an_accelerator_to_another_action_switches_in_one_step() {
+        let state = ShortcutManager::default();
+        let registrar = MockRegistrar::new(&state);
+        apply_table(
+            &state,
+            &registrar,
+            vec![
+                bind(ShortcutAction::NewNote, Some(K1)),
+                bind(ShortcutAction::Dashboard, Some(K2)),
+            ],
+        );
+        registrar.log.borrow_mut().clear();
+
+        // K1 moves from new note to the dashboard; new note is left unbound
+        apply_table(
+            &state,
+            &registrar,
+            vec![
+                bind(ShortcutAction::NewNote, None),
+                bind(ShortcutAction::Dashboard, Some(K1)),
+            ],
+        );
+
+        // K1 was never dropped, so the only OS call is letting go of K2, after the swap
+        assert_eq!(registrar.calls(), vec![format!("unregister {:?}", key(K2))]);
+        let (_, during) = &registrar.log.borrow()[0];
+        assert_eq!(fired_by(during, K1), Some(ShortcutAction::Dashboard));
+        // Still registered at that point, but already reaching nothing
+        assert_eq!(fired_by(during, K2), None);
+
+        let after = registrar.live();
+        assert_eq!(fired_by(&after, K1), Some(ShortcutAction::Dashboard));
+        assert_eq!(fired_by(&after, K2), None);
+        assert!(!registrar.is_registered(key(K2)));
+    }
+
+    #[test]
+    fn swapping_two_accelerators_touches_no_registration() {
+        let state = ShortcutManager::default();
+        let registrar = MockRegistrar::new(&state);
+        apply_table(
+            &state,
+            &registrar,
+            vec![
+                bind(ShortcutAction::NewNote, Some(K1)),
+                bind(ShortcutAction::Dashboard, Some(K2)),
+            ],
+        );
+        registrar.log.borrow_mut().clear();
+
+        apply_table(
+            &state,
+            &registrar,
+            vec![
+                bind(ShortcutAction::NewNote, Some(K2)),
+                bind(ShortcutAction::Dashboard, Some(K1)),
+            ],
+        );
+
+        assert!(registrar.calls().is_empty(), "{:?}", registrar.calls());
+        let after = registrar.live();
+        assert_eq!(fired_by(&after, K1), Some(ShortcutAction::Dashboard));
+        assert_eq!(fired_by(&after, K2), Some(ShortcutAction::NewNote));
+    }
+
+    #[test]
+    fn a_new_accelerator_is_registered_before_the_old_one_is_dropped() {
+        let state = ShortcutManager::default();
+        let registrar = MockRegistrar::new(&state);
+        apply_table(&state, &registrar, vec![bind(ShortcutAction::NewNote, Some(K1))]);
+        registrar.log.borrow_mut().clear();
+
+        apply_table(&state, &registrar, vec![bind(ShortcutAction::NewNote, Some(K3))]);
+
+        assert_eq!(
+            registrar.calls(),
+            vec![format!("register {:?}", key(K3)), format!("unregister {:?}", key(K1))]
+        );
+        let log = registrar.log.borrow();
+        // Before the swap only the old key works, after it only the new one
+        assert_eq!(fired_by(&log[0].1, K1), Some(ShortcutAction::NewNote));
+        assert_eq!(fired_by(&log[0].1, K3), None);
+        assert_eq!(fired_by(&log[1].1, K1), None);
+        assert_eq!(fired_by(&log[1].1, K3), Some(ShortcutAction::NewNote));
+    }
+
+    #[test]
+    fn no_key_ever_reaches_two_actions_during_a_rebind() {
+        let state = ShortcutManager::default();
+        let registrar = MockRegistrar::new(&state);
+        let before = vec![
+            bind(ShortcutAction::NewNote, Some(K1)),
+            bind(ShortcutAction::Dashboard, Some(K2)),
+            bind(ShortcutAction::ShowAll, Some(K3)),
+        ];
+        let after = vec![
+            bind(ShortcutAction::NewNote, Some(K3)),
+            bind(ShortcutAction::Dashboard, Some(K1)),
+            bind(ShortcutAction::ShowAll, None),
+        ];
+        apply_table(&state, &registrar, before);
+        apply_table(&state, &registrar, after);
+
+        // Every recorded moment is either the old table or the new one, never a mix
+        let old: HashMap<Shortcut, ShortcutAction> = [
+            (key(K1), ShortcutAction::NewNote),
+            (key(K2), ShortcutAction::Dashboard),
+            (key(K3), ShortcutAction::ShowAll),
+        ]
+        .into();
+        let new: HashMap<Shortcut, ShortcutAction> =
+            [(key(K1), ShortcutAction::Dashboard), (key(K3), ShortcutAction::NewNote)].into();
+        for (call, live) in registrar.log.borrow().iter() {
+            let agrees = |table: &HashMap<Shortcut, ShortcutAction>| live.iter().all(|(k, a)| table.get(k) == Some(a));
+            assert!(agrees(&old) || agrees(&new), "{}: {:?}", call, live);
+        }
+        assert_eq!(registrar.live(), new);
+    }
+
+    #[test]
+    fn a_conflict_fails_the_later_action() {
+        let state = ShortcutManager::default();
+        let registrar = MockRegistrar::new(&state);
+        apply_table(
+            &state,
+            &registrar,
+            vec![
+                bind(ShortcutAction::NewNote, Some(K1)),
+                bind(ShortcutAction::Dashboard, Some(K1)),
+                bind(ShortcutAction::ShowAll, Some("Alt+Nonsense")),
+            ],
+        );
+
+        assert_eq!(fired_by(&registrar.live(), K1), Some(ShortcutAction::NewNote));
+        let errors = state.errors.lock().unwrap();
+        let conflict = &errors[&ShortcutAction::Dashboard];
+        assert!(conflict.starts_with("ShortcutConflict"), "{}", conflict);
+        let invalid = &errors[&ShortcutAction::ShowAll];
+        assert!(invalid.starts_with("Invalid accelerator"), "{}", invalid);
+        assert!(!errors.contains_key(&ShortcutAction::NewNote));
+    }
+}
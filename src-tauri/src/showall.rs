@@ -0,0 +1,304 @@
+//! The tray's show-all pass. It works on a snapshot of the registry so windows created
+//! or closed while it runs can't make the ordering and the window list disagree; notes
+//! created mid-pass are left to the batch reconciliation in `batch`.
+
+use tauri::{Manager, Runtime};
+
+use crate::applock;
+use crate::focustrack::focus_programmatically;
+use crate::sort::compute_show_order;
+use crate::{get_session_order, WindowKind, WindowRegistry};
+
+/// The window operations the pass needs, keyed by label. Windows that no longer exist
+/// report not visible / not pinned and ignore the rest.
+pub trait WindowOps {
+    fn is_visible(&self, label: &str) -> bool;
+    fn is_pinned(&self, label: &str) -> bool;
+    fn set_pinned(&self, label: &str, pinned: bool);
+    /// Shows and unminimizes the window.
+    fn raise(&self, label: &str);
+    fn hide(&self, label: &str);
+    fn focus(&self, label: &str);
+}
+
+pub struct TauriWindows<'a, R: Runtime>(pub &'a tauri::AppHandle<R>);
+
+impl<R: Runtime> WindowOps for TauriWindows<'_, R> {
+    fn is_visible(&self, label: &str) -> bool {
+        self.0
+            .get_webview_window(label)
+            .is_some_and(|w| w.is_visible().unwrap_or(false))
+    }
+
+    fn is_pinned(&self, label: &str) -> bool {
+        self.0
+            .get_webview_window(label)
+            .is_some_and(|w| w.is_always_on_top().unwrap_or(false))
+    }
+
+    fn set_pinned(&self, label: &str, pinned: bool) {
+        if let Some(window) = self.0.get_webview_window(label) {
+            let _ = window.set_always_on_top(pinned);
+        }
+    }
+
+    fn raise(&self, label: &str) {
+        if applock::is_locked(self.0) {
+            return;
+        }
+        if let Some(window) = self.0.get_webview_window(label) {
+            let _ = window.show();
+            let _ = window.unminimize();
+        }
+    }
+
+    fn hide(&self, label: &str) {
+        if let Some(window) = self.0.get_webview_window(label) {
+            let _ = window.hide();
+        }
+    }
+
+    fn focus(&self, label: &str) {
+        if let Some(window) = self.0.get_webview_window(label) {
+            focus_programmatically(&window);
+        }
+    }
+}
+
+/// Note labels and session order, read together under the registry lock so a window
+/// registering concurrently is either fully in the snapshot or not at all.
+pub fn take_snapshot<R: Runtime>(app: &tauri::AppHandle<R>) -> (Vec<String>, Vec<String>) {
+    let registry = app.state::<WindowRegistry>();
+    let Ok(guard) = registry.0.read() else {
+        return (Vec::new(), Vec::new());
+    };
+    let labels = guard
+        .iter()
+        .filter(|(_, kind)| **kind == WindowKind::Note)
+        .map(|(label, _)| label.clone())
+        .collect();
+    let order = get_session_order(app);
+    drop(guard);
+    (labels, order)
+}
+
+/// Brings every visible snapshot window to the front in session order (oldest at the
+/// bottom) and focuses the newest. Returns the label it focused.
+pub fn show_all_pass(snapshot: &[String], session_order: &[String], ops: &impl WindowOps) -> Option<String> {
+    let mut labels = snapshot.to_vec();
+    compute_show_order(&mut labels, session_order);
+    labels.retain(|label| ops.is_visible(label));
+
+    // Capture pin state BEFORE we start manipulation
+    let pin_states: Vec<bool> = labels.iter().map(|label| ops.is_pinned(label)).collect();
+
+    // Pass 1: "The Hammer" - Bring all to front of OS stack
+    for label in &labels {
+        ops.raise(label);
+        ops.set_pinned(label, true);
+    }
+
+    // Pass 2: "The Release" - Restore original pin states
+    // This allows notes to drop back to normal Z-order but stay above other apps
+    for (label, &was_pinned) in labels.iter().zip(pin_states.iter()) {
+        ops.set_pinned(label, was_pinned);
+    }
+
+    // Final Focus on the topmost (newest) window
+    let top = labels.last()?;
+    ops.focus(top);
+    Some(top.clone())
+}
+
+/// The "boss key": hides every snapshot window if any is visible, otherwise shows them
+/// all through the show-all pass. Returns whether the notes are visible afterwards.
+pub fn toggle_all_pass(snapshot: &[String], session_order: &[String], ops: &impl WindowOps) -> bool {
+    if snapshot.iter().any(|label| ops.is_visible(label)) {
+        for label in snapshot {
+            ops.hide(label);
+        }
+        return false;
+    }
+    for label in snapshot {
+        ops.raise(label);
+    }
+    show_all_pass(snapshot, session_order, ops);
+    !snapshot.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[derive(Clone, Copy, Default)]
+    struct MockWindow {
+        visible: bool,
+        pinned: bool,
+    }
+
+    /// Windows as plain state, plus a log of every call that changed something.
+    #[derive(Default)]
+    struct MockWindows {
+        windows: RefCell<HashMap<String, MockWindow>>,
+        calls: RefCell<Vec<String>>,
+        /// Opened by the first `raise`, like a shortcut creating a note mid-pass
+        created_mid_pass: Option<String>,
+    }
+
+    impl MockWindows {
+        fn with(windows: &[(&str, bool, bool)]) -> Self {
+            let windows = windows
+                .iter()
+                .map(|(label, visible, pinned)| {
+                    let window = MockWindow {
+                        visible: *visible,
+                        pinned: *pinned,
+                    };
+                    (label.to_string(), window)
+                })
+                .collect();
+            MockWindows {
+                windows: RefCell::new(windows),
+                ..MockWindows::default()
+            }
+        }
+
+        fn get(&self, label: &str) -> Option<MockWindow> {
+            self.windows.borrow().get(label).copied()
+        }
+
+        fn update(&self, label: &str, call: String, change: impl FnOnce(&mut MockWindow)) {
+            if let Some(window) = self.windows.borrow_mut().get_mut(label) {
+                change(window);
+                self.calls.borrow_mut().push(call);
+            }
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl WindowOps for MockWindows {
+        fn is_visible(&self, label: &str) -> bool {
+            self.get(label).is_some_and(|w| w.visible)
+        }
+
+        fn is_pinned(&self, label: &str) -> bool {
+            self.get(label).is_some_and(|w| w.pinned)
+        }
+
+        fn set_pinned(&self, label: &str, pinned: bool) {
+            self.update(label, format!("pin {} {}", label, pinned), |w| w.pinned = pinned);
+        }
+
+        fn raise(&self, label: &str) {
+            if let Some(created) = &self.created_mid_pass {
+                let mut windows = self.windows.borrow_mut();
+                if !windows.contains_key(created) {
+                    let window = MockWindow {
+                        visible: true,
+                        pinned: false,
+                    };
+                    windows.insert(created.clone(), window);
+                }
+            }
+            self.update(label, format!("raise {}", label), |w| w.visible = true);
+        }
+
+        fn hide(&self, label: &str) {
+            self.update(label, format!("hide {}", label), |w| w.visible = false);
+        }
+
+        fn focus(&self, label: &str) {
+            self.update(label, format!("focus {}", label), |_| {});
+        }
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn show_all_raises_in_session_order_and_focuses_the_newest() {
+        let ops = MockWindows::with(&[("note-a", true, false), ("note-b", true, true), ("note-c", true, false)]);
+        let snapshot = strings(&["note-c", "note-a", "note-b"]);
+        let order = strings(&["b", "a", "c"]);
+
+        assert_eq!(show_all_pass(&snapshot, &order, &ops).as_deref(), Some("note-c"));
+        let expected = [
+            "raise note-b",
+            "pin note-b true",
+            "raise note-a",
+            "pin note-a true",
+            "raise note-c",
+            "pin note-c true",
+            "pin note-b true",
+            "pin note-a false",
+            "pin note-c false",
+            "focus note-c",
+        ];
+        assert_eq!(ops.calls(), expected);
+    }
+
+    #[test]
+    fn show_all_skips_hidden_windows_and_those_missing_from_the_session() {
+        let ops = MockWindows::with(&[
+            ("note-a", true, false),
+            ("note-b", false, false),
+            ("note-x", true, false),
+        ]);
+        let snapshot = strings(&["note-a", "note-b", "note-x"]);
+        let order = strings(&["a", "b"]);
+
+        // Not in the session order puts `note-x` on top
+        assert_eq!(show_all_pass(&snapshot, &order, &ops).as_deref(), Some("note-x"));
+        assert!(ops.calls().iter().all(|call| !call.contains("note-b")));
+        assert!(!ops.is_pinned("note-a") && !ops.is_pinned("note-x"));
+    }
+
+    #[test]
+    fn windows_created_during_the_pass_are_left_alone() {
+        let mut ops = MockWindows::with(&[("note-a", true, false), ("note-b", true, false)]);
+        ops.created_mid_pass = Some("note-new".to_string());
+        let snapshot = strings(&["note-a", "note-b"]);
+        let order = strings(&["a", "b"]);
+
+        assert_eq!(show_all_pass(&snapshot, &order, &ops).as_deref(), Some("note-b"));
+        assert!(ops.is_visible("note-new"));
+        assert!(ops.calls().iter().all(|call| !call.contains("note-new")));
+    }
+
+    #[test]
+    fn windows_closed_since_the_snapshot_are_skipped() {
+        let ops = MockWindows::with(&[("note-a", true, false)]);
+        let snapshot = strings(&["note-a", "note-gone"]);
+        let order = strings(&["a", "gone"]);
+
+        assert_eq!(show_all_pass(&snapshot, &order, &ops).as_deref(), Some("note-a"));
+        assert_eq!(show_all_pass(&[], &order, &ops), None);
+    }
+
+    #[test]
+    fn toggle_hides_everything_if_anything_shows() {
+        let ops = MockWindows::with(&[("note-a", true, false), ("note-b", false, false)]);
+        let snapshot = strings(&["note-a", "note-b"]);
+
+        assert!(!toggle_all_pass(&snapshot, &[], &ops));
+        assert!(!ops.is_visible("note-a") && !ops.is_visible("note-b"));
+    }
+
+    #[test]
+    fn toggle_shows_everything_if_nothing_shows() {
+        let ops = MockWindows::with(&[("note-a", false, false), ("note-b", false, false)]);
+        let snapshot = strings(&["note-a", "note-b"]);
+        let order = strings(&["b", "a"]);
+
+        assert!(toggle_all_pass(&snapshot, &order, &ops));
+        assert!(ops.is_visible("note-a") && ops.is_visible("note-b"));
+        assert_eq!(ops.calls().last().map(String::as_str), Some("focus note-a"));
+        assert!(!toggle_all_pass(&[], &order, &ops));
+    }
+}
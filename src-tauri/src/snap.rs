@@ -0,0 +1,309 @@
+//! Optional snapping of note windows, configured in settings.bin `snapping`. Once a moved
+//! note has been still for the geometry debounce (there is no event for the mouse being
+//! released), an edge that ended up near a display edge or another note's edge is nudged
+//! onto it; failing that, the corner is rounded to the grid if one is set.
+//!
+//! `arrange_notes` tiles or cascades all visible notes on the display under the cursor.
+
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, Rect, CASCADE_STEP};
+use crate::notewindow::MIN_NOTE_SIZE;
+use crate::rescue;
+use crate::{get_session_order, open_note_rects, WindowRegistry};
+
+const SETTINGS_KEY: &str = "snapping";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SnapSettings {
+    enabled: bool,
+    /// Logical pixels; 0 turns the grid off
+    grid_size: f64,
+    /// How close an edge has to come to snap, in logical pixels
+    threshold: f64,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        SnapSettings {
+            enabled: false,
+            grid_size: 0.0,
+            threshold: 16.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrangeLayout {
+    /// A grid of equal cells filling the display. The cells touch, so snapping (which
+    /// would close any gap narrower than its threshold) leaves them where they are.
+    Tile,
+    /// Stepped down and right from the top left, each note keeping its size
+    Cascade,
+}
+
+fn settings(backend: &impl NotesBackend) -> SnapSettings {
+    backend
+        .read_store("settings.bin", SETTINGS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn window_rect<R: Runtime>(window: &tauri::WebviewWindow<R>) -> Option<Rect> {
+    let scale = window.scale_factor().ok()?;
+    let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+    let size = window.outer_size().ok()?.to_logical::<f64>(scale);
+    Some(Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Where a window spanning `start..start + len` on one axis snaps to: the nearest of the
+/// display's and the `others`' edges within the threshold, else the nearest grid line.
+fn snap_axis(start: f64, len: f64, display: (f64, f64), others: &[(f64, f64)], settings: &SnapSettings) -> f64 {
+    let (display_start, display_len) = display;
+    let candidates = [display_start, display_start + display_len - len]
+        .into_iter()
+        // Left edges lined up, side by side either way, right edges lined up
+        .chain(
+            others
+                .iter()
+                .flat_map(|&(o, o_len)| [o, o + o_len, o - len, o + o_len - len]),
+        );
+    let nearest = candidates
+        .filter(|candidate| (candidate - start).abs() <= settings.threshold)
+        .min_by(|a, b| (a - start).abs().total_cmp(&(b - start).abs()));
+    match nearest {
+        Some(snapped) => snapped,
+        None if settings.grid_size > 0.0 => {
+            display_start + ((start - display_start) / settings.grid_size).round() * settings.grid_size
+        }
+        None => start,
+    }
+}
+
+/// Snaps a note window that came to rest, if snapping is on. Returns whether it moved.
+pub fn snap_window<R: Runtime>(window: &tauri::WebviewWindow<R>) -> bool {
+    let app = window.app_handle();
+    let settings = settings(app);
+    if !settings.enabled || window.is_minimized().unwrap_or(false) {
+        return false;
+    }
+    let (Some(rect), Some(display)) = (
+        window_rect(window),
+        window.current_monitor().ok().flatten().map(|m| meta::monitor_rect(&m)),
+    ) else {
+        return false;
+    };
+    // Only notes alongside on the other axis are worth lining up with
+    let others = open_note_rects(app, window.label());
+    let reach = settings.threshold;
+    let beside_x: Vec<(f64, f64)> = others
+        .iter()
+        .filter(|o| o.y < rect.y + rect.height + reach && rect.y < o.y + o.height + reach)
+        .map(|o| (o.x, o.width))
+        .collect();
+    let beside_y: Vec<(f64, f64)> = others
+        .iter()
+        .filter(|o| o.x < rect.x + rect.width + reach && rect.x < o.x + o.width + reach)
+        .map(|o| (o.y, o.height))
+        .collect();
+    let x = snap_axis(rect.x, rect.width, (display.x, display.width), &beside_x, &settings);
+    let y = snap_axis(rect.y, rect.height, (display.y, display.height), &beside_y, &settings);
+    if (x - rect.x).abs() < 0.5 && (y - rect.y).abs() < 0.5 {
+        return false;
+    }
+    window.set_position(tauri::LogicalPosition::new(x, y)).is_ok()
+}
+
+/// Visible note windows, least recently focused first.
+fn visible_notes<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<tauri::WebviewWindow<R>> {
+    let mut labels = app.state::<WindowRegistry>().note_labels();
+    let order = get_session_order(app);
+    labels.sort_by_key(|label| {
+        let id = label.strip_prefix("note-").unwrap_or(label);
+        (order.iter().position(|o| o == id).unwrap_or(usize::MAX), label.clone())
+    });
+    labels
+        .iter()
+        .filter_map(|label| app.get_webview_window(label))
+        .filter(|window| window.is_visible().unwrap_or(false) && !window.is_minimized().unwrap_or(false))
+        .collect()
+}
+
+fn place<R: Runtime>(window: &tauri::WebviewWindow<R>, rect: Rect) -> Result<(), String> {
+    window
+        .set_size(tauri::LogicalSize::new(rect.width, rect.height))
+        .and_then(|_| window.set_position(tauri::LogicalPosition::new(rect.x, rect.y)))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_snap_settings(app: tauri::AppHandle) -> Result<SnapSettings, String> {
+    Ok(settings(&app))
+}
+
+#[tauri::command]
+pub async fn set_snap_settings(settings: SnapSettings, app: tauri::AppHandle) -> Result<SnapSettings, String> {
+    if !settings.grid_size.is_finite() || settings.grid_size < 0.0 {
+        return Err(format!("Invalid grid size: {}", settings.grid_size));
+    }
+    if !settings.threshold.is_finite() || settings.threshold < 0.0 {
+        return Err(format!("Invalid snap threshold: {}", settings.threshold));
+    }
+    let value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+    app.write_store("settings.bin", SETTINGS_KEY, value)?;
+    Ok(settings)
+}
+
+/// `count` touching cells filling `display`, row by row, as close to square as fits.
+fn tiles(display: Rect, count: usize) -> Vec<Rect> {
+    let columns = (count as f64).sqrt().ceil() as usize;
+    let rows = count.div_ceil(columns);
+    let width = (display.width / columns as f64).max(MIN_NOTE_SIZE.0);
+    let height = (display.height / rows as f64).max(MIN_NOTE_SIZE.1);
+    (0..count)
+        .map(|i| Rect {
+            x: display.x + (i % columns) as f64 * width,
+            y: display.y + (i / columns) as f64 * height,
+            width,
+            height,
+        })
+        .collect()
+}
+
+/// Lays out the visible notes on the display under the cursor. Returns how many moved.
+#[tauri::command]
+pub async fn arrange_notes(layout: ArrangeLayout, app: tauri::AppHandle) -> Result<usize, String> {
+    let display = meta::cursor_display(&app).ok_or("No display found")?;
+    let windows = visible_notes(&app);
+    if windows.is_empty() {
+        return Ok(0);
+    }
+
+    let rects: Vec<Rect> = match layout {
+        ArrangeLayout::Tile => tiles(display, windows.len()),
+        ArrangeLayout::Cascade => {
+            let mut step = 0.0;
+            windows
+                .iter()
+                .map(|window| {
+                    let size = window_rect(window).unwrap_or_else(|| rescue::centered_default(display));
+                    let mut rect = Rect {
+                        x: display.x + CASCADE_STEP + step,
+                        y: display.y + CASCADE_STEP + step,
+                        width: size.width,
+                        height: size.height,
+                    };
+                    // Start over from the top left once the stack would run off the display
+                    if rect.x + rect.width > display.x + display.width
+                        || rect.y + rect.height > display.y + display.height
+                    {
+                        step = 0.0;
+                        rect.x = display.x + CASCADE_STEP;
+                        rect.y = display.y + CASCADE_STEP;
+                    }
+                    step += CASCADE_STEP;
+                    rect
+                })
+                .collect()
+        }
+    };
+
+    let mut moved = 0;
+    for (window, rect) in windows.iter().zip(rects) {
+        match place(window, rect) {
+            Ok(()) => moved += 1,
+            Err(e) => println!("Could not arrange {}: {}", window.label(), e),
+        }
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISPLAY: (f64, f64) = (0.0, 1000.0);
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn settings(grid_size: f64) -> SnapSettings {
+        SnapSettings {
+            enabled: true,
+            grid_size,
+            threshold: 16.0,
+        }
+    }
+
+    #[test]
+    fn edges_near_the_display_edge_snap_onto_it() {
+        assert_eq!(snap_axis(10.0, 300.0, DISPLAY, &[], &settings(0.0)), 0.0);
+        assert_eq!(snap_axis(690.0, 300.0, DISPLAY, &[], &settings(0.0)), 700.0);
+        // Just out of reach stays put
+        assert_eq!(snap_axis(17.0, 300.0, DISPLAY, &[], &settings(0.0)), 17.0);
+    }
+
+    #[test]
+    fn edges_line_up_with_neighbouring_notes() {
+        let other = [(400.0, 200.0)];
+        let s = settings(0.0);
+        // Left edges lined up, side by side on either side, right edges lined up
+        assert_eq!(snap_axis(405.0, 100.0, DISPLAY, &other, &s), 400.0);
+        assert_eq!(snap_axis(610.0, 100.0, DISPLAY, &other, &s), 600.0);
+        assert_eq!(snap_axis(290.0, 100.0, DISPLAY, &other, &s), 300.0);
+        assert_eq!(snap_axis(495.0, 100.0, DISPLAY, &other, &s), 500.0);
+    }
+
+    #[test]
+    fn the_nearest_candidate_wins() {
+        let others = [(100.0, 100.0), (105.0, 100.0)];
+        assert_eq!(snap_axis(104.0, 50.0, DISPLAY, &others, &settings(0.0)), 105.0);
+    }
+
+    #[test]
+    fn the_grid_applies_only_when_no_edge_is_near() {
+        assert_eq!(snap_axis(130.0, 300.0, DISPLAY, &[], &settings(50.0)), 150.0);
+        assert_eq!(snap_axis(124.0, 300.0, DISPLAY, &[], &settings(50.0)), 100.0);
+        // The grid starts at the display's edge, not the desktop's
+        assert_eq!(snap_axis(1130.0, 300.0, (1010.0, 1000.0), &[], &settings(50.0)), 1110.0);
+        // An edge in reach beats the grid line
+        assert_eq!(snap_axis(690.0, 300.0, DISPLAY, &[], &settings(40.0)), 700.0);
+    }
+
+    #[test]
+    fn stored_settings_fill_in_missing_fields() {
+        let stored: SnapSettings = serde_json::from_value(serde_json::json!({ "enabled": true })).unwrap();
+        assert!(stored.enabled);
+        assert_eq!(stored.grid_size, 0.0);
+        assert_eq!(stored.threshold, 16.0);
+    }
+
+    #[test]
+    fn tiles_touch_and_fill_the_display() {
+        let rects = tiles(rect(100.0, 50.0, 1200.0, 800.0), 5);
+        assert_eq!(rects.len(), 5);
+        // Three columns, two rows
+        assert_eq!(rects[0], rect(100.0, 50.0, 400.0, 400.0));
+        assert_eq!(rects[2].x + rects[2].width, 1300.0);
+        assert_eq!(rects[3], rect(100.0, 450.0, 400.0, 400.0));
+        for pair in rects.windows(2).filter(|pair| pair[0].y == pair[1].y) {
+            assert_eq!(pair[0].x + pair[0].width, pair[1].x);
+        }
+    }
+
+    #[test]
+    fn tiles_never_shrink_below_the_minimum_note_size() {
+        for tile in tiles(rect(0.0, 0.0, 800.0, 600.0), 100) {
+            assert!(tile.width >= MIN_NOTE_SIZE.0 && tile.height >= MIN_NOTE_SIZE.1);
+        }
+    }
+}
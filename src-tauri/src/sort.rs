@@ -0,0 +1,429 @@
+use icu_collator::options::CollatorOptions;
+use icu_collator::{Collator, CollatorBorrowed};
+use icu_locale_core::Locale;
+use serde_json::Map;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use tauri::Runtime;
+use tauri_plugin_store::StoreExt;
+
+use crate::settings;
+use crate::NoteInfo;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSort {
+    #[default]
+    ModifiedDesc,
+    CreatedDesc,
+    TitleAsc,
+    /// The user's hand-arranged `manual_order` list
+    Manual,
+    /// Most recently focused first, per the session order
+    OpenedDesc,
+}
+
+/// Everything besides `NoteInfo` itself that an ordering may depend on.
+pub struct SortContext<'a> {
+    pub manual: &'a [String],
+    /// Session order, oldest focus first
+    pub opened: &'a [String],
+    /// Favorites and pinned notes, floated to the top when `pinned_first` is on
+    pub pinned: &'a HashSet<String>,
+    /// BCP 47 tag titles are collated by, see `system_locale`
+    pub locale: &'a str,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct SortSettings {
+    pub sort: NoteSort,
+    pub pinned_first: bool,
+}
+
+/// Each id's index in `list`, built once per sort instead of searching the list per
+/// comparison. A repeated id keeps its first index.
+fn ranks(list: &[String]) -> HashMap<&str, usize> {
+    let mut ranks = HashMap::with_capacity(list.len());
+    for (index, id) in list.iter().enumerate() {
+        ranks.entry(id.as_str()).or_insert(index);
+    }
+    ranks
+}
+
+/// Present values before missing ones, so notes without a timestamp sink to the end.
+fn present_first<T: Ord>(a: Option<T>, b: Option<T>, cmp: impl Fn(&T, &T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// The collator for `locale`, a BCP 47 tag; unknown or empty tags get the root collation.
+/// Case only decides between titles that are otherwise equal, lowercase first.
+fn collator(locale: &str) -> Option<CollatorBorrowed<'static>> {
+    let locale = Locale::try_from_str(locale).unwrap_or(Locale::UNKNOWN);
+    Collator::try_new((&locale).into(), CollatorOptions::default()).ok()
+}
+
+/// By the locale's collation, then by code point so titles the collation considers
+/// equal still order deterministically.
+fn compare_titles(collator: Option<&CollatorBorrowed>, a: &str, b: &str) -> Ordering {
+    collator
+        .map_or(Ordering::Equal, |collator| collator.compare(a, b))
+        .then_with(|| a.cmp(b))
+}
+
+/// What a comparison needs besides the two notes, prepared once per sort.
+struct Sorter<'a> {
+    sort: NoteSort,
+    /// Of the manual or session order, whichever `sort` goes by
+    ranks: HashMap<&'a str, usize>,
+    collator: Option<CollatorBorrowed<'static>>,
+}
+
+impl<'a> Sorter<'a> {
+    fn new(sort: NoteSort, ctx: &SortContext<'a>) -> Self {
+        Sorter {
+            sort,
+            ranks: match sort {
+                NoteSort::Manual => ranks(ctx.manual),
+                NoteSort::OpenedDesc => ranks(ctx.opened),
+                _ => HashMap::new(),
+            },
+            collator: if sort == NoteSort::TitleAsc {
+                collator(ctx.locale)
+            } else {
+                None
+            },
+        }
+    }
+
+    fn compare(&self, a: &NoteInfo, b: &NoteInfo) -> Ordering {
+        let rank = |note: &NoteInfo| self.ranks.get(note.id.as_str()).copied();
+        match self.sort {
+            NoteSort::ModifiedDesc => present_first(a.modified_at, b.modified_at, |a, b| b.cmp(a)),
+            NoteSort::CreatedDesc => present_first(a.created_at, b.created_at, |a, b| b.cmp(a)),
+            NoteSort::TitleAsc => compare_titles(self.collator.as_ref(), &a.title, &b.title),
+            NoteSort::Manual => present_first(rank(a), rank(b), |a, b| a.cmp(b)),
+            NoteSort::OpenedDesc => present_first(rank(a), rank(b), |a, b| b.cmp(a)),
+        }
+    }
+}
+
+/// The single ordering used by every note listing. Ties always fall back to the id so
+/// repeated listings come back identical.
+pub fn sort_notes(notes: &mut [NoteInfo], settings: SortSettings, ctx: &SortContext) {
+    let sorter = Sorter::new(settings.sort, ctx);
+    notes.sort_by(|a, b| {
+        let pinned = if settings.pinned_first {
+            ctx.pinned.contains(&b.id).cmp(&ctx.pinned.contains(&a.id))
+        } else {
+            Ordering::Equal
+        };
+        pinned
+            .then_with(|| sorter.compare(a, b))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Bottom-to-top stacking order for the show-all pass: oldest focus first, notes missing
+/// from the session order last. Labels are `note-{id}`.
+pub fn compute_show_order(labels: &mut [String], opened: &[String]) {
+    let ranks = ranks(opened);
+    let rank = |label: &String| ranks.get(label.strip_prefix("note-").unwrap_or(label)).copied();
+    labels.sort_by(|a, b| present_first(rank(a), rank(b), |a, b| a.cmp(b)));
+}
+
+/// The system locale as a BCP 47 tag, what title sorting collates by.
+pub fn system_locale() -> String {
+    sys_locale::get_locale().unwrap_or_default()
+}
+
+pub fn get_sort_settings<R: Runtime>(app: &tauri::AppHandle<R>) -> SortSettings {
+    settings::current(app).note_sort
+}
+
+pub fn get_manual_order<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<String> {
+    app.store("settings.bin")
+        .ok()
+        .and_then(|store| store.get("manual_order"))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub async fn get_default_sort(app: tauri::AppHandle) -> Result<SortSettings, String> {
+    Ok(get_sort_settings(&app))
+}
+
+#[tauri::command]
+pub async fn set_default_sort(
+    sort: NoteSort,
+    pinned_first: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut settings = get_sort_settings(&app);
+    settings.sort = sort;
+    if let Some(pinned_first) = pinned_first {
+        settings.pinned_first = pinned_first;
+    }
+
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    settings::update(&app, Map::from_iter([("note_sort".to_string(), value)]))?;
+    Ok(())
+}
+
+/// Stores the order used by `NoteSort::Manual`.
+#[tauri::command]
+pub async fn set_manual_order(ids: Vec<String>, app: tauri::AppHandle) -> Result<(), String> {
+    let store = app.store("settings.bin").map_err(|e| e.to_string())?;
+    store.set("manual_order", serde_json::to_value(ids).map_err(|e| e.to_string())?);
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: &str, title: &str, modified_at: Option<u64>, created_at: Option<u64>) -> NoteInfo {
+        NoteInfo {
+            id: id.to_string(),
+            title: title.to_string(),
+            modified_at,
+            created_at,
+            ..NoteInfo::default()
+        }
+    }
+
+    fn ids(notes: &[NoteInfo]) -> Vec<&str> {
+        notes.iter().map(|note| note.id.as_str()).collect()
+    }
+
+    fn sorted(mut notes: Vec<NoteInfo>, sort: NoteSort, ctx: &SortContext) -> Vec<String> {
+        let settings = SortSettings {
+            sort,
+            pinned_first: false,
+        };
+        sort_notes(&mut notes, settings, ctx);
+        ids(&notes).into_iter().map(str::to_string).collect()
+    }
+
+    fn context<'a>(manual: &'a [String], opened: &'a [String], pinned: &'a HashSet<String>) -> SortContext<'a> {
+        SortContext {
+            manual,
+            opened,
+            pinned,
+            locale: "en",
+        }
+    }
+
+    fn strings(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    /// Every order of `notes`, to show a sort doesn't depend on where notes started.
+    fn permutations(notes: &[NoteInfo]) -> Vec<Vec<NoteInfo>> {
+        if notes.len() <= 1 {
+            return vec![notes.to_vec()];
+        }
+        let mut all = Vec::new();
+        for i in 0..notes.len() {
+            let mut rest = notes.to_vec();
+            let first = rest.remove(i);
+            for mut tail in permutations(&rest) {
+                tail.insert(0, first.clone());
+                all.push(tail);
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn every_sort_is_independent_of_the_input_order() {
+        let notes = vec![
+            note("a", "Same", Some(5), None),
+            note("b", "same", Some(5), Some(1)),
+            note("c", "Same", None, Some(1)),
+            note("d", "Other", Some(9), None),
+        ];
+        let manual = strings(&["c", "a"]);
+        let opened = strings(&["b", "d"]);
+        let pinned = HashSet::new();
+        let ctx = context(&manual, &opened, &pinned);
+
+        for sort in [
+            NoteSort::ModifiedDesc,
+            NoteSort::CreatedDesc,
+            NoteSort::TitleAsc,
+            NoteSort::Manual,
+            NoteSort::OpenedDesc,
+        ] {
+            let expected = sorted(notes.clone(), sort, &ctx);
+            for order in permutations(&notes) {
+                assert_eq!(sorted(order, sort, &ctx), expected, "{:?}", sort);
+            }
+        }
+    }
+
+    #[test]
+    fn ties_fall_back_to_the_id() {
+        let notes = vec![
+            note("c", "x", Some(1), None),
+            note("a", "x", Some(1), None),
+            note("b", "x", Some(1), None),
+        ];
+        let pinned = HashSet::new();
+        let ctx = context(&[], &[], &pinned);
+
+        assert_eq!(sorted(notes, NoteSort::ModifiedDesc, &ctx), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn missing_timestamps_sink_to_the_end() {
+        let notes = vec![
+            note("none", "", None, None),
+            note("old", "", Some(1), Some(1)),
+            note("new", "", Some(2), None),
+            note("also-none", "", None, None),
+        ];
+        let pinned = HashSet::new();
+        let ctx = context(&[], &[], &pinned);
+
+        assert_eq!(
+            sorted(notes.clone(), NoteSort::ModifiedDesc, &ctx),
+            ["new", "old", "also-none", "none"]
+        );
+        assert_eq!(
+            sorted(notes, NoteSort::CreatedDesc, &ctx),
+            ["old", "also-none", "new", "none"]
+        );
+    }
+
+    #[test]
+    fn titles_differing_only_in_case_stay_together() {
+        let notes = vec![
+            note("1", "WORK", None, None),
+            note("2", "Works", None, None),
+            note("3", "work", None, None),
+            note("4", "Work", None, None),
+            note("5", "wORK", None, None),
+            note("6", "Apples", None, None),
+        ];
+        let pinned = HashSet::new();
+        let ctx = context(&[], &[], &pinned);
+
+        let order = sorted(notes, NoteSort::TitleAsc, &ctx);
+        assert_eq!(order.first().map(String::as_str), Some("6"));
+        assert_eq!(order.last().map(String::as_str), Some("2"));
+        // Lowercase first among otherwise equal titles
+        assert_eq!(order[1], "3");
+    }
+
+    #[test]
+    fn titles_collate_by_locale() {
+        let notes = vec![note("z", "zebra", None, None), note("o", "örn", None, None)];
+        let pinned = HashSet::new();
+        let by = |locale| {
+            let ctx = SortContext {
+                manual: &[],
+                opened: &[],
+                pinned: &pinned,
+                locale,
+            };
+            sorted(notes.clone(), NoteSort::TitleAsc, &ctx)
+        };
+
+        // Swedish puts ö after z, English sorts it with o
+        assert_eq!(by("sv"), ["z", "o"]);
+        assert_eq!(by("en"), ["o", "z"]);
+        // Accents are secondary to the letters themselves, unlike in code point order
+        let notes = vec![note("b", "Birne", None, None), note("a", "Äpfel", None, None)];
+        let ctx = SortContext {
+            manual: &[],
+            opened: &[],
+            pinned: &pinned,
+            locale: "de",
+        };
+        assert_eq!(sorted(notes, NoteSort::TitleAsc, &ctx), ["a", "b"]);
+    }
+
+    #[test]
+    fn unknown_locales_get_the_root_collation() {
+        // Code point order would put "Beta" first
+        let notes = vec![note("b", "Beta", None, None), note("a", "alpha", None, None)];
+        let pinned = HashSet::new();
+        for locale in ["", "not a locale", "xx-YY"] {
+            let ctx = SortContext {
+                manual: &[],
+                opened: &[],
+                pinned: &pinned,
+                locale,
+            };
+            assert_eq!(
+                sorted(notes.clone(), NoteSort::TitleAsc, &ctx),
+                ["a", "b"],
+                "{:?}",
+                locale
+            );
+        }
+    }
+
+    #[test]
+    fn manual_order_puts_unlisted_notes_last() {
+        let notes = vec![
+            note("a", "", None, None),
+            note("b", "", None, None),
+            note("c", "", None, None),
+        ];
+        // A repeated id keeps its first place
+        let manual = strings(&["c", "a", "c"]);
+        let pinned = HashSet::new();
+        let ctx = context(&manual, &[], &pinned);
+
+        assert_eq!(sorted(notes, NoteSort::Manual, &ctx), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn opened_order_is_most_recent_first() {
+        let notes = vec![
+            note("a", "", None, None),
+            note("b", "", None, None),
+            note("c", "", None, None),
+        ];
+        let opened = strings(&["a", "c"]);
+        let pinned = HashSet::new();
+        let ctx = context(&[], &opened, &pinned);
+
+        assert_eq!(sorted(notes, NoteSort::OpenedDesc, &ctx), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn pinned_notes_float_to_the_top() {
+        let mut notes = vec![
+            note("a", "", Some(3), None),
+            note("b", "", Some(2), None),
+            note("c", "", Some(1), None),
+        ];
+        let pinned = HashSet::from(["c".to_string()]);
+        let ctx = context(&[], &[], &pinned);
+        let settings = SortSettings {
+            sort: NoteSort::ModifiedDesc,
+            pinned_first: true,
+        };
+
+        sort_notes(&mut notes, settings, &ctx);
+        assert_eq!(ids(&notes), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn show_order_stacks_the_latest_focus_on_top() {
+        let mut labels = strings(&["note-c", "note-x", "note-a", "note-b"]);
+        let opened = strings(&["b", "a", "c"]);
+
+        compute_show_order(&mut labels, &opened);
+        assert_eq!(labels, ["note-b", "note-a", "note-c", "note-x"]);
+    }
+}
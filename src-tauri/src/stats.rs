@@ -0,0 +1,146 @@
+//! Word counts and the like for one note (`get_note_stats`) and for all of them
+//! (`get_vault_stats`, the dashboard footer), worked out here so large notes don't have
+//! to be sent to the webview to be counted. Frontmatter isn't counted.
+
+use std::fs;
+
+use crate::meta;
+use crate::noteindex;
+use crate::tags::body_start;
+use crate::tasks::{count_tasks, TaskCounts};
+use crate::timestamps::effective_modified_at;
+use crate::{load_note, notes_dir, to_millis};
+
+/// Words per minute reading times are based on.
+const READING_SPEED: usize = 200;
+
+#[derive(serde::Serialize, Clone, Copy, Default, Debug)]
+struct TextCounts {
+    words: usize,
+    characters: usize,
+    lines: usize,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct NoteStats {
+    #[serde(flatten)]
+    counts: TextCounts,
+    /// The file's size on disk
+    bytes: u64,
+    /// Rounded up, 0 for an empty note
+    reading_minutes: usize,
+    tasks: TaskCounts,
+    /// Done over all tasks, `None` without any
+    task_completion: Option<f64>,
+    /// Unix milliseconds, as the dashboard sorts by
+    modified_at: Option<u64>,
+}
+
+#[derive(serde::Serialize, Default, Debug)]
+pub struct VaultStats {
+    notes: usize,
+    /// Notes locked with a passphrase not entered this session; counted in `notes` and
+    /// `bytes` only
+    locked: usize,
+    #[serde(flatten)]
+    counts: TextCounts,
+    bytes: u64,
+    reading_minutes: usize,
+    tasks: TaskCounts,
+}
+
+/// Words are runs of non-whitespace with a letter or digit in them, so list bullets and
+/// rules don't count.
+fn text_counts(content: &str) -> TextCounts {
+    let body = &content[body_start(content)..];
+    TextCounts {
+        words: body
+            .split_whitespace()
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count(),
+        characters: body.chars().count(),
+        lines: body.lines().count(),
+    }
+}
+
+fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(READING_SPEED)
+}
+
+fn completion(tasks: TaskCounts) -> Option<f64> {
+    let total = tasks.open + tasks.done;
+    (total > 0).then(|| tasks.done as f64 / total as f64)
+}
+
+#[tauri::command]
+pub async fn get_note_stats(id: String, app: tauri::AppHandle) -> Result<NoteStats, String> {
+    let metadata = fs::metadata(notes_dir(&app)?.join(format!("{}.md", id))).map_err(|e| e.to_string())?;
+    let content = load_note(id.clone(), app.clone()).await?;
+    let counts = text_counts(&content);
+    let tasks = count_tasks(&content);
+    let note_meta = meta::get_meta(&app, &id);
+    Ok(NoteStats {
+        counts,
+        bytes: metadata.len(),
+        reading_minutes: reading_minutes(counts.words),
+        tasks,
+        task_completion: completion(tasks),
+        modified_at: effective_modified_at(Some(&note_meta), to_millis(metadata.modified())),
+    })
+}
+
+/// Totals over every note, the trash and archive aside.
+#[tauri::command]
+pub async fn get_vault_stats(app: tauri::AppHandle) -> Result<VaultStats, String> {
+    let dir = notes_dir(&app)?;
+    let mut stats = VaultStats::default();
+    for id in noteindex::listing(&app)?.into_keys() {
+        stats.notes += 1;
+        stats.bytes += fs::metadata(dir.join(format!("{}.md", id)))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let Ok(content) = load_note(id, app.clone()).await else {
+            stats.locked += 1;
+            continue;
+        };
+        let counts = text_counts(&content);
+        stats.counts.words += counts.words;
+        stats.counts.characters += counts.characters;
+        stats.counts.lines += counts.lines;
+        let tasks = count_tasks(&content);
+        stats.tasks.open += tasks.open;
+        stats.tasks.done += tasks.done;
+    }
+    stats.reading_minutes = reading_minutes(stats.counts.words);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_skip_frontmatter_and_bullets() {
+        let counts = text_counts("---\ntags: [a, b]\n---\n# Plan\n\n- milk, eggs\n- ---\n* 2 loaves\n");
+        assert_eq!(counts.words, 5);
+        assert_eq!(counts.lines, 5);
+        assert_eq!(counts.characters, "# Plan\n\n- milk, eggs\n- ---\n* 2 loaves\n".len());
+        assert_eq!(text_counts("héllo wörld").characters, 11);
+        assert_eq!(text_counts("").words, 0);
+    }
+
+    #[test]
+    fn reading_time_rounds_up() {
+        assert_eq!(reading_minutes(0), 0);
+        assert_eq!(reading_minutes(1), 1);
+        assert_eq!(reading_minutes(READING_SPEED), 1);
+        assert_eq!(reading_minutes(READING_SPEED + 1), 2);
+    }
+
+    #[test]
+    fn completion_is_done_over_all() {
+        assert_eq!(completion(TaskCounts::default()), None);
+        assert_eq!(completion(TaskCounts { open: 3, done: 1 }), Some(0.25));
+        assert_eq!(completion(TaskCounts { open: 0, done: 2 }), Some(1.0));
+    }
+}
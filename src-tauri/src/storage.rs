@@ -0,0 +1,409 @@
+//! Free-space watchdog for the data volume. Below the warning level the frontend is
+//! told once per crossing; below the critical level optional writes (journal archiving,
+//! usage flushes) pause so the space left goes to note saves.
+//!
+//! Also tracks whether the notes directory can be written at all. A save failing for
+//! lack of permission or because the directory is gone marks storage unwritable; saves
+//! then go to the write queue (`writequeue.rs`) and the watchdog probes the directory
+//! until it can be written again, when the queue is replayed.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::backend::NotesBackend;
+use crate::error::NoteError;
+use crate::flush::flush_now;
+use crate::{notes_dir, settings, writequeue};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Saves can come in bursts; don't stat the volume more often than this for them.
+const POST_SAVE_MIN_INTERVAL: Duration = Duration::from_secs(5);
+const MB: u64 = 1024 * 1024;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct DiskThresholds {
+    pub warning_bytes: u64,
+    pub critical_bytes: u64,
+}
+
+impl Default for DiskThresholds {
+    fn default() -> Self {
+        DiskThresholds {
+            warning_bytes: 200 * MB,
+            critical_bytes: 50 * MB,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskLevel {
+    Ok,
+    Low,
+    Critical,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageAccess {
+    Writable,
+    /// Writing was refused: permissions changed or the volume is mounted read-only
+    ReadOnly,
+    /// The notes directory or its volume is gone (unplugged drive, unmounted share)
+    Missing,
+}
+
+/// Why storage is degraded; each needs a different remedy from the user.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageProblem {
+    DiskFull,
+    Missing,
+    Permissions,
+}
+
+impl StorageProblem {
+    fn message(self) -> &'static str {
+        match self {
+            StorageProblem::DiskFull => {
+                "The disk is almost full. Free up some space; optional writes are paused until then."
+            }
+            StorageProblem::Missing => {
+                "The notes folder can't be found. Reconnect the drive or share it is on; edits are kept until then."
+            }
+            StorageProblem::Permissions => {
+                "There is no permission to write the notes folder. Restore write access; edits are kept until then."
+            }
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct StorageStateChanged {
+    /// `None` once everything is back to normal
+    reason: Option<StorageProblem>,
+    message: Option<&'static str>,
+    queued_writes: usize,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct StorageInfo {
+    /// Bytes available on the data volume at the last check, `None` if it couldn't be read
+    available: Option<u64>,
+    level: DiskLevel,
+    reduced_writes: bool,
+    thresholds: DiskThresholds,
+    access: StorageAccess,
+    /// Note saves waiting for the notes directory to become writable
+    queued_writes: usize,
+}
+
+#[derive(serde::Serialize, Clone)]
+struct DiskSpaceLow {
+    available: u64,
+}
+
+struct Status {
+    available: Option<u64>,
+    level: DiskLevel,
+    access: StorageAccess,
+    last_check: Option<Instant>,
+}
+
+pub struct StorageState(Mutex<Status>);
+
+impl Default for StorageState {
+    fn default() -> Self {
+        StorageState(Mutex::new(Status {
+            available: None,
+            level: DiskLevel::Ok,
+            access: StorageAccess::Writable,
+            last_check: None,
+        }))
+    }
+}
+
+fn get_thresholds<R: Runtime>(app: &tauri::AppHandle<R>) -> DiskThresholds {
+    app.store("settings.bin")
+        .ok()
+        .and_then(|store| store.get("disk_thresholds"))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn level_for(available: u64, thresholds: &DiskThresholds) -> DiskLevel {
+    if available < thresholds.critical_bytes {
+        DiskLevel::Critical
+    } else if available < thresholds.warning_bytes {
+        DiskLevel::Low
+    } else {
+        DiskLevel::Ok
+    }
+}
+
+/// Whether optional writers should hold off because the volume is nearly full.
+pub fn reduced_writes<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    app.state::<StorageState>()
+        .0
+        .lock()
+        .map(|status| status.level == DiskLevel::Critical)
+        .unwrap_or(false)
+}
+
+/// Error for optional writes refused while in reduced-writes mode or while the notes
+/// directory can't be written.
+pub fn ensure_writes_allowed<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), String> {
+    if reduced_writes(app) {
+        return Err(NoteError::LowDiskSpace.into());
+    }
+    if !is_writable(app) {
+        return Err(NoteError::ReadOnlyStorage {
+            reason: "storage is unavailable".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub fn is_writable<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    app.state::<StorageState>()
+        .0
+        .lock()
+        .map(|status| status.access == StorageAccess::Writable)
+        .unwrap_or(true)
+}
+
+fn access_for(error: &io::Error) -> StorageAccess {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => StorageAccess::ReadOnly,
+        io::ErrorKind::NotFound => StorageAccess::Missing,
+        _ => StorageAccess::Writable,
+    }
+}
+
+/// Tries to create and remove a hidden file, which the scanner and poller skip.
+pub fn probe_access(dir: &Path) -> StorageAccess {
+    let probe = dir.join(".write-probe");
+    match fs::create_dir_all(dir).and_then(|()| fs::write(&probe, b"")) {
+        Ok(()) => {
+            let _ = fs::remove_file(probe);
+            StorageAccess::Writable
+        }
+        Err(e) => access_for(&e),
+    }
+}
+
+fn current_problem(status: &Status) -> Option<StorageProblem> {
+    match (status.access, status.level) {
+        (StorageAccess::Missing, _) => Some(StorageProblem::Missing),
+        (StorageAccess::ReadOnly, _) => Some(StorageProblem::Permissions),
+        (_, DiskLevel::Critical) => Some(StorageProblem::DiskFull),
+        _ => None,
+    }
+}
+
+fn emit_state<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let reason = app
+        .state::<StorageState>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|status| current_problem(&status));
+    let payload = StorageStateChanged {
+        reason,
+        message: reason.map(StorageProblem::message),
+        queued_writes: writequeue::queued_count(app),
+    };
+    app.emit_event("storage-state-changed", payload);
+}
+
+fn set_access<R: Runtime>(app: &tauri::AppHandle<R>, access: StorageAccess) {
+    let previous = {
+        let state = app.state::<StorageState>();
+        let Ok(mut status) = state.0.lock() else {
+            return;
+        };
+        std::mem::replace(&mut status.access, access)
+    };
+    if access == previous {
+        return;
+    }
+    println!("Notes storage changed from {:?} to {:?}", previous, access);
+    if access == StorageAccess::Writable {
+        let left = writequeue::replay(app);
+        if left > 0 {
+            println!("{} queued saves could not be replayed yet", left);
+        }
+        flush_now(app, "storage writable again");
+    }
+    emit_state(app);
+}
+
+/// Turns a failed note write into an error message, marking storage unwritable when the
+/// failure says so.
+pub fn write_error<R: Runtime>(app: &tauri::AppHandle<R>, error: io::Error) -> String {
+    let access = access_for(&error);
+    if access == StorageAccess::Writable {
+        return error.to_string();
+    }
+    set_access(app, access);
+    match access {
+        StorageAccess::ReadOnly => NoteError::ReadOnlyStorage {
+            reason: error.to_string(),
+        }
+        .into(),
+        _ => error.to_string(),
+    }
+}
+
+/// Re-reads free space and reacts to level changes. While the notes directory is
+/// unwritable it is probed too, to notice when it recovers.
+pub fn check_disk<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if !is_writable(app) {
+        if let Ok(notes) = notes_dir(app) {
+            set_access(app, probe_access(&notes));
+        }
+    }
+    // The notes folder can be on its own volume once relocated
+    let dir = notes_dir(app)
+        .ok()
+        .filter(|dir| dir.is_dir())
+        .or_else(|| app.path().app_data_dir().ok());
+    let Some(dir) = dir else {
+        return;
+    };
+    let available = match fs4::available_space(&dir) {
+        Ok(available) => available,
+        Err(e) => {
+            println!("Could not read free space for {:?}: {}", dir, e);
+            return;
+        }
+    };
+    let level = level_for(available, &get_thresholds(app));
+
+    let previous = {
+        let state = app.state::<StorageState>();
+        let Ok(mut status) = state.0.lock() else {
+            return;
+        };
+        status.available = Some(available);
+        status.last_check = Some(Instant::now());
+        std::mem::replace(&mut status.level, level)
+    };
+    if level == previous {
+        return;
+    }
+
+    println!("Disk space level changed from {:?} to {:?} ({} bytes free)", previous, level, available);
+    if previous == DiskLevel::Ok {
+        app.emit_event("disk-space-low", DiskSpaceLow { available });
+    }
+    if level == DiskLevel::Critical {
+        println!("Entering reduced-writes mode");
+    } else if previous == DiskLevel::Critical {
+        println!("Leaving reduced-writes mode, catching up paused writes");
+        flush_now(app, "disk space recovered");
+    }
+    if level == DiskLevel::Critical || previous == DiskLevel::Critical {
+        emit_state(app);
+    }
+}
+
+/// Whether note writes are flushed to the device before they count as saved (the
+/// `fsync_saves` setting, on by default).
+pub fn sync_writes(backend: &impl NotesBackend) -> bool {
+    settings::current(backend).fsync_saves
+}
+
+/// Replaces `file` with `content` via a temp file in the same directory and a rename, so
+/// a crash leaves either the old or the new content, never a truncated mix. The temp
+/// name starts with a dot and ends in `.tmp`, which the scanner skips.
+pub fn write_atomically(file: &Path, content: &str, sync: bool) -> io::Result<()> {
+    let dir = file.parent().ok_or_else(|| io::Error::other("no parent directory"))?;
+    let name = file.file_name().and_then(|n| n.to_str()).unwrap_or("note");
+    let temp = dir.join(format!(".{}.{}.tmp", name, uuid::Uuid::new_v4()));
+
+    let result = (|| {
+        let mut out = fs::File::create(&temp)?;
+        io::Write::write_all(&mut out, content.as_bytes())?;
+        if sync {
+            out.sync_all()?;
+        }
+        drop(out);
+        fs::rename(&temp, file)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Probes the notes directory and its volume afresh, after it was moved.
+pub fn notes_dir_changed<R: Runtime>(app: &tauri::AppHandle<R>) {
+    if let Ok(notes) = notes_dir(app) {
+        set_access(app, probe_access(&notes));
+    }
+    check_disk(app);
+}
+
+/// Called after each note save; throttled so bursts of saves stat the volume once.
+pub fn note_saved<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let due = app
+        .state::<StorageState>()
+        .0
+        .lock()
+        .map(|status| status.last_check.is_none_or(|at| at.elapsed() >= POST_SAVE_MIN_INTERVAL))
+        .unwrap_or(false);
+    if due {
+        check_disk(app);
+    }
+}
+
+pub fn spawn_disk_watchdog<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            check_disk(&app);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+pub fn storage_info<R: Runtime>(app: &tauri::AppHandle<R>) -> StorageInfo {
+    let (available, level, access) = app
+        .state::<StorageState>()
+        .0
+        .lock()
+        .map(|status| (status.available, status.level, status.access))
+        .unwrap_or((None, DiskLevel::Ok, StorageAccess::Writable));
+    StorageInfo {
+        available,
+        level,
+        reduced_writes: level == DiskLevel::Critical,
+        thresholds: get_thresholds(app),
+        access,
+        queued_writes: writequeue::queued_count(app),
+    }
+}
+
+#[tauri::command]
+pub async fn get_storage_info(app: tauri::AppHandle) -> Result<StorageInfo, String> {
+    Ok(storage_info(&app))
+}
+
+#[tauri::command]
+pub async fn set_disk_thresholds(thresholds: DiskThresholds, app: tauri::AppHandle) -> Result<StorageInfo, String> {
+    if thresholds.critical_bytes > thresholds.warning_bytes {
+        return Err("The critical level must not be above the warning level".to_string());
+    }
+    let store = app.store("settings.bin").map_err(|e| e.to_string())?;
+    store.set("disk_thresholds", serde_json::to_value(thresholds).map_err(|e| e.to_string())?);
+    store.save().map_err(|e| e.to_string())?;
+    check_disk(&app);
+    Ok(storage_info(&app))
+}
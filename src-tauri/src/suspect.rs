@@ -0,0 +1,163 @@
+//! Guard against a note file that was truncated behind the app's back (a sync client
+//! writing out an empty file, say). Every save records the note's size as
+//! `NoteMeta::known_size`; when a restored or externally modified file comes back much
+//! smaller, the note is marked suspect and `save_note` refuses to overwrite it without
+//! `acknowledge_suspect`, so the next autosave can't cement the loss. The mark stays until
+//! `clear_suspect` or `restore_from_history`.
+
+use std::fs;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::encryption;
+use crate::error::NoteError;
+use crate::history::latest_snapshot;
+use crate::meta;
+use crate::recycle::{recycle_content, RecycledKind};
+use crate::settings;
+use crate::vault;
+use crate::{notes_dir, now_millis, read_note, write_note};
+
+/// Share of the known size a note may lose before it is suspect.
+pub const DEFAULT_SHRINK_FRACTION: f64 = 0.8;
+/// Notes smaller than this are too short for a size drop to mean anything.
+const MIN_CHECKED_BYTES: u64 = 32;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Suspect {
+    /// Bytes, as last saved through the app
+    pub old_size: u64,
+    /// Bytes found on disk
+    pub new_size: u64,
+    /// Unix millis
+    pub detected_at: u64,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct NoteContentSuspect {
+    id: String,
+    old_size: u64,
+    new_size: u64,
+    /// File name of the newest history snapshot, if `restore_from_history` has one to use
+    latest_snapshot: Option<String>,
+}
+
+fn shrink_fraction<R: Runtime>(app: &tauri::AppHandle<R>) -> f64 {
+    Some(settings::current(app).suspect_shrink_fraction)
+        .filter(|f| (0.0..=1.0).contains(f))
+        .unwrap_or(DEFAULT_SHRINK_FRACTION)
+}
+
+fn is_drastic(old_size: u64, new_size: u64, fraction: f64) -> bool {
+    if old_size < MIN_CHECKED_BYTES || new_size >= old_size {
+        return false;
+    }
+    new_size == 0 || (old_size - new_size) as f64 > old_size as f64 * fraction
+}
+
+/// Records the size of what was just written for note `id`.
+pub fn note_written<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, size: usize) {
+    if let Err(e) = meta::stage_meta(app, id, |meta| meta.known_size = Some(size as u64)) {
+        println!("Failed to record size of {}: {}", id, e);
+    }
+}
+
+/// Compares note `id`'s file with its known size, marking it suspect on a drastic drop.
+/// Other size changes (edits made elsewhere) become the new known size.
+pub fn check<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) {
+    let Ok(dir) = notes_dir(app) else {
+        return;
+    };
+    let Ok(new_size) = fs::metadata(dir.join(format!("{}.md", id))).map(|m| m.len()) else {
+        return;
+    };
+    let current = meta::get_meta(app, id);
+    if current.suspect.is_some() || current.known_size == Some(new_size) {
+        return;
+    }
+    let Some(old_size) = current
+        .known_size
+        .filter(|old| is_drastic(*old, new_size, shrink_fraction(app)))
+    else {
+        if let Err(e) = meta::stage_meta(app, id, |meta| meta.known_size = Some(new_size)) {
+            println!("Failed to record size of {}: {}", id, e);
+        }
+        return;
+    };
+
+    println!(
+        "Note {} shrank from {} to {} bytes, marking it suspect",
+        id, old_size, new_size
+    );
+    let suspect = Suspect {
+        old_size,
+        new_size,
+        detected_at: now_millis(),
+    };
+    if let Err(e) = meta::update_meta(app, id, |meta| meta.suspect = Some(suspect)) {
+        println!("Failed to mark {} suspect: {}", id, e);
+        return;
+    }
+    let snapshot = latest_snapshot(&dir.join(id)).and_then(|(path, _)| Some(path.file_name()?.to_str()?.to_string()));
+    app.emit_note_event(
+        id,
+        "note-content-suspect",
+        NoteContentSuspect {
+            id: id.to_string(),
+            old_size,
+            new_size,
+            latest_snapshot: snapshot,
+        },
+    );
+    app.emit_event("refresh-notes", ());
+}
+
+/// Refuses a save over a suspect note unless the caller acknowledged it.
+pub fn guard_save<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, acknowledged: bool) -> Result<(), String> {
+    match meta::get_meta(app, id).suspect {
+        Some(suspect) if !acknowledged => Err(NoteError::SuspectContent {
+            old_size: suspect.old_size,
+            new_size: suspect.new_size,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Accepts a suspect note's current content as intended.
+#[tauri::command]
+pub async fn clear_suspect(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let size = notes_dir(&app)
+        .ok()
+        .and_then(|dir| fs::metadata(dir.join(format!("{}.md", id))).ok())
+        .map(|m| m.len());
+    meta::update_meta(&app, &id, |meta| {
+        meta.suspect = None;
+        meta.known_size = size;
+    })?;
+    app.emit_event("refresh-notes", ());
+    Ok(())
+}
+
+/// Replaces note `id` with its newest history snapshot and clears the suspect mark. The
+/// content being replaced goes to the trash. Returns the restored content.
+#[tauri::command]
+pub async fn restore_from_history(id: String, app: tauri::AppHandle) -> Result<String, String> {
+    let dir = notes_dir(&app)?;
+    let (snapshot, _) =
+        latest_snapshot(&dir.join(&id)).ok_or_else(|| format!("Note {} has no history snapshots", id))?;
+    let content = vault::read_file(&app, &snapshot)?;
+
+    let current = read_note(&app, &id)?;
+    if !current.is_empty() && current != content {
+        recycle_content(&app, RecycledKind::Trash, &current)?;
+    }
+    write_note(&app, &id, &content)?;
+    meta::update_meta(&app, &id, |meta| meta.suspect = None)?;
+    println!("Restored {} from {:?}", id, snapshot);
+
+    app.emit_note_event(&id, "note-restored-from-history", &id);
+    app.emit_event("refresh-notes", ());
+    encryption::open(&app, &id, content)
+}
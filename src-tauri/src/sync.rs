@@ -0,0 +1,520 @@
+//! Two-way sync of the notes folder with a WebDAV collection (Nextcloud, ownCloud, any
+//! server speaking plain WebDAV), configured in settings.bin `sync`.
+//!
+//! Each note is one `<id>.md` file on the server. After every sync the server's etag and
+//! the local file's mtime and size are kept per note in session.bin `sync_files`; the
+//! next sync compares both sides against that to tell who changed what:
+//!
+//! - changed on one side only: copied over to the other
+//! - changed on both: the server's version is saved next to the note as
+//!   `<id>.sync-conflict-<millis>.md`, which `conflicts.rs` picks up like any sync
+//!   client's copy, and the note isn't uploaded until the conflict is resolved
+//! - gone on one side and unchanged on the other: deleted there too, locally to the trash
+//!
+//! Files travel as stored, so with the vault enabled the server only sees ciphertext.
+//! Downloads are written like a sync client would write them, so the change poller
+//! reloads open windows and records them in the changes feed. Asset folders, history
+//! and note metadata are not synced.
+
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::conflicts;
+use crate::flush::flush_now;
+use crate::limits::effective_limits;
+use crate::recycle::{self, RecycledKind};
+use crate::scan::{is_valid_note_id, scan_notes, ScanEntry, ScanOptions};
+use crate::storage::{self, ensure_writes_allowed};
+use crate::{close_note, notes_dir, now_millis, to_millis};
+
+const CONFIG_KEY: &str = "sync";
+const FILES_KEY: &str = "sync_files";
+const STATUS_KEY: &str = "sync_status";
+const DEFAULT_INTERVAL_MINUTES: u64 = 15;
+/// How often the scheduler looks again while periodic sync is off.
+const IDLE_RECHECK: Duration = Duration::from_secs(60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PROPFIND_BODY: &str =
+    r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:getetag/></d:prop></d:propfind>"#;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct SyncConfig {
+    /// The collection the notes go in, e.g. `https://cloud.example.com/remote.php/dav/files/me/Notes/`
+    url: String,
+    username: String,
+    /// Stored as given; an app password is the thing to use here
+    password: String,
+    /// Minutes between background syncs, 0 for manual only
+    #[serde(default)]
+    interval_minutes: Option<u64>,
+}
+
+/// What was known about a note after it was last synced.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+struct SyncedFile {
+    etag: String,
+    modified_at: Option<u64>,
+    len: u64,
+}
+
+type LocalStamp = (Option<u64>, u64);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, Debug)]
+pub struct SyncReport {
+    uploaded: usize,
+    downloaded: usize,
+    deleted_local: usize,
+    deleted_remote: usize,
+    conflicts: usize,
+    /// Notes that failed and are retried next time
+    failed: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, Debug)]
+pub struct SyncStatus {
+    #[serde(default)]
+    configured: bool,
+    #[serde(default)]
+    running: bool,
+    /// Unix millis of the last sync that reached the server
+    last_synced_at: Option<u64>,
+    last_report: Option<SyncReport>,
+    last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct SyncState {
+    /// Held for the length of a sync
+    running: tokio::sync::Mutex<()>,
+    /// Signalled when the configuration changes
+    wake: tokio::sync::Notify,
+}
+
+enum Action {
+    Unchanged,
+    Uploaded,
+    Downloaded,
+    DeletedLocal,
+    DeletedRemote,
+    Conflict,
+}
+
+fn config(backend: &impl NotesBackend) -> Option<SyncConfig> {
+    backend
+        .read_store("settings.bin", CONFIG_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+fn load_files(backend: &impl NotesBackend) -> HashMap<String, SyncedFile> {
+    backend
+        .read_store("session.bin", FILES_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn status<R: Runtime>(app: &tauri::AppHandle<R>) -> SyncStatus {
+    let mut status: SyncStatus = app
+        .read_store("session.bin", STATUS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    status.configured = config(app).is_some();
+    status.running = app.state::<SyncState>().running.try_lock().is_err();
+    status
+}
+
+fn emit_status<R: Runtime>(app: &tauri::AppHandle<R>) {
+    app.emit_event("sync-status", status(app));
+}
+
+/// `response`, `href` and `getetag` elements, whatever namespace prefix the server uses.
+fn dav_patterns() -> &'static [Regex; 3] {
+    static PATTERNS: OnceLock<[Regex; 3]> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let element = |name: &str| {
+            Regex::new(&format!(
+                r"(?s)<(?:[A-Za-z][\w.-]*:)?{0}\b[^>]*>(.*?)</(?:[A-Za-z][\w.-]*:)?{0}>",
+                name
+            ))
+            .expect("valid pattern")
+        };
+        [element("response"), element("href"), element("getetag")]
+    })
+}
+
+/// Note id -> etag of every note in a PROPFIND multistatus body.
+fn parse_listing(xml: &str) -> HashMap<String, String> {
+    let [response, href, getetag] = dav_patterns();
+    response
+        .captures_iter(xml)
+        .filter_map(|response| {
+            let body = response.get(1)?.as_str();
+            let href = href.captures(body)?.get(1)?.as_str().trim();
+            let id = href.trim_end_matches('/').rsplit('/').next()?.strip_suffix(".md")?;
+            if !is_valid_note_id(id) {
+                return None;
+            }
+            let etag = getetag
+                .captures(body)
+                .and_then(|c| c.get(1))
+                .map(|etag| etag.as_str().trim().replace("&quot;", "\""))
+                .unwrap_or_default();
+            Some((id.to_string(), etag))
+        })
+        .collect()
+}
+
+struct Remote {
+    client: Client,
+    config: SyncConfig,
+    /// The collection URL, ending in `/`
+    base: String,
+}
+
+impl Remote {
+    fn new(config: SyncConfig) -> Result<Self, String> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let base = format!("{}/", config.url.trim().trim_end_matches('/'));
+        Ok(Remote { client, config, base })
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .basic_auth(&self.config.username, Some(&self.config.password))
+    }
+
+    fn note_url(&self, id: &str) -> String {
+        format!("{}{}.md", self.base, id)
+    }
+
+    /// Every note on the server with its etag. The collection is created if missing.
+    async fn list(&self) -> Result<HashMap<String, String>, String> {
+        let propfind = Method::from_bytes(b"PROPFIND").map_err(|e| e.to_string())?;
+        let response = self
+            .request(propfind, &self.base)
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == StatusCode::NOT_FOUND {
+            let mkcol = Method::from_bytes(b"MKCOL").map_err(|e| e.to_string())?;
+            let created = self
+                .request(mkcol, &self.base)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            check(created.status(), "Creating the sync folder")?;
+            return Ok(HashMap::new());
+        }
+        check(response.status(), "Listing the sync folder")?;
+        Ok(parse_listing(&response.text().await.map_err(|e| e.to_string())?))
+    }
+
+    async fn download(&self, id: &str) -> Result<(String, String), String> {
+        let response = self
+            .request(Method::GET, &self.note_url(id))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        check(response.status(), "Downloading")?;
+        let etag = etag_of(&response);
+        Ok((response.text().await.map_err(|e| e.to_string())?, etag))
+    }
+
+    /// Uploads `content` over the version with etag `expected`, or as a new file, and
+    /// returns the new etag.
+    async fn upload(&self, id: &str, content: String, expected: Option<&str>) -> Result<String, String> {
+        let request = self.request(Method::PUT, &self.note_url(id)).body(content);
+        let response = precondition(request, expected)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        check(response.status(), "Uploading")?;
+        Ok(etag_of(&response))
+    }
+
+    async fn delete(&self, id: &str, expected: &str) -> Result<(), String> {
+        let response = precondition(self.request(Method::DELETE, &self.note_url(id)), Some(expected))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status => check(status, "Deleting"),
+        }
+    }
+}
+
+/// Makes `request` fail if the server's file isn't the one with etag `expected` (or,
+/// without one, if it exists at all), so a change made meanwhile isn't overwritten.
+fn precondition(request: RequestBuilder, expected: Option<&str>) -> RequestBuilder {
+    match expected {
+        // Nothing to compare against
+        Some("") => request,
+        Some(etag) => request.header("If-Match", etag),
+        None => request.header("If-None-Match", "*"),
+    }
+}
+
+/// Servers that don't send an etag get it from the next listing instead.
+fn etag_of(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn check(status: StatusCode, what: &str) -> Result<(), String> {
+    match status {
+        status if status.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(format!(
+            "{} was refused ({}); check the user name and password",
+            what, status
+        )),
+        StatusCode::PRECONDITION_FAILED => Err(format!("{} skipped: changed on the server meanwhile", what)),
+        status => Err(format!("{} failed: {}", what, status)),
+    }
+}
+
+fn local_stamp(path: &Path) -> Option<LocalStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((to_millis(metadata.modified()), metadata.len()))
+}
+
+fn synced(etag: String, path: &Path) -> Option<SyncedFile> {
+    let (modified_at, len) = local_stamp(path)?;
+    Some(SyncedFile { etag, modified_at, len })
+}
+
+/// Syncs one note and returns what was done and what to remember about it (`None` once
+/// it's gone from both sides).
+async fn sync_note<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    remote: &Remote,
+    dir: &Path,
+    id: &str,
+    remote_etag: Option<&str>,
+    base: Option<SyncedFile>,
+) -> Result<(Action, Option<SyncedFile>), String> {
+    let path = dir.join(format!("{}.md", id));
+    let local = local_stamp(&path);
+    let local_changed = local.is_some_and(|stamp| base.as_ref().is_none_or(|b| (b.modified_at, b.len) != stamp));
+    let remote_changed = remote_etag.is_some_and(|etag| base.as_ref().is_none_or(|b| b.etag != etag));
+    let sync = storage::sync_writes(app);
+
+    match (local, remote_etag) {
+        (None, None) => Ok((Action::Unchanged, None)),
+        (Some(_), None) if base.is_some() && !local_changed => {
+            recycle::move_to_recycled(app, RecycledKind::Trash, id)?;
+            close_note(app, id);
+            Ok((Action::DeletedLocal, None))
+        }
+        (None, Some(etag)) if base.is_some() && !remote_changed => {
+            remote.delete(id, etag).await?;
+            Ok((Action::DeletedRemote, None))
+        }
+        (None, Some(_)) => {
+            let (content, etag) = remote.download(id).await?;
+            check_size(app, &content)?;
+            storage::write_atomically(&path, &content, sync).map_err(|e| storage::write_error(app, e))?;
+            Ok((Action::Downloaded, synced(etag, &path)))
+        }
+        (Some(_), _) if !remote_changed && !local_changed => Ok((Action::Unchanged, base)),
+        (Some(_), _) if conflicts::is_pending(app, id) => Ok((Action::Unchanged, base)),
+        (Some(_), Some(_)) if !remote_changed => {
+            let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            let etag = remote.upload(id, content, remote_etag).await?;
+            Ok((Action::Uploaded, synced(etag, &path)))
+        }
+        // Never synced, or gone from the server while changed here
+        (Some(_), None) => {
+            let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            let etag = remote.upload(id, content, None).await?;
+            Ok((Action::Uploaded, synced(etag, &path)))
+        }
+        (Some(_), Some(_)) => {
+            let (theirs, etag) = remote.download(id).await?;
+            let ours = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            if theirs == ours {
+                return Ok((Action::Unchanged, synced(etag, &path)));
+            }
+            check_size(app, &theirs)?;
+            if !local_changed {
+                storage::write_atomically(&path, &theirs, sync).map_err(|e| storage::write_error(app, e))?;
+                return Ok((Action::Downloaded, synced(etag, &path)));
+            }
+            let copy = dir.join(format!("{}.sync-conflict-{}.md", id, now_millis()));
+            storage::write_atomically(&copy, &theirs, sync).map_err(|e| storage::write_error(app, e))?;
+            // Ours still counts as changed, so it goes up once the conflict is resolved
+            let (modified_at, len) = base.map_or((None, 0), |b| (b.modified_at, b.len));
+            Ok((Action::Conflict, Some(SyncedFile { etag, modified_at, len })))
+        }
+    }
+}
+
+fn check_size<R: Runtime>(app: &tauri::AppHandle<R>, content: &str) -> Result<(), String> {
+    let limit = effective_limits(app).max_note_bytes;
+    if content.len() > limit {
+        return Err(format!(
+            "Skipped: {} bytes is over the {} byte note limit",
+            content.len(),
+            limit
+        ));
+    }
+    Ok(())
+}
+
+/// One full pass over both sides. Failures of single notes are counted and the first is
+/// returned alongside the report; they are retried next time.
+async fn run<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(SyncReport, Option<String>), String> {
+    let config = config(app).ok_or("Sync is not set up")?;
+    ensure_writes_allowed(app)?;
+    flush_now(app, "sync");
+    let remote = Remote::new(config)?;
+    let listing = remote.list().await?;
+
+    let dir = notes_dir(app)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    conflicts::sweep(app);
+    let mut files = load_files(app);
+    let mut ids: BTreeSet<String> = scan_notes(&dir, ScanOptions::default())
+        .filter_map(|entry| match entry {
+            ScanEntry::Note { id, .. } => Some(id),
+            _ => None,
+        })
+        .collect();
+    ids.extend(listing.keys().cloned());
+    ids.extend(files.keys().cloned());
+
+    let mut report = SyncReport::default();
+    let mut first_error = None;
+    for id in ids {
+        let base = files.get(&id).cloned();
+        match sync_note(app, &remote, &dir, &id, listing.get(&id).map(String::as_str), base).await {
+            Ok((action, record)) => {
+                match action {
+                    Action::Unchanged => {}
+                    Action::Uploaded => report.uploaded += 1,
+                    Action::Downloaded => report.downloaded += 1,
+                    Action::DeletedLocal => report.deleted_local += 1,
+                    Action::DeletedRemote => report.deleted_remote += 1,
+                    Action::Conflict => report.conflicts += 1,
+                }
+                match record {
+                    Some(record) => files.insert(id, record),
+                    None => files.remove(&id),
+                };
+            }
+            Err(e) => {
+                println!("Failed to sync {}: {}", id, e);
+                report.failed += 1;
+                first_error.get_or_insert(format!("{}: {}", id, e));
+            }
+        }
+    }
+
+    app.write_store(
+        "session.bin",
+        FILES_KEY,
+        serde_json::to_value(&files).map_err(|e| e.to_string())?,
+    )?;
+    if report.conflicts > 0 {
+        conflicts::sweep(app);
+    }
+    Ok((report, first_error))
+}
+
+async fn sync<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<SyncStatus, String> {
+    let state = app.state::<SyncState>();
+    let Ok(running) = state.running.try_lock() else {
+        return Err("A sync is already running".to_string());
+    };
+    emit_status(app);
+
+    let mut outcome = status(app);
+    match run(app).await {
+        Ok((report, error)) => {
+            println!("Synced: {:?}", report);
+            outcome.last_synced_at = Some(now_millis());
+            outcome.last_report = Some(report);
+            outcome.last_error = error;
+        }
+        Err(e) => {
+            println!("Sync failed: {}", e);
+            outcome.last_error = Some(e);
+        }
+    }
+    let stored = serde_json::to_value(&outcome).map_err(|e| e.to_string())?;
+    app.write_store("session.bin", STATUS_KEY, stored)?;
+    drop(running);
+
+    emit_status(app);
+    app.emit_event("refresh-notes", ());
+    Ok(status(app))
+}
+
+/// Syncs every `interval_minutes` while sync is set up, and right after it is set up.
+pub fn spawn_sync_scheduler<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = config(&app)
+                .map(|c| c.interval_minutes.unwrap_or(DEFAULT_INTERVAL_MINUTES))
+                .filter(|minutes| *minutes > 0);
+            let wait = interval.map_or(IDLE_RECHECK, |minutes| Duration::from_secs(minutes * 60));
+            let _ = tokio::time::timeout(wait, app.state::<SyncState>().wake.notified()).await;
+            if config(&app).is_some_and(|c| c.interval_minutes != Some(0)) {
+                if let Err(e) = sync(&app).await {
+                    println!("Background sync skipped: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_sync_status(app: tauri::AppHandle) -> Result<SyncStatus, String> {
+    Ok(status(&app))
+}
+
+/// Sets up sync, or turns it off with `None`. Pointing it at another server or folder
+/// forgets what was synced before, so the first sync there compares contents afresh.
+#[tauri::command]
+pub async fn set_sync_config(config: Option<SyncConfig>, app: tauri::AppHandle) -> Result<SyncStatus, String> {
+    if let Some(config) = &config {
+        let url = config.url.trim();
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(format!("{:?} is not an http(s) URL", url));
+        }
+    }
+    let previous_url = self::config(&app).map(|c| c.url);
+    if previous_url != config.as_ref().map(|c| c.url.clone()) {
+        app.write_store("session.bin", FILES_KEY, serde_json::Value::Null)?;
+    }
+    let value = match &config {
+        Some(config) => serde_json::to_value(config).map_err(|e| e.to_string())?,
+        None => serde_json::Value::Null,
+    };
+    app.write_store("settings.bin", CONFIG_KEY, value)?;
+    app.state::<SyncState>().wake.notify_one();
+    emit_status(&app);
+    Ok(status(&app))
+}
+
+#[tauri::command]
+pub async fn sync_now(app: tauri::AppHandle) -> Result<SyncStatus, String> {
+    sync(&app).await
+}
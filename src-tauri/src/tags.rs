@@ -0,0 +1,515 @@
+//! Note tags and the bulk operations on them.
+//!
+//! Where a note's tags live: a note that opens with a YAML frontmatter block keeps them
+//! in its `tags:` key, which stays the single source of truth for that note; every other
+//! note keeps them in `NoteMeta::tags`. Frontmatter is edited in place, touching only
+//! the `tags:` entry so the rest of the block stays byte-for-byte the same.
+//!
+//! Bulk operations work in two passes: every affected note is computed and staged as
+//! a `.tmp` file first, then each is renamed over its note, so a note is either fully
+//! rewritten or untouched. One `refresh-notes` goes out at the end.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::changes::own_change;
+use crate::error::NoteError;
+use crate::limits::effective_limits;
+use crate::meta;
+use crate::noteindex::{self, IndexEntry};
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::vault;
+use crate::{build_note_info, notes_dir, sort_for_display, storage, NoteInfo};
+
+/// How a frontmatter `tags:` entry was written, so a rewrite keeps the style.
+#[derive(Clone, Debug, PartialEq)]
+enum TagStyle {
+    /// `tags: [a, b]`
+    Inline,
+    /// `tags:` followed by `  - a` lines; holds the item prefix (e.g. `"  - "`)
+    Block(String),
+    /// `tags: a, b`
+    Scalar,
+}
+
+/// The `tags:` entry of a frontmatter block, as byte offsets into the note.
+#[derive(Debug)]
+struct TagsEntry {
+    start: usize,
+    end: usize,
+    style: TagStyle,
+    tags: Vec<String>,
+}
+
+/// Where a note's frontmatter is and what its `tags:` entry holds.
+#[derive(Debug)]
+struct Frontmatter {
+    /// Offset of the closing `---` line, where a missing `tags:` entry is added
+    close: usize,
+    entry: Option<TagsEntry>,
+}
+
+/// Lines of `content` with the byte offset each starts at, line endings included.
+fn lines_with_offsets(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+fn unquote(item: &str) -> &str {
+    let item = item.trim();
+    item.strip_prefix('"')
+        .and_then(|i| i.strip_suffix('"'))
+        .or_else(|| item.strip_prefix('\'').and_then(|i| i.strip_suffix('\'')))
+        .unwrap_or(item)
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(unquote)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Offset of the closing line of the note's frontmatter, if it opens with one.
+pub fn frontmatter_close(content: &str) -> Option<usize> {
+    parse_frontmatter(content).map(|f| f.close)
+}
+
+/// Offset of the first line after the frontmatter, 0 without one.
+pub fn body_start(content: &str) -> usize {
+    frontmatter_close(content)
+        .and_then(|close| content[close..].find('\n').map(|end| close + end + 1))
+        .unwrap_or(0)
+}
+
+fn parse_frontmatter(content: &str) -> Option<Frontmatter> {
+    let mut lines = lines_with_offsets(content);
+    let (_, first) = lines.next()?;
+    if first.trim_end() != "---" {
+        return None;
+    }
+
+    let mut entry: Option<TagsEntry> = None;
+    let mut in_block_list = false;
+    for (offset, line) in lines {
+        let text = line.trim_end();
+        if text == "---" || text == "..." {
+            return Some(Frontmatter { close: offset, entry });
+        }
+        if in_block_list {
+            let entry = entry.as_mut()?;
+            let item = text.trim_start();
+            if line.starts_with([' ', '\t']) && (item == "-" || item.starts_with("- ")) {
+                if let TagStyle::Block(prefix) = &mut entry.style {
+                    if prefix.is_empty() {
+                        *prefix = text[..text.len() - item.len()].to_string() + "- ";
+                    }
+                }
+                let tag = unquote(&item[1..]);
+                if !tag.is_empty() {
+                    entry.tags.push(tag.to_string());
+                }
+                entry.end = offset + line.len();
+                continue;
+            }
+            in_block_list = false;
+        }
+        if let Some(value) = text.strip_prefix("tags:") {
+            let value = value.trim();
+            let (style, tags) = if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                (TagStyle::Inline, split_list(list))
+            } else if value.is_empty() {
+                in_block_list = true;
+                (TagStyle::Block(String::new()), Vec::new())
+            } else {
+                (TagStyle::Scalar, split_list(value))
+            };
+            entry = Some(TagsEntry {
+                start: offset,
+                end: offset + line.len(),
+                style,
+                tags,
+            });
+        }
+    }
+    // No closing line: not frontmatter after all
+    None
+}
+
+fn quote_if_needed(tag: &str) -> String {
+    if tag.contains([':', '#', '[', ']', '{', '}', '"', '\'']) || tag.starts_with(['-', '&', '*', '!', '|', '>']) {
+        format!("\"{}\"", tag.replace('"', "\\\""))
+    } else {
+        tag.to_string()
+    }
+}
+
+fn render_entry(style: &TagStyle, tags: &[String], newline: &str) -> String {
+    let items: Vec<String> = tags.iter().map(|t| quote_if_needed(t)).collect();
+    match style {
+        TagStyle::Block(prefix) if !items.is_empty() => {
+            let prefix = if prefix.is_empty() { "  - " } else { prefix };
+            let mut out = format!("tags:{}", newline);
+            for item in items {
+                out.push_str(&format!("{}{}{}", prefix, item, newline));
+            }
+            out
+        }
+        TagStyle::Scalar if !items.is_empty() => format!("tags: {}{}", items.join(", "), newline),
+        _ => format!("tags: [{}]{}", items.join(", "), newline),
+    }
+}
+
+/// `content` with its frontmatter tags replaced, or `None` if it has no frontmatter.
+pub fn rewrite_frontmatter_tags(content: &str, tags: &[String]) -> Option<String> {
+    let frontmatter = parse_frontmatter(content)?;
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let (start, end, style) = match &frontmatter.entry {
+        Some(entry) => (entry.start, entry.end, entry.style.clone()),
+        None => (frontmatter.close, frontmatter.close, TagStyle::Inline),
+    };
+    let rendered = render_entry(&style, tags, newline);
+    Some(format!("{}{}{}", &content[..start], rendered, &content[end..]))
+}
+
+/// A note's tags from wherever they live.
+pub fn read_tags(content: &str, note_meta: &meta::NoteMeta) -> Vec<String> {
+    frontmatter_tags(content).unwrap_or_else(|| note_meta.tags.clone())
+}
+
+/// `read_tags` for a note's index entry.
+fn indexed_tags(entry: &IndexEntry, note_meta: Option<&meta::NoteMeta>) -> Vec<String> {
+    match entry.frontmatter_tags() {
+        Some(tags) => tags.to_vec(),
+        None => note_meta.map(|m| m.tags.clone()).unwrap_or_default(),
+    }
+}
+
+/// Tags from `content`'s frontmatter, `None` if it has none (or it isn't closed).
+pub fn frontmatter_tags(content: &str) -> Option<Vec<String>> {
+    parse_frontmatter(content).map(|frontmatter| frontmatter.entry.map(|e| e.tags).unwrap_or_default())
+}
+
+/// Trims, drops a leading `#` and rejects what can't round-trip through frontmatter.
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('#').trim();
+    if tag.is_empty() {
+        return Err("Tag is empty".to_string());
+    }
+    if tag.contains([',', '\n', '\r']) {
+        return Err(format!("Tag {:?} contains a comma or line break", tag));
+    }
+    Ok(tag.to_string())
+}
+
+/// Adds a tag the user asked for, unless it or a case variant of it is already there.
+fn add_tag(tags: &mut Vec<String>, tag: &str) {
+    if !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+        tags.push(tag.to_string());
+    }
+}
+
+/// Carries over a tag the note already had. Case variants the note already uses side by
+/// side are left alone; only `rename_tag` with `fold_case` folds them.
+fn keep_tag(tags: &mut Vec<String>, tag: &str) {
+    if !tags.iter().any(|t| t == tag) {
+        tags.push(tag.to_string());
+    }
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TagOutcome {
+    Updated { id: String },
+    Unchanged { id: String },
+    Failed { id: String, error: String },
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TagCount {
+    /// The most used spelling
+    tag: String,
+    count: usize,
+    /// Every spelling in use, when there is more than one (`Work`, `work`)
+    variants: Vec<String>,
+}
+
+/// A tag change for one note, computed before anything is written.
+enum Staged {
+    Content { id: String, content: String },
+    Meta { id: String, tags: Vec<String> },
+}
+
+/// Which notes a bulk operation looks at.
+enum Targets<'a> {
+    All,
+    /// Explicit ids report `Unchanged` when nothing needed doing
+    Ids(&'a [String]),
+}
+
+fn target_ids<R: Runtime>(app: &tauri::AppHandle<R>, targets: &Targets) -> Result<Vec<String>, String> {
+    match targets {
+        Targets::Ids(ids) => Ok(ids.to_vec()),
+        Targets::All => Ok(scan_notes(&notes_dir(app)?, ScanOptions::default())
+            .filter_map(|entry| match entry {
+                ScanEntry::Note { id, .. } => Some(id),
+                _ => None,
+            })
+            .collect()),
+    }
+}
+
+/// Applies `change` to the tags of every targeted note. `change` returns the new tag
+/// list, or `None` to leave the note alone.
+fn update_tags<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    targets: Targets,
+    change: impl Fn(&[String]) -> Option<Vec<String>>,
+) -> Result<Vec<TagOutcome>, String> {
+    storage::ensure_writes_allowed(app)?;
+    let dir = notes_dir(app)?;
+    let limit = effective_limits(app).max_note_bytes;
+    let metas = meta::load_all(app);
+    let mut outcomes = Vec::new();
+    let mut staged = Vec::new();
+
+    for id in target_ids(app, &targets)? {
+        let path = dir.join(format!("{}.md", id));
+        let content = match vault::read_file(app, &path) {
+            Ok(content) => content,
+            Err(e) => {
+                outcomes.push(TagOutcome::Failed {
+                    id,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let current = read_tags(&content, &metas.get(&id).cloned().unwrap_or_default());
+        let Some(tags) = change(&current).filter(|tags| *tags != current) else {
+            if matches!(targets, Targets::Ids(_)) {
+                outcomes.push(TagOutcome::Unchanged { id });
+            }
+            continue;
+        };
+        match rewrite_frontmatter_tags(&content, &tags) {
+            Some(content) if content.len() > limit => outcomes.push(TagOutcome::Failed {
+                id,
+                error: NoteError::TooLarge {
+                    size: content.len(),
+                    limit,
+                }
+                .to_string(),
+            }),
+            Some(content) => staged.push(Staged::Content { id, content }),
+            None => staged.push(Staged::Meta { id, tags }),
+        }
+    }
+
+    // Stage every rewritten note before replacing any of them
+    let mut ready = Vec::new();
+    for change in staged {
+        match change {
+            Staged::Content { id, content } => {
+                let tmp = dir.join(format!("{}.md.tmp", id));
+                let written = vault::encode(app, &content)
+                    .and_then(|stored| fs::write(&tmp, stored).map_err(|e| e.to_string()));
+                match written {
+                    Ok(()) => ready.push(Staged::Content { id, content }),
+                    Err(error) => outcomes.push(TagOutcome::Failed { id, error }),
+                }
+            }
+            meta_change => ready.push(meta_change),
+        }
+    }
+
+    for change in ready {
+        let (id, result) = match change {
+            Staged::Content { id, .. } => {
+                let (tmp, path) = (dir.join(format!("{}.md.tmp", id)), dir.join(format!("{}.md", id)));
+                let result = own_change(app, &[&id], || fs::rename(&tmp, &path)).map_err(|e| e.to_string());
+                if result.is_err() {
+                    let _ = fs::remove_file(&tmp);
+                }
+                (id, result)
+            }
+            Staged::Meta { id, tags } => {
+                let result = meta::update_meta(app, &id, |meta| meta.tags = tags).map(|_| ());
+                (id, result)
+            }
+        };
+        outcomes.push(match result {
+            Ok(()) => TagOutcome::Updated { id },
+            Err(error) => TagOutcome::Failed { id, error },
+        });
+    }
+
+    if outcomes.iter().any(|o| matches!(o, TagOutcome::Updated { .. })) {
+        storage::note_saved(app);
+        app.emit_event("refresh-notes", ());
+    }
+    Ok(outcomes)
+}
+
+/// Every tag in use, case variants counted together.
+#[tauri::command]
+pub async fn get_all_tags(app: tauri::AppHandle) -> Result<Vec<TagCount>, String> {
+    let metas = meta::load_all(&app);
+    // lowercase tag -> spelling -> notes using it
+    let mut counts: BTreeMap<String, HashMap<String, usize>> = BTreeMap::new();
+    for (id, entry) in noteindex::listing(&app)? {
+        let mut tags = indexed_tags(&entry, metas.get(&id));
+        tags.sort_by_key(|t| t.to_lowercase());
+        tags.dedup_by_key(|t| t.to_lowercase());
+        for tag in tags {
+            *counts.entry(tag.to_lowercase()).or_default().entry(tag).or_default() += 1;
+        }
+    }
+
+    Ok(counts
+        .into_values()
+        .map(|spellings| {
+            let mut variants: Vec<(String, usize)> = spellings.into_iter().collect();
+            variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            TagCount {
+                tag: variants[0].0.clone(),
+                count: variants.iter().map(|(_, n)| n).sum(),
+                variants: if variants.len() > 1 {
+                    variants.into_iter().map(|(t, _)| t).collect()
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect())
+}
+
+/// Notes carrying `tag` (in any case variant), in the dashboard's sort order.
+#[tauri::command]
+pub async fn get_notes_by_tag(tag: String, app: tauri::AppHandle) -> Result<Vec<NoteInfo>, String> {
+    let wanted = normalize_tag(&tag)?.to_lowercase();
+    let metas = meta::load_all(&app);
+    let mut notes = Vec::new();
+    for (id, entry) in noteindex::listing(&app)? {
+        let tags = indexed_tags(&entry, metas.get(&id));
+        if tags.iter().any(|t| t.to_lowercase() == wanted) {
+            let (modified_at, created_at) = (entry.file_modified_at(), entry.file_created_at());
+            notes.push(build_note_info(id, entry.listing(), modified_at, created_at, &metas));
+        }
+    }
+    sort_for_display(&app, &mut notes, None);
+    Ok(notes)
+}
+
+/// Replaces note `id`'s tags with `tags` and returns them as stored: normalized, with
+/// case duplicates dropped.
+#[tauri::command]
+pub async fn set_note_tags(id: String, tags: Vec<String>, app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let mut normalized = Vec::new();
+    for tag in &tags {
+        add_tag(&mut normalized, &normalize_tag(tag)?);
+    }
+    let outcomes = update_tags(&app, Targets::Ids(std::slice::from_ref(&id)), |_| Some(normalized.clone()))?;
+    if let Some(TagOutcome::Failed { error, .. }) = outcomes.into_iter().next() {
+        return Err(error);
+    }
+    Ok(normalized)
+}
+
+/// Renames `old` to `new` on every note. With `fold_case` every case variant of `old`
+/// (`Work`, `WORK`) is renamed too, which is how duplicates reported by `get_all_tags`
+/// are folded together.
+#[tauri::command]
+pub async fn rename_tag(
+    old: String,
+    new: String,
+    fold_case: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<Vec<TagOutcome>, String> {
+    let (old, new) = (normalize_tag(&old)?, normalize_tag(&new)?);
+    let fold_case = fold_case.unwrap_or(false);
+    let matches = |t: &String| {
+        if fold_case {
+            t.to_lowercase() == old.to_lowercase()
+        } else {
+            *t == old
+        }
+    };
+    update_tags(&app, Targets::All, |tags| {
+        if !tags.iter().any(matches) {
+            return None;
+        }
+        let mut renamed = Vec::new();
+        for tag in tags {
+            if matches(tag) {
+                add_tag(&mut renamed, &new);
+            } else {
+                keep_tag(&mut renamed, tag);
+            }
+        }
+        Some(renamed)
+    })
+}
+
+/// Replaces each of `tags` with `into` on every note carrying any of them.
+#[tauri::command]
+pub async fn merge_tags(tags: Vec<String>, into: String, app: tauri::AppHandle) -> Result<Vec<TagOutcome>, String> {
+    let into = normalize_tag(&into)?;
+    let merged: Vec<String> = tags.iter().map(|t| normalize_tag(t)).collect::<Result<_, _>>()?;
+    update_tags(&app, Targets::All, |current| {
+        if !current.iter().any(|t| merged.contains(t)) {
+            return None;
+        }
+        let mut result = Vec::new();
+        for tag in current {
+            if merged.contains(tag) {
+                add_tag(&mut result, &into);
+            } else {
+                keep_tag(&mut result, tag);
+            }
+        }
+        Some(result)
+    })
+}
+
+#[tauri::command]
+pub async fn delete_tag(tag: String, app: tauri::AppHandle) -> Result<Vec<TagOutcome>, String> {
+    let tag = normalize_tag(&tag)?;
+    update_tags(&app, Targets::All, |current| {
+        current
+            .contains(&tag)
+            .then(|| current.iter().filter(|t| **t != tag).cloned().collect())
+    })
+}
+
+pub fn tag_notes<R: Runtime>(app: &tauri::AppHandle<R>, ids: &[String], tag: &str) -> Result<Vec<TagOutcome>, String> {
+    let tag = normalize_tag(tag)?;
+    update_tags(app, Targets::Ids(ids), |current| {
+        let mut tags = current.to_vec();
+        add_tag(&mut tags, &tag);
+        Some(tags)
+    })
+}
+
+#[tauri::command]
+pub async fn add_tag_to_notes(ids: Vec<String>, tag: String, app: tauri::AppHandle) -> Result<Vec<TagOutcome>, String> {
+    tag_notes(&app, &ids, &tag)
+}
+
+#[tauri::command]
+pub async fn remove_tag_from_notes(
+    ids: Vec<String>,
+    tag: String,
+    app: tauri::AppHandle,
+) -> Result<Vec<TagOutcome>, String> {
+    let tag = normalize_tag(&tag)?;
+    update_tags(&app, Targets::Ids(&ids), |current| {
+        Some(current.iter().filter(|t| **t != tag).cloned().collect())
+    })
+}
@@ -0,0 +1,174 @@
+//! GitHub-style task list items, `- [ ] open` and `- [x] done` (any list marker, any
+//! indent), outside fenced code blocks. Listings carry the counts for task badges, and
+//! `toggle_task` ticks one off by rewriting just its mark.
+
+use crate::backend::NotesBackend;
+use crate::{load_note, save_note, SavedNote};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct TaskCounts {
+    pub open: usize,
+    pub done: usize,
+}
+
+/// Where a task line's mark is: the byte offset of the character between the brackets,
+/// and whether it is checked.
+fn task_mark(line: &str) -> Option<(usize, bool)> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let marker_len = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 || digits > 9 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+    let after_marker = &rest[marker_len..];
+    let spaces = after_marker.len() - after_marker.trim_start_matches([' ', '\t']).len();
+    if spaces == 0 {
+        return None;
+    }
+    let bracket = &after_marker[spaces..];
+    let checked = match bracket.get(..3) {
+        Some("[ ]") => false,
+        Some("[x]") | Some("[X]") => true,
+        _ => return None,
+    };
+    // `- [ ]` alone or followed by text, but not `- [ ]x`
+    if !bracket[3..].is_empty() && !bracket[3..].starts_with([' ', '\t', '\r']) {
+        return None;
+    }
+    Some((indent + marker_len + spaces + 1, checked))
+}
+
+/// The lines of `content` (split on `\n`, zero-based) that are task items, with their
+/// marks. Lines inside fenced code blocks don't count.
+fn task_lines(content: &str) -> impl Iterator<Item = (usize, &str, (usize, bool))> {
+    let mut fence: Option<&str> = None;
+    content.split('\n').enumerate().filter_map(move |(number, line)| {
+        let trimmed = line.trim_start();
+        for marker in ["```", "~~~"] {
+            if trimmed.starts_with(marker) {
+                match fence {
+                    Some(open) if open == marker => fence = None,
+                    None => fence = Some(marker),
+                    Some(_) => {}
+                }
+                return None;
+            }
+        }
+        if fence.is_some() {
+            return None;
+        }
+        task_mark(line).map(|mark| (number, line, mark))
+    })
+}
+
+pub fn count_tasks(content: &str) -> TaskCounts {
+    let mut counts = TaskCounts::default();
+    for (_, _, (_, checked)) in task_lines(content) {
+        if checked {
+            counts.done += 1;
+        } else {
+            counts.open += 1;
+        }
+    }
+    counts
+}
+
+/// `content` with the task on zero-based `line` checked or unchecked, and everything
+/// else (line endings included) untouched.
+fn toggled(content: &str, line: usize) -> Result<(String, bool), String> {
+    let (_, text, (offset, checked)) = task_lines(content)
+        .find(|(number, _, _)| *number == line)
+        .ok_or_else(|| format!("Line {} is not a task", line))?;
+    // `text` borrows from `content`, so its offset there is where the line starts
+    let at = text.as_ptr() as usize - content.as_ptr() as usize + offset;
+    let mut updated = String::with_capacity(content.len());
+    updated.push_str(&content[..at]);
+    updated.push(if checked { ' ' } else { 'x' });
+    updated.push_str(&content[at + 1..]);
+    Ok((updated, !checked))
+}
+
+#[derive(serde::Serialize)]
+pub struct ToggledTask {
+    #[serde(flatten)]
+    saved: SavedNote,
+    checked: bool,
+    tasks: TaskCounts,
+}
+
+/// Checks or unchecks the task on zero-based `line` of note `id` and saves the note the
+/// way `save_note` does. With `expected_modified_at` the toggle fails with
+/// `ModifiedExternally` if the note changed since, rather than tick the wrong line.
+#[tauri::command]
+pub async fn toggle_task(
+    id: String,
+    line: usize,
+    expected_modified_at: Option<u64>,
+    app: tauri::AppHandle,
+) -> Result<ToggledTask, String> {
+    let content = load_note(id.clone(), app.clone()).await?;
+    let (content, checked) = toggled(&content, line)?;
+    let saved = save_note(id.clone(), content, None, expected_modified_at, app.clone()).await?;
+    let tasks = count_tasks(&saved.content);
+    // An open window shows the change as it would an edit made outside the app
+    app.emit_note_event(&id, "note-changed-externally", &id);
+    app.emit_event("refresh-notes", ());
+    Ok(ToggledTask { saved, checked, tasks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_task_items() {
+        assert_eq!(task_mark("- [ ] milk"), Some((3, false)));
+        assert_eq!(task_mark("  * [x] eggs"), Some((5, true)));
+        assert_eq!(task_mark("+ [X]"), Some((3, true)));
+        assert_eq!(task_mark("12. [ ] step"), Some((5, false)));
+        assert_eq!(task_mark("3)\t[ ] step"), Some((4, false)));
+        assert_eq!(task_mark("- [ ]\r"), Some((3, false)));
+    }
+
+    #[test]
+    fn rejects_lookalikes() {
+        for line in [
+            "-[ ] no space",
+            "- [ ]x",
+            "- [y] other",
+            "- []",
+            "[ ] bare",
+            "1234567890. [ ] long",
+            "a. [ ] letter",
+        ] {
+            assert_eq!(task_mark(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn counts_skip_fenced_code() {
+        let content = "- [ ] a\n- [x] b\n```\n- [ ] code\n~~~\n- [x] still code\n```\n* [X] c\n";
+        assert_eq!(count_tasks(content), TaskCounts { open: 1, done: 2 });
+        assert_eq!(count_tasks("no tasks"), TaskCounts::default());
+    }
+
+    #[test]
+    fn toggling_rewrites_only_the_mark() {
+        let content = "Todo\r\n- [ ] milk\r\n  1. [x] eggs\r\n";
+        let (checked, now) = toggled(content, 1).unwrap();
+        assert!(now);
+        assert_eq!(checked, "Todo\r\n- [x] milk\r\n  1. [x] eggs\r\n");
+        let (unchecked, now) = toggled(&checked, 2).unwrap();
+        assert!(!now);
+        assert_eq!(unchecked, "Todo\r\n- [x] milk\r\n  1. [ ] eggs\r\n");
+
+        assert!(toggled(content, 0).is_err());
+        assert!(toggled(content, 9).is_err());
+        assert!(toggled("```\n- [ ] code\n```", 1).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Note templates, kept as plain markdown files in the `templates` data root. They are
+//! made from existing notes with `save_note_as_template`, or from content the frontend
+//! composed with `save_template`, rather than by editing files. The tray's "New from
+//! template" submenu and `create_note_from_template` turn one into a new note.
+//!
+//! A template saved with metadata carries the note's color and tags in its frontmatter,
+//! so notes made from it start out the same way: the tags stay in the note's frontmatter
+//! and the color moves to its metadata (see `derive_metadata`). `{{date}}` becomes the day the note is made.
+
+use regex::Regex;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tauri::menu::{MenuItem, Submenu};
+use tauri::{Manager, Runtime};
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::derived::{derive_metadata, DerivedKind, DerivedMetadata};
+use crate::encryption;
+use crate::error::NoteError;
+use crate::meta;
+use crate::notewindow::NoteWindowOptions;
+use crate::safepath::{sanitize_file_name, Root, SafePath};
+use crate::scan::is_valid_note_id;
+use crate::storage::ensure_writes_allowed;
+use crate::tags::{frontmatter_close, read_tags, rewrite_frontmatter_tags};
+use crate::usage::record_usage;
+use crate::{create_note_window, notes_dir, read_note, write_note};
+
+const DATE_PLACEHOLDER: &str = "{{date}}";
+/// Prefix of the tray menu item ids; the template name follows.
+pub const MENU_ID_PREFIX: &str = "template:";
+
+/// The tray's "New from template" submenu, rebuilt whenever the templates change.
+pub struct TemplateMenu<R: Runtime>(pub Submenu<R>);
+
+fn templates_dir<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    let dir = Root::Templates.dir(app)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn template_path<R: Runtime>(app: &tauri::AppHandle<R>, name: &str) -> Result<(String, SafePath), String> {
+    let name = sanitize_file_name(name)?;
+    let path = templates_dir(app)?.join(format!("{}.md", name));
+    Ok((name.clone(), SafePath::new(app, Root::Templates, path)?))
+}
+
+/// ISO dates as the journal writes them, with an optional time and offset.
+fn date_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\b\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)?\b")
+            .expect("date pattern is valid")
+    })
+}
+
+/// Replaces the instance-specific bits of a note with placeholders.
+fn generalize_content(content: &str) -> String {
+    date_pattern().replace_all(content, DATE_PLACEHOLDER).into_owned()
+}
+
+/// Puts the color and tags into the content's frontmatter, adding a block if there is
+/// none. Tags already in the frontmatter are left as they are.
+fn embed_metadata(content: &str, color: Option<&str>, tags: &[String]) -> String {
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut content = match frontmatter_close(content) {
+        Some(_) => content.to_string(),
+        None => {
+            let with_block = format!("---{0}---{0}{1}", newline, content);
+            rewrite_frontmatter_tags(&with_block, tags).unwrap_or(with_block)
+        }
+    };
+    if let (Some(color), Some(close)) = (color, frontmatter_close(&content)) {
+        let has_color = content[..close].lines().any(|line| line.starts_with("color:"));
+        if !has_color {
+            content.insert_str(close, &format!("color: \"{}\"{}", color, newline));
+        }
+    }
+    content
+}
+
+/// Takes a `color:` line out of the frontmatter (see `embed_metadata`) and returns the
+/// content without it along with the color.
+fn take_color(content: &str) -> (String, Option<String>) {
+    let Some(close) = frontmatter_close(content) else {
+        return (content.to_string(), None);
+    };
+    let mut color = None;
+    let head: String = content[..close]
+        .split_inclusive('\n')
+        .filter(|line| match line.strip_prefix("color:") {
+            Some(value) if color.is_none() => {
+                color = Some(value.trim().trim_matches(['"', '\'']).to_string()).filter(|c| !c.is_empty());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    (format!("{}{}", head, &content[close..]), color)
+}
+
+/// Template names, by name ignoring case.
+pub fn template_names<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = fs::read_dir(templates_dir(app)?)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_str()?.to_string();
+            name.strip_suffix(".md").map(str::to_string)
+        })
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+/// Lists the templates in the tray's submenu.
+pub fn refresh_menu<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Some(menu) = app.try_state::<TemplateMenu<R>>() else {
+        return;
+    };
+    let submenu = &menu.0;
+    if let Ok(items) = submenu.items() {
+        for item in items {
+            let _ = submenu.remove(&item);
+        }
+    }
+
+    let names = template_names(app).unwrap_or_default();
+    let _ = submenu.set_enabled(!names.is_empty());
+    for name in names {
+        if let Ok(item) = MenuItem::with_id(app, format!("{}{}", MENU_ID_PREFIX, name), &name, true, None::<&str>) {
+            let _ = submenu.append(&item);
+        }
+    }
+}
+
+fn notify_changed<R: Runtime>(app: &tauri::AppHandle<R>) {
+    refresh_menu(app);
+    app.emit_event("templates-changed", ());
+}
+
+/// Writes `content` as template `target` through a temp file.
+fn write_template<R: Runtime>(app: &tauri::AppHandle<R>, target: &SafePath, content: &str) -> Result<(), String> {
+    let tmp = target.path().with_extension("md.tmp");
+    fs::write(&tmp, content).map_err(|e| e.to_string())?;
+    SafePath::new(app, Root::Templates, tmp)?.rename_to(target)?;
+    notify_changed(app);
+    Ok(())
+}
+
+/// Template `name` filled in for a note made today: its content, and the color the note
+/// should get.
+pub fn render_template<R: Runtime>(app: &tauri::AppHandle<R>, name: &str) -> Result<(String, Option<String>), String> {
+    let (name, source) = template_path(app, name)?;
+    if !source.path().is_file() {
+        return Err(format!("No template named {:?}", name));
+    }
+    let template = fs::read_to_string(source.path()).map_err(|e| e.to_string())?;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    Ok(take_color(&template.replace(DATE_PLACEHOLDER, &today)))
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TemplateNote {
+    id: String,
+    metadata: DerivedMetadata,
+}
+
+/// Makes a note from template `name` and opens it.
+pub fn new_note_from_template<R: Runtime>(app: &tauri::AppHandle<R>, name: &str) -> Result<TemplateNote, String> {
+    ensure_writes_allowed(app)?;
+    let (content, color) = render_template(app, name)?;
+
+    let id = Uuid::new_v4().to_string();
+    write_note(app, &id, &content)?;
+    let metadata = derive_metadata(
+        &[],
+        DerivedKind::Template {
+            color: color.as_deref(),
+        },
+    );
+    if metadata != DerivedMetadata::default() {
+        meta::update_meta(app, &id, |meta| metadata.apply(meta))?;
+    }
+    create_note_window(app, NoteWindowOptions::open(id.clone()))?;
+    println!("Created note {} from template {:?}", id, name);
+    app.emit_event("refresh-notes", ());
+    Ok(TemplateNote { id, metadata })
+}
+
+/// Template names, sorted.
+#[tauri::command]
+pub async fn list_templates(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    template_names(&app)
+}
+
+/// Saves `content` as template `name` and returns the name it was saved under. An
+/// existing template is only replaced with `overwrite`.
+#[tauri::command]
+pub async fn save_template(
+    name: String,
+    content: String,
+    overwrite: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    ensure_writes_allowed(&app)?;
+    let (name, target) = template_path(&app, &name)?;
+    if target.path().exists() && !overwrite.unwrap_or(false) {
+        return Err(NoteError::TemplateExists { name }.into());
+    }
+    write_template(&app, &target, &content)?;
+    Ok(name)
+}
+
+/// Opens a new note made from template `name` and returns its id and metadata.
+#[tauri::command]
+pub async fn create_note_from_template(name: String, app: tauri::AppHandle) -> Result<TemplateNote, String> {
+    record_usage(&app, "new_note_template");
+    new_note_from_template(&app, &name)
+}
+
+/// Saves note `id` as a template and returns the name it was saved under. `generalize`
+/// replaces dates with `{{date}}`; an existing template is only replaced with `overwrite`.
+#[tauri::command]
+pub async fn save_note_as_template(
+    id: String,
+    name: String,
+    include_metadata: bool,
+    generalize: Option<bool>,
+    overwrite: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    ensure_writes_allowed(&app)?;
+    if !is_valid_note_id(&id) || !notes_dir(&app)?.join(format!("{}.md", id)).is_file() {
+        return Err(format!("Note {} does not exist", id));
+    }
+    let (name, target) = template_path(&app, &name)?;
+    if target.path().exists() && !overwrite.unwrap_or(false) {
+        return Err(NoteError::TemplateExists { name }.into());
+    }
+
+    let mut content = read_note(&app, &id)?;
+    // A template is plain text; it mustn't become a way to decrypt a note
+    encryption::ensure_not_locked(&id, &content)?;
+    if include_metadata {
+        let note_meta = meta::get_meta(&app, &id);
+        let tags = read_tags(&content, &note_meta);
+        content = embed_metadata(&content, note_meta.color.as_deref(), &tags);
+    }
+    if generalize.unwrap_or(false) {
+        content = generalize_content(&content);
+    }
+
+    write_template(&app, &target, &content)?;
+    Ok(name)
+}
+
+#[tauri::command]
+pub async fn delete_template(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    let (name, target) = template_path(&app, &name)?;
+    if !target.path().is_file() {
+        return Err(format!("No template named {:?}", name));
+    }
+    target.remove()?;
+    notify_changed(&app);
+    Ok(())
+}
+
+/// Renames a template and returns its new name. Refuses to replace another template, but
+/// a change of case only is allowed.
+#[tauri::command]
+pub async fn rename_template(old: String, new: String, app: tauri::AppHandle) -> Result<String, String> {
+    let (old, from) = template_path(&app, &old)?;
+    let (new, to) = template_path(&app, &new)?;
+    if !from.path().is_file() {
+        return Err(format!("No template named {:?}", old));
+    }
+    if to.path().exists() && old.to_lowercase() != new.to_lowercase() {
+        return Err(NoteError::TemplateExists { name: new }.into());
+    }
+    from.rename_to(&to)?;
+    notify_changed(&app);
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_become_placeholders() {
+        assert_eq!(generalize_content("Standup 2026-10-16"), "Standup {{date}}");
+        assert_eq!(
+            generalize_content("From 2026-10-01 to 2026-10-31\n2026-11-02: review"),
+            "From {{date}} to {{date}}\n{{date}}: review"
+        );
+    }
+
+    #[test]
+    fn times_and_offsets_go_with_their_date() {
+        for stamp in [
+            "2026-10-16T09:30",
+            "2026-10-16 09:30",
+            "2026-10-16T09:30:15",
+            "2026-10-16T09:30:15.250",
+            "2026-10-16T09:30:15Z",
+            "2026-10-16T09:30:15+02:00",
+            "2026-10-16T09:30-0500",
+        ] {
+            assert_eq!(
+                generalize_content(&format!("at {} sharp", stamp)),
+                "at {{date}} sharp",
+                "{}",
+                stamp
+            );
+        }
+    }
+
+    #[test]
+    fn a_date_followed_by_other_text_keeps_the_text() {
+        assert_eq!(generalize_content("2026-10-16 notes"), "{{date}} notes");
+        assert_eq!(generalize_content("(2026-10-16)"), "({{date}})");
+        assert_eq!(generalize_content("due 2026-10-16, 10:00"), "due {{date}}, 10:00");
+    }
+
+    #[test]
+    fn other_numbers_are_left_alone() {
+        let content = "Call 555-123-4567 at 10:30\nv1.2.3, ticket 12026-10-16, id x2026-10-16\n26-10-16";
+        assert_eq!(generalize_content(content), content);
+    }
+
+    #[test]
+    fn generalizing_twice_changes_nothing_more() {
+        let once = generalize_content("# Week of 2026-10-12\n- [ ] 2026-10-13T08:00Z deploy\n");
+        assert_eq!(once, "# Week of {{date}}\n- [ ] {{date}} deploy\n");
+        assert_eq!(generalize_content(&once), once);
+    }
+}
@@ -0,0 +1,250 @@
+//! Synthetic notes for exercising listing and restore performance. Debug builds only.
+
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+use crate::backend::NotesBackend;
+use crate::changes::own_change;
+use crate::safepath::{Root, SafePath};
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::vault;
+use crate::{notes_dir, pinboard};
+
+/// Every generated note carries this so `clear_test_data` never touches real notes.
+const ORIGIN_MARKER: &str = "<!-- sticky-notes:test-data -->";
+const LOREM: &[&str] = &[
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit.",
+    "Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.",
+    "Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris.",
+    "Duis aute irure dolor in reprehenderit in voluptate velit esse.",
+    "Excepteur sint occaecat cupidatat non proident, sunt in culpa.",
+];
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeDistribution {
+    Small,
+    /// Mostly small notes, some long ones and the odd multi-megabyte outlier
+    Realistic,
+    Large,
+}
+
+#[derive(serde::Serialize)]
+pub struct GenerationReport {
+    created: usize,
+    bytes: usize,
+    elapsed_ms: u128,
+    notes_per_second: f64,
+}
+
+/// Small deterministic generator so the same count always yields the same dataset.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+}
+
+fn paragraph_count(rng: &mut XorShift, distribution: SizeDistribution) -> u64 {
+    match distribution {
+        SizeDistribution::Small => 1 + rng.below(3),
+        SizeDistribution::Large => 50 + rng.below(200),
+        SizeDistribution::Realistic => match rng.below(1000) {
+            0..=4 => 20_000 + rng.below(20_000),
+            5..=99 => 20 + rng.below(80),
+            _ => 1 + rng.below(6),
+        },
+    }
+}
+
+fn synthetic_content(rng: &mut XorShift, index: usize, distribution: SizeDistribution) -> String {
+    let mut content = format!("# Test note {}\n\n", index);
+    for p in 0..paragraph_count(rng, distribution) {
+        match rng.below(4) {
+            0 => {
+                for item in 0..(2 + rng.below(5)) {
+                    let done = if rng.below(2) == 0 { "x" } else { " " };
+                    content.push_str(&format!("- [{}] Task {}.{}\n", done, p, item));
+                }
+            }
+            1 => content.push_str(&format!("## Section {}\n", p)),
+            _ => {
+                for _ in 0..(1 + rng.below(4)) {
+                    content.push_str(LOREM[rng.below(LOREM.len() as u64) as usize]);
+                    content.push(' ');
+                }
+                content.push('\n');
+            }
+        }
+        content.push('\n');
+    }
+    content.push_str(ORIGIN_MARKER);
+    content.push('\n');
+    content
+}
+
+/// Writes `count` synthetic notes with modification times spread over the last six months.
+/// Returns their ids and how many bytes they hold.
+fn generate(
+    backend: &impl NotesBackend,
+    count: usize,
+    distribution: SizeDistribution,
+) -> Result<(Vec<String>, usize), String> {
+    let dir = notes_dir(backend)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15 ^ count as u64);
+    let mut ids = Vec::with_capacity(count);
+    let mut bytes = 0;
+    for index in 0..count {
+        let id = Uuid::new_v4().to_string();
+        let content = synthetic_content(&mut rng, index, distribution);
+        let path = dir.join(format!("{}.md", id));
+        let age = Duration::from_secs(rng.below(180 * 24 * 60 * 60));
+        backend
+            .own_change(&[&id], || {
+                fs::write(&path, &content)?;
+                if let Ok(file) = fs::File::options().write(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now() - age);
+                }
+                Ok::<_, std::io::Error>(())
+            })
+            .map_err(|e| e.to_string())?;
+        bytes += content.len();
+        ids.push(id);
+    }
+    Ok((ids, bytes))
+}
+
+/// Creates `count` synthetic notes with modification times spread over the last six months.
+/// `with_metadata` additionally adds every tenth note to the pinboard.
+#[tauri::command]
+pub async fn generate_test_data(
+    count: usize,
+    size_distribution: SizeDistribution,
+    with_metadata: bool,
+    app: tauri::AppHandle,
+) -> Result<GenerationReport, String> {
+    let started = Instant::now();
+    let (ids, bytes) = generate(&app, count, size_distribution)?;
+    if with_metadata {
+        for id in ids.iter().step_by(10) {
+            pinboard::add_id(&app, id)?;
+        }
+    }
+
+    let elapsed = started.elapsed();
+    println!("Generated {} test notes ({} bytes) in {:?}", count, bytes, elapsed);
+    app.emit_event("refresh-notes", ());
+    Ok(GenerationReport {
+        created: count,
+        bytes,
+        elapsed_ms: elapsed.as_millis(),
+        notes_per_second: count as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+    })
+}
+
+/// Removes every note carrying the test-data marker and returns how many were deleted.
+#[tauri::command]
+pub async fn clear_test_data(app: tauri::AppHandle) -> Result<usize, String> {
+    let mut removed = 0;
+    for entry in scan_notes(&notes_dir(&app)?, ScanOptions::default()) {
+        if let ScanEntry::Note { id, path, .. } = entry {
+            let is_test_data = vault::read_file(&app, &path)
+                .map(|content| content.trim_end().ends_with(ORIGIN_MARKER))
+                .unwrap_or(false);
+            if is_test_data {
+                let path = SafePath::new(&app, Root::Notes, path)?;
+                own_change(&app, &[&id], || path.remove())?;
+                pinboard::remove_id(&app, &id)?;
+                removed += 1;
+            }
+        }
+    }
+
+    app.emit_event("refresh-notes", ());
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    use crate::meta::NoteMeta;
+    use crate::noteindex::{self, IndexEntry};
+    use crate::sort::{self, SortContext, SortSettings};
+    use crate::testing::TempBackend;
+    use crate::{indexed_note_infos, NoteInfo};
+
+    /// What `get_all_notes` does below the app: the index entries, reusing `previous`
+    /// where files are unchanged, turned into sorted listings.
+    fn list(
+        backend: &TempBackend,
+        previous: &HashMap<String, IndexEntry>,
+    ) -> (HashMap<String, IndexEntry>, Vec<NoteInfo>) {
+        let dir = backend.notes_dir().unwrap();
+        let (entries, _) = noteindex::read_entries(&dir, None, previous).unwrap();
+        let mut notes = indexed_note_infos(entries.clone(), &HashMap::<String, NoteMeta>::new());
+        let ctx = SortContext {
+            manual: &[],
+            opened: &[],
+            pinned: &HashSet::new(),
+            locale: "",
+        };
+        sort::sort_notes(&mut notes, SortSettings::default(), &ctx);
+        (entries, notes)
+    }
+
+    #[test]
+    fn generated_notes_carry_the_origin_marker() {
+        let backend = TempBackend::new();
+        let (ids, bytes) = generate(&backend, 20, SizeDistribution::Small).unwrap();
+
+        assert_eq!(ids.len(), 20);
+        assert_eq!(backend.own_changes(), ids);
+        let mut total = 0;
+        for id in &ids {
+            let content = fs::read_to_string(backend.notes_dir().unwrap().join(format!("{}.md", id))).unwrap();
+            assert!(content.trim_end().ends_with(ORIGIN_MARKER));
+            total += content.len();
+        }
+        assert_eq!(total, bytes);
+    }
+
+    /// Debug builds are many times slower than release; the targets leave room for that
+    /// and slow CI machines while still catching a listing that went quadratic.
+    #[test]
+    fn listing_a_thousand_notes_stays_fast() {
+        const COLD_TARGET: Duration = Duration::from_secs(20);
+        const WARM_TARGET: Duration = Duration::from_secs(2);
+
+        let backend = TempBackend::new();
+        generate(&backend, 1000, SizeDistribution::Realistic).unwrap();
+
+        let started = Instant::now();
+        let (entries, cold) = list(&backend, &HashMap::new());
+        let cold_time = started.elapsed();
+
+        let started = Instant::now();
+        let (_, warm) = list(&backend, &entries);
+        let warm_time = started.elapsed();
+
+        println!("Listed 1000 notes cold in {:?}, warm in {:?}", cold_time, warm_time);
+        assert_eq!(cold.len(), 1000);
+        let ids = |notes: &[NoteInfo]| notes.iter().map(|n| n.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&warm), ids(&cold));
+        assert!(cold_time < COLD_TARGET, "cold listing took {:?}", cold_time);
+        assert!(warm_time < WARM_TARGET, "warm listing took {:?}", warm_time);
+        assert!(warm_time < cold_time, "warm {:?}, cold {:?}", warm_time, cold_time);
+    }
+}
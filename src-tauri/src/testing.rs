@@ -0,0 +1,143 @@
+//! `TempBackend`, the `NotesBackend` tests run against: the app data dir is a fresh temp
+//! dir (notes in `notes/` below it, as in the app), the stores live in memory and events
+//! are recorded instead of sent. Nothing needs a window system.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+use crate::backend::NotesBackend;
+use crate::settings::{self, SETTINGS_KEY};
+use crate::vault::VaultKey;
+
+/// An event as it would have been sent: its name, the note it is about and its payload.
+#[derive(Clone, Debug)]
+pub struct SentEvent {
+    pub event: String,
+    pub note: Option<String>,
+    pub payload: serde_json::Value,
+}
+
+pub struct TempBackend {
+    dir: TempDir,
+    stores: Mutex<HashMap<(String, String), serde_json::Value>>,
+    events: Mutex<Vec<SentEvent>>,
+    /// Ids passed to `own_change`, in order
+    own_changes: Mutex<Vec<String>>,
+    vault_key: Mutex<Option<VaultKey>>,
+}
+
+impl TempBackend {
+    pub fn new() -> Self {
+        TempBackend {
+            dir: TempDir::new().expect("temp dir"),
+            stores: Mutex::new(HashMap::new()),
+            events: Mutex::new(Vec::new()),
+            own_changes: Mutex::new(Vec::new()),
+            vault_key: Mutex::new(None),
+        }
+    }
+
+    /// With the vault enabled and unlocked with `key`. The stored config only has to
+    /// exist; nothing here checks a passphrase against it.
+    pub fn with_vault(key: VaultKey) -> Self {
+        let backend = TempBackend::new();
+        let config = serde_json::json!({ "salt": "", "check": "" });
+        backend.write_store("settings.bin", "vault", config).unwrap();
+        backend.set_vault_key(Some(key));
+        backend
+    }
+
+    pub fn set_vault_key(&self, key: Option<VaultKey>) {
+        *self.vault_key.lock().unwrap() = key;
+    }
+
+    /// Changes one setting, like `update_settings` without the side effects.
+    pub fn set_setting(&self, name: &str, value: serde_json::Value) {
+        let mut current = serde_json::to_value(settings::current(self)).unwrap();
+        current[name] = value;
+        self.write_store("settings.bin", SETTINGS_KEY, current).unwrap();
+    }
+
+    /// Writes note `id` straight into the notes dir, as an edit from outside the app would.
+    pub fn put_note(&self, id: &str, content: &str) -> PathBuf {
+        let dir = self.notes_dir().unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.md", id));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    pub fn events(&self) -> Vec<SentEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Names of the events sent so far, in order.
+    pub fn event_names(&self) -> Vec<String> {
+        self.events().into_iter().map(|sent| sent.event).collect()
+    }
+
+    pub fn own_changes(&self) -> Vec<String> {
+        self.own_changes.lock().unwrap().clone()
+    }
+}
+
+impl Default for TempBackend {
+    fn default() -> Self {
+        TempBackend::new()
+    }
+}
+
+impl NotesBackend for TempBackend {
+    fn notes_dir(&self) -> Result<PathBuf, String> {
+        Ok(self.dir.path().join("notes"))
+    }
+
+    fn data_dir(&self) -> Result<PathBuf, String> {
+        Ok(self.dir.path().to_path_buf())
+    }
+
+    fn read_store(&self, store: &str, key: &str) -> Option<serde_json::Value> {
+        let stores = self.stores.lock().unwrap();
+        stores.get(&(store.to_string(), key.to_string())).cloned()
+    }
+
+    fn write_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String> {
+        let mut stores = self.stores.lock().map_err(|e| e.to_string())?;
+        stores.insert((store.to_string(), key.to_string()), value);
+        Ok(())
+    }
+
+    fn stage_store(&self, store: &str, key: &str, value: serde_json::Value) -> Result<(), String> {
+        self.write_store(store, key, value)
+    }
+
+    fn emit_event<S: serde::Serialize + Clone>(&self, event: &str, payload: S) {
+        self.events.lock().unwrap().push(SentEvent {
+            event: event.to_string(),
+            note: None,
+            payload: serde_json::to_value(payload).unwrap_or_default(),
+        });
+    }
+
+    fn emit_note_event<S: serde::Serialize + Clone>(&self, id: &str, event: &str, payload: S) {
+        self.events.lock().unwrap().push(SentEvent {
+            event: event.to_string(),
+            note: Some(id.to_string()),
+            payload: serde_json::to_value(payload).unwrap_or_default(),
+        });
+    }
+
+    fn vault_key(&self) -> Option<VaultKey> {
+        *self.vault_key.lock().unwrap()
+    }
+
+    fn own_change<T>(&self, ids: &[&str], op: impl FnOnce() -> T) -> T {
+        let result = op();
+        let mut own_changes = self.own_changes.lock().unwrap();
+        own_changes.extend(ids.iter().map(|id| id.to_string()));
+        result
+    }
+}
@@ -0,0 +1,213 @@
+//! The note lifecycle against `TempBackend`: saving and loading, the session order and
+//! listing. Deleting is tested in `recycle.rs`.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::backend::NotesBackend;
+use crate::meta::NoteMeta;
+use crate::noteindex;
+use crate::sort::{self, NoteSort, SortContext, SortSettings};
+use crate::testing::TempBackend;
+use crate::vault::VAULT_HEADER;
+use crate::{
+    encode_note, get_session_order, indexed_note_infos, read_note, update_session_order, write_note_file, NoteInfo,
+};
+
+fn save(backend: &TempBackend, id: &str, content: &str) {
+    let stored = encode_note(backend, content).unwrap();
+    write_note_file(backend, id, &stored).unwrap();
+}
+
+fn listing(backend: &TempBackend, metas: &HashMap<String, NoteMeta>) -> Vec<NoteInfo> {
+    let dir = backend.notes_dir().unwrap();
+    let (entries, _) = noteindex::read_entries(&dir, None, &HashMap::new()).unwrap();
+    indexed_note_infos(entries, metas)
+}
+
+#[test]
+fn saved_note_loads_back() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "# Groceries\n- milk\n");
+
+    assert_eq!(read_note(&backend, "a").unwrap(), "# Groceries\n- milk\n");
+    let on_disk = fs::read_to_string(backend.notes_dir().unwrap().join("a.md")).unwrap();
+    assert_eq!(on_disk, "# Groceries\n- milk\n");
+    assert_eq!(backend.own_changes(), ["a"]);
+}
+
+#[test]
+fn saving_again_replaces_the_content() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "first");
+    save(&backend, "a", "second, longer");
+
+    assert_eq!(read_note(&backend, "a").unwrap(), "second, longer");
+    // The atomic write leaves no temp file behind
+    let files = fs::read_dir(backend.notes_dir().unwrap()).unwrap().count();
+    assert_eq!(files, 1);
+}
+
+#[test]
+fn missing_note_loads_empty() {
+    let backend = TempBackend::new();
+    assert_eq!(read_note(&backend, "nowhere").unwrap(), "");
+}
+
+#[test]
+fn oversized_note_is_refused() {
+    let backend = TempBackend::new();
+    backend.set_setting("max_note_bytes", serde_json::json!(1024));
+
+    let error = encode_note(&backend, &"x".repeat(1025)).unwrap_err();
+    assert!(error.starts_with("TooLarge"), "{}", error);
+    assert!(encode_note(&backend, &"x".repeat(1024)).is_ok());
+}
+
+#[test]
+fn vault_notes_are_encrypted_on_disk() {
+    let backend = TempBackend::with_vault([7; 32]);
+    save(&backend, "a", "secret plans");
+
+    let on_disk = fs::read_to_string(backend.notes_dir().unwrap().join("a.md")).unwrap();
+    assert!(on_disk.starts_with(VAULT_HEADER));
+    assert!(!on_disk.contains("secret plans"));
+    assert_eq!(read_note(&backend, "a").unwrap(), "secret plans");
+}
+
+#[test]
+fn locked_vault_refuses_saves_and_loads() {
+    let backend = TempBackend::with_vault([7; 32]);
+    save(&backend, "a", "secret plans");
+    backend.set_vault_key(None);
+
+    for refused in [encode_note(&backend, "more plans"), read_note(&backend, "a")] {
+        assert!(refused.unwrap_err().starts_with("VaultLocked"));
+    }
+}
+
+#[test]
+fn focusing_moves_a_note_to_the_end_of_the_session() {
+    let backend = TempBackend::new();
+    for id in ["a", "b", "c"] {
+        assert!(update_session_order(&backend, id.to_string(), false));
+    }
+    assert!(update_session_order(&backend, "a".to_string(), false));
+
+    assert_eq!(get_session_order(&backend), ["b", "c", "a"]);
+}
+
+#[test]
+fn unchanged_session_order_is_not_written() {
+    let backend = TempBackend::new();
+    update_session_order(&backend, "a".to_string(), false);
+
+    assert!(!update_session_order(&backend, "a".to_string(), false));
+    assert!(!update_session_order(&backend, "missing".to_string(), true));
+    assert_eq!(get_session_order(&backend), ["a"]);
+}
+
+#[test]
+fn closing_drops_a_note_from_the_session() {
+    let backend = TempBackend::new();
+    for id in ["a", "b", "c"] {
+        update_session_order(&backend, id.to_string(), false);
+    }
+
+    assert!(update_session_order(&backend, "b".to_string(), true));
+    assert_eq!(get_session_order(&backend), ["a", "c"]);
+}
+
+#[test]
+fn listing_shows_every_saved_note() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "# Alpha\nfirst note");
+    save(&backend, "b", "\n\nBeta\n- [ ] open task\n- [x] done task");
+
+    let mut notes = listing(&backend, &HashMap::new());
+    notes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let titles: Vec<&str> = notes.iter().map(|n| n.title.as_str()).collect();
+    assert_eq!(titles, ["Alpha", "Beta"]);
+    assert_eq!(notes[0].preview, "# Alpha\nfirst note");
+    assert_eq!((notes[1].tasks.open, notes[1].tasks.done), (1, 1));
+    assert!(notes.iter().all(|n| n.file_modified_at.is_some()));
+}
+
+#[test]
+fn listing_skips_temp_files_and_non_notes() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "Alpha");
+    backend.put_note(".a.md.1234.tmp", "half written");
+    backend.put_note("a (1)", "sync conflict copy");
+
+    let ids: Vec<String> = listing(&backend, &HashMap::new()).into_iter().map(|n| n.id).collect();
+    assert_eq!(ids, ["a"]);
+}
+
+#[test]
+fn listing_uses_given_titles_and_metadata() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "Alpha");
+    let metas = HashMap::from([(
+        "a".to_string(),
+        NoteMeta {
+            title: Some("Renamed".to_string()),
+            color: Some("blue".to_string()),
+            pinned: true,
+            ..NoteMeta::default()
+        },
+    )]);
+
+    let notes = listing(&backend, &metas);
+    assert_eq!(notes[0].title, "Renamed");
+    assert_eq!(notes[0].color.as_deref(), Some("blue"));
+    assert!(notes[0].pinned);
+}
+
+#[test]
+fn listing_rereads_only_changed_notes() {
+    let backend = TempBackend::new();
+    save(&backend, "a", "Alpha");
+    save(&backend, "b", "Beta");
+    let dir = backend.notes_dir().unwrap();
+    let (first, changed) = noteindex::read_entries(&dir, None, &HashMap::new()).unwrap();
+    assert!(changed);
+
+    let (unchanged, changed) = noteindex::read_entries(&dir, None, &first).unwrap();
+    assert!(!changed);
+    assert_eq!(unchanged.len(), 2);
+
+    // A different length is enough, whatever the mtime granularity
+    save(&backend, "b", "Beta, edited");
+    let (entries, changed) = noteindex::read_entries(&dir, None, &first).unwrap();
+    assert!(changed);
+    assert_eq!(entries["b"].title(), "Beta, edited");
+}
+
+#[test]
+fn listing_sorts_by_session_order() {
+    let backend = TempBackend::new();
+    for id in ["a", "b", "c"] {
+        save(&backend, id, id);
+        update_session_order(&backend, id.to_string(), false);
+    }
+    update_session_order(&backend, "a".to_string(), false);
+
+    let mut notes = listing(&backend, &HashMap::new());
+    let opened = get_session_order(&backend);
+    let settings = SortSettings {
+        sort: NoteSort::OpenedDesc,
+        pinned_first: false,
+    };
+    let ctx = SortContext {
+        manual: &[],
+        opened: &opened,
+        pinned: &HashSet::new(),
+        locale: "",
+    };
+    sort::sort_notes(&mut notes, settings, &ctx);
+
+    let ids: Vec<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, ["a", "c", "b"]);
+}
@@ -0,0 +1,143 @@
+//! Light, dark or following the OS, applied by the backend so every window agrees.
+//! `set_theme` saves the mode (the `theme` setting), makes it the native theme of every
+//! window, gives the dashboard a matching background so it doesn't flash the light one
+//! while it repaints, and sends `theme-changed` with the mode and the theme in effect.
+//! Note windows, the pinboard and quick capture are transparent and paint their own
+//! colors from that event.
+//!
+//! The OS theme is what the windows report while they follow it; a change there is
+//! sent as `theme-changed` too when the mode is `system`.
+
+use serde_json::Map;
+use std::sync::Mutex;
+use tauri::window::Color;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::settings::{self, Theme};
+
+/// Dashboard backgrounds; the light one is the one in tauri.conf.json.
+const LIGHT_BACKGROUND: Color = Color(0xFF, 0xF6, 0xCB, 0xFF);
+const DARK_BACKGROUND: Color = Color(0x2B, 0x2A, 0x26, 0xFF);
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectiveTheme {
+    Light,
+    Dark,
+}
+
+impl From<tauri::Theme> for EffectiveTheme {
+    fn from(theme: tauri::Theme) -> Self {
+        match theme {
+            tauri::Theme::Dark => EffectiveTheme::Dark,
+            _ => EffectiveTheme::Light,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct ThemeState {
+    mode: Theme,
+    effective: EffectiveTheme,
+}
+
+/// The OS theme as the windows last reported it.
+pub struct OsTheme(Mutex<EffectiveTheme>);
+
+/// The theme to build a window with: `None` follows the OS.
+pub fn native(backend: &impl NotesBackend) -> Option<tauri::Theme> {
+    match settings::current(backend).theme {
+        Theme::System => None,
+        Theme::Light => Some(tauri::Theme::Light),
+        Theme::Dark => Some(tauri::Theme::Dark),
+    }
+}
+
+fn reported<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<EffectiveTheme> {
+    app.get_webview_window("main")
+        .and_then(|window| window.theme().ok())
+        .map(EffectiveTheme::from)
+}
+
+fn os_theme<R: Runtime>(app: &tauri::AppHandle<R>) -> EffectiveTheme {
+    app.try_state::<OsTheme>()
+        .and_then(|os| os.0.lock().ok().map(|theme| *theme))
+        .unwrap_or(EffectiveTheme::Light)
+}
+
+fn state<R: Runtime>(app: &tauri::AppHandle<R>) -> ThemeState {
+    let mode = settings::current(app).theme;
+    let effective = match mode {
+        Theme::System => os_theme(app),
+        Theme::Light => EffectiveTheme::Light,
+        Theme::Dark => EffectiveTheme::Dark,
+    };
+    ThemeState { mode, effective }
+}
+
+fn apply_to_windows<R: Runtime>(app: &tauri::AppHandle<R>) -> ThemeState {
+    let native = native(app);
+    app.set_theme(native);
+    // Following the OS again: it may have changed while a theme was forced
+    if let (None, Some(now), Some(os)) = (native, reported(app), app.try_state::<OsTheme>()) {
+        if let Ok(mut os) = os.0.lock() {
+            *os = now;
+        }
+    }
+    let state = state(app);
+    if let Some(main_win) = app.get_webview_window("main") {
+        let background = match state.effective {
+            EffectiveTheme::Light => LIGHT_BACKGROUND,
+            EffectiveTheme::Dark => DARK_BACKGROUND,
+        };
+        let _ = main_win.set_background_color(Some(background));
+    }
+    state
+}
+
+/// Applies the saved mode to every window and tells them.
+pub fn apply<R: Runtime>(app: &tauri::AppHandle<R>) -> ThemeState {
+    let state = apply_to_windows(app);
+    println!("Theme: {:?}, showing {:?}", state.mode, state.effective);
+    app.emit_event("theme-changed", state);
+    state
+}
+
+/// Reads the OS theme before anything overrides it, then applies the saved mode.
+pub fn init<R: Runtime>(app: &tauri::AppHandle<R>) {
+    app.manage(OsTheme(Mutex::new(reported(app).unwrap_or(EffectiveTheme::Light))));
+    apply_to_windows(app);
+}
+
+/// A window's `ThemeChanged`. Only means the OS changed while the windows follow it;
+/// every window reports it, so only the first report is passed on.
+pub fn window_theme_changed<R: Runtime>(app: &tauri::AppHandle<R>, theme: tauri::Theme) {
+    if settings::current(app).theme != Theme::System {
+        return;
+    }
+    let Some(os) = app.try_state::<OsTheme>() else {
+        return;
+    };
+    let theme = EffectiveTheme::from(theme);
+    let previous = match os.0.lock() {
+        Ok(mut os) => std::mem::replace(&mut *os, theme),
+        Err(_) => return,
+    };
+    if previous != theme {
+        apply(app);
+    }
+}
+
+#[tauri::command]
+pub async fn get_theme(app: tauri::AppHandle) -> Result<ThemeState, String> {
+    Ok(state(&app))
+}
+
+/// Sets `mode` ("system", "light" or "dark") for every window.
+#[tauri::command]
+pub async fn set_theme(mode: Theme, app: tauri::AppHandle) -> Result<ThemeState, String> {
+    let value = serde_json::to_value(mode).map_err(|e| e.to_string())?;
+    settings::update(&app, Map::from_iter([("theme".to_string(), value)]))?;
+    Ok(state(&app))
+}
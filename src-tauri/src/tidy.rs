@@ -0,0 +1,395 @@
+//! Opt-in markdown cleanup: `-` list markers, at most two blank lines in a row, no
+//! trailing whitespace, one space after heading hashes with a blank line around headings,
+//! and a single trailing newline. Fenced code blocks are passed through byte for byte.
+//!
+//! `tidy` is a pure string transform; saving with `tidy_on_save` on runs it before the
+//! note hits disk and hands the result back so the editor can take it over.
+
+use serde_json::Map;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::encryption;
+use crate::settings;
+use crate::{read_note, write_note};
+
+const MAX_BLANK_RUN: usize = 2;
+
+/// What a tidy pass changed, per rule.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default)]
+pub struct TidyReport {
+    list_markers: usize,
+    trailing_whitespace: usize,
+    blank_lines_removed: usize,
+    headings: usize,
+    final_newline: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TidyResult {
+    /// The tidied note; with `preview` this is what would have been written
+    content: String,
+    changed: bool,
+    report: TidyReport,
+    written: bool,
+}
+
+/// An open fence: its character and run length. It closes on a line holding a run of
+/// at least that many of the same character and nothing else.
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+/// Up to three spaces of indent, then three or more backticks or tildes.
+fn fence_start(line: &str) -> Option<Fence> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = rest.chars().take_while(|c| *c == marker).count();
+    // A backtick fence's info string can't contain backticks
+    if len < 3 || (marker == '`' && rest[len..].contains('`')) {
+        return None;
+    }
+    Some(Fence { marker, len })
+}
+
+fn closes(fence: &Fence, line: &str) -> bool {
+    let trimmed = line.trim();
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    indent <= 3
+        && trimmed.chars().take_while(|c| *c == fence.marker).count() >= fence.len
+        && trimmed.chars().all(|c| c == fence.marker)
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// `***`, `* * *` and friends are rules, not list items.
+fn is_thematic_break(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '*' | '-' | '_') && marks.iter().all(|c| *c == marks[0])
+}
+
+/// `* item` / `+ item` at any indent becomes `- item`.
+fn normalize_list_marker(line: &str) -> Option<String> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let mut chars = rest.chars();
+    let marker = chars.next()?;
+    if !(marker == '*' || marker == '+') || !chars.next().is_some_and(|c| c == ' ' || c == '\t') {
+        return None;
+    }
+    if is_thematic_break(line) {
+        return None;
+    }
+    Some(format!("{}-{}", &line[..indent], &rest[1..]))
+}
+
+/// The heading level if `line` is an ATX heading (`# Title`, `##   Title`, `###`).
+fn heading_level(line: &str) -> Option<usize> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let level = rest.chars().take_while(|c| *c == '#').count();
+    let after = &rest[level..];
+    // `#tag` is text, not a heading missing its space
+    ((1..=6).contains(&level) && (after.is_empty() || after.starts_with(' ') || after.starts_with('\t')))
+        .then_some(level)
+}
+
+/// `##    Title` becomes `## Title`.
+fn normalize_heading(line: &str, level: usize) -> String {
+    let text = line.trim_start_matches(' ')[level..].trim();
+    if text.is_empty() {
+        "#".repeat(level)
+    } else {
+        format!("{} {}", "#".repeat(level), text)
+    }
+}
+
+/// Tidies `content`. Returns it unchanged, with an empty report, when there is
+/// nothing to do.
+pub fn tidy(content: &str) -> (String, TidyReport) {
+    let mut report = TidyReport::default();
+    if content.is_empty() {
+        return (String::new(), report);
+    }
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+
+    // Each output line, and whether it is verbatim fence content that blank-line and
+    // heading rules must not touch
+    let mut lines: Vec<(String, bool)> = Vec::new();
+    let mut fence: Option<Fence> = None;
+    for raw in content.split('\n') {
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+        if let Some(open) = &fence {
+            if closes(open, line) {
+                fence = None;
+            }
+            lines.push((line.to_string(), true));
+            continue;
+        }
+        if let Some(start) = fence_start(line) {
+            fence = Some(start);
+            lines.push((line.to_string(), true));
+            continue;
+        }
+
+        let mut line = line.to_string();
+        let trimmed_len = line.trim_end().len();
+        if trimmed_len != line.len() {
+            line.truncate(trimmed_len);
+            report.trailing_whitespace += 1;
+        }
+        if let Some(normalized) = normalize_list_marker(&line) {
+            line = normalized;
+            report.list_markers += 1;
+        } else if let Some(level) = heading_level(&line) {
+            let normalized = normalize_heading(&line, level);
+            if normalized != line {
+                line = normalized;
+                report.headings += 1;
+            }
+        }
+        lines.push((line, false));
+    }
+
+    // `split` leaves one empty piece after a final newline; it is put back below
+    let ended_with_newline = content.ends_with('\n');
+    if ended_with_newline {
+        lines.pop();
+    }
+
+    let mut out: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut blank_run = 0;
+    for (i, (line, verbatim)) in lines.iter().enumerate() {
+        if *verbatim {
+            blank_run = 0;
+            out.push(line);
+            continue;
+        }
+        if is_blank(line) {
+            blank_run += 1;
+            if blank_run > MAX_BLANK_RUN {
+                report.blank_lines_removed += 1;
+                continue;
+            }
+            out.push(line);
+            continue;
+        }
+        if heading_level(line).is_some() && out.last().is_some_and(|prev| !is_blank(prev)) {
+            out.push("");
+            report.headings += 1;
+        }
+        blank_run = 0;
+        out.push(line);
+        let next_is_text = lines
+            .get(i + 1)
+            .is_some_and(|(next, verbatim)| *verbatim || !is_blank(next));
+        if heading_level(line).is_some() && next_is_text {
+            out.push("");
+            report.headings += 1;
+            blank_run = 1;
+        }
+    }
+
+    // Trailing blank lines go, unless they belong to a fence left open at the end
+    if fence.is_none() {
+        while out.last().is_some_and(|line| is_blank(line)) {
+            out.pop();
+            report.blank_lines_removed += 1;
+        }
+    }
+    if out.is_empty() {
+        return (String::new(), report);
+    }
+
+    let mut tidied = out.join(newline);
+    tidied.push_str(newline);
+    if !ended_with_newline {
+        report.final_newline = true;
+    }
+    (tidied, report)
+}
+
+pub fn tidy_on_save<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    settings::current(app).tidy_on_save
+}
+
+/// The content to save: tidied if `tidy_on_save` is on, as given otherwise.
+pub fn prepare_for_save<R: Runtime>(app: &tauri::AppHandle<R>, content: String) -> String {
+    if tidy_on_save(app) {
+        tidy(&content).0
+    } else {
+        content
+    }
+}
+
+#[tauri::command]
+pub async fn get_tidy_on_save(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(tidy_on_save(&app))
+}
+
+#[tauri::command]
+pub async fn set_tidy_on_save(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    settings::update(&app, Map::from_iter([("tidy_on_save".to_string(), enabled.into())])).map(|_| ())
+}
+
+/// Tidies a saved note regardless of `tidy_on_save`. With `preview` nothing is
+/// written and the result shows what would change.
+#[tauri::command]
+pub async fn tidy_note(id: String, preview: Option<bool>, app: tauri::AppHandle) -> Result<TidyResult, String> {
+    let original = read_note(&app, &id)?;
+    encryption::ensure_not_locked(&id, &original)?;
+    let (content, report) = tidy(&original);
+    let changed = content != original;
+    let write = changed && !preview.unwrap_or(false);
+    if write {
+        write_note(&app, &id, &content)?;
+        app.emit_event("refresh-notes", ());
+    }
+    Ok(TidyResult {
+        content,
+        changed,
+        report,
+        written: write,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn untouched(report: &TidyReport) -> bool {
+        report.list_markers == 0
+            && report.trailing_whitespace == 0
+            && report.blank_lines_removed == 0
+            && report.headings == 0
+            && !report.final_newline
+    }
+
+    #[test]
+    fn list_markers_become_dashes_at_any_indent() {
+        let (tidied, report) = tidy("* a\n+ b\n  * nested\n\t+ tabbed\n- c\n");
+        assert_eq!(tidied, "- a\n- b\n  - nested\n\t- tabbed\n- c\n");
+        assert_eq!(report.list_markers, 4);
+    }
+
+    #[test]
+    fn emphasis_and_rules_are_not_list_items() {
+        let content = "*emphasis* here\n+1 for this\n\n* * *\n\n***\n\n- - -\n";
+        let (tidied, report) = tidy(content);
+        assert_eq!(tidied, content);
+        assert!(untouched(&report), "{:?}", report);
+    }
+
+    #[test]
+    fn blank_runs_collapse_to_two() {
+        let (tidied, report) = tidy("a\n\n\n\n\nb\n\n\nc\n\nd\n");
+        assert_eq!(tidied, "a\n\n\nb\n\n\nc\n\nd\n");
+        assert_eq!(report.blank_lines_removed, 2);
+    }
+
+    #[test]
+    fn trailing_whitespace_is_trimmed() {
+        let (tidied, report) = tidy("a  \nb\t\n  \nc\n");
+        assert_eq!(tidied, "a\nb\n\nc\n");
+        assert_eq!(report.trailing_whitespace, 3);
+    }
+
+    #[test]
+    fn ends_with_exactly_one_newline() {
+        let (tidied, report) = tidy("a");
+        assert_eq!(tidied, "a\n");
+        assert!(report.final_newline);
+
+        let (tidied, report) = tidy("a\n\n\n");
+        assert_eq!(tidied, "a\n");
+        assert_eq!(report.blank_lines_removed, 2);
+        assert!(!report.final_newline);
+
+        assert_eq!(tidy("").0, "");
+        assert_eq!(tidy("\n\n\n \n").0, "");
+    }
+
+    #[test]
+    fn headings_get_one_space_and_blank_lines_around() {
+        let (tidied, report) = tidy("##   Title\ntext\n# A\n## B\n");
+        assert_eq!(tidied, "## Title\n\ntext\n\n# A\n\n## B\n");
+        // One space fix, three blank lines added
+        assert_eq!(report.headings, 4);
+    }
+
+    #[test]
+    fn tags_and_deep_hashes_are_not_headings() {
+        let content = "#tag at the start\n####### seven\n    # indented code\n";
+        let (tidied, report) = tidy(content);
+        assert_eq!(tidied, content);
+        assert!(untouched(&report), "{:?}", report);
+    }
+
+    #[test]
+    fn fenced_code_is_left_byte_identical() {
+        let content =
+            "Intro\n\n```rust\n* not a list  \n\n\n\n\n##   not a heading\t\n```\n\n~~~~\n```\n+ still code \n~~~~\n";
+        let (tidied, report) = tidy(content);
+        assert_eq!(tidied, content);
+        assert!(untouched(&report), "{:?}", report);
+    }
+
+    #[test]
+    fn text_around_a_fence_is_tidied_but_not_its_content() {
+        let fenced = "```\n*  a  \n\n\n\n#b\n```\n";
+        let (tidied, report) = tidy(&format!("*  a  \n{}# After", fenced));
+        assert_eq!(tidied, format!("-  a\n{}\n# After\n", fenced));
+        assert_eq!(report.list_markers, 1);
+        assert_eq!(report.trailing_whitespace, 1);
+        assert_eq!(report.headings, 1);
+        assert!(report.final_newline);
+    }
+
+    #[test]
+    fn a_longer_fence_only_closes_on_a_run_as_long() {
+        let content = "````\n```\n* inside\n````\n* outside\n";
+        assert_eq!(tidy(content).0, "````\n```\n* inside\n````\n- outside\n");
+    }
+
+    #[test]
+    fn a_fence_left_open_keeps_its_trailing_blank_lines() {
+        let content = "```\ncode\n\n\n";
+        let (tidied, report) = tidy(content);
+        assert_eq!(tidied, content);
+        assert!(untouched(&report), "{:?}", report);
+    }
+
+    #[test]
+    fn tables_keep_their_alignment() {
+        let (tidied, report) = tidy("| a  | b |\n|----|:-:|\n| * x | + y |  \n| # 1 | 2 |\n");
+        assert_eq!(tidied, "| a  | b |\n|----|:-:|\n| * x | + y |\n| # 1 | 2 |\n");
+        assert_eq!(report.trailing_whitespace, 1);
+        assert_eq!(report.list_markers, 0);
+        assert_eq!(report.headings, 0);
+    }
+
+    #[test]
+    fn crlf_notes_stay_crlf() {
+        let (tidied, _) = tidy("a  \r\n* b\r\n\r\n\r\n\r\n\r\nc");
+        assert_eq!(tidied, "a\r\n- b\r\n\r\n\r\nc\r\n");
+    }
+
+    #[test]
+    fn tidying_twice_changes_nothing_more() {
+        let messy = "#  Groceries  \n* milk\n+ eggs\n\n\n\n\n```\n*  keep  \n```\n| a | b |  \n##Notes\n## Done";
+        let (once, _) = tidy(messy);
+        let (twice, report) = tidy(&once);
+        assert_eq!(twice, once);
+        assert!(untouched(&report), "{:?}", report);
+    }
+}
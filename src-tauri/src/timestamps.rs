@@ -0,0 +1,223 @@
+//! When a note's content last changed, independent of the file's mtime.
+//!
+//! Restoring from a backup or a sync client that resets mtimes leaves every file with
+//! the restore moment, so "sort by modified" stops meaning anything. Saves that change
+//! the content record `NoteMeta::content_modified_at` instead, and listings use it ahead
+//! of the mtime:
+//!
+//! 1. `content_modified_at` from a save through the app
+//! 2. `content_modified_at` estimated by `rebuild_timestamps` (`content_modified_inferred`)
+//! 3. the file's mtime, for notes not saved since this was tracked
+//!
+//! Edits made outside the app only move the mtime.
+
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tauri::Runtime;
+
+use crate::backend::NotesBackend;
+use crate::meta::{self, NoteMeta};
+use crate::history::latest_snapshot;
+use crate::journal;
+use crate::safepath::Root;
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::vault;
+use crate::{notes_dir, now_millis, to_millis};
+
+/// This many notes sharing an mtime second is taken as a bulk restore, not editing.
+const CLUSTER_MIN_NOTES: usize = 5;
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateSource {
+    /// The newest snapshot in the note's `history/` folder, dated by its file name
+    History,
+    /// The latest journal entry written for the note
+    Journal,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TimestampEstimate {
+    id: String,
+    /// The clustered mtime, Unix millis
+    file_modified_at: Option<u64>,
+    /// What `content_modified_at` was set to; `None` if nothing to go on was found
+    content_modified_at: Option<u64>,
+    source: Option<EstimateSource>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct MtimeCluster {
+    /// Unix seconds shared by the notes' mtimes
+    second: u64,
+    notes: usize,
+}
+
+/// What `rebuild_timestamps` found. Notes with a save-recorded `content_modified_at` are
+/// never touched; estimates only replace a missing or previously estimated one.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TimestampRebuild {
+    clusters: Vec<MtimeCluster>,
+    estimates: Vec<TimestampEstimate>,
+}
+
+/// Records a content change for note `id` if `new` differs from what was on disk, and
+/// the creation time if there was no file before.
+pub fn note_written<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, previous: Option<&str>, new: &str) {
+    if previous == Some(new) {
+        return;
+    }
+    let now = now_millis();
+    let result = meta::stage_meta(app, id, |meta| {
+        meta.content_modified_at = Some(now);
+        meta.content_modified_inferred = false;
+        if previous.is_none() {
+            meta.created_at.get_or_insert(now);
+        }
+    });
+    if let Err(e) = result {
+        println!("Failed to record content change of {}: {}", id, e);
+    }
+}
+
+/// The value listings sort "modified" by, per the precedence above.
+pub fn effective_modified_at(meta: Option<&NoteMeta>, file_modified_at: Option<u64>) -> Option<u64> {
+    meta.and_then(|m| m.content_modified_at).or(file_modified_at)
+}
+
+fn local_millis(datetime: NaiveDateTime) -> Option<u64> {
+    let millis = Local.from_local_datetime(&datetime).earliest()?.timestamp_millis();
+    u64::try_from(millis).ok()
+}
+
+/// Dates without a time of day (journal headings, date-named snapshots) count as noon,
+/// so the estimate lands on the right day in any time zone nearby.
+fn at_noon(date: NaiveDate) -> Option<u64> {
+    local_millis(date.and_hms_opt(12, 0, 0)?)
+}
+
+/// Snapshot file names start with Unix millis or an ISO date, optionally with a time.
+pub fn parse_snapshot_name(stem: &str) -> Option<u64> {
+    let digits: String = stem.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() == 13 {
+        return digits.parse().ok();
+    }
+    let date = NaiveDate::parse_from_str(stem.get(..10)?, "%Y-%m-%d").ok()?;
+    let time = stem.get(11..19).and_then(|t| {
+        NaiveTime::parse_from_str(t, "%H-%M-%S")
+            .or_else(|_| NaiveTime::parse_from_str(t, "%H:%M:%S"))
+            .ok()
+    });
+    match time {
+        Some(time) => local_millis(date.and_time(time)),
+        None => at_noon(date),
+    }
+}
+
+fn from_history(asset_dir: &Path) -> Option<u64> {
+    latest_snapshot(asset_dir).map(|(_, taken_at)| taken_at)
+}
+
+/// Latest journal heading date per note id, from the entry markers `journal.rs` writes.
+fn journal_dates<R: Runtime>(app: &tauri::AppHandle<R>) -> HashMap<String, u64> {
+    let mut dates = HashMap::new();
+    let Some(entries) = Root::Journal.dir(app).ok().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return dates;
+    };
+    for entry in entries.flatten() {
+        // Encrypted months can't be read while the vault is locked
+        let Ok(content) = vault::read_file(app, &entry.path()) else {
+            continue;
+        };
+        let mut heading_date = None;
+        for line in content.lines() {
+            if let Some(heading) = line.strip_prefix("## ") {
+                heading_date = heading
+                    .get(..10)
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                    .and_then(at_noon);
+            } else if let Some(id) = journal::marker_note_id(line) {
+                if let Some(date) = heading_date {
+                    let latest = dates.entry(id.to_string()).or_insert(date);
+                    *latest = (*latest).max(date);
+                }
+            }
+        }
+    }
+    dates
+}
+
+/// Finds notes whose mtimes are clustered on one second and estimates their content
+/// timestamps from history snapshots, then journal entries.
+#[tauri::command]
+pub async fn rebuild_timestamps(app: tauri::AppHandle) -> Result<TimestampRebuild, String> {
+    let dir = notes_dir(&app)?;
+    let mtimes: Vec<(String, Option<u64>)> = scan_notes(&dir, ScanOptions::default())
+        .filter_map(|entry| match entry {
+            ScanEntry::Note { id, path, .. } => {
+                let modified = fs::metadata(&path).ok().and_then(|m| to_millis(m.modified()));
+                Some((id, modified))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut per_second: HashMap<u64, usize> = HashMap::new();
+    for (_, modified) in &mtimes {
+        if let Some(modified) = modified {
+            *per_second.entry(modified / 1000).or_default() += 1;
+        }
+    }
+    let mut clusters: Vec<MtimeCluster> = per_second
+        .into_iter()
+        .filter(|(_, notes)| *notes >= CLUSTER_MIN_NOTES)
+        .map(|(second, notes)| MtimeCluster { second, notes })
+        .collect();
+    clusters.sort_by_key(|c| c.second);
+
+    let metas = meta::load_all(&app);
+    let journal = journal_dates(&app);
+    let mut estimates = Vec::new();
+    for (id, modified) in mtimes {
+        let clustered = modified.is_some_and(|m| clusters.iter().any(|c| c.second == m / 1000));
+        let recorded = metas
+            .get(&id)
+            .is_some_and(|m| m.content_modified_at.is_some() && !m.content_modified_inferred);
+        if !clustered || recorded {
+            continue;
+        }
+
+        let (estimate, source) = match from_history(&dir.join(&id)) {
+            Some(at) => (Some(at), Some(EstimateSource::History)),
+            None => match journal.get(&id) {
+                Some(at) => (Some(*at), Some(EstimateSource::Journal)),
+                None => (None, None),
+            },
+        };
+        if let Some(at) = estimate {
+            meta::stage_meta(&app, &id, |meta| {
+                meta.content_modified_at = Some(at);
+                meta.content_modified_inferred = true;
+            })?;
+        }
+        estimates.push(TimestampEstimate {
+            id,
+            file_modified_at: modified,
+            content_modified_at: estimate,
+            source,
+        });
+    }
+    estimates.sort_by(|a, b| a.id.cmp(&b.id));
+
+    println!(
+        "Timestamp rebuild: {} clustered notes, {} estimated",
+        estimates.len(),
+        estimates.iter().filter(|e| e.source.is_some()).count()
+    );
+    if estimates.iter().any(|e| e.source.is_some()) {
+        app.emit_event("refresh-notes", ());
+    }
+    Ok(TimestampRebuild { clusters, estimates })
+}
@@ -0,0 +1,99 @@
+//! Titles given to notes by hand. A note's title is normally its first line (see
+//! `derive_title`); `rename_note` stores one in its `NoteMeta` instead, which then names
+//! the note everywhere (listings, the tray, its window and `[[links]]`) while the file
+//! keeps its id. Renaming to an empty title goes back to the first line.
+
+use tauri::Manager;
+
+use crate::backend::NotesBackend;
+use crate::links;
+use crate::meta::{self, NoteMeta};
+use crate::noteindex;
+use crate::notemenu;
+
+const MAX_TITLE_CHARS: usize = 200;
+
+#[derive(serde::Serialize, Clone)]
+struct NoteRenamed {
+    id: String,
+    title: String,
+}
+
+/// The title a note goes by: the one it was given, else `derived`, its first line.
+pub fn effective_title(note_meta: Option<&NoteMeta>, derived: &str) -> String {
+    note_meta
+        .and_then(|m| m.title.clone())
+        .unwrap_or_else(|| derived.to_string())
+}
+
+/// `title` trimmed, `None` if that leaves nothing. Brackets and pipes would break
+/// `[[Title|label]]` links to the note.
+fn validate(title: &str) -> Result<Option<String>, String> {
+    let title = title.trim();
+    if title.chars().any(|c| matches!(c, '[' | ']' | '|') || c.is_control()) {
+        return Err("Titles can't contain brackets, pipes or line breaks".to_string());
+    }
+    if title.chars().count() > MAX_TITLE_CHARS {
+        return Err(format!("Titles can be at most {} characters", MAX_TITLE_CHARS));
+    }
+    Ok((!title.is_empty()).then(|| title.to_string()))
+}
+
+/// Gives note `id` a title, or with an empty one takes it away. Returns the title the
+/// note now goes by.
+#[tauri::command]
+pub async fn rename_note(id: String, title: String, app: tauri::AppHandle) -> Result<String, String> {
+    let title = validate(&title)?;
+    let derived = noteindex::listing(&app)?
+        .get(&id)
+        .map(|entry| entry.title().to_string())
+        .ok_or_else(|| format!("No note with id {}", id))?;
+    let old_title = effective_title(Some(&meta::get_meta(&app, &id)), &derived);
+    let note_meta = meta::update_meta(&app, &id, |meta| meta.title = title)?;
+    let new_title = effective_title(Some(&note_meta), &derived);
+
+    if let Some(window) = app.get_webview_window(&format!("note-{}", id)) {
+        let _ = window.set_title(&new_title);
+    }
+    if new_title != old_title {
+        links::target_renamed(&app, &id, &old_title, &new_title);
+    }
+    notemenu::title_changed(&app, &id);
+    app.emit_note_event(
+        &id,
+        "note-renamed",
+        NoteRenamed {
+            id: id.clone(),
+            title: new_title.clone(),
+        },
+    );
+    app.emit_event("refresh-notes", ());
+    Ok(new_title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_trimmed_and_checked() {
+        assert_eq!(validate("  Groceries "), Ok(Some("Groceries".to_string())));
+        assert_eq!(validate("   "), Ok(None));
+        for bad in ["[draft]", "a|b", "two\nlines", "tab\there"] {
+            assert!(validate(bad).is_err(), "{:?}", bad);
+        }
+        assert!(validate(&"é".repeat(MAX_TITLE_CHARS)).is_ok());
+        assert!(validate(&"é".repeat(MAX_TITLE_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn given_titles_win_over_the_first_line() {
+        let named = NoteMeta {
+            title: Some("Shopping".to_string()),
+            ..NoteMeta::default()
+        };
+        assert_eq!(effective_title(Some(&named), "Groceries"), "Shopping");
+        assert_eq!(effective_title(Some(&NoteMeta::default()), "Groceries"), "Groceries");
+        assert_eq!(effective_title(None, "Groceries"), "Groceries");
+    }
+}
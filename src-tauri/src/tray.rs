@@ -0,0 +1,242 @@
+//! The tray icon, and what happens when there isn't a usable one (e.g. GNOME without
+//! the AppIndicator extension). The dashboard hides on close and notes skip the
+//! taskbar, both of which assume the tray is there to bring things back; without it
+//! the dashboard is shown and only minimizes on close, notes appear in the window list,
+//! and the frontend gets `tray-unavailable` so it can offer the tray's actions inline.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{
+    menu::Menu,
+    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
+    Manager, Runtime,
+};
+
+use crate::backend::NotesBackend;
+use crate::batch::BatchFocusGuard;
+use crate::usage::record_usage;
+use crate::{applock, focusmode, show_dashboard, showall, WindowRegistry};
+
+const TRAY_ID: &str = "main";
+
+pub struct TrayState {
+    available: AtomicBool,
+    last_error: Mutex<Option<String>>,
+    /// Items needing the user's attention per source, summed into the tray badge
+    attention: Mutex<BTreeMap<&'static str, usize>>,
+}
+
+impl Default for TrayState {
+    fn default() -> Self {
+        TrayState {
+            // Until `init_tray` says otherwise, so early note windows skip the taskbar as usual
+            available: AtomicBool::new(true),
+            last_error: Mutex::new(None),
+            attention: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct TrayStatus {
+    available: bool,
+    /// Why the tray is considered unavailable, verbatim where it came from a build error
+    error: Option<String>,
+}
+
+pub fn tray_available<R: Runtime>(app: &tauri::AppHandle<R>) -> bool {
+    app.state::<TrayState>().available.load(Ordering::SeqCst)
+}
+
+pub fn tray_status<R: Runtime>(app: &tauri::AppHandle<R>) -> TrayStatus {
+    let state = app.state::<TrayState>();
+    TrayStatus {
+        available: state.available.load(Ordering::SeqCst),
+        error: state.last_error.lock().ok().and_then(|e| e.clone()),
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct AttentionChanged {
+    total: usize,
+    sources: BTreeMap<&'static str, usize>,
+}
+
+/// Shows the attention total as the tray icon's title (next to the icon where the
+/// platform supports it) and in its tooltip.
+fn show_attention<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let sources = app
+        .state::<TrayState>()
+        .attention
+        .lock()
+        .map(|a| a.clone())
+        .unwrap_or_default();
+    let total = sources.values().sum();
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let name = &app.package_info().name;
+        let (title, tooltip) = match total {
+            0 => (None, name.clone()),
+            n => (Some(n.to_string()), format!("{} — {} need attention", name, n)),
+        };
+        let _ = tray.set_title(title);
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+    let payload = AttentionChanged { total, sources };
+    app.emit_event("attention-changed", payload);
+}
+
+/// Sets how many items from `source` (e.g. `"conflicts"`) need the user's attention.
+pub fn set_attention<R: Runtime>(app: &tauri::AppHandle<R>, source: &'static str, count: usize) {
+    let changed = app
+        .state::<TrayState>()
+        .attention
+        .lock()
+        .map(|mut attention| attention.insert(source, count).unwrap_or(0) != count)
+        .unwrap_or(false);
+    if changed {
+        show_attention(app);
+    }
+}
+
+/// GNOME only shows tray icons through an extension, and building the icon succeeds
+/// either way. Ubuntu's GNOME ships the extension, so it is left out.
+#[cfg(target_os = "linux")]
+fn suspect_missing_host() -> Option<String> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+    (desktop.contains("gnome") && !desktop.contains("ubuntu"))
+        .then(|| format!("desktop {:?} may not show tray icons without an extension", desktop))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn suspect_missing_host() -> Option<String> {
+    None
+}
+
+fn on_tray_event<R: Runtime>(tray: &tauri::tray::TrayIcon<R>, event: TrayIconEvent) {
+    if let TrayIconEvent::Click {
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+    } = event
+    {
+        let handle = tray.app_handle();
+        record_usage(handle, "show_all");
+        show_all(handle);
+    }
+}
+
+/// Brings every note window forward, as a left click on the tray icon does.
+pub fn show_all<R: Runtime>(handle: &tauri::AppHandle<R>) {
+    // Locked, the dashboard's PIN screen is all there is to show
+    if applock::is_locked(handle) {
+        show_dashboard(handle);
+        return;
+    }
+    focusmode::end_focus(handle);
+
+    // Ignore 'Focused' events during this mass operation; released shortly after
+    // the guard drops at the end of this function
+    let _batch = BatchFocusGuard::begin(handle, "show-all");
+
+    // Only windows registered before this point take part; anything created
+    // mid-pass is reconciled when the batch ends
+    let (labels, order) = showall::take_snapshot(handle);
+    showall::show_all_pass(&labels, &order, &showall::TauriWindows(handle));
+}
+
+/// Hides all note windows, or brings them all back if they are all hidden. Returns
+/// whether they are visible afterwards.
+pub fn toggle_all<R: Runtime>(handle: &tauri::AppHandle<R>) -> bool {
+    if applock::is_locked(handle) {
+        return false;
+    }
+    focusmode::end_focus(handle);
+    let _batch = BatchFocusGuard::begin(handle, "toggle-all");
+    let (labels, order) = showall::take_snapshot(handle);
+    showall::toggle_all_pass(&labels, &order, &showall::TauriWindows(handle))
+}
+
+#[tauri::command]
+pub async fn toggle_all_notes(app: tauri::AppHandle) -> Result<bool, String> {
+    record_usage(&app, "toggle_all");
+    Ok(toggle_all(&app))
+}
+
+fn build_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
+    let menu = app.state::<Menu<R>>();
+    let icon = app
+        .default_window_icon()
+        .cloned()
+        .ok_or_else(|| tauri::Error::AssetNotFound("default window icon".to_string()))?;
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(icon)
+        .menu(&*menu)
+        .show_menu_on_left_click(false)
+        .on_tray_icon_event(on_tray_event)
+        .build(app)?;
+    show_attention(app);
+    Ok(())
+}
+
+/// Makes everything reachable without the tray, or puts the tray-based behavior back.
+fn apply<R: Runtime>(app: &tauri::AppHandle<R>, available: bool, error: Option<String>) {
+    let state = app.state::<TrayState>();
+    state.available.store(available, Ordering::SeqCst);
+    if let Ok(mut last_error) = state.last_error.lock() {
+        *last_error = error.clone();
+    }
+
+    for label in app.state::<WindowRegistry>().note_labels() {
+        if let Some(window) = app.get_webview_window(&label) {
+            let _ = window.set_skip_taskbar(available);
+        }
+    }
+    if !available {
+        if let Some(main_win) = app.get_webview_window("main") {
+            let _ = main_win.show();
+            let _ = main_win.unminimize();
+        }
+        app.emit_event("tray-unavailable", tray_status(app));
+    }
+}
+
+/// Builds the tray icon at startup. Needs the tray `Menu` to be managed already.
+pub fn init_tray<R: Runtime>(app: &tauri::AppHandle<R>) {
+    match build_tray(app) {
+        Ok(()) => match suspect_missing_host() {
+            Some(reason) => {
+                println!("Tray icon built, but {}; keeping windows reachable without it", reason);
+                apply(app, false, Some(reason));
+            }
+            None => apply(app, true, None),
+        },
+        Err(e) => {
+            println!("Tray icon could not be created: {}", e);
+            apply(app, false, Some(e.to_string()));
+        }
+    }
+}
+
+/// Rebuilds the tray icon, for when the user has installed a tray host since startup.
+/// A successful build is taken at its word here, unlike at startup.
+#[tauri::command]
+pub async fn retry_tray_init(app: tauri::AppHandle) -> Result<TrayStatus, String> {
+    let _ = app.remove_tray_by_id(TRAY_ID);
+    match build_tray(&app) {
+        Ok(()) => {
+            println!("Tray icon rebuilt");
+            apply(&app, true, None);
+        }
+        Err(e) => {
+            println!("Tray icon could not be created: {}", e);
+            apply(&app, false, Some(e.to_string()));
+        }
+    }
+    Ok(tray_status(&app))
+}
+
+#[tauri::command]
+pub async fn get_tray_status(app: tauri::AppHandle) -> Result<TrayStatus, String> {
+    Ok(tray_status(&app))
+}
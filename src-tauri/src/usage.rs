@@ -0,0 +1,215 @@
+use serde_json::Map;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::flush::{flush_now, FlushOutcome, Flushable};
+use crate::localstate::flush_local_state;
+use crate::settings;
+use crate::{now_millis, storage};
+
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default)]
+pub struct FeatureUsage {
+    count: u64,
+    first_used: u64,
+    last_used: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct UsageReport {
+    enabled: bool,
+    generated_at: u64,
+    features: HashMap<String, FeatureUsage>,
+}
+
+/// Purely local feature counters. Nothing here ever leaves the machine and only
+/// feature names are recorded, never note ids or content.
+pub struct UsageTracker {
+    enabled: AtomicBool,
+    dirty: AtomicBool,
+    features: Mutex<HashMap<String, FeatureUsage>>,
+}
+
+impl UsageTracker {
+    pub fn load<R: Runtime>(app: &tauri::AppHandle<R>) -> Self {
+        let enabled = settings::current(app).collect_local_usage;
+        let features = app
+            .store("usage.bin")
+            .ok()
+            .and_then(|store| store.get("feature_usage"))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        UsageTracker {
+            enabled: AtomicBool::new(enabled),
+            dirty: AtomicBool::new(false),
+            features: Mutex::new(features),
+        }
+    }
+
+    fn record(&self, feature: &str) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(mut features) = self.features.lock() {
+            let now = now_millis();
+            let usage = features.entry(feature.to_string()).or_insert(FeatureUsage {
+                count: 0,
+                first_used: now,
+                last_used: now,
+            });
+            usage.count += 1;
+            usage.last_used = now;
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> HashMap<String, FeatureUsage> {
+        self.features.lock().map(|f| f.clone()).unwrap_or_default()
+    }
+}
+
+/// Turns counting on or off, after `collect_local_usage` changed.
+pub fn set_enabled<R: Runtime>(app: &tauri::AppHandle<R>, enabled: bool) {
+    if let Some(tracker) = app.try_state::<UsageTracker>() {
+        tracker.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Counts one use of `feature`. Only touches memory; `flush_usage` does the disk write.
+pub fn record_usage<R: Runtime>(app: &tauri::AppHandle<R>, feature: &str) {
+    app.state::<UsageTracker>().record(feature);
+}
+
+/// Persists counters if anything changed since the last flush.
+pub fn flush_usage<R: Runtime>(app: &tauri::AppHandle<R>) -> FlushOutcome {
+    let tracker = app.state::<UsageTracker>();
+    if !tracker.dirty.swap(false, Ordering::Relaxed) {
+        return FlushOutcome::Clean;
+    }
+    let result = app.store("usage.bin").map_err(|e| e.to_string()).and_then(|store| {
+        store.set("feature_usage", serde_json::to_value(tracker.snapshot()).map_err(|e| e.to_string())?);
+        store.save().map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(()) => FlushOutcome::Wrote,
+        Err(error) => {
+            // Keep the counters dirty so the next flush retries
+            tracker.dirty.store(true, Ordering::Relaxed);
+            println!("Failed to flush usage counters: {}", error);
+            FlushOutcome::Failed { error }
+        }
+    }
+}
+
+pub struct UsageFlush;
+
+impl<R: Runtime> Flushable<R> for UsageFlush {
+    fn name(&self) -> &'static str {
+        "usage"
+    }
+
+    fn flush(&self, app: &tauri::AppHandle<R>) -> FlushOutcome {
+        flush_usage(app)
+    }
+}
+
+pub fn spawn_usage_flusher<R: Runtime>(app: tauri::AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            // Caught up by the storage watchdog once space recovers
+            if !storage::reduced_writes(&app) {
+                flush_usage(&app);
+                flush_local_state(&app);
+            }
+        }
+    });
+}
+
+fn build_report<R: Runtime>(app: &tauri::AppHandle<R>) -> UsageReport {
+    let tracker = app.state::<UsageTracker>();
+    UsageReport {
+        enabled: tracker.enabled.load(Ordering::Relaxed),
+        generated_at: now_millis(),
+        features: tracker.snapshot(),
+    }
+}
+
+#[tauri::command]
+pub async fn set_collect_local_usage(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    let patch = Map::from_iter([("collect_local_usage".to_string(), enabled.into())]);
+    settings::update(&app, patch).map(|_| ())
+}
+
+#[tauri::command]
+pub async fn get_usage_report(app: tauri::AppHandle) -> Result<UsageReport, String> {
+    Ok(build_report(&app))
+}
+
+#[tauri::command]
+pub async fn export_usage_report(path: String, app: tauri::AppHandle) -> Result<(), String> {
+    flush_now(&app, "export");
+    let json = serde_json::to_string_pretty(&build_report(&app)).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn reset_usage(app: tauri::AppHandle) -> Result<(), String> {
+    let tracker = app.state::<UsageTracker>();
+    if let Ok(mut features) = tracker.features.lock() {
+        features.clear();
+    }
+    tracker.dirty.store(false, Ordering::Relaxed);
+
+    let store = app.store("usage.bin").map_err(|e| e.to_string())?;
+    store.delete("feature_usage");
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(enabled: bool) -> UsageTracker {
+        UsageTracker {
+            enabled: AtomicBool::new(enabled),
+            dirty: AtomicBool::new(false),
+            features: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn counting_is_a_no_op_while_disabled() {
+        let tracker = tracker(false);
+        tracker.record("export");
+        tracker.record("export");
+        assert!(tracker.snapshot().is_empty());
+        assert!(!tracker.dirty.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn counts_accumulate_while_enabled() {
+        let tracker = tracker(true);
+        tracker.record("export");
+        tracker.record("export");
+        tracker.record("pinboard");
+
+        let features = tracker.snapshot();
+        assert_eq!(features.len(), 2);
+        let export = features["export"];
+        assert_eq!(export.count, 2);
+        assert!(export.first_used <= export.last_used);
+        assert_eq!(features["pinboard"].count, 1);
+        assert!(tracker.dirty.load(Ordering::Relaxed));
+
+        tracker.enabled.store(false, Ordering::Relaxed);
+        tracker.record("export");
+        assert_eq!(tracker.snapshot()["export"].count, 2);
+    }
+}
@@ -0,0 +1,286 @@
+//! Whole-vault encryption at rest. When enabled, every note file, history snapshot,
+//! trashed or archived note and journal month is stored as `VAULT_HEADER` followed by the base64 of a nonce
+//! and its AES-256-GCM ciphertext, under one key derived from a master passphrase
+//! (Argon2id, salt in settings.bin `vault`). The passphrase is asked for once per session;
+//! `read_note`, `write_note` and the listings decode and encode with the key held in
+//! `VaultState`, so nothing else sees ciphertext.
+//!
+//! Plain files are still read as they are, so a note dropped into the folder from outside
+//! stays readable and is encrypted on its next save. Per-note locks (`encryption.rs`) sit
+//! inside this layer. Exports are encrypted too, see `instance::export_all`. Note metadata
+//! and templates are not encrypted.
+
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::cache::PreviewCache;
+use crate::changes::own_change;
+use crate::encryption::{decrypt, derive_key, encrypt, random_salt};
+use crate::error::NoteError;
+use crate::flush::flush_now;
+use crate::history::snapshots;
+use crate::noteindex;
+use crate::safepath::Root;
+use crate::scan::{scan_notes, ScanEntry, ScanOptions};
+use crate::storage::{self, ensure_writes_allowed};
+
+/// First line of every file encrypted with the vault key.
+pub const VAULT_HEADER: &str = "<!-- sticky-notes-vault v1 -->\n";
+const CONFIG_KEY: &str = "vault";
+/// Encrypted into the config so a passphrase can be checked without touching a note.
+const CHECK_TEXT: &str = "sticky-notes vault check";
+
+pub type VaultKey = [u8; 32];
+
+/// The vault key, held from `unlock_vault` (or `enable_vault`) until `lock_vault`.
+#[derive(Default)]
+pub struct VaultState {
+    key: RwLock<Option<VaultKey>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct VaultConfig {
+    /// Base64
+    salt: String,
+    /// Base64 of `CHECK_TEXT` encrypted with the key
+    check: String,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug)]
+pub struct VaultStatus {
+    enabled: bool,
+    unlocked: bool,
+}
+
+fn config(backend: &impl NotesBackend) -> Option<VaultConfig> {
+    backend
+        .read_store("settings.bin", CONFIG_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+pub fn is_enabled(backend: &impl NotesBackend) -> bool {
+    config(backend).is_some()
+}
+
+pub fn key<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<VaultKey> {
+    *app.state::<VaultState>().key.read().ok()?
+}
+
+fn set_key<R: Runtime>(app: &tauri::AppHandle<R>, key: Option<VaultKey>) -> Result<(), String> {
+    *app.state::<VaultState>().key.write().map_err(|e| e.to_string())? = key;
+    Ok(())
+}
+
+pub fn is_encoded(content: &str) -> bool {
+    content.starts_with(VAULT_HEADER)
+}
+
+/// `stored` decrypted with `key`; plain content comes back unchanged.
+pub fn decode_with(key: Option<&VaultKey>, stored: String) -> Result<String, String> {
+    let Some(body) = stored.strip_prefix(VAULT_HEADER) else {
+        return Ok(stored);
+    };
+    let key = key.ok_or(NoteError::VaultLocked)?;
+    let sealed = STANDARD
+        .decode(body.trim())
+        .map_err(|e| format!("Vault file is damaged: {}", e))?;
+    let plain = decrypt(key, &sealed).ok_or_else(|| "Vault file doesn't decrypt with the vault key".to_string())?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// `stored` as the rest of the app should see it; `VaultLocked` if it's encrypted and the
+/// vault hasn't been unlocked this session.
+pub fn decode(backend: &impl NotesBackend, stored: String) -> Result<String, String> {
+    decode_with(backend.vault_key().as_ref(), stored)
+}
+
+fn encode_with(key: &VaultKey, content: &str) -> Result<String, String> {
+    let sealed = encrypt(key, content.as_bytes())?;
+    Ok(format!("{}{}\n", VAULT_HEADER, STANDARD.encode(sealed)))
+}
+
+/// What should be written to disk for `content`: its encryption while the vault is
+/// enabled, else the content itself. Already encrypted content is left as it is.
+pub fn encode(backend: &impl NotesBackend, content: &str) -> Result<String, String> {
+    if is_encoded(content) || !is_enabled(backend) {
+        return Ok(content.to_string());
+    }
+    let key = backend.vault_key().ok_or(NoteError::VaultLocked)?;
+    encode_with(&key, content)
+}
+
+/// Reads a note or snapshot file, decrypting it if needed.
+pub fn read_file(backend: &impl NotesBackend, path: &Path) -> Result<String, String> {
+    decode(backend, fs::read_to_string(path).map_err(|e| e.to_string())?)
+}
+
+/// The key for `passphrase`, if it is the vault's.
+fn check_passphrase(backend: &impl NotesBackend, passphrase: &str) -> Result<VaultKey, String> {
+    let config = config(backend).ok_or("The vault is not enabled")?;
+    let damaged = |e: String| format!("Vault settings are damaged: {}", e);
+    let salt = STANDARD.decode(&config.salt).map_err(|e| damaged(e.to_string()))?;
+    let check = STANDARD.decode(&config.check).map_err(|e| damaged(e.to_string()))?;
+    let key = derive_key(passphrase, &salt)?;
+    match decrypt(&key, &check) {
+        Some(plain) if plain == CHECK_TEXT.as_bytes() => Ok(key),
+        _ => Err(NoteError::WrongPassword.into()),
+    }
+}
+
+/// Rewrites every note, snapshot, recycled note and journal month from `from` to `to` (`None` being plain
+/// text). Files already in the target form are left alone, so an interrupted run can be
+/// repeated. Returns how many files were rewritten.
+fn convert_all<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    from: Option<&VaultKey>,
+    to: Option<&VaultKey>,
+) -> Result<usize, String> {
+    let sync = storage::sync_writes(app);
+    let mut converted = 0;
+    let mut failures = Vec::new();
+    for root in [Root::Notes, Root::Trash, Root::Archive, Root::Journal] {
+        let dir = root.dir(app)?;
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in scan_notes(&dir, ScanOptions::default()) {
+            let ScanEntry::Note { id, path, .. } = entry else {
+                continue;
+            };
+            let files = std::iter::once(path).chain(snapshots(&dir.join(&id)).into_iter().map(|(p, _)| p));
+            for file in files {
+                let result = (|| -> Result<bool, String> {
+                    let stored = fs::read_to_string(&file).map_err(|e| e.to_string())?;
+                    if is_encoded(&stored) == to.is_some() {
+                        return Ok(false);
+                    }
+                    let plain = decode_with(from, stored)?;
+                    let content = match to {
+                        Some(key) => encode_with(key, &plain)?,
+                        None => plain,
+                    };
+                    let write = || storage::write_atomically(&file, &content, sync).map_err(|e| e.to_string());
+                    if root == Root::Notes {
+                        own_change(app, &[&id], write)?;
+                    } else {
+                        write()?;
+                    }
+                    Ok(true)
+                })();
+                match result {
+                    Ok(true) => converted += 1,
+                    Ok(false) => {}
+                    Err(e) => failures.push(format!("{:?}: {}", file, e)),
+                }
+            }
+        }
+    }
+    if !failures.is_empty() {
+        return Err(format!(
+            "{} files could not be converted, first: {}",
+            failures.len(),
+            failures[0]
+        ));
+    }
+    Ok(converted)
+}
+
+/// Listings show placeholders while the vault is locked, and real previews once it isn't.
+fn vault_changed<R: Runtime>(app: &tauri::AppHandle<R>) {
+    app.state::<PreviewCache>().clear();
+    noteindex::reset(app);
+    app.emit_event("vault-changed", get_status(app));
+    app.emit_event("refresh-notes", ());
+}
+
+fn get_status<R: Runtime>(app: &tauri::AppHandle<R>) -> VaultStatus {
+    VaultStatus {
+        enabled: is_enabled(app),
+        unlocked: key(app).is_some(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_vault_status(app: tauri::AppHandle) -> Result<VaultStatus, String> {
+    Ok(get_status(&app))
+}
+
+/// Turns the vault on with `passphrase` and encrypts everything already stored. The vault
+/// starts out unlocked.
+#[tauri::command]
+pub async fn enable_vault(passphrase: String, app: tauri::AppHandle) -> Result<usize, String> {
+    ensure_writes_allowed(&app)?;
+    if is_enabled(&app) {
+        return Err("The vault is already enabled".to_string());
+    }
+    if passphrase.is_empty() {
+        return Err("The passphrase is empty".to_string());
+    }
+    flush_now(&app, "vault");
+
+    let salt = random_salt();
+    let key = derive_key(&passphrase, &salt)?;
+    let config = VaultConfig {
+        salt: STANDARD.encode(salt),
+        check: STANDARD.encode(encrypt(&key, CHECK_TEXT.as_bytes())?),
+    };
+    // Recorded first: files converted before a failure must stay readable
+    app.write_store(
+        "settings.bin",
+        CONFIG_KEY,
+        serde_json::to_value(config).map_err(|e| e.to_string())?,
+    )?;
+    set_key(&app, Some(key))?;
+    let result = convert_all(&app, None, Some(&key));
+    vault_changed(&app);
+    let converted = result?;
+    println!("Vault enabled, {} files encrypted", converted);
+    Ok(converted)
+}
+
+#[tauri::command]
+pub async fn unlock_vault(passphrase: String, app: tauri::AppHandle) -> Result<(), String> {
+    let key = check_passphrase(&app, &passphrase)?;
+    set_key(&app, Some(key))?;
+    vault_changed(&app);
+    Ok(())
+}
+
+/// Writes out pending edits and forgets the key; notes can't be read again until
+/// `unlock_vault`.
+#[tauri::command]
+pub async fn lock_vault(app: tauri::AppHandle) -> Result<(), String> {
+    if !is_enabled(&app) {
+        return Err("The vault is not enabled".to_string());
+    }
+    flush_now(&app, "vault");
+    set_key(&app, None)?;
+    vault_changed(&app);
+    Ok(())
+}
+
+/// Decrypts everything back to plain files and turns the vault off. If some files can't
+/// be converted the vault stays on, and a retry picks up where this left off.
+#[tauri::command]
+pub async fn disable_vault(passphrase: String, app: tauri::AppHandle) -> Result<usize, String> {
+    ensure_writes_allowed(&app)?;
+    let key = check_passphrase(&app, &passphrase)?;
+    flush_now(&app, "vault");
+    set_key(&app, Some(key))?;
+
+    let result = convert_all(&app, Some(&key), None);
+    if result.is_ok() {
+        app.write_store("settings.bin", CONFIG_KEY, serde_json::Value::Null)?;
+        set_key(&app, None)?;
+    }
+    vault_changed(&app);
+    let converted = result?;
+    println!("Vault disabled, {} files decrypted", converted);
+    Ok(converted)
+}
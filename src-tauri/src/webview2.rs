@@ -0,0 +1,68 @@
+//! Windows start-up check for the WebView2 runtime. Without it Tauri fails deep inside
+//! window creation, so `run()` asks first and, if it's missing, explains the problem in
+//! a native dialog before anything else starts. The dialog offers the Evergreen
+//! bootstrapper download and a retry that continues normal start-up once the runtime
+//! has been installed, without a relaunch.
+
+use webview2_com::Microsoft::Web::WebView2::Win32::GetAvailableCoreWebView2BrowserVersionString;
+use windows::core::{HSTRING, PCWSTR, PWSTR};
+use windows::Win32::UI::WindowsAndMessaging::{
+    MessageBoxW, IDCONTINUE, IDTRYAGAIN, MB_CANCELTRYCONTINUE, MB_ICONWARNING, MB_SETFOREGROUND,
+};
+
+/// Microsoft's Evergreen bootstrapper, which installs the current runtime.
+const BOOTSTRAPPER_URL: &str = "https://go.microsoft.com/fwlink/p/?LinkId=2124703";
+
+/// The installed runtime's version, or `None` if there isn't a usable one.
+pub fn runtime_version() -> Option<String> {
+    let mut version = PWSTR::null();
+    unsafe { GetAvailableCoreWebView2BrowserVersionString(PCWSTR::null(), &mut version) }.ok()?;
+    if version.is_null() {
+        return None;
+    }
+    Some(webview2_com::take_pwstr(version)).filter(|v| !v.is_empty())
+}
+
+fn prompt_missing() -> i32 {
+    let text = HSTRING::from(
+        "Sticky Notes needs the Microsoft Edge WebView2 Runtime, which isn't installed on this computer.\n\n\
+         Continue: open the WebView2 download page\n\
+         Try Again: check again after installing it\n\
+         Cancel: quit",
+    );
+    let caption = HSTRING::from("WebView2 Runtime required");
+    unsafe {
+        MessageBoxW(
+            None,
+            &text,
+            &caption,
+            MB_CANCELTRYCONTINUE | MB_ICONWARNING | MB_SETFOREGROUND,
+        )
+        .0
+    }
+}
+
+/// Returns once the runtime is available (`true`) or the user gave up (`false`), in
+/// which case the caller must exit without creating any window.
+pub fn ensure_runtime() -> bool {
+    loop {
+        if let Some(version) = runtime_version() {
+            println!("WebView2 runtime {} found", version);
+            return true;
+        }
+        println!("WebView2 runtime not found, start-up is on hold");
+
+        match prompt_missing() {
+            r if r == IDCONTINUE.0 => {
+                if let Err(e) = tauri_plugin_opener::open_url(BOOTSTRAPPER_URL, None::<&str>) {
+                    println!("Could not open the WebView2 download page: {}", e);
+                }
+            }
+            r if r == IDTRYAGAIN.0 => {}
+            _ => {
+                println!("WebView2 runtime still missing, exiting");
+                return false;
+            }
+        }
+    }
+}
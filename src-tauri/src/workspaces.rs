@@ -0,0 +1,262 @@
+//! Workspaces: named sets of open notes with their window geometry, kept in `session.bin`
+//! under `workspaces` (name -> notes). Loading one closes the note windows that aren't in
+//! it and opens the rest where they were, so "work" and "personal" layouts can be switched
+//! from the dashboard or the tray's Workspaces submenu.
+//!
+//! A note may be listed by id alone (`duplicate_note` adds copies that way); it then opens
+//! wherever a note without geometry would.
+
+use std::collections::BTreeMap;
+use tauri::menu::{MenuItem, Submenu};
+use tauri::{Manager, Runtime};
+
+use crate::applock;
+use crate::backend::NotesBackend;
+use crate::batch::BatchFocusGuard;
+use crate::meta::{self, Rect};
+use crate::notewindow::NoteWindowOptions;
+use crate::usage::record_usage;
+use crate::{close_note, create_note_window, get_session_order, notes_dir, WindowRegistry};
+
+/// `session.bin` key holding the workspaces.
+const WORKSPACES_KEY: &str = "workspaces";
+pub const MENU_ID_PREFIX: &str = "workspace:";
+const MAX_NAME_CHARS: usize = 64;
+
+/// The tray's "Workspaces" submenu.
+pub struct WorkspaceMenu<R: Runtime>(pub Submenu<R>);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+struct WorkspaceNote {
+    id: String,
+    /// Logical; `None` when the window was minimized or the note was added by id
+    #[serde(default)]
+    geometry: Option<Rect>,
+}
+
+/// As stored: either a full entry or just the note's id.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum StoredNote {
+    Id(String),
+    Note(WorkspaceNote),
+}
+
+impl From<StoredNote> for WorkspaceNote {
+    fn from(stored: StoredNote) -> Self {
+        match stored {
+            StoredNote::Id(id) => WorkspaceNote { id, geometry: None },
+            StoredNote::Note(note) => note,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct WorkspaceInfo {
+    name: String,
+    note_count: usize,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LoadedWorkspace {
+    name: String,
+    opened: usize,
+    /// Notes in the workspace that no longer exist
+    missing: Vec<String>,
+}
+
+fn load_all(backend: &impl NotesBackend) -> BTreeMap<String, Vec<WorkspaceNote>> {
+    let Some(serde_json::Value::Object(stored)) = backend.read_store("session.bin", WORKSPACES_KEY) else {
+        return BTreeMap::new();
+    };
+    stored
+        .into_iter()
+        .filter_map(|(name, notes)| {
+            let notes: Vec<StoredNote> = serde_json::from_value(notes).ok()?;
+            Some((name, notes.into_iter().map(WorkspaceNote::from).collect()))
+        })
+        .collect()
+}
+
+fn save_all<R: Runtime>(app: &tauri::AppHandle<R>, all: &BTreeMap<String, Vec<WorkspaceNote>>) -> Result<(), String> {
+    let value = serde_json::to_value(all).map_err(|e| e.to_string())?;
+    app.write_store("session.bin", WORKSPACES_KEY, value)?;
+    refresh_menu(app);
+    app.emit_event("workspaces-changed", ());
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS || name.chars().any(char::is_control) {
+        return Err(format!("Invalid workspace name: {:?}", name));
+    }
+    Ok(name.to_string())
+}
+
+pub fn exists(backend: &impl NotesBackend, name: &str) -> bool {
+    load_all(backend).contains_key(name)
+}
+
+/// Adds note `id` to workspace `name` without geometry, unless it is in there already.
+pub fn add_note<R: Runtime>(app: &tauri::AppHandle<R>, name: &str, id: &str) -> Result<(), String> {
+    let mut all = load_all(app);
+    let notes = all
+        .get_mut(name)
+        .ok_or_else(|| format!("No workspace named {:?}", name))?;
+    if notes.iter().any(|note| note.id == id) {
+        return Ok(());
+    }
+    notes.push(WorkspaceNote {
+        id: id.to_string(),
+        geometry: None,
+    });
+    save_all(app, &all)
+}
+
+/// Position and inner size, the way note geometry is saved in `note_meta`.
+fn window_geometry<R: Runtime>(window: &tauri::WebviewWindow<R>) -> Option<Rect> {
+    if window.is_minimized().unwrap_or(false) {
+        return None;
+    }
+    let scale = window.scale_factor().ok()?;
+    let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+    let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+    Some(Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Lists the workspaces in the tray's submenu.
+pub fn refresh_menu<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let Some(menu) = app.try_state::<WorkspaceMenu<R>>() else {
+        return;
+    };
+    let submenu = &menu.0;
+    if let Ok(items) = submenu.items() {
+        for item in items {
+            let _ = submenu.remove(&item);
+        }
+    }
+
+    let names: Vec<String> = load_all(app).into_keys().collect();
+    let _ = submenu.set_enabled(!names.is_empty());
+    for name in names {
+        if let Ok(item) = MenuItem::with_id(app, format!("{}{}", MENU_ID_PREFIX, name), &name, true, None::<&str>) {
+            let _ = submenu.append(&item);
+        }
+    }
+}
+
+/// Switches to workspace `name`: note windows not in it close, the others open or move
+/// to where they were saved.
+pub fn load<R: Runtime>(app: &tauri::AppHandle<R>, name: &str) -> Result<LoadedWorkspace, String> {
+    // Windows already open are only shown, which doesn't go through create_note_window
+    applock::ensure_unlocked(app)?;
+    let notes = load_all(app)
+        .remove(name)
+        .ok_or_else(|| format!("No workspace named {:?}", name))?;
+    let dir = notes_dir(app)?;
+    let (present, missing): (Vec<WorkspaceNote>, Vec<WorkspaceNote>) = notes
+        .into_iter()
+        .partition(|note| dir.join(format!("{}.md", note.id)).is_file());
+
+    // Opening a batch of windows would otherwise reorder the session by focus
+    let _batch = BatchFocusGuard::begin(app, "workspace");
+    for label in app.state::<WindowRegistry>().note_labels() {
+        let id = label.strip_prefix("note-").unwrap_or(&label);
+        if !present.iter().any(|note| note.id == id) {
+            close_note(app, id);
+        }
+    }
+
+    let displays = meta::display_rects(app);
+    let mut opened = 0;
+    for note in &present {
+        let geometry = note.geometry.map(|rect| meta::clamp_to_displays(rect, &displays));
+        let result = match (app.get_webview_window(&format!("note-{}", note.id)), geometry) {
+            (Some(window), Some(rect)) => window
+                .set_size(tauri::LogicalSize::new(rect.width, rect.height))
+                .and_then(|_| window.set_position(tauri::LogicalPosition::new(rect.x, rect.y)))
+                .and_then(|_| window.show())
+                .map_err(|e| e.to_string()),
+            (Some(window), None) => window.show().map_err(|e| e.to_string()),
+            (None, geometry) => {
+                let mut options = NoteWindowOptions::open(&note.id);
+                if let Some(rect) = geometry {
+                    options = options.geometry(rect);
+                }
+                create_note_window(app, options).map(|_| ())
+            }
+        };
+        match result {
+            Ok(()) => opened += 1,
+            Err(e) => println!("Failed to open note {} of workspace {:?}: {}", note.id, name, e),
+        }
+    }
+
+    Ok(LoadedWorkspace {
+        name: name.to_string(),
+        opened,
+        missing: missing.into_iter().map(|note| note.id).collect(),
+    })
+}
+
+/// Saves the open note windows and their geometry as workspace `name`, replacing any
+/// workspace of that name.
+#[tauri::command]
+pub async fn save_workspace(name: String, app: tauri::AppHandle) -> Result<WorkspaceInfo, String> {
+    let name = validate_name(&name)?;
+    let order = get_session_order(&app);
+    let mut labels = app.state::<WindowRegistry>().note_labels();
+    labels.sort_by_key(|label| {
+        let id = label.strip_prefix("note-").unwrap_or(label);
+        (order.iter().position(|o| o == id).unwrap_or(usize::MAX), label.clone())
+    });
+    let notes: Vec<WorkspaceNote> = labels
+        .iter()
+        .filter_map(|label| {
+            let window = app.get_webview_window(label)?;
+            Some(WorkspaceNote {
+                id: label.strip_prefix("note-")?.to_string(),
+                geometry: window_geometry(&window),
+            })
+        })
+        .collect();
+
+    let note_count = notes.len();
+    let mut all = load_all(&app);
+    all.insert(name.clone(), notes);
+    save_all(&app, &all)?;
+    record_usage(&app, "save_workspace");
+    Ok(WorkspaceInfo { name, note_count })
+}
+
+#[tauri::command]
+pub async fn load_workspace(name: String, app: tauri::AppHandle) -> Result<LoadedWorkspace, String> {
+    record_usage(&app, "load_workspace");
+    load(&app, &name)
+}
+
+#[tauri::command]
+pub async fn list_workspaces(app: tauri::AppHandle) -> Result<Vec<WorkspaceInfo>, String> {
+    Ok(load_all(&app)
+        .into_iter()
+        .map(|(name, notes)| WorkspaceInfo {
+            name,
+            note_count: notes.len(),
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn delete_workspace(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    let mut all = load_all(&app);
+    if all.remove(&name).is_none() {
+        return Err(format!("No workspace named {:?}", name));
+    }
+    save_all(&app, &all)
+}
@@ -0,0 +1,126 @@
+//! Note saves held back while the notes directory can't be written (permissions taken
+//! away, drive gone). Only the latest content per note is kept. The queue is mirrored to
+//! the app's cache directory, which usually lives elsewhere than the notes, so a quit
+//! while storage is unavailable doesn't lose it; `replay` writes it out on recovery.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{Manager, Runtime};
+
+use crate::backend::NotesBackend;
+use crate::vault;
+use crate::{now_millis, write_note};
+
+const FILE_NAME: &str = "queued_writes.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+struct QueuedWrite {
+    content: String,
+    queued_at: u64,
+}
+
+pub struct WriteQueue(Mutex<BTreeMap<String, QueuedWrite>>);
+
+fn file_path<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(app.path().app_cache_dir().map_err(|e| e.to_string())?.join(FILE_NAME))
+}
+
+/// Best effort; the in-memory queue is what counts while the app runs.
+fn persist<R: Runtime>(app: &tauri::AppHandle<R>, queue: &BTreeMap<String, QueuedWrite>) {
+    let result = file_path(app).and_then(|path| {
+        if queue.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            };
+        }
+        let json = serde_json::to_string(queue).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, json).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        println!("Failed to mirror queued writes: {}", e);
+    }
+}
+
+impl WriteQueue {
+    pub fn load<R: Runtime>(app: &tauri::AppHandle<R>) -> Self {
+        let queue: BTreeMap<String, QueuedWrite> = file_path(app)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        if !queue.is_empty() {
+            println!("{} note saves queued from the last session", queue.len());
+        }
+        WriteQueue(Mutex::new(queue))
+    }
+}
+
+/// Holds `content` as note `id`'s latest save until storage is writable again.
+pub fn enqueue<R: Runtime>(app: &tauri::AppHandle<R>, id: &str, content: &str) -> Result<(), String> {
+    let state = app.state::<WriteQueue>();
+    let mut queue = state.0.lock().map_err(|e| e.to_string())?;
+    queue.insert(
+        id.to_string(),
+        QueuedWrite {
+            // The mirror file is outside the notes folder but still on disk
+            content: vault::encode(app, content)?,
+            queued_at: now_millis(),
+        },
+    );
+    persist(app, &queue);
+    Ok(())
+}
+
+/// The queued content of note `id`, which is newer than what's on disk.
+pub fn queued_content<R: Runtime>(app: &tauri::AppHandle<R>, id: &str) -> Option<String> {
+    let state = app.state::<WriteQueue>();
+    let queue = state.0.lock().ok()?;
+    let content = queue.get(id)?.content.clone();
+    vault::decode(app, content).ok()
+}
+
+pub fn queued_count<R: Runtime>(app: &tauri::AppHandle<R>) -> usize {
+    app.state::<WriteQueue>().0.lock().map(|q| q.len()).unwrap_or(0)
+}
+
+/// Writes the queued saves out, oldest first, stopping at the first failure. Returns how
+/// many are still queued.
+pub fn replay<R: Runtime>(app: &tauri::AppHandle<R>) -> usize {
+    let mut pending: Vec<(String, QueuedWrite)> = match app.state::<WriteQueue>().0.lock() {
+        Ok(queue) => queue.iter().map(|(id, write)| (id.clone(), write.clone())).collect(),
+        Err(_) => return 0,
+    };
+    pending.sort_by_key(|(_, write)| write.queued_at);
+
+    let mut written = Vec::new();
+    for (id, write) in &pending {
+        if let Err(e) = write_note(app, id, &write.content) {
+            println!("Replaying queued save of {} failed: {}", id, e);
+            break;
+        }
+        written.push((id.clone(), write.queued_at));
+    }
+
+    let state = app.state::<WriteQueue>();
+    let Ok(mut queue) = state.0.lock() else {
+        return 0;
+    };
+    for (id, queued_at) in &written {
+        // Leave it if a newer save was queued while replaying
+        if queue.get(id).is_some_and(|write| write.queued_at == *queued_at) {
+            queue.remove(id);
+        }
+    }
+    persist(app, &queue);
+    if !written.is_empty() {
+        println!("Replayed {} queued note saves", written.len());
+        app.emit_event("refresh-notes", ());
+    }
+    queue.len()
+}